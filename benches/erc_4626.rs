@@ -1,5 +1,5 @@
 use alloy::primitives::{address, U256};
-use amms::amms::{amm::AutomatedMarketMaker, erc_4626::ERC4626Vault};
+use amms::amms::{amm::AutomatedMarketMaker, erc_4626::ERC4626Vault, Token};
 use criterion::{criterion_group, criterion_main, Criterion};
 use rand::Rng;
 
@@ -8,20 +8,18 @@ fn simulate_swap(c: &mut Criterion) {
     let token_b = address!("fc0d6cf33e38bce7ca7d89c0e292274031b7157a");
 
     let pool = ERC4626Vault {
-        vault_token: token_a,
-        vault_token_decimals: 18,
-        asset_token: token_b,
-        asset_token_decimals: 18,
+        vault_token: Token::new_with_decimals(token_a, 18),
+        asset_token: Token::new_with_decimals(token_b, 18),
         vault_reserve: U256::from(20_000_000_u128),
         asset_reserve: U256::from(20_000_000_u128),
         deposit_fee: 300,
         withdraw_fee: 300,
     };
 
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
     c.bench_function("erc4626_simulate_swap", |b| {
         b.iter_with_setup(
-            || U256::from(rng.gen_range(1_000..=1e24 as u128)),
+            || U256::from(rng.random_range(1_000..=1e24 as u128)),
             |amount| {
                 let _ = pool.simulate_swap(token_a, token_b, amount).unwrap();
             },
@@ -33,10 +33,10 @@ fn simulate_swap(c: &mut Criterion) {
             .expect("Could not init pool")
     });
 
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
     c.bench_function("uniswap_v3_simulate_swap", |b| {
         b.iter_with_setup(
-            || U256::from(rng.gen_range(1_000..=1e24 as u128)),
+            || U256::from(rng.random_range(1_000..=1e24 as u128)),
             |amount| {
                 let _ = pool.simulate_swap(token_a, token_b, amount).unwrap();
             },
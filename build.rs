@@ -19,6 +19,18 @@ const TARGET_CONTRACTS: &[&str] = &[
     "GetUniswapV3PoolSlot0BatchRequest",
     "GetUniswapV3PoolTickBitmapBatchRequest",
     "GetUniswapV3PoolTickDataBatchRequest",
+    "GetUniswapV3PoolObservationsBatchRequest",
+    "GetUniswapV4PoolTickBitmapBatchRequest",
+    "GetCurveStableSwapPoolDataBatchRequest",
+    "GetCurveCryptoSwapPoolsBatchRequest",
+    "GetCurveCryptoSwapPoolDataBatchRequest",
+    "GetBalancerV2PoolDataBatchRequest",
+    "GetSolidlyPoolDataBatchRequest",
+    "GetAlgebraPoolDataBatchRequest",
+    "GetAlgebraPoolTickTableBatchRequest",
+    "GetAlgebraPoolTickDataBatchRequest",
+    "GetLiquidityBookPairDataBatchRequest",
+    "TokenTaxDetector",
 ];
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
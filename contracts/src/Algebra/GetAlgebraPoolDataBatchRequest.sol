@@ -0,0 +1,107 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetAlgebraPoolDataBatchRequest {
+    // Algebra V1 pools do not expose a tick spacing and always use 60
+    int24 internal constant DEFAULT_TICK_SPACING = 60;
+
+    struct PoolData {
+        address tokenA;
+        uint8 tokenADecimals;
+        address tokenB;
+        uint8 tokenBDecimals;
+        uint128 liquidity;
+        uint160 sqrtPrice;
+        int24 tick;
+        int24 tickSpacing;
+        uint16 fee;
+    }
+
+    constructor(address[] memory pools) {
+        PoolData[] memory allPoolData = new PoolData[](pools.length);
+
+        for (uint256 i = 0; i < pools.length; ++i) {
+            address poolAddress = pools[i];
+
+            if (codeSizeIsZero(poolAddress)) continue;
+
+            allPoolData[i] = getPoolData(poolAddress);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(allPoolData);
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    function getPoolData(address pool) internal view returns (PoolData memory poolData) {
+        poolData.tokenA = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("token0()")))));
+        poolData.tokenB = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("token1()")))));
+
+        if (codeSizeIsZero(poolData.tokenA) || codeSizeIsZero(poolData.tokenB)) {
+            return PoolData(address(0), 0, address(0), 0, 0, 0, 0, 0, 0);
+        }
+
+        poolData.tokenADecimals = getTokenDecimals(poolData.tokenA);
+        poolData.tokenBDecimals = getTokenDecimals(poolData.tokenB);
+
+        // Every Algebra version starts the global state with the price, tick and current fee
+        (bool success, bytes memory data) = pool.staticcall(abi.encodeWithSignature("globalState()"));
+        if (!success || data.length < 96) {
+            return PoolData(address(0), 0, address(0), 0, 0, 0, 0, 0, 0);
+        }
+        (poolData.sqrtPrice, poolData.tick, poolData.fee) = abi.decode(data, (uint160, int24, uint16));
+
+        poolData.liquidity = uint128(uint256(readWord(pool, abi.encodeWithSignature("liquidity()"))));
+
+        poolData.tickSpacing = int24(int256(uint256(readWord(pool, abi.encodeWithSignature("tickSpacing()")))));
+        if (poolData.tickSpacing == 0) {
+            poolData.tickSpacing = DEFAULT_TICK_SPACING;
+        }
+    }
+
+    function readWord(address target, bytes memory payload) internal view returns (bytes32) {
+        (bool success, bytes memory data) = target.staticcall(payload);
+
+        if (success && data.length == 32) {
+            return abi.decode(data, (bytes32));
+        } else {
+            return bytes32(0);
+        }
+    }
+
+    function getTokenDecimals(address token) internal view returns (uint8) {
+        (bool success, bytes memory data) = token.staticcall(abi.encodeWithSignature("decimals()"));
+
+        if (success) {
+            uint256 decimals;
+            if (data.length == 32) {
+                (decimals) = abi.decode(data, (uint256));
+                if (decimals == 0 || decimals > 255) {
+                    return 0;
+                } else {
+                    return uint8(decimals);
+                }
+            } else {
+                return 0;
+            }
+        } else {
+            return 0;
+        }
+    }
+
+    function codeSizeIsZero(address target) internal view returns (bool) {
+        if (target.code.length == 0) {
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetAlgebraPoolTickDataBatchRequest {
+    struct TickDataInfo {
+        address pool;
+        int24[] ticks;
+    }
+
+    struct Info {
+        uint128 liquidityTotal;
+        int128 liquidityDelta;
+    }
+
+    constructor(TickDataInfo[] memory allPoolInfo) {
+        Info[][] memory tickInfoReturn = new Info[][](allPoolInfo.length);
+
+        for (uint256 i = 0; i < allPoolInfo.length; ++i) {
+            Info[] memory tickInfo = new Info[](allPoolInfo[i].ticks.length);
+            for (uint256 j = 0; j < allPoolInfo[i].ticks.length; ++j) {
+                tickInfo[j] = getTick(allPoolInfo[i].pool, allPoolInfo[i].ticks[j]);
+            }
+            tickInfoReturn[i] = tickInfo;
+        }
+
+        bytes memory abiEncodedData = abi.encode(tickInfoReturn);
+
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    /// @dev The layout of the tick struct differs between Algebra versions, but all of them
+    ///      start with the total and net liquidity
+    function getTick(address pool, int24 tick) internal view returns (Info memory info) {
+        (bool success, bytes memory data) = pool.staticcall(abi.encodeWithSignature("ticks(int24)", tick));
+
+        if (success && data.length >= 64) {
+            (info.liquidityTotal, info.liquidityDelta) = abi.decode(data, (uint128, int128));
+        }
+    }
+}
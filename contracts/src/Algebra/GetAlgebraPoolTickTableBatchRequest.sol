@@ -0,0 +1,60 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetAlgebraPoolTickTableBatchRequest {
+    struct TickTableInfo {
+        address pool;
+        int16 minWord;
+        int16 maxWord;
+    }
+
+    constructor(TickTableInfo[] memory allPoolInfo) {
+        uint256[][] memory allTickTables = new uint256[][](allPoolInfo.length);
+
+        for (uint256 i = 0; i < allPoolInfo.length; ++i) {
+            TickTableInfo memory info = allPoolInfo[i];
+            IAlgebraPoolState pool = IAlgebraPoolState(info.pool);
+
+            uint256[] memory tickTable = new uint256[]((uint256(uint16(info.maxWord - info.minWord)) + 1) * 2);
+
+            uint256 wordIdx = 0;
+            for (int16 j = info.minWord; j <= info.maxWord; ++j) {
+                uint256 word = pool.tickTable(j);
+
+                if (word == 0) {
+                    continue;
+                }
+
+                tickTable[wordIdx] = uint256(int256(j));
+                ++wordIdx;
+
+                tickTable[wordIdx] = word;
+                ++wordIdx;
+            }
+
+            assembly {
+                mstore(tickTable, wordIdx)
+            }
+
+            allTickTables[i] = tickTable;
+        }
+
+        bytes memory abiEncodedData = abi.encode(allTickTables);
+
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+}
+
+interface IAlgebraPoolState {
+    /// @notice Returns 256 packed tick initialized boolean values, compressed by the tick spacing
+    function tickTable(int16 wordPosition) external view returns (uint256);
+}
@@ -6,6 +6,7 @@ interface IBPool {
     function getDenormalizedWeight(address token) external returns (uint);
     function getSwapFee() external returns (uint);
     function getBalance(address token) external returns (uint);
+    function totalSupply() external view returns (uint);
 }
 
 interface IERC20 {
@@ -23,6 +24,7 @@ contract GetBalancerPoolDataBatchRequest {
         uint256[] liquidity;
         uint256[] weights;
         uint32 fee;
+        uint256 totalSupply;
     }
 
     constructor(address[] memory pools) {
@@ -63,6 +65,7 @@ contract GetBalancerPoolDataBatchRequest {
 
             // Grab the swap fee
             poolData.fee = uint32(IBPool(poolAddress).getSwapFee());
+            poolData.totalSupply = IBPool(poolAddress).totalSupply();
             poolData.tokens = tokens;
             poolData.decimals = decimals;
             poolData.liquidity = liquidity;
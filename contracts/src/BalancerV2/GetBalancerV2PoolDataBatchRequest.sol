@@ -0,0 +1,149 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetBalancerV2PoolDataBatchRequest {
+    uint256 internal constant ONE = 1e18;
+
+    struct PoolData {
+        bytes32 poolId;
+        address[] tokens;
+        uint8[] decimals;
+        uint256[] balances;
+        uint256[] scalingFactors;
+        uint256[] weights;
+        uint256 amp;
+        bool ampUpdating;
+        uint256 swapFee;
+        uint256 bptIndex;
+        uint256 totalSupply;
+    }
+
+    constructor(address vault, address[] memory pools) {
+        PoolData[] memory allPoolData = new PoolData[](pools.length);
+
+        for (uint256 i = 0; i < pools.length; ++i) {
+            address poolAddress = pools[i];
+
+            if (codeSizeIsZero(poolAddress)) continue;
+
+            allPoolData[i] = getPoolData(vault, poolAddress);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(allPoolData);
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    function getPoolData(address vault, address pool) internal view returns (PoolData memory poolData) {
+        poolData.poolId = readWord(pool, abi.encodeWithSignature("getPoolId()"));
+        if (poolData.poolId == bytes32(0)) return poolData;
+
+        (bool success, bytes memory data) =
+            vault.staticcall(abi.encodeWithSignature("getPoolTokens(bytes32)", poolData.poolId));
+        if (!success) return poolData;
+
+        (address[] memory tokens, uint256[] memory balances,) = abi.decode(data, (address[], uint256[], uint256));
+
+        uint8[] memory decimals = new uint8[](tokens.length);
+        for (uint256 j = 0; j < tokens.length; ++j) {
+            decimals[j] = getTokenDecimals(tokens[j]);
+        }
+
+        poolData.tokens = tokens;
+        poolData.decimals = decimals;
+        poolData.balances = balances;
+        poolData.scalingFactors = getScalingFactors(pool, tokens, decimals);
+        poolData.weights = readWords(pool, abi.encodeWithSignature("getNormalizedWeights()"));
+
+        (success, data) = pool.staticcall(abi.encodeWithSignature("getAmplificationParameter()"));
+        if (success && data.length == 96) {
+            (poolData.amp, poolData.ampUpdating,) = abi.decode(data, (uint256, bool, uint256));
+        }
+
+        poolData.swapFee = uint256(readWord(pool, abi.encodeWithSignature("getSwapFeePercentage()")));
+
+        (success, data) = pool.staticcall(abi.encodeWithSignature("getBptIndex()"));
+        poolData.bptIndex = success && data.length == 32 ? abi.decode(data, (uint256)) : type(uint256).max;
+
+        poolData.totalSupply = uint256(readWord(pool, abi.encodeWithSignature("totalSupply()")));
+    }
+
+    function getScalingFactors(address pool, address[] memory tokens, uint8[] memory decimals)
+        internal
+        view
+        returns (uint256[] memory scalingFactors)
+    {
+        scalingFactors = readWords(pool, abi.encodeWithSignature("getScalingFactors()"));
+        if (scalingFactors.length == tokens.length) return scalingFactors;
+
+        // Pools deployed before `getScalingFactors` was introduced scale by decimals, and by the
+        // cached price rate of the token if the pool has rate providers
+        scalingFactors = new uint256[](tokens.length);
+        for (uint256 j = 0; j < tokens.length; ++j) {
+            scalingFactors[j] = decimals[j] <= 18 ? ONE * 10 ** (18 - decimals[j]) : ONE;
+
+            (bool success, bytes memory data) =
+                pool.staticcall(abi.encodeWithSignature("getPriceRateCache(address)", tokens[j]));
+            if (success && data.length == 96) {
+                (uint256 rate,,) = abi.decode(data, (uint256, uint256, uint256));
+                scalingFactors[j] = scalingFactors[j] * rate / ONE;
+            }
+        }
+    }
+
+    function readWords(address target, bytes memory payload) internal view returns (uint256[] memory) {
+        (bool success, bytes memory data) = target.staticcall(payload);
+
+        if (success && data.length >= 64) {
+            return abi.decode(data, (uint256[]));
+        } else {
+            return new uint256[](0);
+        }
+    }
+
+    function readWord(address target, bytes memory payload) internal view returns (bytes32) {
+        (bool success, bytes memory data) = target.staticcall(payload);
+
+        if (success && data.length >= 32) {
+            return abi.decode(data, (bytes32));
+        } else {
+            return bytes32(0);
+        }
+    }
+
+    function getTokenDecimals(address token) internal view returns (uint8) {
+        (bool success, bytes memory data) = token.staticcall(abi.encodeWithSignature("decimals()"));
+
+        if (success) {
+            uint256 decimals;
+            if (data.length == 32) {
+                (decimals) = abi.decode(data, (uint256));
+                if (decimals == 0 || decimals > 255) {
+                    return 0;
+                } else {
+                    return uint8(decimals);
+                }
+            } else {
+                return 0;
+            }
+        } else {
+            return 0;
+        }
+    }
+
+    function codeSizeIsZero(address target) internal view returns (bool) {
+        if (target.code.length == 0) {
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
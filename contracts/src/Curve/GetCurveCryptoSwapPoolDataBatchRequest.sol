@@ -0,0 +1,193 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetCurveCryptoSwapPoolDataBatchRequest {
+    address internal constant ETH = 0xEeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE;
+    uint256 internal constant MAX_COINS = 3;
+
+    struct Fees {
+        uint256 midFee;
+        uint256 outFee;
+        uint256 feeGamma;
+        uint256 adminFee;
+    }
+
+    struct PoolData {
+        address[] coins;
+        uint8[] decimals;
+        uint256[] balances;
+        uint256[] priceScale;
+        uint256[] priceOracle;
+        uint256[] lastPrices;
+        uint256 lastPricesTimestamp;
+        uint256 D;
+        uint256 initialAGamma;
+        uint256 futureAGamma;
+        uint256 initialAGammaTime;
+        uint256 futureAGammaTime;
+        Fees fees;
+        uint256 allowedExtraProfit;
+        uint256 adjustmentStep;
+        uint256 maHalfTime;
+        uint256 xcpProfit;
+        uint256 xcpProfitA;
+        uint256 virtualPrice;
+        bool notAdjusted;
+        uint256 totalSupply;
+        bool isNG;
+    }
+
+    constructor(address[] memory pools) {
+        PoolData[] memory allPoolData = new PoolData[](pools.length);
+
+        for (uint256 i = 0; i < pools.length; ++i) {
+            address poolAddress = pools[i];
+
+            if (codeSizeIsZero(poolAddress)) continue;
+
+            allPoolData[i] = getPoolData(poolAddress);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(allPoolData);
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    function getPoolData(address pool) internal view returns (PoolData memory poolData) {
+        address[] memory coins = getCoins(pool);
+        uint8[] memory decimals = new uint8[](coins.length);
+        uint256[] memory balances = new uint256[](coins.length);
+
+        for (uint256 j = 0; j < coins.length; ++j) {
+            decimals[j] = coins[j] == ETH ? 18 : getTokenDecimals(coins[j]);
+            balances[j] = uint256(readWord(pool, abi.encodeWithSignature("balances(uint256)", j)));
+        }
+
+        poolData.coins = coins;
+        poolData.decimals = decimals;
+        poolData.balances = balances;
+
+        getPrices(pool, coins.length, poolData);
+        getParameters(pool, poolData);
+
+        address lpToken = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("token()")))));
+        poolData.totalSupply = uint256(readWord(lpToken, abi.encodeWithSignature("totalSupply()")));
+
+        // Only pools deployed by the NG factories delegate their math to a `MATH` contract
+        poolData.isNG = readWord(pool, abi.encodeWithSignature("MATH()")) != bytes32(0);
+    }
+
+    function getPrices(address pool, uint256 nCoins, PoolData memory poolData) internal view {
+        uint256[] memory priceScale = new uint256[](nCoins - 1);
+        uint256[] memory priceOracle = new uint256[](nCoins - 1);
+        uint256[] memory lastPrices = new uint256[](nCoins - 1);
+
+        if (nCoins == 2) {
+            priceScale[0] = uint256(readWord(pool, abi.encodeWithSignature("price_scale()")));
+            priceOracle[0] = uint256(readWord(pool, abi.encodeWithSignature("price_oracle()")));
+            lastPrices[0] = uint256(readWord(pool, abi.encodeWithSignature("last_prices()")));
+
+            // Two coin pools return the oracle moved up to the current block, so the moving
+            // average continues from here
+            poolData.lastPricesTimestamp = block.timestamp;
+        } else {
+            for (uint256 k = 0; k < nCoins - 1; ++k) {
+                priceScale[k] = uint256(readWord(pool, abi.encodeWithSignature("price_scale(uint256)", k)));
+                priceOracle[k] = uint256(readWord(pool, abi.encodeWithSignature("price_oracle(uint256)", k)));
+                lastPrices[k] = uint256(readWord(pool, abi.encodeWithSignature("last_prices(uint256)", k)));
+            }
+
+            poolData.lastPricesTimestamp =
+                uint256(readWord(pool, abi.encodeWithSignature("last_prices_timestamp()")));
+        }
+
+        poolData.priceScale = priceScale;
+        poolData.priceOracle = priceOracle;
+        poolData.lastPrices = lastPrices;
+    }
+
+    function getParameters(address pool, PoolData memory poolData) internal view {
+        poolData.D = uint256(readWord(pool, abi.encodeWithSignature("D()")));
+        poolData.initialAGamma = uint256(readWord(pool, abi.encodeWithSignature("initial_A_gamma()")));
+        poolData.futureAGamma = uint256(readWord(pool, abi.encodeWithSignature("future_A_gamma()")));
+        poolData.initialAGammaTime = uint256(readWord(pool, abi.encodeWithSignature("initial_A_gamma_time()")));
+        poolData.futureAGammaTime = uint256(readWord(pool, abi.encodeWithSignature("future_A_gamma_time()")));
+
+        poolData.fees.midFee = uint256(readWord(pool, abi.encodeWithSignature("mid_fee()")));
+        poolData.fees.outFee = uint256(readWord(pool, abi.encodeWithSignature("out_fee()")));
+        poolData.fees.feeGamma = uint256(readWord(pool, abi.encodeWithSignature("fee_gamma()")));
+        poolData.fees.adminFee = uint256(readWord(pool, abi.encodeWithSignature("admin_fee()")));
+        poolData.allowedExtraProfit = uint256(readWord(pool, abi.encodeWithSignature("allowed_extra_profit()")));
+        poolData.adjustmentStep = uint256(readWord(pool, abi.encodeWithSignature("adjustment_step()")));
+        poolData.maHalfTime = uint256(readWord(pool, abi.encodeWithSignature("ma_half_time()")));
+
+        poolData.xcpProfit = uint256(readWord(pool, abi.encodeWithSignature("xcp_profit()")));
+        poolData.xcpProfitA = uint256(readWord(pool, abi.encodeWithSignature("xcp_profit_a()")));
+        poolData.virtualPrice = uint256(readWord(pool, abi.encodeWithSignature("virtual_price()")));
+        poolData.notAdjusted = uint256(readWord(pool, abi.encodeWithSignature("not_adjusted()"))) != 0;
+    }
+
+    function getCoins(address pool) internal view returns (address[] memory) {
+        address[] memory coins = new address[](MAX_COINS);
+        uint256 count = 0;
+
+        for (uint256 j = 0; j < MAX_COINS; ++j) {
+            (bool success, bytes memory data) = pool.staticcall(abi.encodeWithSignature("coins(uint256)", j));
+            if (!success || data.length != 32) break;
+            coins[j] = abi.decode(data, (address));
+            ++count;
+        }
+
+        assembly {
+            mstore(coins, count)
+        }
+
+        return coins;
+    }
+
+    function readWord(address target, bytes memory payload) internal view returns (bytes32) {
+        (bool success, bytes memory data) = target.staticcall(payload);
+
+        if (success && data.length == 32) {
+            return abi.decode(data, (bytes32));
+        } else {
+            return bytes32(0);
+        }
+    }
+
+    function getTokenDecimals(address token) internal view returns (uint8) {
+        (bool success, bytes memory data) = token.staticcall(abi.encodeWithSignature("decimals()"));
+
+        if (success) {
+            uint256 decimals;
+            if (data.length == 32) {
+                (decimals) = abi.decode(data, (uint256));
+                if (decimals == 0 || decimals > 255) {
+                    return 0;
+                } else {
+                    return uint8(decimals);
+                }
+            } else {
+                return 0;
+            }
+        } else {
+            return 0;
+        }
+    }
+
+    function codeSizeIsZero(address target) internal view returns (bool) {
+        if (target.code.length == 0) {
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
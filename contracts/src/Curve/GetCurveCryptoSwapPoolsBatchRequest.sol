@@ -0,0 +1,35 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+interface ICurveCryptoFactory {
+    function pool_list(uint256 idx) external view returns (address);
+    function pool_count() external view returns (uint256);
+}
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetCurveCryptoSwapPoolsBatchRequest {
+    constructor(uint256 from, uint256 step, address factory) {
+        uint256 poolCount = ICurveCryptoFactory(factory).pool_count();
+
+        step = from + step > poolCount ? poolCount - from : step;
+
+        // There is a max number of pool as a too big returned data times out the rpc
+        address[] memory pools = new address[](step);
+
+        for (uint256 i = 0; i < step; ++i) {
+            pools[i] = ICurveCryptoFactory(factory).pool_list(from + i);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(pools);
+
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetCurveStableSwapPoolDataBatchRequest {
+    address internal constant ETH = 0xEeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE;
+    uint256 internal constant MAX_COINS = 8;
+
+    struct PoolData {
+        address[] coins;
+        uint8[] decimals;
+        uint256[] balances;
+        uint256[] storedRates;
+        uint256 initialA;
+        uint256 futureA;
+        uint256 initialATime;
+        uint256 futureATime;
+        uint256 aPrecision;
+        uint256 fee;
+        uint256 adminFee;
+        uint256 offpegFeeMultiplier;
+        uint256 totalSupply;
+        address basePool;
+    }
+
+    constructor(address[] memory pools) {
+        PoolData[] memory allPoolData = new PoolData[](pools.length);
+
+        for (uint256 i = 0; i < pools.length; ++i) {
+            address poolAddress = pools[i];
+
+            if (codeSizeIsZero(poolAddress)) continue;
+
+            allPoolData[i] = getPoolData(poolAddress);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(allPoolData);
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    function getPoolData(address pool) internal view returns (PoolData memory poolData) {
+        // Older pools index coins and balances with int128, newer ones with uint256
+        (address[] memory coins, bool int128Index) = getCoins(pool);
+        uint8[] memory decimals = new uint8[](coins.length);
+        uint256[] memory balances = new uint256[](coins.length);
+
+        for (uint256 j = 0; j < coins.length; ++j) {
+            decimals[j] = coins[j] == ETH ? 18 : getTokenDecimals(coins[j]);
+            balances[j] = uint256(
+                int128Index
+                    ? readWord(pool, abi.encodeWithSignature("balances(int128)", int128(int256(j))))
+                    : readWord(pool, abi.encodeWithSignature("balances(uint256)", j))
+            );
+        }
+
+        poolData.coins = coins;
+        poolData.decimals = decimals;
+        poolData.balances = balances;
+        poolData.storedRates = getStoredRates(pool, coins.length);
+
+        poolData.initialA = uint256(readWord(pool, abi.encodeWithSignature("initial_A()")));
+        poolData.futureA = uint256(readWord(pool, abi.encodeWithSignature("future_A()")));
+        poolData.initialATime = uint256(readWord(pool, abi.encodeWithSignature("initial_A_time()")));
+        poolData.futureATime = uint256(readWord(pool, abi.encodeWithSignature("future_A_time()")));
+        poolData.aPrecision = getAPrecision(pool);
+
+        poolData.fee = uint256(readWord(pool, abi.encodeWithSignature("fee()")));
+        poolData.adminFee = uint256(readWord(pool, abi.encodeWithSignature("admin_fee()")));
+        poolData.offpegFeeMultiplier = uint256(readWord(pool, abi.encodeWithSignature("offpeg_fee_multiplier()")));
+        poolData.totalSupply = getTotalSupply(pool);
+        poolData.basePool = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("base_pool()")))));
+    }
+
+    function getStoredRates(address pool, uint256 nCoins) internal view returns (uint256[] memory rates) {
+        // Pools with rate oracles expose their rates, plain pools derive them from decimals
+        (bool success, bytes memory data) = pool.staticcall(abi.encodeWithSignature("stored_rates()"));
+        if (success && data.length >= 64 + 32 * nCoins) {
+            rates = abi.decode(data, (uint256[]));
+        }
+    }
+
+    function getAPrecision(address pool) internal view returns (uint256) {
+        // Pools without A_precise() store A without precision
+        uint256 a = uint256(readWord(pool, abi.encodeWithSignature("A()")));
+        uint256 aPrecise = uint256(readWord(pool, abi.encodeWithSignature("A_precise()")));
+        return aPrecise == 0 || a == 0 ? 1 : aPrecise / a;
+    }
+
+    function getTotalSupply(address pool) internal view returns (uint256) {
+        // Factory pools are their own LP token, older pools reference a separate token
+        address lpToken = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("lp_token()")))));
+        if (lpToken == address(0)) {
+            lpToken = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("token()")))));
+        }
+        if (lpToken == address(0)) {
+            lpToken = pool;
+        }
+        return uint256(readWord(lpToken, abi.encodeWithSignature("totalSupply()")));
+    }
+
+    function getCoins(address pool) internal view returns (address[] memory, bool) {
+        address[] memory coins = new address[](MAX_COINS);
+        bool int128Index = false;
+        uint256 count = 0;
+
+        for (uint256 j = 0; j < MAX_COINS; ++j) {
+            (bool success, bytes memory data) = pool.staticcall(abi.encodeWithSignature("coins(uint256)", j));
+            if (!success || data.length != 32) {
+                if (j == 0) {
+                    int128Index = true;
+                }
+                break;
+            }
+            coins[j] = abi.decode(data, (address));
+            ++count;
+        }
+
+        if (int128Index) {
+            for (uint256 j = 0; j < MAX_COINS; ++j) {
+                (bool success, bytes memory data) =
+                    pool.staticcall(abi.encodeWithSignature("coins(int128)", int128(int256(j))));
+                if (!success || data.length != 32) break;
+                coins[j] = abi.decode(data, (address));
+                ++count;
+            }
+        }
+
+        assembly {
+            mstore(coins, count)
+        }
+
+        return (coins, int128Index);
+    }
+
+    function readWord(address target, bytes memory payload) internal view returns (bytes32) {
+        (bool success, bytes memory data) = target.staticcall(payload);
+
+        if (success && data.length == 32) {
+            return abi.decode(data, (bytes32));
+        } else {
+            return bytes32(0);
+        }
+    }
+
+    function getTokenDecimals(address token) internal view returns (uint8) {
+        (bool success, bytes memory data) = token.staticcall(abi.encodeWithSignature("decimals()"));
+
+        if (success) {
+            uint256 decimals;
+            if (data.length == 32) {
+                (decimals) = abi.decode(data, (uint256));
+                if (decimals == 0 || decimals > 255) {
+                    return 0;
+                } else {
+                    return uint8(decimals);
+                }
+            } else {
+                return 0;
+            }
+        } else {
+            return 0;
+        }
+    }
+
+    function codeSizeIsZero(address target) internal view returns (bool) {
+        if (target.code.length == 0) {
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+interface IERC20 {
+    function balanceOf(address account) external view returns (uint256);
+}
+
+/**
+ * @dev This contract is never deployed. Its runtime code is placed at a pool holding the token
+ * and at two empty wallets with `eth_call` state overrides, so that the token sees transfers
+ * out of the pool, between the wallets and back into the pool.
+ */
+contract TokenTaxDetector {
+    /// @notice Buys `amount` of `token` from this pool into `wallet`, transfers what arrived to
+    /// `recipient` and sells it back into this pool. Taxes are returned in basis points.
+    function detectTax(
+        address token,
+        uint256 amount,
+        address wallet,
+        address recipient
+    ) external returns (uint256 buyTax, uint256 sellTax, uint256 transferTax) {
+        uint256 bought = forward(token, wallet, amount);
+        buyTax = tax(amount, bought);
+
+        uint256 transferred = TokenTaxDetector(wallet).forward(
+            token,
+            recipient,
+            bought
+        );
+        transferTax = tax(bought, transferred);
+
+        uint256 sold = TokenTaxDetector(recipient).forward(
+            token,
+            address(this),
+            transferred
+        );
+        sellTax = tax(transferred, sold);
+    }
+
+    /// @notice Transfers `amount` of `token` to `to` and returns the amount `to` received.
+    function forward(
+        address token,
+        address to,
+        uint256 amount
+    ) public returns (uint256) {
+        uint256 balanceBefore = IERC20(token).balanceOf(to);
+
+        (bool success, bytes memory data) = token.call(
+            abi.encodeWithSignature("transfer(address,uint256)", to, amount)
+        );
+        require(
+            success && (data.length == 0 || abi.decode(data, (bool))),
+            "Transfer failed"
+        );
+
+        uint256 balanceAfter = IERC20(token).balanceOf(to);
+        return balanceAfter > balanceBefore ? balanceAfter - balanceBefore : 0;
+    }
+}
+
+uint256 constant BPS_MAX = 10000;
+
+/// @dev Rounds up, so a tax is never reported as lower than it is
+function tax(uint256 sent, uint256 received) pure returns (uint256) {
+    if (sent == 0) return BPS_MAX;
+    if (received >= sent) return 0;
+
+    return ((sent - received) * BPS_MAX + sent - 1) / sent;
+}
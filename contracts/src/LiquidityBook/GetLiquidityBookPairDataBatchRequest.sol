@@ -0,0 +1,176 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetLiquidityBookPairDataBatchRequest {
+    struct PairData {
+        address tokenX;
+        uint8 tokenXDecimals;
+        address tokenY;
+        uint8 tokenYDecimals;
+        uint16 binStep;
+        uint24 activeId;
+        uint16 baseFactor;
+        uint16 filterPeriod;
+        uint16 decayPeriod;
+        uint16 reductionFactor;
+        uint24 variableFeeControl;
+        uint16 protocolShare;
+        uint24 maxVolatilityAccumulator;
+        uint24 volatilityAccumulator;
+        uint24 volatilityReference;
+        uint24 idReference;
+        uint40 timeOfLastUpdate;
+        uint24[] binIds;
+        uint128[] reserveX;
+        uint128[] reserveY;
+    }
+
+    /// @param pairs The pairs to fetch
+    /// @param binsPerSide The max number of non-empty bins fetched on each side of the active bin
+    constructor(address[] memory pairs, uint256 binsPerSide) {
+        PairData[] memory allPairData = new PairData[](pairs.length);
+
+        for (uint256 i = 0; i < pairs.length; ++i) {
+            address pairAddress = pairs[i];
+
+            if (codeSizeIsZero(pairAddress)) continue;
+
+            ILBPair pair = ILBPair(pairAddress);
+            PairData memory pairData = allPairData[i];
+
+            pairData.tokenX = pair.getTokenX();
+            pairData.tokenY = pair.getTokenY();
+            pairData.tokenXDecimals = getTokenDecimals(pairData.tokenX);
+            pairData.tokenYDecimals = getTokenDecimals(pairData.tokenY);
+            pairData.binStep = pair.getBinStep();
+            pairData.activeId = pair.getActiveId();
+
+            getFeeParameters(pair, pairData);
+            getBins(pair, pairData, binsPerSide);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(allPairData);
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    function getFeeParameters(ILBPair pair, PairData memory pairData) internal view {
+        (
+            pairData.baseFactor,
+            pairData.filterPeriod,
+            pairData.decayPeriod,
+            pairData.reductionFactor,
+            pairData.variableFeeControl,
+            pairData.protocolShare,
+            pairData.maxVolatilityAccumulator
+        ) = pair.getStaticFeeParameters();
+
+        (
+            pairData.volatilityAccumulator,
+            pairData.volatilityReference,
+            pairData.idReference,
+            pairData.timeOfLastUpdate
+        ) = pair.getVariableFeeParameters();
+    }
+
+    function getBins(ILBPair pair, PairData memory pairData, uint256 binsPerSide) internal view {
+        uint24[] memory binIds = new uint24[](binsPerSide * 2 + 1);
+        uint256 count = 0;
+
+        // The active bin, followed by the non-empty bins below and above it
+        binIds[count++] = pairData.activeId;
+        for (uint256 side = 0; side < 2; ++side) {
+            bool swapForY = side == 0;
+            uint24 id = pairData.activeId;
+
+            for (uint256 j = 0; j < binsPerSide; ++j) {
+                id = pair.getNextNonEmptyBin(swapForY, id);
+                if (id == 0 || id == type(uint24).max) break;
+
+                binIds[count++] = id;
+            }
+        }
+
+        uint128[] memory reserveX = new uint128[](count);
+        uint128[] memory reserveY = new uint128[](count);
+        for (uint256 j = 0; j < count; ++j) {
+            (reserveX[j], reserveY[j]) = pair.getBin(binIds[j]);
+        }
+
+        assembly {
+            mstore(binIds, count)
+        }
+
+        pairData.binIds = binIds;
+        pairData.reserveX = reserveX;
+        pairData.reserveY = reserveY;
+    }
+
+    function getTokenDecimals(address token) internal view returns (uint8) {
+        (bool success, bytes memory data) = token.staticcall(abi.encodeWithSignature("decimals()"));
+
+        if (success) {
+            uint256 decimals;
+            if (data.length == 32) {
+                (decimals) = abi.decode(data, (uint256));
+                if (decimals == 0 || decimals > 255) {
+                    return 0;
+                } else {
+                    return uint8(decimals);
+                }
+            } else {
+                return 0;
+            }
+        } else {
+            return 0;
+        }
+    }
+
+    function codeSizeIsZero(address target) internal view returns (bool) {
+        if (target.code.length == 0) {
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
+
+interface ILBPair {
+    function getTokenX() external view returns (address);
+    function getTokenY() external view returns (address);
+    function getBinStep() external view returns (uint16);
+    function getActiveId() external view returns (uint24);
+    function getBin(uint24 id) external view returns (uint128 binReserveX, uint128 binReserveY);
+    function getNextNonEmptyBin(bool swapForY, uint24 id) external view returns (uint24 nextId);
+
+    function getStaticFeeParameters()
+        external
+        view
+        returns (
+            uint16 baseFactor,
+            uint16 filterPeriod,
+            uint16 decayPeriod,
+            uint16 reductionFactor,
+            uint24 variableFeeControl,
+            uint16 protocolShare,
+            uint24 maxVolatilityAccumulator
+        );
+
+    function getVariableFeeParameters()
+        external
+        view
+        returns (
+            uint24 volatilityAccumulator,
+            uint24 volatilityReference,
+            uint24 idReference,
+            uint40 timeOfLastUpdate
+        );
+}
@@ -0,0 +1,101 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetSolidlyPoolDataBatchRequest {
+    struct PoolData {
+        address tokenA;
+        address tokenB;
+        uint8 tokenADecimals;
+        uint8 tokenBDecimals;
+        uint256 reserve0;
+        uint256 reserve1;
+        bool stable;
+        uint256 fee;
+    }
+
+    constructor(address factory, address[] memory pools) {
+        PoolData[] memory allPoolData = new PoolData[](pools.length);
+
+        for (uint256 i = 0; i < pools.length; ++i) {
+            address poolAddress = pools[i];
+
+            if (codeSizeIsZero(poolAddress)) continue;
+
+            allPoolData[i] = getPoolData(factory, poolAddress);
+        }
+
+        bytes memory _abiEncodedData = abi.encode(allPoolData);
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(_abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+
+    function getPoolData(address factory, address pool) internal view returns (PoolData memory poolData) {
+        address tokenA = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("token0()")))));
+        address tokenB = address(uint160(uint256(readWord(pool, abi.encodeWithSignature("token1()")))));
+
+        if (codeSizeIsZero(tokenA) || codeSizeIsZero(tokenB)) return poolData;
+
+        uint8 tokenADecimals = getTokenDecimals(tokenA);
+        uint8 tokenBDecimals = getTokenDecimals(tokenB);
+        if (tokenADecimals == 0 || tokenBDecimals == 0) return poolData;
+
+        (bool success, bytes memory data) = pool.staticcall(abi.encodeWithSignature("getReserves()"));
+        if (!success || data.length < 64) return poolData;
+
+        (poolData.reserve0, poolData.reserve1) = abi.decode(data, (uint256, uint256));
+        poolData.stable = uint256(readWord(pool, abi.encodeWithSignature("stable()"))) != 0;
+        poolData.fee =
+            uint256(readWord(factory, abi.encodeWithSignature("getFee(address,bool)", pool, poolData.stable)));
+
+        poolData.tokenA = tokenA;
+        poolData.tokenB = tokenB;
+        poolData.tokenADecimals = tokenADecimals;
+        poolData.tokenBDecimals = tokenBDecimals;
+    }
+
+    function readWord(address target, bytes memory payload) internal view returns (bytes32) {
+        (bool success, bytes memory data) = target.staticcall(payload);
+
+        if (success && data.length >= 32) {
+            return abi.decode(data, (bytes32));
+        } else {
+            return bytes32(0);
+        }
+    }
+
+    function getTokenDecimals(address token) internal view returns (uint8) {
+        (bool success, bytes memory data) = token.staticcall(abi.encodeWithSignature("decimals()"));
+
+        if (success) {
+            uint256 decimals;
+            if (data.length == 32) {
+                (decimals) = abi.decode(data, (uint256));
+                if (decimals == 0 || decimals > 255) {
+                    return 0;
+                } else {
+                    return uint8(decimals);
+                }
+            } else {
+                return 0;
+            }
+        } else {
+            return 0;
+        }
+    }
+
+    function codeSizeIsZero(address target) internal view returns (bool) {
+        if (target.code.length == 0) {
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
@@ -10,6 +10,12 @@ interface IUniswapV2Pair {
         external
         view
         returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+
+    function totalSupply() external view returns (uint256);
+
+    function price0CumulativeLast() external view returns (uint256);
+
+    function price1CumulativeLast() external view returns (uint256);
 }
 
 interface IERC20 {
@@ -28,6 +34,10 @@ contract GetUniswapV2PoolDataBatchRequest {
         uint112 reserve1;
         uint8 tokenADecimals;
         uint8 tokenBDecimals;
+        uint256 totalSupply;
+        uint32 blockTimestampLast;
+        uint256 price0CumulativeLast;
+        uint256 price1CumulativeLast;
     }
 
     constructor(address[] memory pools) {
@@ -107,9 +117,20 @@ contract GetUniswapV2PoolDataBatchRequest {
             }
 
             // Get reserves
-            (poolData.reserve0, poolData.reserve1, ) = IUniswapV2Pair(
-                poolAddress
-            ).getReserves();
+            (
+                poolData.reserve0,
+                poolData.reserve1,
+                poolData.blockTimestampLast
+            ) = IUniswapV2Pair(poolAddress).getReserves();
+
+            // Get the total supply of LP shares
+            poolData.totalSupply = IUniswapV2Pair(poolAddress).totalSupply();
+
+            // Get the cumulative prices as of the last update
+            poolData.price0CumulativeLast = IUniswapV2Pair(poolAddress)
+                .price0CumulativeLast();
+            poolData.price1CumulativeLast = IUniswapV2Pair(poolAddress)
+                .price1CumulativeLast();
 
             allPoolData[i] = poolData;
         }
@@ -0,0 +1,77 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+
+contract GetUniswapV3PoolObservationsBatchRequest {
+    struct ObservationsInfo {
+        address pool;
+        uint16[] indices;
+    }
+
+    struct Observation {
+        uint32 blockTimestamp;
+        int56 tickCumulative;
+        uint160 secondsPerLiquidityCumulativeX128;
+        bool initialized;
+    }
+
+    constructor(ObservationsInfo[] memory allPoolInfo) {
+        Observation[][] memory observationsReturn = new Observation[][](
+            allPoolInfo.length
+        );
+
+        for (uint256 i = 0; i < allPoolInfo.length; ++i) {
+            Observation[] memory observations = new Observation[](
+                allPoolInfo[i].indices.length
+            );
+            for (uint256 j = 0; j < allPoolInfo[i].indices.length; ++j) {
+                (
+                    uint32 blockTimestamp,
+                    int56 tickCumulative,
+                    uint160 secondsPerLiquidityCumulativeX128,
+                    bool initialized
+                ) = IUniswapV3PoolOracle(allPoolInfo[i].pool).observations(
+                        allPoolInfo[i].indices[j]
+                    );
+
+                observations[j] = Observation({
+                    blockTimestamp: blockTimestamp,
+                    tickCumulative: tickCumulative,
+                    secondsPerLiquidityCumulativeX128: secondsPerLiquidityCumulativeX128,
+                    initialized: initialized
+                });
+            }
+            observationsReturn[i] = observations;
+        }
+
+        // ensure abi encoding, not needed here but increase reusability for different return types
+        // note: abi.encode add a first 32 bytes word with the address of the original data
+        bytes memory abiEncodedData = abi.encode(observationsReturn);
+
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+}
+
+interface IUniswapV3PoolOracle {
+    /// @notice Returns data about a specific observation index
+    function observations(
+        uint256 index
+    )
+        external
+        view
+        returns (
+            uint32 blockTimestamp,
+            int56 tickCumulative,
+            uint160 secondsPerLiquidityCumulativeX128,
+            bool initialized
+        );
+}
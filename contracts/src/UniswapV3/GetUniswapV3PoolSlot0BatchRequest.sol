@@ -10,6 +10,12 @@ contract GetUniswapV3PoolSlot0BatchRequest {
         int24 tick;
         uint128 liquidity;
         uint256 sqrtPrice;
+        uint32 feeProtocol;
+        uint256 feeGrowthGlobal0X128;
+        uint256 feeGrowthGlobal1X128;
+        uint16 observationIndex;
+        uint16 observationCardinality;
+        uint16 observationCardinalityNext;
     }
 
     constructor(address[] memory pools) {
@@ -22,7 +28,17 @@ contract GetUniswapV3PoolSlot0BatchRequest {
             IUniswapV3PoolState pool = IUniswapV3PoolState(poolAddress);
             slot0Data.liquidity = pool.liquidity();
 
-            (slot0Data.sqrtPrice, slot0Data.tick, , , , , ) = pool.slot0();
+            (
+                slot0Data.sqrtPrice,
+                slot0Data.tick,
+                slot0Data.observationIndex,
+                slot0Data.observationCardinality,
+                slot0Data.observationCardinalityNext,
+                slot0Data.feeProtocol,
+            ) = pool.slot0();
+
+            slot0Data.feeGrowthGlobal0X128 = pool.feeGrowthGlobal0X128();
+            slot0Data.feeGrowthGlobal1X128 = pool.feeGrowthGlobal1X128();
 
             allSlot0Data[i] = slot0Data;
         }
@@ -80,9 +96,16 @@ interface IUniswapV3PoolState {
             uint16 observationIndex,
             uint16 observationCardinality,
             uint16 observationCardinalityNext,
-            uint8 feeProtocol,
+            // uint8 on Uniswap, widened to fit the packed protocol fees of PancakeSwap V3
+            uint32 feeProtocol,
             bool unlocked
         );
 
     function liquidity() external view returns (uint128);
+
+    /// @notice The fee growth as a Q128.128 fees of token0 collected per unit of liquidity for the entire life of the pool
+    function feeGrowthGlobal0X128() external view returns (uint256);
+
+    /// @notice The fee growth as a Q128.128 fees of token1 collected per unit of liquidity for the entire life of the pool
+    function feeGrowthGlobal1X128() external view returns (uint256);
 }
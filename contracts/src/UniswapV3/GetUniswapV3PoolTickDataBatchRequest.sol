@@ -16,6 +16,8 @@ contract GetUniswapV3PoolTickDataBatchRequest {
         bool initialized;
         uint128 liquidityGross;
         int128 liquidityNet;
+        uint256 feeGrowthOutside0X128;
+        uint256 feeGrowthOutside1X128;
     }
 
     constructor(TickDataInfo[] memory allPoolInfo) {
@@ -31,7 +33,9 @@ contract GetUniswapV3PoolTickDataBatchRequest {
                 tickInfo[j] = Info({
                     liquidityGross: tick.liquidityGross,
                     liquidityNet: tick.liquidityNet,
-                    initialized: tick.initialized
+                    initialized: tick.initialized,
+                    feeGrowthOutside0X128: tick.feeGrowthOutside0X128,
+                    feeGrowthOutside1X128: tick.feeGrowthOutside1X128
                 });
             }
             tickInfoReturn[i] = tickInfo;
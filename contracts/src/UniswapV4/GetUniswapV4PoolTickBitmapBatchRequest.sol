@@ -0,0 +1,78 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @dev This contract is not meant to be deployed. Instead, use a static call with the
+ *       deployment bytecode as payload.
+ */
+contract GetUniswapV4PoolTickBitmapBatchRequest {
+    /// @dev Storage slot of the `pools` mapping in the `PoolManager`
+    uint256 internal constant POOLS_SLOT = 6;
+    /// @dev Offset of the `tickBitmap` mapping within `Pool.State`
+    uint256 internal constant TICK_BITMAP_OFFSET = 5;
+
+    struct TickBitmapInfo {
+        address poolManager;
+        bytes32 poolId;
+        int16 minWord;
+        int16 maxWord;
+    }
+
+    constructor(TickBitmapInfo[] memory allPoolInfo) {
+        uint256[][] memory allTickBitmaps = new uint256[][](allPoolInfo.length);
+
+        for (uint256 i = 0; i < allPoolInfo.length; ++i) {
+            TickBitmapInfo memory info = allPoolInfo[i];
+
+            uint256 tickBitmapSlot = uint256(keccak256(abi.encode(info.poolId, POOLS_SLOT))) + TICK_BITMAP_OFFSET;
+            uint256 wordCount = uint256(int256(info.maxWord) - int256(info.minWord)) + 1;
+
+            bytes32[] memory slots = new bytes32[](wordCount);
+            for (uint256 j = 0; j < wordCount; ++j) {
+                int256 word = int256(info.minWord) + int256(j);
+                slots[j] = keccak256(abi.encode(word, tickBitmapSlot));
+            }
+
+            bytes32[] memory values = IPoolManager(info.poolManager).extsload(slots);
+
+            uint256[] memory tickBitmaps = new uint256[](wordCount * 2);
+
+            uint256 wordIdx = 0;
+            for (uint256 j = 0; j < wordCount; ++j) {
+                uint256 tickBitmap = uint256(values[j]);
+
+                if (tickBitmap == 0) {
+                    continue;
+                }
+
+                tickBitmaps[wordIdx] = uint256(int256(info.minWord) + int256(j));
+                ++wordIdx;
+
+                tickBitmaps[wordIdx] = tickBitmap;
+                ++wordIdx;
+            }
+
+            assembly {
+                mstore(tickBitmaps, wordIdx)
+            }
+
+            allTickBitmaps[i] = tickBitmaps;
+        }
+
+        // ensure abi encoding, not needed here but increase reusability for different return types
+        // note: abi.encode add a first 32 bytes word with the address of the original data
+        bytes memory abiEncodedData = abi.encode(allTickBitmaps);
+
+        assembly {
+            // Return from the start of the data (discarding the original data address)
+            // up to the end of the memory used
+            let dataStart := add(abiEncodedData, 0x20)
+            return(dataStart, sub(msize(), dataStart))
+        }
+    }
+}
+
+interface IPoolManager {
+    /// @notice Called by external contracts to access granular pool state
+    function extsload(bytes32[] calldata slots) external view returns (bytes32[] memory values);
+}
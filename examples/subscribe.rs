@@ -28,19 +28,22 @@ async fn main() -> eyre::Result<()> {
 
     let state_space_manager = StateSpaceBuilder::new(sync_provider.clone())
         .with_factories(factories)
+        .with_discovery()
         .sync()
         .await?;
 
     /*
     The subscribe method listens for new blocks and fetches
     all logs matching any `sync_events()` specified by the AMM variants in the state space.
-    Under the hood, this method applies all state changes to any affected AMMs and returns a Vec of
-    addresses, indicating which AMMs have been updated.
+    Under the hood, this method applies all state changes to any affected AMMs and returns the
+    addresses of the AMMs that have been updated. With discovery enabled, pools created by the
+    factories are also initialized and added to the state space, and returned as new AMMs.
     */
     let mut stream = state_space_manager.subscribe().await?.take(5);
-    while let Some(updated_amms) = stream.next().await {
-        if let Ok(amms) = updated_amms {
-            println!("Updated AMMs: {:?}", amms);
+    while let Some(update) = stream.next().await {
+        if let Ok(update) = update {
+            println!("Updated AMMs: {:?}", update.affected_amms);
+            println!("New AMMs: {:?}", update.new_amms);
         }
     }
 
@@ -0,0 +1,1053 @@
+use std::{collections::HashMap, future::Future};
+
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{aliases::I24, Address, Bytes, B256, I256, U256},
+    providers::Provider,
+    rpc::types::{Filter, FilterSet, Log},
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::info;
+use uniswap_v3_math::tick_math::{MAX_TICK, MIN_TICK};
+
+use super::{
+    amm::{check_amount_out, AutomatedMarketMaker, SwapOutcome, AMM},
+    error::AMMError,
+    factory::{AutomatedMarketMakerFactory, DiscoverySync},
+    uniswap_v3::{
+        check_sqrt_price_limit, compute_swap, default_sqrt_price_limit, price_at_sqrt_price,
+        price_exact_at_sqrt_price, tick_to_word, update_position, CurrentState, Info, ProtocolFee,
+    },
+    Token,
+};
+use GetAlgebraPoolTickDataBatchRequest::TickDataInfo;
+use GetAlgebraPoolTickTableBatchRequest::TickTableInfo;
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract IAlgebraFactory {
+        event Pool(address indexed token0, address indexed token1, address pool);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract IAlgebraPool {
+        function swap(address recipient, bool zeroToOne, int256 amountRequired, uint160 limitSqrtPrice, bytes calldata data) external returns (int256 amount0, int256 amount1);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract IAlgebraPoolEvents {
+        event Mint(
+            address sender,
+            address indexed owner,
+            int24 indexed bottomTick,
+            int24 indexed topTick,
+            uint128 liquidityAmount,
+            uint256 amount0,
+            uint256 amount1
+        );
+
+        event Burn(
+            address indexed owner,
+            int24 indexed bottomTick,
+            int24 indexed topTick,
+            uint128 liquidityAmount,
+            uint256 amount0,
+            uint256 amount1
+        );
+
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 price,
+            uint128 liquidity,
+            int24 tick
+        );
+
+        event Fee(uint16 fee);
+
+        event TickSpacing(int24 newTickSpacing);
+    }
+
+    #[derive(Debug)]
+    struct AlgebraPoolData {
+        address tokenA;
+        uint8 tokenADecimals;
+        address tokenB;
+        uint8 tokenBDecimals;
+        uint128 liquidity;
+        uint160 sqrtPrice;
+        int24 tick;
+        int24 tickSpacing;
+        uint16 fee;
+    }
+}
+
+sol! {
+    #[sol(rpc)]
+    GetAlgebraPoolDataBatchRequest,
+    "src/amms/abi/GetAlgebraPoolDataBatchRequest.json",
+}
+
+sol! {
+    #[sol(rpc)]
+    GetAlgebraPoolTickTableBatchRequest,
+    "src/amms/abi/GetAlgebraPoolTickTableBatchRequest.json",
+}
+
+sol! {
+    #[sol(rpc)]
+    GetAlgebraPoolTickDataBatchRequest,
+    "src/amms/abi/GetAlgebraPoolTickDataBatchRequest.json",
+}
+
+#[derive(Error, Debug)]
+pub enum AlgebraError {
+    #[error("Error initializing Algebra pool")]
+    InitializationError,
+    #[error("Liquidity Underflow")]
+    LiquidityUnderflow,
+}
+
+/// An Algebra concentrated liquidity pool, as deployed by Camelot, QuickSwap V3 and THENA.
+///
+/// Algebra pools follow the Uniswap V3 tick model, but keep their price in `globalState`, charge
+/// a dynamic fee and may change their tick spacing after deployment. Both the fee and the tick
+/// spacing are pool state synced from `Fee` and `TickSpacing` events.
+///
+/// The fee is recalculated by the pool on the first swap of a block, so a simulated swap uses the
+/// fee emitted by the last `Fee` event.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlgebraPool {
+    pub address: Address,
+    pub token_a: Token,
+    pub token_b: Token,
+    pub liquidity: u128,
+    pub sqrt_price: U256,
+    /// Current swap fee in hundredths of a bip
+    pub fee: u32,
+    pub tick: i32,
+    pub tick_spacing: i32,
+    pub tick_bitmap: HashMap<i16, U256>,
+    pub ticks: HashMap<i32, Info>,
+}
+
+impl AutomatedMarketMaker for AlgebraPool {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        vec![
+            IAlgebraPoolEvents::Mint::SIGNATURE_HASH,
+            IAlgebraPoolEvents::Burn::SIGNATURE_HASH,
+            IAlgebraPoolEvents::Swap::SIGNATURE_HASH,
+            IAlgebraPoolEvents::Fee::SIGNATURE_HASH,
+            IAlgebraPoolEvents::TickSpacing::SIGNATURE_HASH,
+        ]
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        let event_signature = log.topics()[0];
+        match event_signature {
+            IAlgebraPoolEvents::Swap::SIGNATURE_HASH => {
+                let swap_event = IAlgebraPoolEvents::Swap::decode_log(log.as_ref())?;
+
+                self.sqrt_price = swap_event.price.to();
+                self.liquidity = swap_event.liquidity;
+                self.tick = swap_event.tick.as_i32();
+
+                info!(
+                    target = "amms::algebra::sync",
+                    address = ?self.address,
+                    sqrt_price = ?self.sqrt_price,
+                    liquidity = ?self.liquidity,
+                    tick = ?self.tick,
+                    "Swap"
+                );
+            }
+            IAlgebraPoolEvents::Mint::SIGNATURE_HASH => {
+                let mint_event = IAlgebraPoolEvents::Mint::decode_log(log.as_ref())?;
+
+                self.modify_position(
+                    mint_event.bottomTick.as_i32(),
+                    mint_event.topTick.as_i32(),
+                    mint_event.liquidityAmount as i128,
+                )?;
+
+                info!(
+                    target = "amms::algebra::sync",
+                    address = ?self.address,
+                    liquidity = ?self.liquidity,
+                    "Mint"
+                );
+            }
+            IAlgebraPoolEvents::Burn::SIGNATURE_HASH => {
+                let burn_event = IAlgebraPoolEvents::Burn::decode_log(log.as_ref())?;
+
+                self.modify_position(
+                    burn_event.bottomTick.as_i32(),
+                    burn_event.topTick.as_i32(),
+                    -(burn_event.liquidityAmount as i128),
+                )?;
+
+                info!(
+                    target = "amms::algebra::sync",
+                    address = ?self.address,
+                    liquidity = ?self.liquidity,
+                    "Burn"
+                );
+            }
+            IAlgebraPoolEvents::Fee::SIGNATURE_HASH => {
+                let fee_event = IAlgebraPoolEvents::Fee::decode_log(log.as_ref())?;
+
+                self.fee = fee_event.fee as u32;
+
+                info!(
+                    target = "amms::algebra::sync",
+                    address = ?self.address,
+                    fee = ?self.fee,
+                    "Fee"
+                );
+            }
+            IAlgebraPoolEvents::TickSpacing::SIGNATURE_HASH => {
+                let tick_spacing_event = IAlgebraPoolEvents::TickSpacing::decode_log(log.as_ref())?;
+
+                // The pool does not rewrite its tick table when the spacing changes, so neither do we
+                self.tick_spacing = tick_spacing_event.newTickSpacing.as_i32();
+
+                info!(
+                    target = "amms::algebra::sync",
+                    address = ?self.address,
+                    tick_spacing = ?self.tick_spacing,
+                    "TickSpacing"
+                );
+            }
+            _ => {
+                return Err(AMMError::UnrecognizedEventSignature(event_signature));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_in.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+
+        Ok(token_out.after_buy_tax((-current_state.amount_calculated).into_raw()))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_in.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+
+        let amount_out = token_out.after_buy_tax((-current_state.amount_calculated).into_raw());
+
+        // Update the pool state
+        self.liquidity = current_state.liquidity;
+        self.sqrt_price = current_state.sqrt_price_x_96;
+        self.tick = current_state.tick;
+
+        Ok(amount_out)
+    }
+
+    fn tokens(&self) -> Vec<Address> {
+        vec![self.token_a.address, self.token_b.address]
+    }
+
+    fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
+        price_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        price_exact_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
+
+    fn simulate_swap_outcome(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let price_before = self.calculate_price(base_token, quote_token)?;
+        if amount_in.is_zero() {
+            return Ok(SwapOutcome::empty(price_before));
+        }
+
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+        let price_after = price_at_sqrt_price(
+            current_state.sqrt_price_x_96,
+            &self.token_a,
+            &self.token_b,
+            base_token,
+        )?;
+
+        Ok(current_state
+            .outcome(
+                token_in.after_sell_tax(amount_in),
+                price_before,
+                price_after,
+            )
+            .with_taxes(amount_in, token_in, token_out))
+    }
+
+    /// Encodes `swap` on the pool as an exact input swap without a price limit. The amount in is
+    /// paid in the swap callback.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        let zero_for_one = base_token == self.token_a.address;
+        Ok(IAlgebraPool::swapCall {
+            recipient,
+            zeroToOne: zero_for_one,
+            amountRequired: I256::from_raw(amount_in),
+            limitSqrtPrice: default_sqrt_price_limit(zero_for_one).to(),
+            data: Bytes::new(),
+        }
+        .abi_encode()
+        .into())
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut pools =
+            AlgebraPool::get_pool_data(vec![self.address], block_number, provider.clone()).await?;
+
+        AlgebraFactory::sync_tick_tables(&mut pools, block_number, provider.clone()).await?;
+        AlgebraFactory::sync_tick_data(&mut pools, block_number, provider).await?;
+
+        pools.pop().ok_or(AlgebraError::InitializationError.into())
+    }
+}
+
+impl AlgebraPool {
+    // Create a new, unsynced Algebra pool
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            ..Default::default()
+        }
+    }
+
+    /// Fetches the global state, liquidity and tick spacing of `pools` at `block_number`, skipping
+    /// addresses that are not Algebra pools.
+    pub async fn get_pool_data<N, P>(
+        pools: Vec<Address>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Self>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let deployer = GetAlgebraPoolDataBatchRequest::deploy_builder(provider, pools.clone());
+        let res = deployer.block(block_number).call_raw().await?;
+
+        let data = <Vec<AlgebraPoolData> as SolValue>::abi_decode(&res)?;
+
+        Ok(pools
+            .into_iter()
+            .zip(data)
+            .filter(|(_, pool_data)| !pool_data.tokenA.is_zero())
+            .map(|(address, pool_data)| AlgebraPool {
+                address,
+                token_a: Token::new_with_decimals(pool_data.tokenA, pool_data.tokenADecimals),
+                token_b: Token::new_with_decimals(pool_data.tokenB, pool_data.tokenBDecimals),
+                liquidity: pool_data.liquidity,
+                sqrt_price: U256::from(pool_data.sqrtPrice),
+                fee: pool_data.fee as u32,
+                tick: pool_data.tick.as_i32(),
+                tick_spacing: pool_data.tickSpacing.as_i32(),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    /// Modifies a positions liquidity in the pool.
+    pub fn modify_position(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i128,
+    ) -> Result<(), AlgebraError> {
+        update_position(
+            &mut self.ticks,
+            &mut self.tick_bitmap,
+            self.tick_spacing,
+            tick_lower,
+            tick_upper,
+            liquidity_delta,
+        );
+
+        // Only positions in range contribute to the active liquidity
+        if self.tick >= tick_lower && self.tick < tick_upper {
+            self.liquidity = self
+                .liquidity
+                .checked_add_signed(liquidity_delta)
+                .ok_or(AlgebraError::LiquidityUnderflow)?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs the swap loop from the current pool state at the current fee, without mutating the
+    /// pool.
+    ///
+    /// A positive `amount_specified` is an exact input swap.
+    pub fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+    ) -> Result<CurrentState, AMMError> {
+        self.swap_to_limit(
+            zero_for_one,
+            amount_specified,
+            default_sqrt_price_limit(zero_for_one),
+        )
+    }
+
+    /// Runs the swap loop from the current pool state at the current fee without mutating the
+    /// pool, stopping once the price reaches `sqrt_price_limit_x_96`.
+    pub fn swap_to_limit(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<CurrentState, AMMError> {
+        let current_state = CurrentState {
+            sqrt_price_x_96: self.sqrt_price,
+            amount_calculated: I256::ZERO,
+            amount_specified_remaining: amount_specified,
+            tick: self.tick,
+            liquidity: self.liquidity,
+            fee_amount: U256::ZERO,
+            protocol_fee: U256::ZERO,
+            ticks_crossed: 0,
+            fee_growth_global_x_128: U256::ZERO,
+            crossed_ticks: vec![],
+        };
+
+        compute_swap(
+            &self.tick_bitmap,
+            &self.ticks,
+            self.tick_spacing,
+            self.fee,
+            ProtocolFee::None,
+            zero_for_one,
+            sqrt_price_limit_x_96,
+            current_state,
+        )
+    }
+
+    /// Simulates swapping `amount_in` of `base_token` with a price limit, as the pool does when
+    /// called with `sqrt_price_limit_x_96`.
+    ///
+    /// The swap stops once the price reaches the limit, so the outcome may be a partial fill that
+    /// consumes less than `amount_in`.
+    pub fn simulate_swap_with_limit(
+        &self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        Ok(self
+            .swap_with_limit(base_token, amount_in, sqrt_price_limit_x_96)?
+            .1)
+    }
+
+    /// Simulates swapping `amount_in` of `base_token` with a price limit, mutating the pool state.
+    pub fn simulate_swap_with_limit_mut(
+        &mut self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let (current_state, outcome) =
+            self.swap_with_limit(base_token, amount_in, sqrt_price_limit_x_96)?;
+
+        self.liquidity = current_state.liquidity;
+        self.sqrt_price = current_state.sqrt_price_x_96;
+        self.tick = current_state.tick;
+
+        Ok(outcome)
+    }
+
+    /// Returns the token sold into and the token bought from the pool.
+    fn swap_tokens(&self, zero_for_one: bool) -> (&Token, &Token) {
+        if zero_for_one {
+            (&self.token_a, &self.token_b)
+        } else {
+            (&self.token_b, &self.token_a)
+        }
+    }
+
+    fn swap_with_limit(
+        &self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<(CurrentState, SwapOutcome), AMMError> {
+        let zero_for_one = base_token == self.token_a.address;
+        check_sqrt_price_limit(self.sqrt_price, sqrt_price_limit_x_96, zero_for_one)?;
+
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let amount_received = token_in.after_sell_tax(amount_in);
+
+        let current_state = self.swap_to_limit(
+            zero_for_one,
+            I256::from_raw(amount_received),
+            sqrt_price_limit_x_96,
+        )?;
+        let outcome = current_state.outcome(
+            amount_received,
+            price_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)?,
+            price_at_sqrt_price(
+                current_state.sqrt_price_x_96,
+                &self.token_a,
+                &self.token_b,
+                base_token,
+            )?,
+        );
+
+        Ok((
+            current_state,
+            outcome.with_taxes(amount_in, token_in, token_out),
+        ))
+    }
+
+    /// Returns every initialized tick in the pool's tick table.
+    fn initialized_ticks(&self) -> Vec<I24> {
+        let mut ticks = vec![];
+        for (word_pos, word) in self.tick_bitmap.iter() {
+            for bit in 0..256 {
+                if word.bit(bit) {
+                    let tick = (*word_pos as i32 * 256 + bit as i32) * self.tick_spacing;
+                    ticks.push(I24::unchecked_from(tick));
+                }
+            }
+        }
+
+        ticks
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AlgebraFactory {
+    pub address: Address,
+    pub creation_block: u64,
+}
+
+impl AlgebraFactory {
+    /// Max number of tick table words read per batch request
+    const TICK_TABLE_STEP: i32 = 6900;
+    /// Max number of ticks read per batch request
+    const TICK_DATA_STEP: usize = 60;
+
+    pub fn new(address: Address, creation_block: u64) -> Self {
+        Self {
+            address,
+            creation_block,
+        }
+    }
+
+    pub async fn get_all_pools<N, P>(
+        &self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let disc_filter = Filter::new()
+            .event_signature(FilterSet::from(vec![self.pool_creation_event()]))
+            .address(vec![self.address()]);
+
+        let sync_provider = provider.clone();
+        let mut futures = FuturesUnordered::new();
+
+        let sync_step = 100_000;
+        let mut latest_block = self.creation_block;
+        while latest_block < block_number.as_u64().unwrap_or_default() {
+            let mut block_filter = disc_filter.clone();
+            let from_block = latest_block;
+            let to_block = (from_block + sync_step).min(block_number.as_u64().unwrap_or_default());
+
+            block_filter = block_filter.from_block(from_block);
+            block_filter = block_filter.to_block(to_block);
+
+            let sync_provider = sync_provider.clone();
+
+            futures.push(async move { sync_provider.get_logs(&block_filter).await });
+
+            latest_block = to_block + 1;
+        }
+
+        let mut pools = vec![];
+        while let Some(res) = futures.next().await {
+            let logs = res?;
+
+            for log in logs {
+                pools.push(self.create_pool(log)?);
+            }
+        }
+
+        Ok(pools)
+    }
+
+    pub async fn sync_all_pools<N, P>(
+        amms: Vec<AMM>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let step = 255;
+        let pools = amms.iter().map(|amm| amm.address()).collect::<Vec<_>>();
+
+        let mut futures_unordered = pools
+            .chunks(step)
+            .map(|group| AlgebraPool::get_pool_data(group.to_vec(), block_number, provider.clone()))
+            .collect::<FuturesUnordered<_>>();
+
+        let mut pools = Vec::new();
+        while let Some(res) = futures_unordered.next().await {
+            pools.extend(res?.into_iter().filter(|pool| {
+                pool.liquidity > 0 && pool.token_a.decimals > 0 && pool.token_b.decimals > 0
+            }));
+        }
+
+        AlgebraFactory::sync_tick_tables(&mut pools, block_number, provider.clone()).await?;
+        AlgebraFactory::sync_tick_data(&mut pools, block_number, provider).await?;
+
+        Ok(pools.into_iter().map(AMM::AlgebraPool).collect())
+    }
+
+    /// Syncs the tick table of each pool across the full tick range.
+    async fn sync_tick_tables<N, P>(
+        pools: &mut [AlgebraPool],
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        // Split the word range of every pool into groups of at most TICK_TABLE_STEP words
+        let mut groups = vec![];
+        let mut group = vec![];
+        let mut group_range = 0;
+        for (idx, pool) in pools.iter().enumerate() {
+            let mut min_word = tick_to_word(MIN_TICK, pool.tick_spacing);
+            let max_word = tick_to_word(MAX_TICK, pool.tick_spacing);
+
+            while min_word <= max_word {
+                let range = (max_word - min_word + 1).min(Self::TICK_TABLE_STEP - group_range);
+
+                group.push((
+                    idx,
+                    TickTableInfo {
+                        pool: pool.address,
+                        minWord: min_word as i16,
+                        maxWord: (min_word + range - 1) as i16,
+                    },
+                ));
+
+                min_word += range;
+                group_range += range;
+
+                if group_range >= Self::TICK_TABLE_STEP {
+                    groups.push(std::mem::take(&mut group));
+                    group_range = 0;
+                }
+            }
+        }
+
+        if !group.is_empty() {
+            groups.push(group);
+        }
+
+        let mut futures = FuturesUnordered::new();
+        for group in groups {
+            let provider = provider.clone();
+            futures.push(async move {
+                let (indices, calldata): (Vec<usize>, Vec<TickTableInfo>) =
+                    group.into_iter().unzip();
+
+                let return_data =
+                    GetAlgebraPoolTickTableBatchRequest::deploy_builder(provider, calldata)
+                        .call_raw()
+                        .block(block_number)
+                        .await?;
+
+                Ok::<(Vec<usize>, Bytes), AMMError>((indices, return_data))
+            });
+        }
+
+        while let Some(res) = futures.next().await {
+            let (indices, return_data) = res?;
+            let return_data = <Vec<Vec<U256>> as SolValue>::abi_decode(&return_data)?;
+
+            for (tick_table, idx) in return_data.iter().zip(indices) {
+                for chunk in tick_table.chunks_exact(2) {
+                    let word_pos = I256::from_raw(chunk[0]).as_i16();
+                    pools[idx].tick_bitmap.insert(word_pos, chunk[1]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Syncs the liquidity of every initialized tick of each pool.
+    async fn sync_tick_data<N, P>(
+        pools: &mut [AlgebraPool],
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        // Split the initialized ticks of every pool into groups of at most TICK_DATA_STEP ticks
+        let mut groups = vec![];
+        let mut group = vec![];
+        let mut group_ticks = 0;
+        for (idx, pool) in pools.iter().enumerate() {
+            let mut ticks = pool.initialized_ticks();
+
+            while !ticks.is_empty() {
+                let selected = ticks
+                    .drain(..(Self::TICK_DATA_STEP - group_ticks).min(ticks.len()))
+                    .collect::<Vec<_>>();
+                group_ticks += selected.len();
+
+                group.push((
+                    idx,
+                    TickDataInfo {
+                        pool: pool.address,
+                        ticks: selected,
+                    },
+                ));
+
+                if group_ticks >= Self::TICK_DATA_STEP {
+                    groups.push(std::mem::take(&mut group));
+                    group_ticks = 0;
+                }
+            }
+        }
+
+        if !group.is_empty() {
+            groups.push(group);
+        }
+
+        let mut futures = FuturesUnordered::new();
+        for group in groups {
+            let provider = provider.clone();
+            futures.push(async move {
+                let calldata = group.iter().map(|(_, info)| info.clone()).collect();
+
+                let return_data =
+                    GetAlgebraPoolTickDataBatchRequest::deploy_builder(provider, calldata)
+                        .call_raw()
+                        .block(block_number)
+                        .await?;
+
+                Ok::<(Vec<(usize, TickDataInfo)>, Bytes), AMMError>((group, return_data))
+            });
+        }
+
+        while let Some(res) = futures.next().await {
+            let (group, return_data) = res?;
+            let return_data = <Vec<Vec<(u128, i128)>> as SolValue>::abi_decode(&return_data)?;
+
+            for (tick_data, (idx, info)) in return_data.iter().zip(group) {
+                for ((liquidity_gross, liquidity_net), tick) in tick_data.iter().zip(info.ticks) {
+                    pools[idx].ticks.insert(
+                        tick.as_i32(),
+                        Info::new(*liquidity_gross, *liquidity_net, *liquidity_gross > 0),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AutomatedMarketMakerFactory for AlgebraFactory {
+    type PoolVariant = AlgebraPool;
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn pool_creation_event(&self) -> B256 {
+        IAlgebraFactory::Pool::SIGNATURE_HASH
+    }
+
+    /// Creates an unsynced pool. The fee and tick spacing are not part of the creation event and
+    /// are read from the pool when it is synced.
+    fn create_pool(&self, log: Log) -> Result<AMM, AMMError> {
+        let event = IAlgebraFactory::Pool::decode_log(&log.inner)?;
+
+        Ok(AMM::AlgebraPool(AlgebraPool {
+            address: event.pool,
+            token_a: event.token0.into(),
+            token_b: event.token1.into(),
+            ..Default::default()
+        }))
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+}
+
+impl DiscoverySync for AlgebraFactory {
+    fn discover<N, P>(
+        &self,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::algebra::discover",
+            address = ?self.address,
+            "Discovering all pools"
+        );
+
+        self.get_all_pools(to_block, provider)
+    }
+
+    fn sync<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::algebra::sync",
+            address = ?self.address,
+            "Syncing all pools"
+        );
+
+        AlgebraFactory::sync_all_pools(amms, to_block, provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::uniswap_v3::UniswapV3Pool;
+    use alloy::primitives::{address, LogData, U160};
+
+    fn weth_usdc_pool() -> AlgebraPool {
+        let mut pool = AlgebraPool {
+            address: address!("B1026b8e7276e7AC75410F1fcbbe21796e8f7526"),
+            token_a: Token::new_with_decimals(
+                address!("82aF49447D8a07e3bd95BD0d56f35241523fBab1"),
+                18,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("af88d065e77c8cC2239327C5EDb3A432268e5831"),
+                6,
+            ),
+            sqrt_price: U256::from(3961400000000000000000000_u128),
+            tick: -198080,
+            tick_spacing: 60,
+            fee: 500,
+            ..Default::default()
+        };
+
+        pool.modify_position(-201060, -195060, 10_000_000_000_000_000)
+            .unwrap();
+        pool.modify_position(-198600, -197580, 40_000_000_000_000_000)
+            .unwrap();
+
+        pool
+    }
+
+    fn pool_log(pool: Address, data: LogData) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address: pool,
+                data,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_matches_uniswap_v3() -> eyre::Result<()> {
+        let pool = weth_usdc_pool();
+
+        // With the same ticks and fee the swap loop must agree with Uniswap V3
+        let uniswap_v3_pool = UniswapV3Pool {
+            address: pool.address,
+            token_a: pool.token_a.clone(),
+            token_b: pool.token_b.clone(),
+            liquidity: pool.liquidity,
+            sqrt_price: pool.sqrt_price,
+            fee: pool.fee,
+            tick: pool.tick,
+            tick_spacing: pool.tick_spacing,
+            tick_bitmap: pool.tick_bitmap.clone(),
+            ticks: pool.ticks.clone(),
+            ..Default::default()
+        };
+
+        for (base_token, amount_in) in [
+            (pool.token_a.address, U256::from(10_u128.pow(18))),
+            (pool.token_a.address, U256::from(50 * 10_u128.pow(18))),
+            (pool.token_b.address, U256::from(2_000_000_000_u128)),
+            (pool.token_b.address, U256::from(100_000_000_000_u128)),
+        ] {
+            let amount_out = pool.simulate_swap(base_token, Address::default(), amount_in)?;
+            let expected =
+                uniswap_v3_pool.simulate_swap(base_token, Address::default(), amount_in)?;
+
+            assert!(!amount_out.is_zero());
+            assert_eq!(amount_out, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_fee_and_tick_spacing() -> eyre::Result<()> {
+        let mut pool = weth_usdc_pool();
+        let amount_in = U256::from(10_u128.pow(18));
+        let amount_out = pool.simulate_swap(pool.token_a.address, Address::default(), amount_in)?;
+
+        let fee_event = IAlgebraPoolEvents::Fee { fee: 3000 };
+        pool.sync(&pool_log(pool.address, fee_event.encode_log_data()))?;
+        assert_eq!(pool.fee, 3000);
+
+        // A higher fee leaves less to swap
+        let amount_out_after_fee =
+            pool.simulate_swap(pool.token_a.address, Address::default(), amount_in)?;
+        assert!(amount_out_after_fee < amount_out);
+
+        let tick_spacing_event = IAlgebraPoolEvents::TickSpacing {
+            newTickSpacing: I24::unchecked_from(10),
+        };
+        pool.sync(&pool_log(
+            pool.address,
+            tick_spacing_event.encode_log_data(),
+        ))?;
+        assert_eq!(pool.tick_spacing, 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_mint_burn_swap() -> eyre::Result<()> {
+        let mut pool = weth_usdc_pool();
+        let liquidity = pool.liquidity;
+
+        let mint_event = IAlgebraPoolEvents::Mint {
+            sender: Address::default(),
+            owner: Address::default(),
+            bottomTick: I24::unchecked_from(-198120),
+            topTick: I24::unchecked_from(-198000),
+            liquidityAmount: 1_000_000,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+        };
+        pool.sync(&pool_log(pool.address, mint_event.encode_log_data()))?;
+        assert_eq!(pool.liquidity, liquidity + 1_000_000);
+        assert!(pool.ticks[&-198120].initialized);
+
+        let burn_event = IAlgebraPoolEvents::Burn {
+            owner: Address::default(),
+            bottomTick: I24::unchecked_from(-198120),
+            topTick: I24::unchecked_from(-198000),
+            liquidityAmount: 1_000_000,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+        };
+        pool.sync(&pool_log(pool.address, burn_event.encode_log_data()))?;
+        assert_eq!(pool.liquidity, liquidity);
+        assert!(!pool.ticks.contains_key(&-198120));
+
+        let swap_event = IAlgebraPoolEvents::Swap {
+            sender: Address::default(),
+            recipient: Address::default(),
+            amount0: I256::ZERO,
+            amount1: I256::ZERO,
+            price: U160::from(3961537733323809029291866_u128),
+            liquidity: 1,
+            tick: I24::unchecked_from(-198079),
+        };
+        pool.sync(&pool_log(pool.address, swap_event.encode_log_data()))?;
+        assert_eq!(pool.liquidity, 1);
+        assert_eq!(pool.tick, -198079);
+
+        Ok(())
+    }
+}
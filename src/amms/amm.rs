@@ -1,26 +1,158 @@
 use super::{
-    balancer::BalancerPool, erc_4626::ERC4626Vault, error::AMMError, uniswap_v2::UniswapV2Pool,
+    algebra::AlgebraPool,
+    balancer::BalancerPool,
+    balancer_v2::BalancerV2Pool,
+    curve::{crypto_swap::CurveCryptoSwapPool, stable_swap::CurveStableSwapPool},
+    erc_4626::ERC4626Vault,
+    error::AMMError,
+    liquidity_book::LiquidityBookPair,
+    solidly::SolidlyPool,
+    uniswap_v2::UniswapV2Pool,
     uniswap_v3::UniswapV3Pool,
+    uniswap_v4::UniswapV4Pool,
+    Token,
 };
 use alloy::{
     eips::BlockId,
     network::Network,
-    primitives::{Address, B256, U256},
+    primitives::{Address, Bytes, B256, U256},
     providers::Provider,
     rpc::types::Log,
 };
 use eyre::Result;
 use serde::{Deserialize, Serialize};
-use std::hash::{Hash, Hasher};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+/// Key of an AMM in the state space.
+///
+/// AMMs are keyed by their address, except for AMMs living in a singleton contract without an
+/// address of their own, such as Uniswap V4 pools, which are keyed by the singleton and their id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AMMKey {
+    Address(Address),
+    Singleton { singleton: Address, id: B256 },
+}
+
+impl From<Address> for AMMKey {
+    fn from(address: Address) -> Self {
+        AMMKey::Address(address)
+    }
+}
+
+impl From<&Address> for AMMKey {
+    fn from(address: &Address) -> Self {
+        AMMKey::Address(*address)
+    }
+}
+
+impl From<&AMMKey> for AMMKey {
+    fn from(key: &AMMKey) -> Self {
+        *key
+    }
+}
+
+impl fmt::Display for AMMKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AMMKey::Address(address) => write!(f, "{address}"),
+            AMMKey::Singleton { singleton, id } => write!(f, "{singleton}/{id}"),
+        }
+    }
+}
+
+/// Detailed result of a simulated swap.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SwapOutcome {
+    /// Amount of `base_token` consumed by the swap, fees included
+    pub amount_in: U256,
+    /// Amount of `quote_token` received
+    pub amount_out: U256,
+    /// Fee paid in `base_token`, if the AMM reports it
+    pub fee: Option<U256>,
+    /// Part of `fee` taken by the protocol rather than liquidity providers, if the AMM reports it
+    pub protocol_fee: Option<U256>,
+    /// Price of `base_token` in terms of `quote_token` before the swap
+    pub price_before: f64,
+    /// Price of `base_token` in terms of `quote_token` after the swap
+    pub price_after: f64,
+    /// Number of initialized ticks or bins crossed by the swap
+    pub ticks_crossed: u32,
+    /// Whether the swap ran out of liquidity before consuming the full amount in
+    pub liquidity_exhausted: bool,
+}
+
+impl SwapOutcome {
+    /// The outcome of swapping nothing at `price`.
+    pub fn empty(price: f64) -> Self {
+        Self {
+            fee: Some(U256::ZERO),
+            price_before: price,
+            price_after: price,
+            ..Default::default()
+        }
+    }
+
+    /// Accounts for the taxes of `token_in` and `token_out` on an outcome simulated with the
+    /// amount the pool received, given that `amount_in` was sent.
+    pub fn with_taxes(mut self, amount_in: U256, token_in: &Token, token_out: &Token) -> Self {
+        self.amount_in = token_in
+            .before_sell_tax(self.amount_in)
+            .map_or(amount_in, |sent| sent.min(amount_in));
+        self.amount_out = token_out.after_buy_tax(self.amount_out);
+        self
+    }
+}
+
+/// The price range liquidity is provided over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LiquidityRange {
+    /// The whole price range, as AMMs with fungible LP shares provide it
+    #[default]
+    Full,
+    /// A tick range of a concentrated liquidity AMM
+    Ticks { tick_lower: i32, tick_upper: i32 },
+}
+
+/// Result of simulated liquidity provision or withdrawal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LiquidityOutcome {
+    /// LP shares, or liquidity of a concentrated liquidity position, minted or burned
+    pub liquidity: U256,
+    /// Amount of each token of the AMM deposited or withdrawn, in the order of `tokens()`
+    pub amounts: Vec<U256>,
+}
 
 #[allow(async_fn_in_trait)]
 pub trait AutomatedMarketMaker {
     /// Address of the AMM
     fn address(&self) -> Address;
 
+    /// Key of the AMM in the state space
+    fn key(&self) -> AMMKey {
+        AMMKey::Address(self.address())
+    }
+
     /// Event signatures that indicate when the AMM should be synced
     fn sync_events(&self) -> Vec<B256>;
 
+    /// Returns the key of the AMM a log should be routed to, for AMMs whose events are emitted by
+    /// a singleton contract rather than by the AMM itself
+    fn log_key(_log: &Log) -> Option<AMMKey>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Returns the addresses of other AMMs whose logs also update the state of this AMM, such as
+    /// the base pool held by a Curve meta pool
+    fn dependencies(&self) -> Vec<Address> {
+        vec![]
+    }
+
     /// Syncs the AMM state
     fn sync(&mut self, log: &Log) -> Result<(), AMMError>;
 
@@ -30,6 +162,16 @@ pub trait AutomatedMarketMaker {
     /// Calculates the price of `base_token` in terms of `quote_token`
     fn calculate_price(&self, base_token: Address, quote_token: Address) -> Result<f64, AMMError>;
 
+    /// Calculates the price of `base_token` in terms of `quote_token` as a Q128.128 fixed point
+    /// number, adjusted for token decimals
+    fn calculate_price_exact(
+        &self,
+        _base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        Err(AMMError::ExactPriceNotSupported(self.address()))
+    }
+
     /// Simulate a swap
     /// Returns the amount_out in `quote token` for a given `amount_in` of `base_token`
     fn simulate_swap(
@@ -48,17 +190,176 @@ pub trait AutomatedMarketMaker {
         amount_in: U256,
     ) -> Result<U256, AMMError>;
 
+    /// Simulate a swap, reporting the fee, price impact and liquidity crossed along with the
+    /// amount out
+    ///
+    /// The default implementation swaps a copy of the AMM and does not report fees or ticks crossed
+    fn simulate_swap_outcome(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<SwapOutcome, AMMError>
+    where
+        Self: Clone + Sized,
+    {
+        let price_before = self.calculate_price(base_token, quote_token)?;
+
+        let mut amm = self.clone();
+        let amount_out = amm.simulate_swap_mut(base_token, quote_token, amount_in)?;
+
+        Ok(SwapOutcome {
+            amount_in,
+            amount_out,
+            price_before,
+            price_after: amm.calculate_price(base_token, quote_token)?,
+            ..Default::default()
+        })
+    }
+
+    /// Simulate an exact output swap
+    /// Returns the amount_in of `base_token` required to receive `amount_out` of `quote_token`
+    fn simulate_swap_exact_out(
+        &self,
+        _base_token: Address,
+        _quote_token: Address,
+        _amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        Err(AMMError::ExactOutNotSupported(self.address()))
+    }
+
+    /// Simulate an exact output swap, mutating the AMM state
+    /// Returns the amount_in of `base_token` required to receive `amount_out` of `quote_token`
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        _base_token: Address,
+        _quote_token: Address,
+        _amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        Err(AMMError::ExactOutNotSupported(self.address()))
+    }
+
+    /// Simulate adding liquidity over `range` with at most `amounts` of each token, in the order of
+    /// `tokens()`
+    /// Returns the liquidity minted and the amounts it takes, which may be less than `amounts`
+    fn simulate_add_liquidity(
+        &self,
+        _range: LiquidityRange,
+        _amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        Err(AMMError::LiquidityNotSupported(self.address()))
+    }
+
+    /// Simulate adding liquidity over `range` with at most `amounts` of each token, mutating the
+    /// AMM state
+    /// Returns the liquidity minted and the amounts it takes, which may be less than `amounts`
+    fn simulate_add_liquidity_mut(
+        &mut self,
+        _range: LiquidityRange,
+        _amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        Err(AMMError::LiquidityNotSupported(self.address()))
+    }
+
+    /// Simulate removing `liquidity` from `range`
+    /// Returns the liquidity burned and the amounts of each token it releases
+    fn simulate_remove_liquidity(
+        &self,
+        _range: LiquidityRange,
+        _liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        Err(AMMError::LiquidityNotSupported(self.address()))
+    }
+
+    /// Simulate removing `liquidity` from `range`, mutating the AMM state
+    /// Returns the liquidity burned and the amounts of each token it releases
+    fn simulate_remove_liquidity_mut(
+        &mut self,
+        _range: LiquidityRange,
+        _liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        Err(AMMError::LiquidityNotSupported(self.address()))
+    }
+
+    /// Calculates the time weighted average price of `base_token` in terms of `quote_token` over
+    /// the `seconds_ago` seconds up to `block_timestamp`, as a Q128.128 fixed point number adjusted
+    /// for token decimals
+    ///
+    /// The average is computed from the oracle state synced locally, so the AMM must track it
+    fn twap(
+        &self,
+        _base_token: Address,
+        _quote_token: Address,
+        _seconds_ago: u32,
+        _block_timestamp: u64,
+    ) -> Result<U256, AMMError> {
+        Err(AMMError::OracleNotTracked(self.address()))
+    }
+
+    /// Encodes the call that swaps `amount_in` of `base_token` for `quote_token` on the AMM, paying
+    /// `recipient` where the call takes one
+    ///
+    /// Errors if less than `min_out` is received when swapping against the local state, and passes
+    /// `min_out` on to calls that take a minimum amount out. The call expects the amount in to be
+    /// paid the AMM's own way, e.g. transferred to a pair beforehand or in a swap callback.
+    fn swap_calldata(
+        &self,
+        _base_token: Address,
+        _quote_token: Address,
+        _amount_in: U256,
+        _min_out: U256,
+        _recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        Err(AMMError::SwapCalldataNotSupported(self.address()))
+    }
+
     // Initializes an empty pool and syncs state up to `block_number`
     async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
     where
         Self: Sized,
         N: Network,
         P: Provider<N> + Clone;
+
+    /// Detects the taxes of the AMM tokens at `block_number` with [`Token::detect_tax`], for AMMs
+    /// that hold balances of the tokens to measure them with. Other AMMs are left unchanged
+    async fn detect_taxes<N, P>(
+        &mut self,
+        _block_number: BlockId,
+        _provider: P,
+    ) -> Result<(), AMMError>
+    where
+        Self: Sized,
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        Ok(())
+    }
+}
+
+/// Simulates swapping `amount_in` of `base_token` for `quote_token`, erroring if less than
+/// `min_out` is received.
+pub fn check_amount_out<A: AutomatedMarketMaker>(
+    amm: &A,
+    base_token: Address,
+    quote_token: Address,
+    amount_in: U256,
+    min_out: U256,
+) -> Result<U256, AMMError> {
+    let amount_out = amm.simulate_swap(base_token, quote_token, amount_in)?;
+    if amount_out < min_out {
+        return Err(AMMError::InsufficientAmountOut {
+            amount_out,
+            min_out,
+        });
+    }
+
+    Ok(amount_out)
 }
 
 macro_rules! amm {
     ($($pool_type:ident),+ $(,)?) => {
         #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[allow(clippy::large_enum_variant)]
         pub enum AMM {
             $($pool_type($pool_type),)+
         }
@@ -76,6 +377,27 @@ macro_rules! amm {
                 }
             }
 
+            fn key(&self) -> AMMKey {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.key(),)+
+                }
+            }
+
+            fn log_key(log: &Log) -> Option<AMMKey> {
+                $(
+                    if let Some(key) = $pool_type::log_key(log) {
+                        return Some(key);
+                    }
+                )+
+                None
+            }
+
+            fn dependencies(&self) -> Vec<Address> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.dependencies(),)+
+                }
+            }
+
             fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
                 match self {
                     $(AMM::$pool_type(pool) => pool.sync(log),)+
@@ -94,6 +416,48 @@ macro_rules! amm {
                 }
             }
 
+            fn simulate_swap_outcome(&self, base_token: Address, quote_token: Address, amount_in: U256) -> Result<SwapOutcome, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_swap_outcome(base_token, quote_token, amount_in),)+
+                }
+            }
+
+            fn simulate_swap_exact_out(&self, base_token: Address, quote_token: Address, amount_out: U256) -> Result<U256, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_swap_exact_out(base_token, quote_token, amount_out),)+
+                }
+            }
+
+            fn simulate_swap_exact_out_mut(&mut self, base_token: Address, quote_token: Address, amount_out: U256) -> Result<U256, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_swap_exact_out_mut(base_token, quote_token, amount_out),)+
+                }
+            }
+
+            fn simulate_add_liquidity(&self, range: LiquidityRange, amounts: &[U256]) -> Result<LiquidityOutcome, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_add_liquidity(range, amounts),)+
+                }
+            }
+
+            fn simulate_add_liquidity_mut(&mut self, range: LiquidityRange, amounts: &[U256]) -> Result<LiquidityOutcome, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_add_liquidity_mut(range, amounts),)+
+                }
+            }
+
+            fn simulate_remove_liquidity(&self, range: LiquidityRange, liquidity: U256) -> Result<LiquidityOutcome, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_remove_liquidity(range, liquidity),)+
+                }
+            }
+
+            fn simulate_remove_liquidity_mut(&mut self, range: LiquidityRange, liquidity: U256) -> Result<LiquidityOutcome, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.simulate_remove_liquidity_mut(range, liquidity),)+
+                }
+            }
+
             fn tokens(&self) -> Vec<Address> {
                 match self {
                     $(AMM::$pool_type(pool) => pool.tokens(),)+
@@ -106,6 +470,25 @@ macro_rules! amm {
                 }
             }
 
+            fn calculate_price_exact(&self, base_token: Address, quote_token: Address) -> Result<U256, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.calculate_price_exact(base_token, quote_token),)+
+                }
+            }
+
+            fn twap(&self, base_token: Address, quote_token: Address, seconds_ago: u32, block_timestamp: u64) -> Result<U256, AMMError> {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.twap(base_token, quote_token, seconds_ago, block_timestamp),)+
+                }
+            }
+
+            fn swap_calldata(&self, base_token: Address, quote_token: Address, amount_in: U256, min_out: U256, recipient: Address) -> Result<Bytes, AMMError> {
+                // Some pools have inherent `swap_calldata` methods taking the raw call arguments
+                match self {
+                    $(AMM::$pool_type(pool) => AutomatedMarketMaker::swap_calldata(pool, base_token, quote_token, amount_in, min_out, recipient),)+
+                }
+            }
+
             async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
             where
                 Self: Sized,
@@ -116,6 +499,17 @@ macro_rules! amm {
                     $(AMM::$pool_type(pool) => pool.init(block_number, provider).await.map(AMM::$pool_type),)+
                 }
             }
+
+            async fn detect_taxes<N, P>(&mut self, block_number: BlockId, provider: P) -> Result<(), AMMError>
+            where
+                Self: Sized,
+                N: Network,
+                P: Provider<N> + Clone,
+            {
+                match self {
+                    $(AMM::$pool_type(pool) => pool.detect_taxes(block_number, provider).await,)+
+                }
+            }
         }
 
 
@@ -134,13 +528,13 @@ macro_rules! amm {
 
         impl Hash for AMM {
             fn hash<H: Hasher>(&self, state: &mut H) {
-                self.address().hash(state);
+                self.key().hash(state);
             }
         }
 
         impl PartialEq for AMM {
             fn eq(&self, other: &Self) -> bool {
-                self.address() == other.address()
+                self.key() == other.key()
             }
         }
 
@@ -156,4 +550,16 @@ macro_rules! amm {
     };
 }
 
-amm!(UniswapV2Pool, UniswapV3Pool, ERC4626Vault, BalancerPool);
+amm!(
+    UniswapV2Pool,
+    UniswapV3Pool,
+    UniswapV4Pool,
+    ERC4626Vault,
+    BalancerPool,
+    BalancerV2Pool,
+    CurveStableSwapPool,
+    CurveCryptoSwapPool,
+    SolidlyPool,
+    AlgebraPool,
+    LiquidityBookPair
+);
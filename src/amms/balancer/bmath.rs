@@ -182,3 +182,97 @@ pub fn calculate_out_given_in(
     let z = bsub(BONE, x)?;
     bmul(token_balance_out, z)
 }
+
+/**********************************************************************************************
+// calcInGivenOut                                                                            //
+// aI = tokenAmountIn                                                                        //
+// bO = tokenBalanceOut               /  /     bO      \    (wO / wI)      \                 //
+// bI = tokenBalanceIn          bI * |  | ------------  | ^            - 1  |                //
+// aO = tokenAmountOut    aI =        \  \ ( bO - aO ) /                   /                 //
+// wI = tokenWeightIn           --------------------------------------------                 //
+// wO = tokenWeightOut                          ( 1 - sF )                                   //
+// sF = swapFee                                                                              //
+ **********************************************************************************************/
+pub fn calculate_in_given_out(
+    token_balance_in: U256,
+    token_weight_in: U256,
+    token_balance_out: U256,
+    token_weight_out: U256,
+    token_amount_out: U256,
+    swap_fee: U256,
+) -> Result<U256, BalancerError> {
+    let weight_ratio = bdiv(token_weight_out, token_weight_in)?;
+    let diff = bsub(token_balance_out, token_amount_out)?;
+    let y = bdiv(token_balance_out, diff)?;
+    let x = bpow(y, weight_ratio)?;
+    let z = bsub(x, BONE)?;
+    let adjusted_in = bsub(BONE, swap_fee)?;
+    bdiv(bmul(token_balance_in, z)?, adjusted_in)
+}
+
+/**********************************************************************************************
+// calcPoolOutGivenSingleIn                                                                  //
+// pAo = poolAmountOut         /                                              \              //
+// tAi = tokenAmountIn        ///      /     //    wI \      \\       \     wI \             //
+// wI = tokenWeightIn        //| tAi *| 1 - || 1 - --  | * sF || + tBi \    --  \            //
+// tW = totalWeight     pAo=||  \      \     \\    tW /      //         | ^ tW   | * pS - pS //
+// tBi = tokenBalanceIn      \\  ------------------------------------- /        /            //
+// pS = poolSupply            \\                    tBi               /        /             //
+// sF = swapFee                \                                              /              //
+ **********************************************************************************************/
+pub fn calculate_pool_out_given_single_in(
+    token_balance_in: U256,
+    token_weight_in: U256,
+    pool_supply: U256,
+    total_weight: U256,
+    token_amount_in: U256,
+    swap_fee: U256,
+) -> Result<U256, BalancerError> {
+    // Charge the trading fee for the proportion of tokenAi
+    // which is implicitly traded to the other pool tokens.
+    let normalized_weight = bdiv(token_weight_in, total_weight)?;
+    let zaz = bmul(bsub(BONE, normalized_weight)?, swap_fee)?;
+    let token_amount_in_after_fee = bmul(token_amount_in, bsub(BONE, zaz)?)?;
+
+    let new_token_balance_in = badd(token_balance_in, token_amount_in_after_fee)?;
+    let token_in_ratio = bdiv(new_token_balance_in, token_balance_in)?;
+
+    let pool_ratio = bpow(token_in_ratio, normalized_weight)?;
+    let new_pool_supply = bmul(pool_ratio, pool_supply)?;
+    bsub(new_pool_supply, pool_supply)
+}
+
+/**********************************************************************************************
+// calcPoolInGivenSingleOut                                                                  //
+// pAi = poolAmountIn               // /               tAo             \\     / wO \     \   //
+// bO = tokenBalanceOut            // | bO - -------------------------- |\   | ---- |     \  //
+// tAo = tokenAmountOut      pS - ||   \     1 - ((1 - (tO / tW)) * sF)/  | ^ \ tW /  * pS | //
+// ps = poolSupply                 \\ -----------------------------------/                /  //
+// wO = tokenWeightOut  pAi =       \\               bO                 /                /   //
+// tW = totalWeight           -------------------------------------------------------------  //
+// sF = swapFee                                        ( 1 - eF )                            //
+// eF = exitFee                                                                              //
+ **********************************************************************************************/
+pub fn calculate_pool_in_given_single_out(
+    token_balance_out: U256,
+    token_weight_out: U256,
+    pool_supply: U256,
+    total_weight: U256,
+    token_amount_out: U256,
+    swap_fee: U256,
+) -> Result<U256, BalancerError> {
+    // Charge the trading fee for the proportion of tokenAo
+    // which is implicitly traded to the other pool tokens.
+    let normalized_weight = bdiv(token_weight_out, total_weight)?;
+    let zar = bmul(bsub(BONE, normalized_weight)?, swap_fee)?;
+    let token_amount_out_before_swap_fee = bdiv(token_amount_out, bsub(BONE, zar)?)?;
+
+    let new_token_balance_out = bsub(token_balance_out, token_amount_out_before_swap_fee)?;
+    let token_out_ratio = bdiv(new_token_balance_out, token_balance_out)?;
+
+    let pool_ratio = bpow(token_out_ratio, normalized_weight)?;
+    let new_pool_supply = bmul(pool_ratio, pool_supply)?;
+
+    // The exit fee of a BPool is zero, so the pool amount in is not grossed up for it
+    bsub(pool_supply, new_pool_supply)
+}
@@ -5,11 +5,11 @@ use std::{collections::HashMap, future::Future};
 use alloy::{
     eips::BlockId,
     network::Network,
-    primitives::{Address, B256, U256},
+    primitives::{Address, Bytes, FixedBytes, B256, U256},
     providers::Provider,
     rpc::types::{Filter, FilterSet, Log},
     sol,
-    sol_types::{SolEvent, SolValue},
+    sol_types::{SolCall, SolEvent, SolInterface, SolValue},
 };
 use async_trait::async_trait;
 use futures::{stream::FuturesUnordered, StreamExt};
@@ -18,13 +18,15 @@ use rug::{float::Round, Float};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::info;
+use uniswap_v3_math::full_math::mul_div;
 
 use super::{
-    amm::{AutomatedMarketMaker, AMM},
+    amm::{check_amount_out, AutomatedMarketMaker, LiquidityOutcome, LiquidityRange, AMM},
     consts::{BONE, MPFR_T_PRECISION},
     error::AMMError,
     factory::{AutomatedMarketMakerFactory, DiscoverySync},
-    float::u256_to_float,
+    float::{float_to_u256_saturating, u256_to_float},
+    price::ratio_to_q128,
     Token,
 };
 
@@ -52,6 +54,21 @@ sol! {
             uint256         tokenAmountOut
         );
 
+        event LOG_CALL(
+            bytes4  indexed sig,
+            address indexed caller,
+            bytes           data
+        ) anonymous;
+
+        function joinPool(uint poolAmountOut, uint[] calldata maxAmountsIn) external;
+        function exitPool(uint poolAmountIn, uint[] calldata minAmountsOut) external;
+        function joinswapExternAmountIn(address tokenIn, uint tokenAmountIn, uint minPoolAmountOut) external returns (uint poolAmountOut);
+        function joinswapPoolAmountOut(address tokenIn, uint poolAmountOut, uint maxAmountIn) external returns (uint tokenAmountIn);
+        function exitswapPoolAmountIn(address tokenOut, uint poolAmountIn, uint minAmountOut) external returns (uint tokenAmountOut);
+        function exitswapExternAmountOut(address tokenOut, uint tokenAmountOut, uint maxPoolAmountIn) external returns (uint poolAmountIn);
+
+        function swapExactAmountIn(address tokenIn, uint tokenAmountIn, address tokenOut, uint minAmountOut, uint maxPrice) external returns (uint tokenAmountOut, uint spotPriceAfter);
+
         function getSpotPrice(address tokenIn, address tokenOut) external returns (uint256);
         function calcOutGivenIn(
             uint tokenBalanceIn,
@@ -100,6 +117,8 @@ pub enum BalancerError {
     SubUnderflow,
     #[error("Multiplication overflow")]
     MulOverflow,
+    #[error("Insufficient pool shares")]
+    InsufficientPoolShares,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
@@ -109,6 +128,9 @@ pub struct BalancerPool {
     state: HashMap<Address, TokenPoolState>,
     /// The Swap Fee on the Pool.
     fee: u32,
+    /// Total supply of pool shares.
+    #[serde(default)]
+    total_supply: U256,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -125,11 +147,14 @@ impl AutomatedMarketMaker for BalancerPool {
     }
 
     fn sync_events(&self) -> Vec<B256> {
-        vec![
+        let mut events = vec![
             IBPool::LOG_SWAP::SIGNATURE_HASH,
             IBPool::LOG_JOIN::SIGNATURE_HASH,
             IBPool::LOG_EXIT::SIGNATURE_HASH,
-        ]
+        ];
+        events.extend(pool_share_calls());
+
+        events
     }
 
     fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
@@ -179,6 +204,16 @@ impl AutomatedMarketMaker for BalancerPool {
                 address = ?self.address,
                 state = ?self.state, "Exit"
             );
+        } else if pool_share_calls().contains(&signature) {
+            let call_event = IBPool::LOG_CALL::decode_log(log.as_ref())?;
+
+            self.sync_total_supply(&call_event.data.data)?;
+
+            info!(
+                target = "amm::balancer::sync",
+                address = ?self.address,
+                total_supply = ?self.total_supply, "Call"
+            );
         } else {
             return Err(AMMError::UnrecognizedEventSignature(signature));
         }
@@ -242,6 +277,33 @@ impl AutomatedMarketMaker for BalancerPool {
         Ok(ratio.to_f64_round(Round::Nearest))
     }
 
+    /// Calculates the spot price of `base_token` in `quote_token` as a Q128.128 fixed point number,
+    /// following the same formula as `calculate_price`.
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        let token_in = self
+            .state
+            .get(&base_token)
+            .ok_or(BalancerError::TokenInDoesNotExist)?;
+
+        let token_out = self
+            .state
+            .get(&quote_token)
+            .ok_or(BalancerError::TokenOutDoesNotExist)?;
+
+        let price = ratio_to_q128(
+            token_out.liquidity * token_in.weight,
+            token_out.token.decimals,
+            token_in.liquidity * token_out.weight,
+            token_in.token.decimals,
+        )?;
+
+        mul_div(price, BONE, BONE - U256::from(self.fee)).map_err(|_| AMMError::PriceOverflow)
+    }
+
     /// Locally simulates a swap in the AMM.
     ///
     /// # Returns
@@ -262,14 +324,16 @@ impl AutomatedMarketMaker for BalancerPool {
             .get(&quote_token)
             .ok_or(BalancerError::TokenOutDoesNotExist)?;
 
-        Ok(bmath::calculate_out_given_in(
+        let amount_out = bmath::calculate_out_given_in(
             token_in.liquidity,
             token_in.weight,
             token_out.liquidity,
             token_out.weight,
-            amount_in,
+            token_in.token.after_sell_tax(amount_in),
             U256::from(self.fee),
-        )?)
+        )?;
+
+        Ok(token_out.token.after_buy_tax(amount_out))
     }
 
     /// Locally simulates a swap in the AMM.
@@ -293,6 +357,7 @@ impl AutomatedMarketMaker for BalancerPool {
             .get(&quote_token)
             .ok_or(BalancerError::TokenOutDoesNotExist)?;
 
+        let amount_in = token_in.token.after_sell_tax(amount_in);
         let out = bmath::calculate_out_given_in(
             token_in.liquidity,
             token_in.weight,
@@ -301,11 +366,157 @@ impl AutomatedMarketMaker for BalancerPool {
             amount_in,
             U256::from(self.fee),
         )?;
+        let amount_received = token_out.token.after_buy_tax(out);
 
         self.state.get_mut(&base_token).unwrap().liquidity += amount_in;
         self.state.get_mut(&quote_token).unwrap().liquidity -= out;
 
-        Ok(out)
+        Ok(amount_received)
+    }
+
+    /// Locally simulates an exact output swap in the AMM.
+    ///
+    /// # Returns
+    /// The amount of `token_in` required to receive `amount_out` of `token_out`.
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        let token_in = self
+            .state
+            .get(&base_token)
+            .ok_or(BalancerError::TokenInDoesNotExist)?;
+
+        let token_out = self
+            .state
+            .get(&quote_token)
+            .ok_or(BalancerError::TokenOutDoesNotExist)?;
+
+        let amount_in = bmath::calculate_in_given_out(
+            token_in.liquidity,
+            token_in.weight,
+            token_out.liquidity,
+            token_out.weight,
+            token_out.token.before_buy_tax(amount_out)?,
+            U256::from(self.fee),
+        )?;
+
+        token_in.token.before_sell_tax(amount_in)
+    }
+
+    /// Locally simulates an exact output swap in the AMM.
+    /// Mutates the AMM state to the state of the AMM after swapping.
+    ///
+    /// # Returns
+    /// The amount of `token_in` required to receive `amount_out` of `token_out`.
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Address,
+        quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        let token_in = self
+            .state
+            .get(&base_token)
+            .ok_or(BalancerError::TokenInDoesNotExist)?;
+
+        let token_out = self
+            .state
+            .get(&quote_token)
+            .ok_or(BalancerError::TokenOutDoesNotExist)?;
+
+        let amount_out = token_out.token.before_buy_tax(amount_out)?;
+        let amount_in = bmath::calculate_in_given_out(
+            token_in.liquidity,
+            token_in.weight,
+            token_out.liquidity,
+            token_out.weight,
+            amount_out,
+            U256::from(self.fee),
+        )?;
+        let amount_sent = token_in.token.before_sell_tax(amount_in)?;
+
+        self.state.get_mut(&base_token).unwrap().liquidity += amount_in;
+        self.state.get_mut(&quote_token).unwrap().liquidity -= amount_out;
+
+        Ok(amount_sent)
+    }
+
+    /// Simulates `joinPool` for the most pool shares that at most `amounts` of each token buy.
+    fn simulate_add_liquidity(
+        &self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        self.join_pool(range, amounts)
+    }
+
+    /// Simulates `joinPool` for the most pool shares that at most `amounts` of each token buy.
+    /// Mutates the AMM state to the state of the AMM after joining.
+    fn simulate_add_liquidity_mut(
+        &mut self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        let outcome = self.join_pool(range, amounts)?;
+
+        for (token_state, amount) in self.state.values_mut().zip(&outcome.amounts) {
+            token_state.liquidity += amount;
+        }
+        self.total_supply += outcome.liquidity;
+
+        Ok(outcome)
+    }
+
+    /// Simulates `exitPool` with `liquidity` pool shares.
+    fn simulate_remove_liquidity(
+        &self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        self.exit_pool(range, liquidity)
+    }
+
+    /// Simulates `exitPool` with `liquidity` pool shares.
+    /// Mutates the AMM state to the state of the AMM after exiting.
+    fn simulate_remove_liquidity_mut(
+        &mut self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        let outcome = self.exit_pool(range, liquidity)?;
+
+        for (token_state, amount) in self.state.values_mut().zip(&outcome.amounts) {
+            token_state.liquidity -= amount;
+        }
+        self.total_supply -= outcome.liquidity;
+
+        Ok(outcome)
+    }
+
+    /// Encodes `swapExactAmountIn` on the pool without a maximum price. The pool pulls the amount
+    /// in from and pays the amount out to the caller, so `recipient` is unused.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        _recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        Ok(IBPool::swapExactAmountInCall {
+            tokenIn: base_token,
+            tokenAmountIn: amount_in,
+            tokenOut: quote_token,
+            minAmountOut: min_out,
+            maxPrice: U256::MAX,
+        }
+        .abi_encode()
+        .into())
     }
 
     async fn init<N, P>(mut self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
@@ -318,10 +529,10 @@ impl AutomatedMarketMaker for BalancerPool {
         let res = deployer.block(block_number).call_raw().await?;
 
         let mut data =
-            <Vec<(Vec<Address>, Vec<u16>, Vec<U256>, Vec<U256>, u32)> as SolValue>::abi_decode(
+            <Vec<(Vec<Address>, Vec<u16>, Vec<U256>, Vec<U256>, u32, U256)> as SolValue>::abi_decode(
                 &res,
             )?;
-        let (tokens, decimals, liquidity, weights, fee) = if !data.is_empty() {
+        let (tokens, decimals, liquidity, weights, fee, total_supply) = if !data.is_empty() {
             data.remove(0)
         } else {
             return Err(BalancerError::InitializationError.into());
@@ -346,6 +557,7 @@ impl AutomatedMarketMaker for BalancerPool {
 
         self.state = token_state;
         self.fee = fee;
+        self.total_supply = total_supply;
 
         Ok(self)
     }
@@ -359,9 +571,215 @@ impl BalancerPool {
             ..Default::default()
         }
     }
+
+    /// Calculates the amount of `base_token` to sell for the marginal rate of a swap into
+    /// `quote_token`, the rate at which its amount out grows with its amount in, to fall to
+    /// `marginal_rate`. The rate is in raw token units, after fees and taxes.
+    ///
+    /// Returns zero if the marginal rate is already at or below `marginal_rate`.
+    pub fn amount_in_to_marginal_rate(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        marginal_rate: f64,
+    ) -> Result<U256, AMMError> {
+        let token_in = self
+            .state
+            .get(&base_token)
+            .ok_or(BalancerError::TokenInDoesNotExist)?;
+
+        let token_out = self
+            .state
+            .get(&quote_token)
+            .ok_or(BalancerError::TokenOutDoesNotExist)?;
+
+        let marginal_rate = token_in
+            .token
+            .untaxed_marginal_rate(&token_out.token, marginal_rate);
+
+        // With w = w_in / w_out, the marginal rate after the pool receives x is
+        // fee * w * b_out * b_in^w / (b_in + fee * x)^(w + 1)
+        let bone = u256_to_float(BONE)?;
+        let fee = (bone.clone() - u256_to_float(U256::from(self.fee))?) / bone;
+        let weight = u256_to_float(token_in.weight)? / u256_to_float(token_out.weight)?;
+        let balance_in = u256_to_float(token_in.liquidity)?;
+        let balance_out = u256_to_float(token_out.liquidity)?;
+
+        // Solved in logarithms, as the powers of the balances overflow
+        let ln_balance: Float = ((fee.clone() * &weight * balance_out / marginal_rate).ln()
+            + weight.clone() * balance_in.clone().ln())
+            / (weight + 1);
+        let amount_received = (ln_balance.exp() - balance_in) / fee;
+
+        // Grossing up an amount past `U256::MAX` for the sell tax saturates as well
+        Ok(token_in
+            .token
+            .before_sell_tax(float_to_u256_saturating(amount_received))
+            .unwrap_or(U256::MAX))
+    }
+
+    /// Returns the pool shares and token amounts of the largest `joinPool` that at most `amounts`
+    /// of each token, in the order of `tokens()`, pay for.
+    fn join_pool(
+        &self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        self.check_liquidity_request(range, amounts.len())?;
+
+        // The pool rounds the amounts in to the nearest unit, so the share ratio is rounded down
+        // a unit further to keep every amount in within its maximum
+        let ratio = self
+            .state
+            .values()
+            .zip(amounts)
+            .map(|(token_state, amount)| {
+                (amount * BONE)
+                    .checked_div(token_state.liquidity)
+                    .unwrap_or_default()
+            })
+            .min()
+            .unwrap_or_default()
+            .saturating_sub(U256::from(1));
+        let pool_amount_out = ratio * self.total_supply / BONE;
+        if pool_amount_out.is_zero() {
+            return Err(BalancerError::InsufficientPoolShares.into());
+        }
+
+        let ratio = bmath::bdiv(pool_amount_out, self.total_supply)?;
+        let amounts = self
+            .state
+            .values()
+            .map(|token_state| bmath::bmul(ratio, token_state.liquidity))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(LiquidityOutcome {
+            liquidity: pool_amount_out,
+            amounts,
+        })
+    }
+
+    /// Returns the token amounts, in the order of `tokens()`, that `exitPool` pays out for
+    /// `pool_amount_in` shares.
+    fn exit_pool(
+        &self,
+        range: LiquidityRange,
+        pool_amount_in: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        self.check_liquidity_request(range, self.state.len())?;
+        if pool_amount_in > self.total_supply {
+            return Err(BalancerError::InsufficientPoolShares.into());
+        }
+
+        // The exit fee of a BPool is zero, so every share is redeemed
+        let ratio = bmath::bdiv(pool_amount_in, self.total_supply)?;
+        let amounts = self
+            .state
+            .values()
+            .map(|token_state| bmath::bmul(ratio, token_state.liquidity))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(LiquidityOutcome {
+            liquidity: pool_amount_in,
+            amounts,
+        })
+    }
+
+    fn check_liquidity_request(
+        &self,
+        range: LiquidityRange,
+        amounts: usize,
+    ) -> Result<(), AMMError> {
+        if range != LiquidityRange::Full {
+            return Err(AMMError::LiquidityNotSupported(self.address));
+        }
+        if amounts != self.state.len() {
+            return Err(AMMError::TokenAmountsMismatch {
+                expected: self.state.len(),
+                actual: amounts,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Updates the total supply for a call that mints or burns pool shares, decoded from the
+    /// calldata logged by `LOG_CALL`. The call is logged before it runs, so balances are those
+    /// the pool priced the call with.
+    fn sync_total_supply(&mut self, calldata: &[u8]) -> Result<(), AMMError> {
+        let total_weight = self
+            .state
+            .values()
+            .map(|token_state| token_state.weight)
+            .sum::<U256>();
+
+        self.total_supply = match IBPool::IBPoolCalls::abi_decode(calldata)? {
+            IBPool::IBPoolCalls::joinPool(call) => {
+                bmath::badd(self.total_supply, call.poolAmountOut)?
+            }
+            IBPool::IBPoolCalls::joinswapPoolAmountOut(call) => {
+                bmath::badd(self.total_supply, call.poolAmountOut)?
+            }
+            IBPool::IBPoolCalls::exitPool(call) => {
+                bmath::bsub(self.total_supply, call.poolAmountIn)?
+            }
+            IBPool::IBPoolCalls::exitswapPoolAmountIn(call) => {
+                bmath::bsub(self.total_supply, call.poolAmountIn)?
+            }
+            IBPool::IBPoolCalls::joinswapExternAmountIn(call) => {
+                let token_in = self
+                    .state
+                    .get(&call.tokenIn)
+                    .ok_or(BalancerError::TokenInDoesNotExist)?;
+                let pool_amount_out = bmath::calculate_pool_out_given_single_in(
+                    token_in.liquidity,
+                    token_in.weight,
+                    self.total_supply,
+                    total_weight,
+                    call.tokenAmountIn,
+                    U256::from(self.fee),
+                )?;
+
+                bmath::badd(self.total_supply, pool_amount_out)?
+            }
+            IBPool::IBPoolCalls::exitswapExternAmountOut(call) => {
+                let token_out = self
+                    .state
+                    .get(&call.tokenOut)
+                    .ok_or(BalancerError::TokenOutDoesNotExist)?;
+                let pool_amount_in = bmath::calculate_pool_in_given_single_out(
+                    token_out.liquidity,
+                    token_out.weight,
+                    self.total_supply,
+                    total_weight,
+                    call.tokenAmountOut,
+                    U256::from(self.fee),
+                )?;
+
+                bmath::bsub(self.total_supply, pool_amount_in)?
+            }
+            _ => self.total_supply,
+        };
+
+        Ok(())
+    }
+}
+
+/// `LOG_CALL` topics of the calls that mint or burn pool shares. A BPool logs every call it
+/// receives as an anonymous event whose first topic is the selector of the call.
+fn pool_share_calls() -> [B256; 6] {
+    [
+        IBPool::joinPoolCall::SELECTOR,
+        IBPool::exitPoolCall::SELECTOR,
+        IBPool::joinswapExternAmountInCall::SELECTOR,
+        IBPool::joinswapPoolAmountOutCall::SELECTOR,
+        IBPool::exitswapPoolAmountInCall::SELECTOR,
+        IBPool::exitswapExternAmountOutCall::SELECTOR,
+    ]
+    .map(|selector| FixedBytes::right_padding_from(&selector))
 }
 
-#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BalancerFactory {
     pub address: Address,
     pub creation_block: u64,
@@ -511,11 +929,11 @@ impl BalancerFactory {
             futures_unordered.push(async move {
                 let res = deployer.call_raw().block(block_number).await?;
 
-                let return_data = <Vec<(Vec<Address>, Vec<u16>, Vec<U256>, Vec<U256>, u32)> as SolValue>::abi_decode(
+                let return_data = <Vec<(Vec<Address>, Vec<u16>, Vec<U256>, Vec<U256>, u32, U256)> as SolValue>::abi_decode(
                     &res,
                 )?;
 
-                Ok::<(Vec<Address>, Vec<(Vec<Address>, Vec<u16>, Vec<U256>, Vec<U256>, u32)>), AMMError>((
+                Ok::<(Vec<Address>, Vec<(Vec<Address>, Vec<u16>, Vec<U256>, Vec<U256>, u32, U256)>), AMMError>((
                     group,
                     return_data,
                 ))
@@ -560,18 +978,13 @@ impl BalancerFactory {
 
                 pool.state = token_state;
                 pool.fee = pool_data.4;
+                pool.total_supply = pool_data.5;
             }
         }
 
         let amms = amms
-            .into_iter()
-            .filter_map(|(_, amm)| {
-                if amm.tokens().iter().any(|t| t.is_zero()) {
-                    None
-                } else {
-                    Some(amm)
-                }
-            })
+            .into_values()
+            .filter(|amm| !amm.tokens().iter().any(|t| t.is_zero()))
             .collect();
 
         Ok(amms)
@@ -580,17 +993,19 @@ impl BalancerFactory {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{collections::HashMap, sync::Arc};
 
     use alloy::{
-        primitives::{address, U256},
+        primitives::{address, Address, U256},
         providers::ProviderBuilder,
+        rpc::types::Log,
+        sol_types::{SolCall, SolEvent},
     };
     use eyre::Ok;
 
     use crate::amms::{
-        amm::AutomatedMarketMaker,
-        balancer::{BalancerPool, IBPool::IBPoolInstance},
+        amm::{AutomatedMarketMaker, LiquidityRange},
+        balancer::{BalancerPool, IBPool, IBPool::IBPoolInstance},
     };
     use crate::amms::{balancer::TokenPoolState, Token};
 
@@ -664,6 +1079,212 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    pub fn test_simulate_swap_exact_out() -> eyre::Result<()> {
+        let weth =
+            Token::new_with_decimals(address!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"), 6);
+
+        let mut balancer_pool = BalancerPool {
+            address: address!("8a649274E4d777FFC6851F13d23A86BBFA2f2Fbf"),
+            state: HashMap::from([
+                (
+                    weth.address,
+                    TokenPoolState {
+                        liquidity: U256::from(10512868599101770417_u128),
+                        weight: U256::from(25000000000000000000_u128),
+                        token: weth.clone(),
+                    },
+                ),
+                (
+                    usdc.address,
+                    TokenPoolState {
+                        liquidity: U256::from(22394300283_u128),
+                        weight: U256::from(25000000000000000000_u128),
+                        token: usdc.clone(),
+                    },
+                ),
+            ]),
+            fee: 640942080,
+            ..Default::default()
+        };
+
+        let amount_out = U256::from(2_000_000_000_u128);
+        let amount_in =
+            balancer_pool.simulate_swap_exact_out(weth.address, usdc.address, amount_out)?;
+
+        // The amount in buys back the amount out, up to the rounding of the fixed point math
+        let exact_in_out = balancer_pool.simulate_swap(weth.address, usdc.address, amount_in)?;
+        assert!(exact_in_out.abs_diff(amount_out) <= U256::from(1_000));
+
+        assert_eq!(
+            balancer_pool.simulate_swap_exact_out_mut(weth.address, usdc.address, amount_out)?,
+            amount_in
+        );
+        assert_eq!(
+            balancer_pool.state[&usdc.address].liquidity,
+            U256::from(20394300283_u128)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_amount_in_to_marginal_rate() -> eyre::Result<()> {
+        let weth =
+            Token::new_with_decimals(address!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"), 6);
+
+        let balancer_pool = BalancerPool {
+            address: address!("8a649274E4d777FFC6851F13d23A86BBFA2f2Fbf"),
+            state: HashMap::from([
+                (
+                    weth.address,
+                    TokenPoolState {
+                        liquidity: U256::from(10512868599101770417_u128),
+                        weight: U256::from(40000000000000000000_u128),
+                        token: weth.clone(),
+                    },
+                ),
+                (
+                    usdc.address,
+                    TokenPoolState {
+                        liquidity: U256::from(22394300283_u128),
+                        weight: U256::from(10000000000000000000_u128),
+                        token: usdc.clone(),
+                    },
+                ),
+            ]),
+            fee: 640942080,
+            ..Default::default()
+        };
+
+        // The marginal rate of a swap of `amount_in`, measured over a small increment
+        let probe = U256::from(10_u64.pow(12));
+        let marginal_rate = |amount_in: U256| -> eyre::Result<f64> {
+            let amount_out = balancer_pool.simulate_swap(weth.address, usdc.address, amount_in)?;
+            let probed_out =
+                balancer_pool.simulate_swap(weth.address, usdc.address, amount_in + probe)?;
+            Ok(f64::from(probed_out - amount_out) / f64::from(probe))
+        };
+        let spot_rate = marginal_rate(U256::ZERO)?;
+
+        assert_eq!(
+            balancer_pool.amount_in_to_marginal_rate(
+                weth.address,
+                usdc.address,
+                spot_rate * 1.1
+            )?,
+            U256::ZERO
+        );
+
+        let target_rate = spot_rate * 0.9;
+        let amount_in =
+            balancer_pool.amount_in_to_marginal_rate(weth.address, usdc.address, target_rate)?;
+        assert!((marginal_rate(amount_in)? / target_rate - 1.0).abs() < 1e-3);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_simulate_liquidity() -> eyre::Result<()> {
+        let weth =
+            Token::new_with_decimals(address!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"), 6);
+
+        let balancer_pool = BalancerPool {
+            address: address!("8a649274E4d777FFC6851F13d23A86BBFA2f2Fbf"),
+            state: HashMap::from([
+                (
+                    weth.address,
+                    TokenPoolState {
+                        liquidity: U256::from(10512868599101770417_u128),
+                        weight: U256::from(25000000000000000000_u128),
+                        token: weth.clone(),
+                    },
+                ),
+                (
+                    usdc.address,
+                    TokenPoolState {
+                        liquidity: U256::from(22394300283_u128),
+                        weight: U256::from(25000000000000000000_u128),
+                        token: usdc.clone(),
+                    },
+                ),
+            ]),
+            fee: 640942080,
+            total_supply: U256::from(100_u128 * 10_u128.pow(18)),
+        };
+
+        // 1% of the WETH balance with plenty of USDC joins for about 1% of the shares
+        let amounts = balancer_pool
+            .tokens()
+            .iter()
+            .map(|token| {
+                if *token == weth.address {
+                    U256::from(105128685991017704_u128)
+                } else {
+                    U256::from(10_u128.pow(12))
+                }
+            })
+            .collect::<Vec<_>>();
+        let outcome = balancer_pool.simulate_add_liquidity(LiquidityRange::Full, &amounts)?;
+        assert!(outcome
+            .amounts
+            .iter()
+            .zip(&amounts)
+            .all(|(used, max)| used <= max));
+        assert!(
+            outcome.liquidity.abs_diff(U256::from(10_u128.pow(18))) < U256::from(10_u128.pow(3))
+        );
+
+        let mut joined = balancer_pool.clone();
+        assert_eq!(
+            joined.simulate_add_liquidity_mut(LiquidityRange::Full, &amounts)?,
+            outcome
+        );
+
+        // A join is synced from the call the pool logs
+        let mut synced = balancer_pool.clone();
+        let call_event = IBPool::LOG_CALL {
+            sig: IBPool::joinPoolCall::SELECTOR.into(),
+            caller: Address::default(),
+            data: IBPool::joinPoolCall {
+                poolAmountOut: outcome.liquidity,
+                maxAmountsIn: amounts.clone(),
+            }
+            .abi_encode()
+            .into(),
+        };
+        synced.sync(&Log {
+            inner: alloy::primitives::Log {
+                address: balancer_pool.address,
+                data: call_event.encode_log_data(),
+            },
+            ..Default::default()
+        })?;
+        assert_eq!(synced.total_supply, joined.total_supply);
+
+        // Exiting with the shares joined pays out no more than was paid in
+        let exited =
+            joined.simulate_remove_liquidity_mut(LiquidityRange::Full, outcome.liquidity)?;
+        assert!(exited
+            .amounts
+            .iter()
+            .zip(&outcome.amounts)
+            .all(|(out, paid)| out <= paid && paid - out <= U256::from(1)));
+        assert_eq!(joined.total_supply, balancer_pool.total_supply);
+
+        assert!(balancer_pool
+            .simulate_add_liquidity(LiquidityRange::Full, &amounts[..1])
+            .is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     pub async fn test_simulate_swap() -> eyre::Result<()> {
         let provider = Arc::new(
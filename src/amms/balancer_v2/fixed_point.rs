@@ -0,0 +1,110 @@
+//! Port of Balancer's `FixedPoint` library for 18 decimal fixed point numbers, rounding in the
+//! direction the pools do.
+//!
+//! Reference:
+//! https://github.com/balancer/balancer-v2-monorepo/blob/master/pkg/solidity-utils/contracts/math/FixedPoint.sol
+
+use alloy::primitives::U256;
+
+use super::{log_exp_math, BalancerV2Error};
+use crate::amms::consts::{BONE, U256_1, U256_10000, U256_2, U256_4};
+
+/// Relative error bound of `log_exp_math::pow`, used to round its result.
+const MAX_POW_RELATIVE_ERROR: U256 = U256_10000;
+
+pub fn sub(a: U256, b: U256) -> Result<U256, BalancerV2Error> {
+    a.checked_sub(b).ok_or(BalancerV2Error::SubUnderflow)
+}
+
+pub fn mul_down(a: U256, b: U256) -> U256 {
+    a * b / BONE
+}
+
+pub fn mul_up(a: U256, b: U256) -> U256 {
+    let product = a * b;
+    if product.is_zero() {
+        U256::ZERO
+    } else {
+        (product - U256_1) / BONE + U256_1
+    }
+}
+
+pub fn div_down(a: U256, b: U256) -> Result<U256, BalancerV2Error> {
+    if b.is_zero() {
+        return Err(BalancerV2Error::DivZero);
+    }
+
+    Ok(a * BONE / b)
+}
+
+pub fn div_up(a: U256, b: U256) -> Result<U256, BalancerV2Error> {
+    if b.is_zero() {
+        return Err(BalancerV2Error::DivZero);
+    }
+
+    if a.is_zero() {
+        Ok(U256::ZERO)
+    } else {
+        Ok((a * BONE - U256_1) / b + U256_1)
+    }
+}
+
+/// Computes `x^y` rounding down. The result is guaranteed not to be above the true value.
+pub fn pow_down(x: U256, y: U256) -> Result<U256, BalancerV2Error> {
+    // Common exponents are computed exactly
+    if y == BONE {
+        Ok(x)
+    } else if y == BONE * U256_2 {
+        Ok(mul_down(x, x))
+    } else if y == BONE * U256_4 {
+        let square = mul_down(x, x);
+        Ok(mul_down(square, square))
+    } else {
+        let raw = log_exp_math::pow(x, y)?;
+        let max_error = mul_up(raw, MAX_POW_RELATIVE_ERROR) + U256_1;
+        Ok(raw.saturating_sub(max_error))
+    }
+}
+
+/// Computes `x^y` rounding up. The result is guaranteed not to be below the true value.
+pub fn pow_up(x: U256, y: U256) -> Result<U256, BalancerV2Error> {
+    if y == BONE {
+        Ok(x)
+    } else if y == BONE * U256_2 {
+        Ok(mul_up(x, x))
+    } else if y == BONE * U256_4 {
+        let square = mul_up(x, x);
+        Ok(mul_up(square, square))
+    } else {
+        let raw = log_exp_math::pow(x, y)?;
+        let max_error = mul_up(raw, MAX_POW_RELATIVE_ERROR) + U256_1;
+        Ok(raw + max_error)
+    }
+}
+
+/// Returns `1 - x`, saturating at zero.
+pub fn complement(x: U256) -> U256 {
+    BONE.saturating_sub(x)
+}
+
+/// Integer division rounding up, as in Balancer's `Math.divUp`.
+pub fn div_up_raw(a: U256, b: U256) -> Result<U256, BalancerV2Error> {
+    if b.is_zero() {
+        return Err(BalancerV2Error::DivZero);
+    }
+
+    if a.is_zero() {
+        Ok(U256::ZERO)
+    } else {
+        Ok((a - U256_1) / b + U256_1)
+    }
+}
+
+/// Integer division rounding down, as in Balancer's `Math.divDown`.
+pub fn div_down_raw(a: U256, b: U256) -> Result<U256, BalancerV2Error> {
+    if b.is_zero() {
+        return Err(BalancerV2Error::DivZero);
+    }
+
+    Ok(a / b)
+}
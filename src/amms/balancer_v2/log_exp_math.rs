@@ -0,0 +1,227 @@
+//! Port of Balancer's `LogExpMath` library, computing `x^y` for 18 decimal fixed point numbers
+//! through natural logarithms and exponentials.
+//!
+//! Reference:
+//! https://github.com/balancer/balancer-v2-monorepo/blob/master/pkg/solidity-utils/contracts/math/LogExpMath.sol
+
+use alloy::primitives::{uint, I256, U256};
+
+use super::BalancerV2Error;
+
+const ONE_18: I256 = I256::from_raw(uint!(1000000000000000000_U256));
+const ONE_20: I256 = I256::from_raw(uint!(100000000000000000000_U256));
+const ONE_36: I256 = I256::from_raw(uint!(1000000000000000000000000000000000000_U256));
+
+const MAX_NATURAL_EXPONENT: I256 = I256::from_raw(uint!(130000000000000000000_U256));
+// -41e18 in two's complement
+const MIN_NATURAL_EXPONENT: I256 = I256::from_raw(U256::from_limbs([
+    14340232221128654848,
+    18446744073709551613,
+    18446744073709551615,
+    18446744073709551615,
+]));
+
+const LN_36_LOWER_BOUND: I256 = I256::from_raw(uint!(900000000000000000_U256));
+const LN_36_UPPER_BOUND: I256 = I256::from_raw(uint!(1100000000000000000_U256));
+
+// 2^254 / ONE_20
+const MILD_EXPONENT_BOUND: U256 =
+    uint!(289480223093290488558927462521719769633174961664101410098_U256);
+
+// 18 decimal constants
+const X0: I256 = I256::from_raw(uint!(128000000000000000000_U256)); // 2^7
+const A0: I256 = I256::from_raw(uint!(
+    38877084059945950922200000000000000000000000000000000000_U256
+)); // e^(x0) (no decimals)
+const X1: I256 = I256::from_raw(uint!(64000000000000000000_U256)); // 2^6
+const A1: I256 = I256::from_raw(uint!(6235149080811616882910000000_U256)); // e^(x1) (no decimals)
+
+// 20 decimal constants
+const X2: I256 = I256::from_raw(uint!(3200000000000000000000_U256)); // 2^5
+const A2: I256 = I256::from_raw(uint!(7896296018268069516100000000000000_U256)); // e^(x2)
+const X3: I256 = I256::from_raw(uint!(1600000000000000000000_U256)); // 2^4
+const A3: I256 = I256::from_raw(uint!(888611052050787263676000000_U256)); // e^(x3)
+const X4: I256 = I256::from_raw(uint!(800000000000000000000_U256)); // 2^3
+const A4: I256 = I256::from_raw(uint!(298095798704172827474000_U256)); // e^(x4)
+const X5: I256 = I256::from_raw(uint!(400000000000000000000_U256)); // 2^2
+const A5: I256 = I256::from_raw(uint!(5459815003314423907810_U256)); // e^(x5)
+const X6: I256 = I256::from_raw(uint!(200000000000000000000_U256)); // 2^1
+const A6: I256 = I256::from_raw(uint!(738905609893065022723_U256)); // e^(x6)
+const X7: I256 = I256::from_raw(uint!(100000000000000000000_U256)); // 2^0
+const A7: I256 = I256::from_raw(uint!(271828182845904523536_U256)); // e^(x7)
+const X8: I256 = I256::from_raw(uint!(50000000000000000000_U256)); // 2^-1
+const A8: I256 = I256::from_raw(uint!(164872127070012814685_U256)); // e^(x8)
+const X9: I256 = I256::from_raw(uint!(25000000000000000000_U256)); // 2^-2
+const A9: I256 = I256::from_raw(uint!(128402541668774148407_U256)); // e^(x9)
+const X10: I256 = I256::from_raw(uint!(12500000000000000000_U256)); // 2^-3
+const A10: I256 = I256::from_raw(uint!(113314845306682631683_U256)); // e^(x10)
+const X11: I256 = I256::from_raw(uint!(6250000000000000000_U256)); // 2^-4
+const A11: I256 = I256::from_raw(uint!(106449445891785942956_U256)); // e^(x11)
+
+/// Computes `x^y` for 18 decimal fixed point `x` and `y`, with a relative error of at most
+/// `10^-14` in practice.
+pub fn pow(x: U256, y: U256) -> Result<U256, BalancerV2Error> {
+    if y.is_zero() {
+        return Ok(ONE_18.into_raw());
+    }
+
+    if x.is_zero() {
+        return Ok(U256::ZERO);
+    }
+
+    if x.bit(255) {
+        return Err(BalancerV2Error::XOutOfBounds);
+    }
+    let x = I256::from_raw(x);
+
+    if y >= MILD_EXPONENT_BOUND {
+        return Err(BalancerV2Error::YOutOfBounds);
+    }
+    let y = I256::from_raw(y);
+
+    // ln(x) is computed with 36 decimals of precision when x is close to one
+    let logx_times_y = if LN_36_LOWER_BOUND < x && x < LN_36_UPPER_BOUND {
+        let ln_36_x = ln_36(x);
+        ((ln_36_x / ONE_18) * y + ((ln_36_x % ONE_18) * y) / ONE_18) / ONE_18
+    } else {
+        (ln(x) * y) / ONE_18
+    };
+
+    if logx_times_y < MIN_NATURAL_EXPONENT || logx_times_y > MAX_NATURAL_EXPONENT {
+        return Err(BalancerV2Error::ProductOutOfBounds);
+    }
+
+    Ok(exp(logx_times_y)?.into_raw())
+}
+
+/// Computes `e^x` for an 18 decimal fixed point `x`.
+pub fn exp(x: I256) -> Result<I256, BalancerV2Error> {
+    if x < MIN_NATURAL_EXPONENT || x > MAX_NATURAL_EXPONENT {
+        return Err(BalancerV2Error::InvalidExponent);
+    }
+
+    if x.is_negative() {
+        // e^(-x) = 1 / e^x
+        return Ok((ONE_18 * ONE_18) / exp(-x)?);
+    }
+
+    // The largest powers are stored without decimals, and are only used when x is large enough
+    // for the result to have no meaningful decimals
+    let mut x = x;
+    let first_an = if x >= X0 {
+        x -= X0;
+        A0
+    } else if x >= X1 {
+        x -= X1;
+        A1
+    } else {
+        I256::ONE
+    };
+
+    // The remaining terms use 20 decimals
+    x *= I256::from_raw(U256::from(100));
+
+    let mut product = ONE_20;
+    for (x_n, a_n) in [
+        (X2, A2),
+        (X3, A3),
+        (X4, A4),
+        (X5, A5),
+        (X6, A6),
+        (X7, A7),
+        (X8, A8),
+        (X9, A9),
+    ] {
+        if x >= x_n {
+            x -= x_n;
+            product = (product * a_n) / ONE_20;
+        }
+    }
+
+    // x is now smaller than x9, the Taylor series converges quickly from here
+    let mut series_sum = ONE_20;
+    let mut term = x;
+    series_sum += term;
+    for n in 2..=12_u64 {
+        term = ((term * x) / ONE_20) / I256::from_raw(U256::from(n));
+        series_sum += term;
+    }
+
+    Ok((((product * series_sum) / ONE_20) * first_an) / I256::from_raw(U256::from(100)))
+}
+
+/// Computes `ln(a)` for an 18 decimal fixed point `a`.
+fn ln(a: I256) -> I256 {
+    if a < ONE_18 {
+        // ln(a) = -ln(1 / a)
+        return -ln((ONE_18 * ONE_18) / a);
+    }
+
+    let mut a = a;
+    let mut sum = I256::ZERO;
+    if a >= A0 * ONE_18 {
+        a /= A0;
+        sum += X0;
+    }
+
+    if a >= A1 * ONE_18 {
+        a /= A1;
+        sum += X1;
+    }
+
+    // The remaining terms use 20 decimals
+    let hundred = I256::from_raw(U256::from(100));
+    sum *= hundred;
+    a *= hundred;
+
+    for (x_n, a_n) in [
+        (X2, A2),
+        (X3, A3),
+        (X4, A4),
+        (X5, A5),
+        (X6, A6),
+        (X7, A7),
+        (X8, A8),
+        (X9, A9),
+        (X10, A10),
+        (X11, A11),
+    ] {
+        if a >= a_n {
+            a = (a * ONE_20) / a_n;
+            sum += x_n;
+        }
+    }
+
+    // ln(a) = 2 * (z + z^3 / 3 + z^5 / 5 + ...) where z = (a - 1) / (a + 1)
+    let z = ((a - ONE_20) * ONE_20) / (a + ONE_20);
+    let z_squared = (z * z) / ONE_20;
+
+    let mut num = z;
+    let mut series_sum = num;
+    for n in [3_u64, 5, 7, 9, 11] {
+        num = (num * z_squared) / ONE_20;
+        series_sum += num / I256::from_raw(U256::from(n));
+    }
+
+    series_sum *= I256::from_raw(U256::from(2));
+
+    (sum + series_sum) / hundred
+}
+
+/// Computes `ln(x)` with 36 decimals of precision for an 18 decimal fixed point `x` close to one.
+fn ln_36(x: I256) -> I256 {
+    let x = x * ONE_18;
+
+    // ln(x) = 2 * (z + z^3 / 3 + z^5 / 5 + ...) where z = (x - 1) / (x + 1)
+    let z = ((x - ONE_36) * ONE_36) / (x + ONE_36);
+    let z_squared = (z * z) / ONE_36;
+
+    let mut num = z;
+    let mut series_sum = num;
+    for n in [3_u64, 5, 7, 9, 11, 13, 15] {
+        num = (num * z_squared) / ONE_36;
+        series_sum += num / I256::from_raw(U256::from(n));
+    }
+
+    series_sum * I256::from_raw(U256::from(2))
+}
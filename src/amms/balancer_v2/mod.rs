@@ -0,0 +1,1332 @@
+pub mod fixed_point;
+pub mod log_exp_math;
+pub mod stable_math;
+pub mod weighted_math;
+
+use std::{collections::HashMap, future::Future};
+
+use alloy::{
+    consensus::BlockHeader,
+    eips::{BlockId, BlockNumberOrTag},
+    network::{BlockResponse, Network},
+    primitives::{Address, Bytes, B256, I256, U256},
+    providers::Provider,
+    rpc::types::{Filter, FilterSet, Log},
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use rug::{float::Round, Float};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::info;
+
+use super::{
+    amm::{check_amount_out, AMMKey, AutomatedMarketMaker, AMM},
+    consts::{BALANCER_V2_AMP_PRECISION, BONE, MPFR_T_PRECISION, U256_10},
+    error::AMMError,
+    factory::{AutomatedMarketMakerFactory, DiscoverySync},
+    float::{float_to_q128, u256_to_float},
+    Token,
+};
+use fixed_point::{div_down, mul_down, mul_up};
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract IBalancerV2Vault {
+        event Swap(bytes32 indexed poolId, address indexed tokenIn, address indexed tokenOut, uint256 amountIn, uint256 amountOut);
+        event PoolBalanceChanged(bytes32 indexed poolId, address indexed liquidityProvider, address[] tokens, int256[] deltas, uint256[] protocolFeeAmounts);
+        event PoolBalanceManaged(bytes32 indexed poolId, address indexed assetManager, address indexed token, int256 cashDelta, int256 managedDelta);
+        event PoolRegistered(bytes32 indexed poolId, address indexed poolAddress, uint8 specialization);
+
+        struct BatchSwapStep {
+            bytes32 poolId;
+            uint256 assetInIndex;
+            uint256 assetOutIndex;
+            uint256 amount;
+            bytes userData;
+        }
+
+        struct FundManagement {
+            address sender;
+            bool fromInternalBalance;
+            address recipient;
+            bool toInternalBalance;
+        }
+
+        struct SingleSwap {
+            bytes32 poolId;
+            uint8 kind;
+            address assetIn;
+            address assetOut;
+            uint256 amount;
+            bytes userData;
+        }
+
+        function swap(SingleSwap memory singleSwap, FundManagement memory funds, uint256 limit, uint256 deadline) external payable returns (uint256 amountCalculated);
+        function queryBatchSwap(uint8 kind, BatchSwapStep[] memory swaps, address[] memory assets, FundManagement memory funds) external returns (int256[] memory assetDeltas);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract IBalancerV2Pool {
+        event SwapFeePercentageChanged(uint256 swapFeePercentage);
+        event AmpUpdateStarted(uint256 startValue, uint256 endValue, uint256 startTime, uint256 endTime);
+        event AmpUpdateStopped(uint256 currentValue);
+        event TokenRateCacheUpdated(uint256 indexed tokenIndex, uint256 rate);
+        event PriceRateCacheUpdated(address indexed token, uint256 rate);
+    }
+
+    #[derive(Debug)]
+    struct BalancerV2PoolData {
+        bytes32 poolId;
+        address[] tokens;
+        uint8[] decimals;
+        uint256[] balances;
+        uint256[] scalingFactors;
+        uint256[] weights;
+        uint256 amp;
+        bool ampUpdating;
+        uint256 swapFee;
+        uint256 bptIndex;
+        uint256 totalSupply;
+    }
+}
+
+sol! {
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IGetBalancerV2PoolDataBatchRequest,
+    "src/amms/abi/GetBalancerV2PoolDataBatchRequest.json",
+}
+
+#[derive(Error, Debug)]
+pub enum BalancerV2Error {
+    #[error("Error initializing Balancer V2 pool")]
+    InitializationError,
+    #[error("Token not in pool")]
+    TokenNotInPool,
+    #[error("Amount in exceeds the maximum in ratio")]
+    MaxInRatio,
+    #[error("Division by zero")]
+    DivZero,
+    #[error("Subtraction underflow")]
+    SubUnderflow,
+    #[error("Addition overflow")]
+    AddOverflow,
+    #[error("Invariant did not converge")]
+    InvariantDidNotConverge,
+    #[error("Token balance did not converge")]
+    BalanceDidNotConverge,
+    #[error("Base out of bounds")]
+    XOutOfBounds,
+    #[error("Exponent out of bounds")]
+    YOutOfBounds,
+    #[error("Product of the exponent and the logarithm of the base out of bounds")]
+    ProductOutOfBounds,
+    #[error("Invalid natural exponent")]
+    InvalidExponent,
+}
+
+/// The invariant a Balancer V2 pool prices swaps with.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BalancerV2PoolKind {
+    #[default]
+    Weighted,
+    Stable,
+    /// A stable pool that registers its own BPT as a pool token at `bpt_index`. Swaps in and out
+    /// of the BPT are single token joins and exits.
+    ComposableStable {
+        bpt_index: usize,
+    },
+}
+
+/// A pool whose balances are held by the Balancer V2 Vault.
+///
+/// The Vault emits swaps and liquidity changes keyed by pool id, the leading 20 bytes of which
+/// are the pool address. Parameters such as the swap fee, amplification and token rates are
+/// emitted by the pool itself.
+///
+/// Composable stable pools mint BPT for protocol fees and proportional joins outside of the
+/// Vault's events. Their supply is tracked through the invariant growth of liquidity changes,
+/// which makes BPT swaps approximate until the pool is synced again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BalancerV2Pool {
+    pub address: Address,
+    pub vault: Address,
+    pub pool_id: B256,
+    pub kind: BalancerV2PoolKind,
+    pub tokens: Vec<Token>,
+    /// Vault balances of each token, including balances held by asset managers
+    pub balances: Vec<U256>,
+    /// Factors upscaling each token to 18 decimals and applying its rate, as 18 decimal fixed
+    /// point numbers
+    pub scaling_factors: Vec<U256>,
+    /// Normalized weights of weighted pools
+    pub weights: Vec<U256>,
+    /// Amplification parameter at `amp_start_time`, multiplied by [`BALANCER_V2_AMP_PRECISION`]
+    pub amp_start: U256,
+    /// Amplification parameter from `amp_end_time` onwards
+    pub amp_end: U256,
+    pub amp_start_time: u64,
+    pub amp_end_time: u64,
+    /// Swap fee as an 18 decimal fixed point number
+    pub swap_fee: U256,
+    /// Total supply of the BPT, including BPT held by the Vault for composable stable pools
+    pub total_supply: U256,
+    /// Timestamp of the block the pool is synced to, at which the amplification is evaluated
+    #[serde(default)]
+    pub block_timestamp: u64,
+}
+
+/// Returns the address of the pool identified by `pool_id`.
+pub fn pool_id_to_address(pool_id: B256) -> Address {
+    Address::from_slice(&pool_id[..20])
+}
+
+impl AutomatedMarketMaker for BalancerV2Pool {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        vec![
+            IBalancerV2Vault::Swap::SIGNATURE_HASH,
+            IBalancerV2Vault::PoolBalanceChanged::SIGNATURE_HASH,
+            IBalancerV2Vault::PoolBalanceManaged::SIGNATURE_HASH,
+            IBalancerV2Pool::SwapFeePercentageChanged::SIGNATURE_HASH,
+            IBalancerV2Pool::AmpUpdateStarted::SIGNATURE_HASH,
+            IBalancerV2Pool::AmpUpdateStopped::SIGNATURE_HASH,
+            IBalancerV2Pool::TokenRateCacheUpdated::SIGNATURE_HASH,
+            IBalancerV2Pool::PriceRateCacheUpdated::SIGNATURE_HASH,
+        ]
+    }
+
+    fn log_key(log: &Log) -> Option<AMMKey> {
+        let topics = log.topics();
+        match topics.first() {
+            Some(&IBalancerV2Vault::Swap::SIGNATURE_HASH)
+            | Some(&IBalancerV2Vault::PoolBalanceChanged::SIGNATURE_HASH)
+            | Some(&IBalancerV2Vault::PoolBalanceManaged::SIGNATURE_HASH) => topics
+                .get(1)
+                .map(|pool_id| pool_id_to_address(*pool_id).into()),
+            _ => None,
+        }
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        let event_signature = log.topics()[0];
+
+        // Balance events are routed by the pool id they carry, so only the Vault may emit them
+        let vault_event = matches!(
+            event_signature,
+            IBalancerV2Vault::Swap::SIGNATURE_HASH
+                | IBalancerV2Vault::PoolBalanceChanged::SIGNATURE_HASH
+                | IBalancerV2Vault::PoolBalanceManaged::SIGNATURE_HASH
+        );
+        if vault_event && log.address() != self.vault {
+            return Ok(());
+        }
+
+        self.block_timestamp = log.block_timestamp.unwrap_or(self.block_timestamp);
+
+        match event_signature {
+            IBalancerV2Vault::Swap::SIGNATURE_HASH => {
+                let swap_event = IBalancerV2Vault::Swap::decode_log(log.as_ref())?;
+
+                let i = self.token_index(swap_event.tokenIn)?;
+                let j = self.token_index(swap_event.tokenOut)?;
+                self.balances[i] = self.balances[i]
+                    .checked_add(swap_event.amountIn)
+                    .ok_or(BalancerV2Error::AddOverflow)?;
+                self.balances[j] = self.balances[j]
+                    .checked_sub(swap_event.amountOut)
+                    .ok_or(BalancerV2Error::SubUnderflow)?;
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    balances = ?self.balances, "Swap"
+                );
+            }
+            IBalancerV2Vault::PoolBalanceChanged::SIGNATURE_HASH => {
+                let balance_event = IBalancerV2Vault::PoolBalanceChanged::decode_log(log.as_ref())?;
+
+                let invariant = self.composable_invariant()?;
+                for ((token, delta), protocol_fee) in balance_event
+                    .tokens
+                    .iter()
+                    .zip(&balance_event.deltas)
+                    .zip(&balance_event.protocolFeeAmounts)
+                {
+                    let i = self.token_index(*token)?;
+                    self.balances[i] = apply_delta(self.balances[i], *delta)?
+                        .checked_sub(*protocol_fee)
+                        .ok_or(BalancerV2Error::SubUnderflow)?;
+                }
+
+                // BPT minted or burned by the join or exit scales with the invariant
+                if let Some(invariant) = invariant.filter(|invariant| !invariant.is_zero()) {
+                    let virtual_supply = self.virtual_supply();
+                    if let Some(new_invariant) = self.composable_invariant()? {
+                        let new_virtual_supply = virtual_supply * new_invariant / invariant;
+                        self.total_supply = (self.total_supply + new_virtual_supply)
+                            .checked_sub(virtual_supply)
+                            .ok_or(BalancerV2Error::SubUnderflow)?;
+                    }
+                }
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    balances = ?self.balances, "Pool balance changed"
+                );
+            }
+            IBalancerV2Vault::PoolBalanceManaged::SIGNATURE_HASH => {
+                let managed_event = IBalancerV2Vault::PoolBalanceManaged::decode_log(log.as_ref())?;
+
+                let i = self.token_index(managed_event.token)?;
+                let delta = managed_event
+                    .cashDelta
+                    .checked_add(managed_event.managedDelta)
+                    .ok_or(BalancerV2Error::AddOverflow)?;
+                self.balances[i] = apply_delta(self.balances[i], delta)?;
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    balances = ?self.balances, "Pool balance managed"
+                );
+            }
+            IBalancerV2Pool::SwapFeePercentageChanged::SIGNATURE_HASH => {
+                let fee_event =
+                    IBalancerV2Pool::SwapFeePercentageChanged::decode_log(log.as_ref())?;
+                self.swap_fee = fee_event.swapFeePercentage;
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    swap_fee = ?self.swap_fee, "Swap fee changed"
+                );
+            }
+            IBalancerV2Pool::AmpUpdateStarted::SIGNATURE_HASH => {
+                let ramp_event = IBalancerV2Pool::AmpUpdateStarted::decode_log(log.as_ref())?;
+                self.amp_start = ramp_event.startValue;
+                self.amp_end = ramp_event.endValue;
+                self.amp_start_time = ramp_event.startTime.saturating_to();
+                self.amp_end_time = ramp_event.endTime.saturating_to();
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    amp_start = ?self.amp_start,
+                    amp_end = ?self.amp_end, "Amp update started"
+                );
+            }
+            IBalancerV2Pool::AmpUpdateStopped::SIGNATURE_HASH => {
+                let stop_event = IBalancerV2Pool::AmpUpdateStopped::decode_log(log.as_ref())?;
+                self.amp_start = stop_event.currentValue;
+                self.amp_end = stop_event.currentValue;
+                self.amp_start_time = self.block_timestamp;
+                self.amp_end_time = self.block_timestamp;
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    amp = ?self.amp_end, "Amp update stopped"
+                );
+            }
+            IBalancerV2Pool::TokenRateCacheUpdated::SIGNATURE_HASH => {
+                let rate_event = IBalancerV2Pool::TokenRateCacheUpdated::decode_log(log.as_ref())?;
+
+                let i = usize::try_from(rate_event.tokenIndex)
+                    .ok()
+                    .filter(|i| *i < self.tokens.len())
+                    .ok_or(BalancerV2Error::TokenNotInPool)?;
+                self.scaling_factors[i] =
+                    mul_down(self.decimals_scaling_factor(i), rate_event.rate);
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    scaling_factors = ?self.scaling_factors, "Token rate cache updated"
+                );
+            }
+            IBalancerV2Pool::PriceRateCacheUpdated::SIGNATURE_HASH => {
+                let rate_event = IBalancerV2Pool::PriceRateCacheUpdated::decode_log(log.as_ref())?;
+
+                let i = self.token_index(rate_event.token)?;
+                self.scaling_factors[i] =
+                    mul_down(self.decimals_scaling_factor(i), rate_event.rate);
+
+                info!(
+                    target = "amms::balancer_v2::sync",
+                    address = ?self.address,
+                    scaling_factors = ?self.scaling_factors, "Price rate cache updated"
+                );
+            }
+            _ => return Err(AMMError::UnrecognizedEventSignature(event_signature)),
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<Address> {
+        self.tokens.iter().map(|token| token.address).collect()
+    }
+
+    /// Returns the spot price of the base token in the quote token, excluding fees.
+    fn calculate_price(&self, base_token: Address, quote_token: Address) -> Result<f64, AMMError> {
+        Ok(self
+            .spot_price(base_token, quote_token)?
+            .to_f64_round(Round::Nearest))
+    }
+
+    /// Returns the spot price of the base token in the quote token, excluding fees, at
+    /// `MPFR_T_PRECISION` bits.
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        float_to_q128(self.spot_price(base_token, quote_token)?)
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let (i, j) = self.token_indices(base_token, quote_token)?;
+        let amount_out = self.swap_given_in(i, j, self.tokens[i].after_sell_tax(amount_in))?;
+
+        Ok(self.tokens[j].after_buy_tax(amount_out))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let (i, j) = self.token_indices(base_token, quote_token)?;
+        let amount_in = self.tokens[i].after_sell_tax(amount_in);
+        let amount_out = self.swap_given_in(i, j, amount_in)?;
+
+        self.balances[i] += amount_in;
+        self.balances[j] -= amount_out;
+
+        Ok(self.tokens[j].after_buy_tax(amount_out))
+    }
+
+    /// Encodes `swap` on the Vault as a given in swap without a deadline. The Vault pulls the
+    /// amount in from `recipient`, which must be the caller or have approved it as a relayer.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        self.token_indices(base_token, quote_token)?;
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        Ok(IBalancerV2Vault::swapCall {
+            singleSwap: IBalancerV2Vault::SingleSwap {
+                poolId: self.pool_id,
+                // `GIVEN_IN`
+                kind: 0,
+                assetIn: base_token,
+                assetOut: quote_token,
+                amount: amount_in,
+                userData: Bytes::new(),
+            },
+            funds: IBalancerV2Vault::FundManagement {
+                sender: recipient,
+                fromInternalBalance: false,
+                recipient,
+                toInternalBalance: false,
+            },
+            limit: min_out,
+            deadline: U256::MAX,
+        }
+        .abi_encode()
+        .into())
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        BalancerV2Pool::get_pool_data(self.vault, vec![self.address], block_number, provider)
+            .await?
+            .pop()
+            .ok_or(BalancerV2Error::InitializationError.into())
+    }
+}
+
+impl BalancerV2Pool {
+    pub fn new(vault: Address, address: Address) -> Self {
+        Self {
+            address,
+            vault,
+            ..Default::default()
+        }
+    }
+
+    /// Fetches the state of `pools` at `block_number`, skipping pools that are neither weighted
+    /// nor stable pools.
+    ///
+    /// Pools only expose their current amplification and whether it is being updated, so the ramp
+    /// of stable pools updating it is restored from their last `AmpUpdateStarted` log.
+    pub async fn get_pool_data<N, P>(
+        vault: Address,
+        pools: Vec<Address>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Self>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let block = provider
+            .get_block(block_number)
+            .await?
+            .ok_or(AMMError::BlockNotFound(block_number))?;
+        let (number, block_timestamp) = (block.header().number(), block.header().timestamp());
+
+        let deployer = IGetBalancerV2PoolDataBatchRequest::deploy_builder(
+            provider.clone(),
+            vault,
+            pools.clone(),
+        );
+        let res = deployer.block(block_number).call_raw().await?;
+
+        let data = <Vec<BalancerV2PoolData> as SolValue>::abi_decode(&res)?;
+
+        let ramping_pools = pools
+            .iter()
+            .zip(&data)
+            .filter(|(_, pool_data)| pool_data.ampUpdating)
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+
+        let mut pools = pools
+            .into_iter()
+            .zip(data)
+            .filter_map(|(address, pool_data)| {
+                BalancerV2Pool::from_pool_data(vault, address, pool_data, block_timestamp)
+            })
+            .collect::<Vec<_>>();
+
+        if !ramping_pools.is_empty() {
+            let filter = Filter::new()
+                .address(ramping_pools)
+                .event_signature(vec![
+                    IBalancerV2Pool::AmpUpdateStarted::SIGNATURE_HASH,
+                    IBalancerV2Pool::AmpUpdateStopped::SIGNATURE_HASH,
+                ])
+                .from_block(BlockNumberOrTag::Earliest)
+                .to_block(number);
+            let logs = provider.get_logs(&filter).await?;
+            restore_amp_ramps(&mut pools, &logs)?;
+        }
+
+        Ok(pools)
+    }
+
+    fn from_pool_data(
+        vault: Address,
+        address: Address,
+        pool_data: BalancerV2PoolData,
+        block_timestamp: u64,
+    ) -> Option<Self> {
+        let n_tokens = pool_data.tokens.len();
+        if n_tokens < 2
+            || pool_data.balances.len() != n_tokens
+            || pool_data.scalingFactors.len() != n_tokens
+        {
+            return None;
+        }
+
+        let kind = if pool_data.weights.len() == n_tokens {
+            BalancerV2PoolKind::Weighted
+        } else if pool_data.amp.is_zero() {
+            return None;
+        } else {
+            match usize::try_from(pool_data.bptIndex) {
+                Ok(bpt_index) if bpt_index < n_tokens => {
+                    BalancerV2PoolKind::ComposableStable { bpt_index }
+                }
+                _ => BalancerV2PoolKind::Stable,
+            }
+        };
+
+        Some(Self {
+            address,
+            vault,
+            pool_id: pool_data.poolId,
+            kind,
+            tokens: pool_data
+                .tokens
+                .into_iter()
+                .zip(pool_data.decimals)
+                .map(|(token, decimals)| Token::new_with_decimals(token, decimals))
+                .collect(),
+            balances: pool_data.balances,
+            scaling_factors: pool_data.scalingFactors,
+            weights: pool_data.weights,
+            amp_start: pool_data.amp,
+            amp_end: pool_data.amp,
+            amp_start_time: 0,
+            amp_end_time: 0,
+            swap_fee: pool_data.swapFee,
+            total_supply: pool_data.totalSupply,
+            block_timestamp,
+        })
+    }
+
+    /// Returns the amplification parameter at `timestamp`, interpolating linearly while the
+    /// parameter is being updated.
+    pub fn amp(&self, timestamp: u64) -> U256 {
+        if timestamp >= self.amp_end_time || self.amp_end_time <= self.amp_start_time {
+            return self.amp_end;
+        }
+
+        let elapsed = U256::from(timestamp.saturating_sub(self.amp_start_time));
+        let duration = U256::from(self.amp_end_time - self.amp_start_time);
+        if self.amp_end > self.amp_start {
+            self.amp_start + (self.amp_end - self.amp_start) * elapsed / duration
+        } else {
+            self.amp_start - (self.amp_start - self.amp_end) * elapsed / duration
+        }
+    }
+
+    /// Returns the BPT supply outside of the Vault for composable stable pools, and the total
+    /// supply otherwise.
+    pub fn virtual_supply(&self) -> U256 {
+        match self.kind {
+            BalancerV2PoolKind::ComposableStable { bpt_index } => {
+                self.total_supply.saturating_sub(self.balances[bpt_index])
+            }
+            _ => self.total_supply,
+        }
+    }
+
+    /// Returns the amount of token `j` received for `amount_in` of token `i`.
+    fn swap_given_in(&self, i: usize, j: usize, amount_in: U256) -> Result<U256, BalancerV2Error> {
+        match self.kind {
+            BalancerV2PoolKind::Weighted => {
+                let amount_in = self.subtract_swap_fee(amount_in)?;
+                let amount_out = weighted_math::calc_out_given_in(
+                    mul_down(self.balances[i], self.scaling_factors[i]),
+                    self.weights[i],
+                    mul_down(self.balances[j], self.scaling_factors[j]),
+                    self.weights[j],
+                    mul_down(amount_in, self.scaling_factors[i]),
+                )?;
+
+                div_down(amount_out, self.scaling_factors[j])
+            }
+            BalancerV2PoolKind::Stable => self.stable_swap_given_in(i, j, amount_in),
+            BalancerV2PoolKind::ComposableStable { bpt_index } => {
+                if i == bpt_index || j == bpt_index {
+                    self.bpt_swap_given_in(i, j, bpt_index, amount_in)
+                } else {
+                    self.stable_swap_given_in(i, j, amount_in)
+                }
+            }
+        }
+    }
+
+    /// Swaps between two pool tokens of a stable pool. The BPT of composable stable pools is
+    /// left out of the invariant.
+    fn stable_swap_given_in(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: U256,
+    ) -> Result<U256, BalancerV2Error> {
+        let amount_in = mul_down(self.subtract_swap_fee(amount_in)?, self.scaling_factors[i]);
+
+        let amp = self.amp(self.block_timestamp);
+        let xp = self.invariant_balances();
+        let invariant = stable_math::calculate_invariant(amp, &xp)?;
+        let amount_out = stable_math::calc_out_given_in(
+            amp,
+            &xp,
+            self.invariant_index(i),
+            self.invariant_index(j),
+            amount_in,
+            invariant,
+        )?;
+
+        div_down(amount_out, self.scaling_factors[j])
+    }
+
+    /// Swaps into or out of the BPT of a composable stable pool through a single token join or
+    /// exit.
+    fn bpt_swap_given_in(
+        &self,
+        i: usize,
+        j: usize,
+        bpt_index: usize,
+        amount_in: U256,
+    ) -> Result<U256, BalancerV2Error> {
+        let amount_in = mul_down(amount_in, self.scaling_factors[i]);
+
+        let amp = self.amp(self.block_timestamp);
+        let xp = self.invariant_balances();
+        let invariant = stable_math::calculate_invariant(amp, &xp)?;
+
+        let amount_out = if j == bpt_index {
+            let mut amounts_in = vec![U256::ZERO; xp.len()];
+            amounts_in[self.invariant_index(i)] = amount_in;
+
+            stable_math::calc_bpt_out_given_exact_tokens_in(
+                amp,
+                &xp,
+                &amounts_in,
+                self.virtual_supply(),
+                invariant,
+                self.swap_fee,
+            )?
+        } else {
+            stable_math::calc_token_out_given_exact_bpt_in(
+                amp,
+                &xp,
+                self.invariant_index(j),
+                amount_in,
+                self.virtual_supply(),
+                invariant,
+                self.swap_fee,
+            )?
+        };
+
+        div_down(amount_out, self.scaling_factors[j])
+    }
+
+    /// Returns the spot price of the base token in the quote token in whole tokens, excluding fees.
+    fn spot_price(&self, base_token: Address, quote_token: Address) -> Result<Float, AMMError> {
+        let (i, j) = self.token_indices(base_token, quote_token)?;
+
+        // Price of the base token in the quote token, in upscaled units
+        let price = match self.kind {
+            BalancerV2PoolKind::Weighted => {
+                let xp = self.upscaled_balances();
+                (u256_to_float(xp[j])? / u256_to_float(self.weights[j])?)
+                    / (u256_to_float(xp[i])? / u256_to_float(self.weights[i])?)
+            }
+            BalancerV2PoolKind::Stable | BalancerV2PoolKind::ComposableStable { .. } => {
+                self.stable_spot_price(i, j)?
+            }
+        };
+
+        // Convert upscaled units back to whole tokens
+        Ok(price * u256_to_float(self.scaling_factors[i])?
+            / u256_to_float(self.scaling_factors[j])?
+            * u256_to_float(U256_10.pow(U256::from(self.tokens[i].decimals)))?
+            / u256_to_float(U256_10.pow(U256::from(self.tokens[j].decimals)))?)
+    }
+
+    /// Spot price of token `i` in token `j` in upscaled units, from the partial derivatives of
+    /// the stable invariant. The BPT is priced through the derivative of the invariant with
+    /// respect to each balance.
+    fn stable_spot_price(&self, i: usize, j: usize) -> Result<Float, AMMError> {
+        let amp = self.amp(self.block_timestamp);
+        let xp = self.invariant_balances();
+        let invariant = stable_math::calculate_invariant(amp, &xp)?;
+
+        let n = xp.len();
+        let d = u256_to_float(invariant)?;
+        let ann = u256_to_float(amp * U256::from(n))? / u256_to_float(BALANCER_V2_AMP_PRECISION)?;
+
+        // D_P = D^(n+1) / (n^n * prod(x))
+        let mut d_p = d.clone();
+        for x in &xp {
+            d_p = d_p * d.clone() / (u256_to_float(*x)? * Float::with_val(MPFR_T_PRECISION, n));
+        }
+
+        // Increase of the invariant for one unit of token k
+        let d_invariant = |k: usize| -> Result<Float, AMMError> {
+            let partial = ann.clone() + d_p.clone() / u256_to_float(xp[k])?;
+            let invariant_partial = ann.clone() - Float::with_val(MPFR_T_PRECISION, 1)
+                + d_p.clone() * (n + 1) / d.clone();
+            Ok(partial / invariant_partial)
+        };
+
+        let bpt_index = match self.kind {
+            BalancerV2PoolKind::ComposableStable { bpt_index } => Some(bpt_index),
+            _ => None,
+        };
+        let bpt_price = d.clone() / u256_to_float(self.virtual_supply())?;
+
+        let value = |k: usize| -> Result<Float, AMMError> {
+            if Some(k) == bpt_index {
+                Ok(bpt_price.clone())
+            } else {
+                d_invariant(self.invariant_index(k))
+            }
+        };
+
+        Ok(value(i)? / value(j)?)
+    }
+
+    /// Returns the upscaled balances the stable invariant is computed over, which leave out the
+    /// BPT of composable stable pools.
+    fn invariant_balances(&self) -> Vec<U256> {
+        let mut xp = self.upscaled_balances();
+        if let BalancerV2PoolKind::ComposableStable { bpt_index } = self.kind {
+            xp.remove(bpt_index);
+        }
+
+        xp
+    }
+
+    /// Maps a pool token index to its position in [`Self::invariant_balances`].
+    fn invariant_index(&self, k: usize) -> usize {
+        match self.kind {
+            BalancerV2PoolKind::ComposableStable { bpt_index } if k > bpt_index => k - 1,
+            _ => k,
+        }
+    }
+
+    /// Returns the stable invariant of a composable stable pool.
+    fn composable_invariant(&self) -> Result<Option<U256>, BalancerV2Error> {
+        let BalancerV2PoolKind::ComposableStable { .. } = self.kind else {
+            return Ok(None);
+        };
+
+        Ok(Some(stable_math::calculate_invariant(
+            self.amp(self.block_timestamp),
+            &self.invariant_balances(),
+        )?))
+    }
+
+    fn upscaled_balances(&self) -> Vec<U256> {
+        self.balances
+            .iter()
+            .zip(&self.scaling_factors)
+            .map(|(balance, scaling_factor)| mul_down(*balance, *scaling_factor))
+            .collect()
+    }
+
+    /// Deducts the swap fee from an amount in, rounding the fee up.
+    fn subtract_swap_fee(&self, amount: U256) -> Result<U256, BalancerV2Error> {
+        fixed_point::sub(amount, mul_up(amount, self.swap_fee))
+    }
+
+    /// Scaling factor of token `i` before its rate is applied.
+    fn decimals_scaling_factor(&self, i: usize) -> U256 {
+        let decimals = self.tokens[i].decimals.min(18);
+        BONE * U256_10.pow(U256::from(18 - decimals))
+    }
+
+    fn token_index(&self, token: Address) -> Result<usize, BalancerV2Error> {
+        self.tokens
+            .iter()
+            .position(|t| t.address == token)
+            .ok_or(BalancerV2Error::TokenNotInPool)
+    }
+
+    fn token_indices(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+    ) -> Result<(usize, usize), BalancerV2Error> {
+        match (
+            self.token_index(base_token)?,
+            self.token_index(quote_token)?,
+        ) {
+            (i, j) if i != j => Ok((i, j)),
+            _ => Err(BalancerV2Error::TokenNotInPool),
+        }
+    }
+}
+
+/// The Balancer V2 Vault, discovering pools through their registration.
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BalancerV2Vault {
+    pub address: Address,
+    pub creation_block: u64,
+}
+
+impl BalancerV2Vault {
+    pub fn new(address: Address, creation_block: u64) -> Self {
+        Self {
+            address,
+            creation_block,
+        }
+    }
+
+    pub async fn get_all_pools<N, P>(
+        &self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let disc_filter = Filter::new()
+            .event_signature(FilterSet::from(vec![self.pool_creation_event()]))
+            .address(vec![self.address()]);
+
+        let sync_provider = provider.clone();
+        let mut futures = FuturesUnordered::new();
+
+        let sync_step = 100_000;
+        let mut latest_block = self.creation_block;
+        while latest_block < block_number.as_u64().unwrap_or_default() {
+            let mut block_filter = disc_filter.clone();
+            let from_block = latest_block;
+            let to_block = (from_block + sync_step).min(block_number.as_u64().unwrap_or_default());
+
+            block_filter = block_filter.from_block(from_block);
+            block_filter = block_filter.to_block(to_block);
+
+            let sync_provider = sync_provider.clone();
+
+            futures.push(async move { sync_provider.get_logs(&block_filter).await });
+
+            latest_block = to_block + 1;
+        }
+
+        let mut pools = vec![];
+        while let Some(res) = futures.next().await {
+            let logs = res?;
+
+            for log in logs {
+                pools.push(self.create_pool(log)?);
+            }
+        }
+
+        Ok(pools)
+    }
+
+    pub async fn sync_all_pools<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let step = 50;
+        let pools = amms.iter().map(|amm| amm.address()).collect::<Vec<_>>();
+
+        let mut futures_unordered = pools
+            .chunks(step)
+            .map(|group| {
+                BalancerV2Pool::get_pool_data(
+                    self.address,
+                    group.to_vec(),
+                    block_number,
+                    provider.clone(),
+                )
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut amms = Vec::new();
+        while let Some(res) = futures_unordered.next().await {
+            amms.extend(res?.into_iter().map(AMM::BalancerV2Pool));
+        }
+
+        Ok(amms)
+    }
+}
+
+impl AutomatedMarketMakerFactory for BalancerV2Vault {
+    type PoolVariant = BalancerV2Pool;
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn pool_creation_event(&self) -> B256 {
+        IBalancerV2Vault::PoolRegistered::SIGNATURE_HASH
+    }
+
+    fn create_pool(&self, log: Log) -> Result<AMM, AMMError> {
+        let registered_event = IBalancerV2Vault::PoolRegistered::decode_log(&log.inner)?;
+
+        let mut pool = BalancerV2Pool::new(self.address, registered_event.poolAddress);
+        pool.pool_id = registered_event.poolId;
+
+        Ok(AMM::BalancerV2Pool(pool))
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+}
+
+impl DiscoverySync for BalancerV2Vault {
+    fn discover<N, P>(
+        &self,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::balancer_v2::discover",
+            address = ?self.address,
+            "Discovering all pools"
+        );
+
+        self.get_all_pools(to_block, provider)
+    }
+
+    fn sync<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::balancer_v2::sync",
+            address = ?self.address,
+            "Syncing all pools"
+        );
+
+        self.sync_all_pools(amms, to_block, provider)
+    }
+}
+
+/// Applies a signed balance delta reported by the Vault.
+fn apply_delta(balance: U256, delta: I256) -> Result<U256, BalancerV2Error> {
+    if delta.is_negative() {
+        balance
+            .checked_sub(delta.unsigned_abs())
+            .ok_or(BalancerV2Error::SubUnderflow)
+    } else {
+        balance
+            .checked_add(delta.into_raw())
+            .ok_or(BalancerV2Error::AddOverflow)
+    }
+}
+
+/// Restores the amplification ramp of `pools` from their amplification update `logs`, in emission
+/// order. Pools whose last update is a ramp still ongoing at their block timestamp interpolate
+/// along it, the others keep the amplification they were fetched with.
+fn restore_amp_ramps(pools: &mut [BalancerV2Pool], logs: &[Log]) -> Result<(), AMMError> {
+    let mut last_updates = HashMap::new();
+    for log in logs {
+        let ramp = if log.topic0() == Some(&IBalancerV2Pool::AmpUpdateStarted::SIGNATURE_HASH) {
+            Some(IBalancerV2Pool::AmpUpdateStarted::decode_log(log.as_ref())?.data)
+        } else {
+            None
+        };
+        last_updates.insert(log.address(), ramp);
+    }
+
+    for pool in pools {
+        if let Some(Some(ramp)) = last_updates.get(&pool.address) {
+            let end_time = ramp.endTime.saturating_to();
+            if end_time > pool.block_timestamp {
+                pool.amp_start = ramp.startValue;
+                pool.amp_end = ramp.endValue;
+                pool.amp_start_time = ramp.startTime.saturating_to();
+                pool.amp_end_time = end_time;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::consts::U256_1;
+    use alloy::{
+        primitives::{address, b256},
+        providers::ProviderBuilder,
+        rpc::client::ClientBuilder,
+        transports::layers::{RetryBackoffLayer, ThrottleLayer},
+    };
+
+    const VAULT: Address = address!("BA12222222228d8Ba445958a75a0704d566BF2C8");
+
+    fn weighted_pool() -> BalancerV2Pool {
+        let weth =
+            Token::new_with_decimals(address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), 6);
+
+        BalancerV2Pool {
+            address: address!("96646936b91d6B9D7D0c47C496AfBF3D6ec7B6f8"),
+            vault: VAULT,
+            pool_id: b256!("96646936b91d6b9d7d0c47c496afbf3d6ec7b6f8000200000000000000000019"),
+            kind: BalancerV2PoolKind::Weighted,
+            tokens: vec![weth, usdc],
+            balances: vec![U256::from(1_000e18), U256::from(2_000_000e6)],
+            scaling_factors: vec![BONE, BONE * U256::from(1e12)],
+            weights: vec![U256::from(5e17), U256::from(5e17)],
+            swap_fee: U256::from(3e15),
+            ..Default::default()
+        }
+    }
+
+    fn composable_stable_pool() -> BalancerV2Pool {
+        let bpt =
+            Token::new_with_decimals(address!("79c58f70905F734641735BC61e45c19dD9Ad60bC"), 18);
+        let dai =
+            Token::new_with_decimals(address!("6B175474E89094C44Da98b954EedeAC495271d0F"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), 6);
+        let usdt =
+            Token::new_with_decimals(address!("dAC17F958D2ee523a2206206994597C13D831ec7"), 6);
+
+        let preminted = U256::from(2).pow(U256::from(111));
+        BalancerV2Pool {
+            address: bpt.address,
+            vault: VAULT,
+            kind: BalancerV2PoolKind::ComposableStable { bpt_index: 0 },
+            tokens: vec![bpt, dai, usdc, usdt],
+            balances: vec![
+                preminted - U256::from(3_000_000e18),
+                U256::from(1_000_000e18),
+                U256::from(1_000_000e6),
+                U256::from(1_000_000e6),
+            ],
+            scaling_factors: vec![BONE, BONE, BONE * U256::from(1e12), BONE * U256::from(1e12)],
+            amp_start: U256::from(2_000_000),
+            amp_end: U256::from(2_000_000),
+            swap_fee: U256::from(1e14),
+            total_supply: preminted,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pow() {
+        let expected = 2.0_f64.sqrt();
+        let result = log_exp_math::pow(U256::from(2e18), U256::from(5e17)).unwrap();
+        let result = result.to::<u128>() as f64 / 1e18;
+        assert!((result - expected).abs() / expected < 1e-14);
+
+        let expected = 0.95_f64.powf(0.25);
+        let result = log_exp_math::pow(U256::from(95e16), U256::from(25e16)).unwrap();
+        let result = result.to::<u128>() as f64 / 1e18;
+        assert!((result - expected).abs() / expected < 1e-14);
+    }
+
+    #[test]
+    fn test_simulate_swap_weighted() {
+        let pool = weighted_pool();
+
+        let amount_out = pool
+            .simulate_swap(
+                pool.tokens[0].address,
+                pool.tokens[1].address,
+                U256::from(1e18),
+            )
+            .unwrap();
+        assert_eq!(amount_out, U256::from(1_992_013_962_u64));
+
+        let price = pool
+            .calculate_price(pool.tokens[0].address, pool.tokens[1].address)
+            .unwrap();
+        assert_eq!(price, 2000.0);
+    }
+
+    #[test]
+    fn test_simulate_swap_composable_stable() {
+        let mut pool = composable_stable_pool();
+        let [bpt, dai, usdc, _] = [0, 1, 2, 3].map(|i| pool.tokens[i].address);
+
+        // Swaps between balanced stable coins cost the swap fee
+        let amount_out = pool.simulate_swap(dai, usdc, U256::from(1_000e18)).unwrap();
+        assert!(amount_out < U256::from(999_900_000) && amount_out > U256::from(999_800_000));
+
+        // A single token join and exit charges the fee on the unbalanced part only
+        let bpt_out = pool
+            .simulate_swap_mut(usdc, bpt, U256::from(1_000e6))
+            .unwrap();
+        assert!(bpt_out < U256::from(1_000e18) && bpt_out > U256::from(999e18));
+        assert_eq!(pool.virtual_supply(), U256::from(3_000_000e18) + bpt_out);
+
+        let usdc_out = pool.simulate_swap(bpt, usdc, bpt_out).unwrap();
+        assert!(usdc_out < U256::from(1_000e6) && usdc_out > U256::from(999e6));
+
+        let price = pool.calculate_price(bpt, dai).unwrap();
+        assert!((price - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_sync_vault_swap() {
+        let mut pool = weighted_pool();
+        let mut expected = pool.clone();
+        let amount_in = U256::from(1e18);
+        let amount_out = expected
+            .simulate_swap_mut(pool.tokens[0].address, pool.tokens[1].address, amount_in)
+            .unwrap();
+
+        let event = IBalancerV2Vault::Swap {
+            poolId: pool.pool_id,
+            tokenIn: pool.tokens[0].address,
+            tokenOut: pool.tokens[1].address,
+            amountIn: amount_in,
+            amountOut: amount_out,
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: VAULT,
+                data: event.encode_log_data(),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(AMM::log_key(&log), Some(pool.key()));
+
+        // The same event emitted by any other contract leaves the pool untouched
+        let mut spoofed = log.clone();
+        spoofed.inner.address = Address::with_last_byte(1);
+        pool.sync(&spoofed).unwrap();
+        assert_eq!(pool.balances, weighted_pool().balances);
+
+        pool.sync(&log).unwrap();
+        assert_eq!(pool.balances, expected.balances);
+
+        // Balances reported out of the pool's reserves are rejected
+        let drain = IBalancerV2Vault::Swap {
+            amountOut: pool.balances[1] + U256_1,
+            ..event
+        };
+        let mut drain_log = log;
+        drain_log.inner.data = drain.encode_log_data();
+        assert!(pool.sync(&drain_log).is_err());
+    }
+
+    #[test]
+    fn test_restore_amp_ramps() -> eyre::Result<()> {
+        let ramp = IBalancerV2Pool::AmpUpdateStarted {
+            startValue: U256::from(1_000_000),
+            endValue: U256::from(2_000_000),
+            startTime: U256::from(1_700_000_000),
+            endTime: U256::from(1_700_086_400),
+        };
+        let stop = IBalancerV2Pool::AmpUpdateStopped {
+            currentValue: U256::from(1_500_000),
+        };
+        let amp_log = |address, data| Log {
+            inner: alloy::primitives::Log { address, data },
+            ..Default::default()
+        };
+
+        let (ramping, stopped) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let mut pools = [ramping, stopped].map(|address| BalancerV2Pool {
+            address,
+            block_timestamp: 1_700_043_200,
+            ..composable_stable_pool()
+        });
+        let logs = [
+            amp_log(ramping, ramp.encode_log_data()),
+            amp_log(stopped, ramp.encode_log_data()),
+            amp_log(stopped, stop.encode_log_data()),
+        ];
+        restore_amp_ramps(&mut pools, &logs)?;
+
+        // A pool mid-ramp at its block interpolates along the ramp, a stopped one keeps its amp
+        assert_eq!(
+            pools[0].amp(pools[0].block_timestamp),
+            U256::from(1_500_000)
+        );
+        assert_eq!(
+            pools[1].amp(pools[1].block_timestamp),
+            U256::from(2_000_000)
+        );
+
+        // Synced logs move the amplification with their block
+        let mut pool = pools[0].clone();
+        let amount_in = U256::from(1_000e18);
+        let (dai, usdc) = (pool.tokens[1].address, pool.tokens[2].address);
+        let amount_out = pool.simulate_swap(dai, usdc, amount_in)?;
+        let fee_event = IBalancerV2Pool::SwapFeePercentageChanged {
+            swapFeePercentage: pool.swap_fee,
+        };
+        pool.sync(&Log {
+            block_timestamp: Some(1_700_086_400),
+            ..amp_log(pool.address, fee_event.encode_log_data())
+        })?;
+        assert_eq!(pool.amp(pool.block_timestamp), U256::from(2_000_000));
+        assert!(pool.simulate_swap(dai, usdc, amount_in)? > amount_out);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_query_batch_swap() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_PROVIDER")?;
+
+        let client = ClientBuilder::default()
+            .layer(ThrottleLayer::new(250))
+            .layer(RetryBackoffLayer::new(5, 200, 330))
+            .http(rpc_endpoint.parse()?);
+
+        let provider = ProviderBuilder::new().connect_client(client);
+        let block_number = BlockId::from(22_000_000);
+        let vault = IBalancerV2Vault::new(VAULT, provider.clone());
+
+        // 80BAL-20WETH and the wstETH/WETH composable stable pool
+        for address in [
+            address!("5c6Ee304399DBdB9C8Ef030aB642B10820DB8F56"),
+            address!("93d199263632a4EF4Bb438F1feB99e57b4b5f0BD"),
+        ] {
+            let pool = BalancerV2Pool::new(VAULT, address)
+                .init(block_number, provider.clone())
+                .await?;
+
+            for i in 0..pool.tokens.len() {
+                let j = (i + 1) % pool.tokens.len();
+                let amount_in = U256_10.pow(U256::from(pool.tokens[i].decimals));
+                let amount_out =
+                    pool.simulate_swap(pool.tokens[i].address, pool.tokens[j].address, amount_in)?;
+
+                let asset_deltas = vault
+                    .queryBatchSwap(
+                        0,
+                        vec![IBalancerV2Vault::BatchSwapStep {
+                            poolId: pool.pool_id,
+                            assetInIndex: U256::ZERO,
+                            assetOutIndex: U256_1,
+                            amount: amount_in,
+                            userData: Default::default(),
+                        }],
+                        vec![pool.tokens[i].address, pool.tokens[j].address],
+                        IBalancerV2Vault::FundManagement {
+                            sender: Address::ZERO,
+                            fromInternalBalance: false,
+                            recipient: Address::ZERO,
+                            toInternalBalance: false,
+                        },
+                    )
+                    .block(block_number)
+                    .call()
+                    .await?;
+                let expected_amount_out = asset_deltas[1].unsigned_abs();
+
+                // Pools deployed before exact powers were special cased round pow differently
+                assert!(
+                    amount_out.abs_diff(expected_amount_out)
+                        <= expected_amount_out / U256::from(1e12)
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Swap math of Balancer V2 stable pools, including the single token joins and exits used by
+//! composable stable pools to swap their own BPT.
+//!
+//! `amp` is the amplification parameter multiplied by [`BALANCER_V2_AMP_PRECISION`]. Balances
+//! and amounts are upscaled to 18 decimals.
+//!
+//! Reference:
+//! https://github.com/balancer/balancer-v2-monorepo/blob/master/pkg/pool-stable/contracts/StableMath.sol
+
+use alloy::primitives::U256;
+
+use super::{
+    fixed_point::{complement, div_down, div_down_raw, div_up, div_up_raw, mul_down, mul_up, sub},
+    BalancerV2Error,
+};
+use crate::amms::consts::{
+    BALANCER_V2_AMP_PRECISION, BALANCER_V2_MAX_ITERATIONS, BONE, U256_1, U256_2,
+};
+
+/// Computes the invariant of `balances` through Newton's method.
+pub fn calculate_invariant(amp: U256, balances: &[U256]) -> Result<U256, BalancerV2Error> {
+    let num_tokens = U256::from(balances.len());
+    let sum = balances
+        .iter()
+        .fold(U256::ZERO, |acc, balance| acc + balance);
+    if sum.is_zero() {
+        return Ok(U256::ZERO);
+    }
+
+    let amp_times_total = amp * num_tokens;
+    let mut invariant = sum;
+    for _ in 0..BALANCER_V2_MAX_ITERATIONS {
+        let mut d_p = invariant;
+        for balance in balances {
+            d_p = div_down_raw(d_p * invariant, balance * num_tokens)?;
+        }
+
+        let prev_invariant = invariant;
+        invariant = div_down_raw(
+            (amp_times_total * sum / BALANCER_V2_AMP_PRECISION + d_p * num_tokens) * invariant,
+            (amp_times_total - BALANCER_V2_AMP_PRECISION) * invariant / BALANCER_V2_AMP_PRECISION
+                + (num_tokens + U256_1) * d_p,
+        )?;
+
+        if invariant.abs_diff(prev_invariant) <= U256_1 {
+            return Ok(invariant);
+        }
+    }
+
+    Err(BalancerV2Error::InvariantDidNotConverge)
+}
+
+/// Computes the amount of token `index_out` received for `amount_in` of token `index_in`. The
+/// swap fee must already be deducted from `amount_in`.
+pub fn calc_out_given_in(
+    amp: U256,
+    balances: &[U256],
+    index_in: usize,
+    index_out: usize,
+    amount_in: U256,
+    invariant: U256,
+) -> Result<U256, BalancerV2Error> {
+    let mut balances = balances.to_vec();
+    balances[index_in] += amount_in;
+
+    let final_balance_out = get_token_balance_given_invariant_and_all_other_balances(
+        amp, &balances, invariant, index_out,
+    )?;
+
+    // The amount out is rounded down by one wei in favor of the pool
+    sub(sub(balances[index_out], final_balance_out)?, U256_1)
+}
+
+/// Computes the BPT minted for joining with `amounts_in`. Amounts in excess of a proportional
+/// join are charged the swap fee.
+pub fn calc_bpt_out_given_exact_tokens_in(
+    amp: U256,
+    balances: &[U256],
+    amounts_in: &[U256],
+    bpt_total_supply: U256,
+    current_invariant: U256,
+    swap_fee: U256,
+) -> Result<U256, BalancerV2Error> {
+    let sum_balances = balances
+        .iter()
+        .fold(U256::ZERO, |acc, balance| acc + balance);
+
+    let mut balance_ratios_with_fee = Vec::with_capacity(balances.len());
+    let mut invariant_ratio_with_fees = U256::ZERO;
+    for (balance, amount_in) in balances.iter().zip(amounts_in) {
+        let current_weight = div_down(*balance, sum_balances)?;
+        let balance_ratio = div_down(balance + amount_in, *balance)?;
+        invariant_ratio_with_fees += mul_down(balance_ratio, current_weight);
+        balance_ratios_with_fee.push(balance_ratio);
+    }
+
+    let mut new_balances = Vec::with_capacity(balances.len());
+    for ((balance, amount_in), balance_ratio) in
+        balances.iter().zip(amounts_in).zip(balance_ratios_with_fee)
+    {
+        let amount_in_without_fee = if balance_ratio > invariant_ratio_with_fees {
+            let non_taxable_amount = mul_down(*balance, sub(invariant_ratio_with_fees, BONE)?);
+            let taxable_amount = sub(*amount_in, non_taxable_amount)?;
+            non_taxable_amount + mul_down(taxable_amount, BONE - swap_fee)
+        } else {
+            *amount_in
+        };
+
+        new_balances.push(balance + amount_in_without_fee);
+    }
+
+    let new_invariant = calculate_invariant(amp, &new_balances)?;
+    let invariant_ratio = div_down(new_invariant, current_invariant)?;
+
+    // No BPT is minted if the invariant did not grow
+    if invariant_ratio > BONE {
+        Ok(mul_down(bpt_total_supply, invariant_ratio - BONE))
+    } else {
+        Ok(U256::ZERO)
+    }
+}
+
+/// Computes the amount of token `token_index` received for burning `bpt_amount_in`. The part of
+/// the withdrawal in excess of a proportional exit is charged the swap fee.
+pub fn calc_token_out_given_exact_bpt_in(
+    amp: U256,
+    balances: &[U256],
+    token_index: usize,
+    bpt_amount_in: U256,
+    bpt_total_supply: U256,
+    current_invariant: U256,
+    swap_fee: U256,
+) -> Result<U256, BalancerV2Error> {
+    let new_invariant = mul_up(
+        div_up(sub(bpt_total_supply, bpt_amount_in)?, bpt_total_supply)?,
+        current_invariant,
+    );
+
+    let new_balance = get_token_balance_given_invariant_and_all_other_balances(
+        amp,
+        balances,
+        new_invariant,
+        token_index,
+    )?;
+    let amount_out_without_fee = sub(balances[token_index], new_balance)?;
+
+    let sum_balances = balances
+        .iter()
+        .fold(U256::ZERO, |acc, balance| acc + balance);
+    let current_weight = div_down(balances[token_index], sum_balances)?;
+    let taxable_percentage = complement(current_weight);
+
+    let taxable_amount = mul_up(amount_out_without_fee, taxable_percentage);
+    let non_taxable_amount = sub(amount_out_without_fee, taxable_amount)?;
+
+    Ok(non_taxable_amount + mul_down(taxable_amount, BONE - swap_fee))
+}
+
+/// Computes the balance of token `token_index` that satisfies `invariant` given the other
+/// balances, rounding up.
+pub fn get_token_balance_given_invariant_and_all_other_balances(
+    amp: U256,
+    balances: &[U256],
+    invariant: U256,
+    token_index: usize,
+) -> Result<U256, BalancerV2Error> {
+    let num_tokens = U256::from(balances.len());
+    let amp_times_total = amp * num_tokens;
+
+    let mut sum = balances[0];
+    let mut p_d = balances[0] * num_tokens;
+    for balance in &balances[1..] {
+        p_d = div_down_raw(p_d * balance * num_tokens, invariant)?;
+        sum += balance;
+    }
+    sum -= balances[token_index];
+
+    let inv2 = invariant * invariant;
+    // The balance of the token is removed from c by multiplying it back in
+    let c = div_up_raw(inv2, amp_times_total * p_d)?
+        * BALANCER_V2_AMP_PRECISION
+        * balances[token_index];
+    let b = sum + div_down_raw(invariant, amp_times_total)? * BALANCER_V2_AMP_PRECISION;
+
+    let mut token_balance = div_up_raw(inv2 + c, invariant + b)?;
+    for _ in 0..BALANCER_V2_MAX_ITERATIONS {
+        let prev_token_balance = token_balance;
+        token_balance = div_up_raw(
+            token_balance * token_balance + c,
+            sub(token_balance * U256_2 + b, invariant)?,
+        )?;
+
+        if token_balance.abs_diff(prev_token_balance) <= U256_1 {
+            return Ok(token_balance);
+        }
+    }
+
+    Err(BalancerV2Error::BalanceDidNotConverge)
+}
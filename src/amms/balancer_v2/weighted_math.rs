@@ -0,0 +1,43 @@
+//! Swap math of Balancer V2 weighted pools.
+//!
+//! Reference:
+//! https://github.com/balancer/balancer-v2-monorepo/blob/master/pkg/pool-weighted/contracts/WeightedMath.sol
+
+use alloy::primitives::U256;
+
+use super::{
+    fixed_point::{complement, div_down, div_up, mul_down, pow_up},
+    BalancerV2Error,
+};
+use crate::amms::consts::BALANCER_V2_MAX_IN_RATIO;
+
+/**********************************************************************************************
+// outGivenIn                                                                                //
+// aO = amountOut                                                                            //
+// bO = balanceOut                                                                           //
+// bI = balanceIn              /      /            bI             \    (wI / wO) \           //
+// aI = amountIn    aO = bO * |  1 - | --------------------------  | ^            |          //
+// wI = weightIn               \      \       ( bI + aI )         /              /           //
+// wO = weightOut                                                                            //
+ **********************************************************************************************/
+/// Computes the amount out for `amount_in`, with balances and amounts upscaled to 18 decimals
+/// and the swap fee already deducted from `amount_in`.
+pub fn calc_out_given_in(
+    balance_in: U256,
+    weight_in: U256,
+    balance_out: U256,
+    weight_out: U256,
+    amount_in: U256,
+) -> Result<U256, BalancerV2Error> {
+    // Pools do not accept swaps larger than 30% of the balance in
+    if amount_in > mul_down(balance_in, BALANCER_V2_MAX_IN_RATIO) {
+        return Err(BalancerV2Error::MaxInRatio);
+    }
+
+    let denominator = balance_in + amount_in;
+    let base = div_up(balance_in, denominator)?;
+    let exponent = div_down(weight_in, weight_out)?;
+    let power = pow_up(base, exponent)?;
+
+    Ok(mul_down(balance_out, complement(power)))
+}
@@ -23,11 +23,35 @@ pub const U256_1: U256 = U256::from_limbs([1, 0, 0, 0]);
 
 // Uniswap V3 specific
 pub const POPULATE_TICK_DATA_STEP: u64 = 100000;
+pub const Q64: U256 = U256::from_limbs([0, 1, 0, 0]);
+pub const Q96: U256 = U256::from_limbs([0, 4294967296, 0, 0]);
 pub const Q128: U256 = U256::from_limbs([0, 0, 1, 0]);
 pub const Q224: U256 = U256::from_limbs([0, 0, 0, 4294967296]);
 
 // Balancer V2 specific
 pub const BONE: U256 = U256::from_limbs([0xDE0B6B3A7640000, 0, 0, 0]);
+pub const BALANCER_V2_AMP_PRECISION: U256 = U256_1000;
+pub const BALANCER_V2_MAX_IN_RATIO: U256 = U256::from_limbs([300000000000000000, 0, 0, 0]);
+pub const BALANCER_V2_MAX_ITERATIONS: usize = 255;
+
+// Curve specific
+pub const CURVE_FEE_DENOMINATOR: U256 = U256_10E_10;
+pub const CURVE_PRECISION: U256 = U256::from_limbs([0xDE0B6B3A7640000, 0, 0, 0]);
+pub const CURVE_MAX_ITERATIONS: usize = 255;
+pub const CURVE_A_MULTIPLIER: U256 = U256_10000;
+
+// Solidly specific
+pub const SOLIDLY_FEE_DENOMINATOR: U256 = U256_10000;
+pub const SOLIDLY_PRECISION: U256 = U256::from_limbs([0xDE0B6B3A7640000, 0, 0, 0]);
+pub const SOLIDLY_MAX_ITERATIONS: usize = 255;
+/// Custom fee a Solidly factory stores for pools charging no fee, as zero resets to the default
+pub const SOLIDLY_ZERO_FEE_INDICATOR: u32 = 420;
+
+// Liquidity Book specific
+pub const LIQUIDITY_BOOK_PRECISION: U256 = U256::from_limbs([0xDE0B6B3A7640000, 0, 0, 0]);
+pub const LIQUIDITY_BOOK_BASIS_POINT_MAX: U256 = U256_10000;
+pub const LIQUIDITY_BOOK_REAL_ID_SHIFT: i32 = 1 << 23;
+pub const LIQUIDITY_BOOK_BINS_PER_SIDE: usize = 100;
 
 // Others
 pub const U128_0X10000000000000000: u128 = 18446744073709551616;
@@ -0,0 +1,331 @@
+use alloy::primitives::U256;
+
+use crate::amms::consts::{
+    CURVE_A_MULTIPLIER, CURVE_MAX_ITERATIONS, CURVE_PRECISION, U256_1, U256_2, U256_4,
+};
+
+use super::CurveError;
+
+// Bounds asserted by the pool contracts to keep the Newton iterations well behaved
+const U256_1E9: U256 = U256::from_limbs([1000000000, 0, 0, 0]);
+const U256_1E11: U256 = U256::from_limbs([100000000000, 0, 0, 0]);
+const U256_1E14: U256 = U256::from_limbs([100000000000000, 0, 0, 0]);
+const U256_1E16: U256 = U256::from_limbs([10000000000000000, 0, 0, 0]);
+const U256_1E17: U256 = U256::from_limbs([100000000000000000, 0, 0, 0]);
+const U256_1E20: U256 = U256::from_limbs([7766279631452241920, 5, 0, 0]);
+const U256_1E33: U256 = U256::from_limbs([4089650035136921600, 54210108624275, 0, 0]);
+const U256_100: U256 = U256::from_limbs([100, 0, 0, 0]);
+const EXP_PRECISION: U256 = U256::from_limbs([10000000000, 0, 0, 0]);
+
+/// Computes `(x[0] * x[1] * ...) ^ (1 / N)`.
+///
+/// Two coin pools use a specialized iteration, which rounds differently from the generic one.
+pub fn geometric_mean(unsorted_x: &[U256], sort: bool) -> Result<U256, CurveError> {
+    let x = if sort {
+        sort_descending(unsorted_x)
+    } else {
+        unsorted_x.to_vec()
+    };
+    if x.iter().any(|x| x.is_zero()) {
+        return Err(CurveError::ZeroBalance);
+    }
+
+    let n_coins = U256::from(x.len());
+    let mut d = x[0];
+    for _ in 0..CURVE_MAX_ITERATIONS {
+        let d_prev = d;
+        d = if x.len() == 2 {
+            (d + x[0] * x[1] / d) / U256_2
+        } else {
+            let tmp = x.iter().fold(CURVE_PRECISION, |tmp, x| tmp * x / d);
+            d * ((n_coins - U256_1) * CURVE_PRECISION + tmp) / (n_coins * CURVE_PRECISION)
+        };
+
+        let diff = d.abs_diff(d_prev);
+        if diff <= U256_1 || diff * CURVE_PRECISION < d {
+            return Ok(d);
+        }
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+/// Computes the invariant `D` for the normalized balances `x_unsorted`.
+///
+/// `ann` is `A * N^N * A_MULTIPLIER`, as stored by the pool.
+pub fn newton_d(ann: U256, gamma: U256, x_unsorted: &[U256]) -> Result<U256, CurveError> {
+    let x = sort_descending(x_unsorted);
+    let n_coins = U256::from(x.len());
+
+    if x[0] < U256_1E9 || x[0] > U256_1E33 {
+        return Err(CurveError::UnsafeValues);
+    }
+    let min_frac = if x.len() == 2 { U256_1E14 } else { U256_1E11 };
+    if x[1..]
+        .iter()
+        .any(|x_i| x_i * CURVE_PRECISION / x[0] < min_frac)
+    {
+        return Err(CurveError::UnsafeValues);
+    }
+
+    let mut d = n_coins * geometric_mean(&x, false)?;
+    let s = x.iter().fold(U256::ZERO, |acc, x| acc + x);
+
+    for _ in 0..CURVE_MAX_ITERATIONS {
+        let d_prev = d;
+
+        let k0 = if x.len() == 2 {
+            CURVE_PRECISION * U256_4 * x[0] / d * x[1] / d
+        } else {
+            x.iter().fold(CURVE_PRECISION, |k0, x| k0 * x * n_coins / d)
+        };
+        if k0.is_zero() {
+            return Err(CurveError::UnsafeValues);
+        }
+
+        let g1k0 = g1k0(gamma, k0);
+        let mul1 = CURVE_PRECISION * d / gamma * g1k0 / gamma * g1k0 * CURVE_A_MULTIPLIER / ann;
+        let mul2 = U256_2 * CURVE_PRECISION * n_coins * k0 / g1k0;
+
+        let neg_fprime = (s + s * mul2 / CURVE_PRECISION + mul1 * n_coins / k0)
+            .checked_sub(mul2 * d / CURVE_PRECISION)
+            .filter(|neg_fprime| !neg_fprime.is_zero())
+            .ok_or(CurveError::UnsafeValues)?;
+
+        let d_plus = d * (neg_fprime + s) / neg_fprime;
+        let mut d_minus = d * d / neg_fprime;
+        if CURVE_PRECISION > k0 {
+            d_minus += d * (mul1 / neg_fprime) / CURVE_PRECISION * (CURVE_PRECISION - k0) / k0;
+        } else {
+            d_minus = d_minus
+                .checked_sub(
+                    d * (mul1 / neg_fprime) / CURVE_PRECISION * (k0 - CURVE_PRECISION) / k0,
+                )
+                .ok_or(CurveError::UnsafeValues)?;
+        }
+
+        d = if d_plus > d_minus {
+            d_plus - d_minus
+        } else {
+            (d_minus - d_plus) / U256_2
+        };
+
+        if d.abs_diff(d_prev) * U256_1E14 < d.max(U256_1E16) {
+            // Make sure the next newton_y is safe
+            for x_i in &x {
+                let frac = x_i * CURVE_PRECISION / d;
+                if frac < U256_1E16 || frac > U256_1E20 {
+                    return Err(CurveError::UnsafeValues);
+                }
+            }
+            return Ok(d);
+        }
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+/// Computes the normalized balance of coin `i` that satisfies the invariant `d` given the other
+/// balances in `x`.
+pub fn newton_y(ann: U256, gamma: U256, x: &[U256], d: U256, i: usize) -> Result<U256, CurveError> {
+    if i >= x.len() {
+        return Err(CurveError::InvalidCoinIndex);
+    }
+    if d < U256_1E17 || d > U256_1E33 {
+        return Err(CurveError::UnsafeValues);
+    }
+
+    let n_coins = U256::from(x.len());
+    let (mut y, k0_i, s_i, convergence_limit) = if x.len() == 2 {
+        let x_j = x[1 - i];
+        if x_j.is_zero() {
+            return Err(CurveError::ZeroBalance);
+        }
+
+        let k0_i = CURVE_PRECISION * U256_2 * x_j / d;
+        if k0_i < U256_1E16 * U256_2 || k0_i > U256_1E20 * U256_2 {
+            return Err(CurveError::UnsafeValues);
+        }
+
+        let convergence_limit = (x_j / U256_1E14).max(d / U256_1E14).max(U256_100);
+        (d * d / (x_j * U256_4), k0_i, x_j, convergence_limit)
+    } else {
+        for (k, x_k) in x.iter().enumerate() {
+            let frac = x_k * CURVE_PRECISION / d;
+            if k != i && (frac < U256_1E16 || frac > U256_1E20) {
+                return Err(CurveError::UnsafeValues);
+            }
+        }
+
+        let mut x_sorted = x.to_vec();
+        x_sorted[i] = U256::ZERO;
+        let x_sorted = sort_descending(&x_sorted);
+        let convergence_limit = (x_sorted[0] / U256_1E14).max(d / U256_1E14).max(U256_100);
+
+        // Smallest balances first for y, largest first for K0_i
+        let mut y = d / n_coins;
+        let mut s_i = U256::ZERO;
+        for x_k in x_sorted[..x.len() - 1].iter().rev() {
+            y = y * d / (x_k * n_coins);
+            s_i += x_k;
+        }
+        let k0_i = x_sorted[..x.len() - 1]
+            .iter()
+            .fold(CURVE_PRECISION, |k0_i, x_k| k0_i * x_k * n_coins / d);
+
+        (y, k0_i, s_i, convergence_limit)
+    };
+
+    for _ in 0..CURVE_MAX_ITERATIONS {
+        let y_prev = y;
+
+        let k0 = k0_i * y * n_coins / d;
+        let s = s_i + y;
+
+        let g1k0 = g1k0(gamma, k0);
+        let mul1 = CURVE_PRECISION * d / gamma * g1k0 / gamma * g1k0 * CURVE_A_MULTIPLIER / ann;
+        let mul2 = CURVE_PRECISION + U256_2 * CURVE_PRECISION * k0 / g1k0;
+
+        let yfprime = CURVE_PRECISION * y + s * mul2 + mul1;
+        let dyfprime = d * mul2;
+        if yfprime < dyfprime {
+            y = y_prev / U256_2;
+            continue;
+        }
+        let yfprime = yfprime - dyfprime;
+        if y.is_zero() {
+            return Err(CurveError::UnsafeValues);
+        }
+
+        let fprime = yfprime / y;
+        if fprime.is_zero() || k0.is_zero() {
+            return Err(CurveError::UnsafeValues);
+        }
+
+        let mut y_minus = mul1 / fprime;
+        let y_plus = (yfprime + CURVE_PRECISION * d) / fprime + y_minus * CURVE_PRECISION / k0;
+        y_minus += CURVE_PRECISION * s / fprime;
+
+        y = if y_plus < y_minus {
+            y_prev / U256_2
+        } else {
+            y_plus - y_minus
+        };
+
+        if y.abs_diff(y_prev) < convergence_limit.max(y / U256_1E14) {
+            let frac = y * CURVE_PRECISION / d;
+            if frac < U256_1E16 || frac > U256_1E20 {
+                return Err(CurveError::UnsafeValues);
+            }
+            return Ok(y);
+        }
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+/// Dynamic fee for the normalized balances `xp`, out of 1e10.
+///
+/// The fee moves from `mid_fee` when the pool is balanced towards `out_fee` as it gets
+/// imbalanced, at a rate set by `fee_gamma`.
+pub fn fee(xp: &[U256], mid_fee: U256, out_fee: U256, fee_gamma: U256) -> U256 {
+    let f = if xp.len() == 2 {
+        let s = xp[0] + xp[1];
+        fee_gamma * CURVE_PRECISION
+            / (fee_gamma + CURVE_PRECISION - CURVE_PRECISION * U256_4 * xp[0] / s * xp[1] / s)
+    } else {
+        reduction_coefficient(xp, fee_gamma)
+    };
+
+    (mid_fee * f + out_fee * (CURVE_PRECISION - f)) / CURVE_PRECISION
+}
+
+/// Computes `fee_gamma / (fee_gamma + (1 - K))` with `K = prod(x) / (sum(x) / N)^N`.
+pub fn reduction_coefficient(x: &[U256], fee_gamma: U256) -> U256 {
+    let n_coins = U256::from(x.len());
+    let s = x.iter().fold(U256::ZERO, |acc, x| acc + x);
+
+    let k = x.iter().fold(CURVE_PRECISION, |k, x| k * n_coins * x / s);
+    if fee_gamma.is_zero() {
+        return k;
+    }
+
+    fee_gamma * CURVE_PRECISION / (fee_gamma + CURVE_PRECISION - k)
+}
+
+/// Computes `1e18 * 0.5 ^ (power / 1e18)`, used to decay the price oracle.
+pub fn halfpow(power: U256) -> Result<U256, CurveError> {
+    let intpow = power / CURVE_PRECISION;
+    let otherpow = power - intpow * CURVE_PRECISION;
+    if intpow > U256::from(59) {
+        return Ok(U256::ZERO);
+    }
+
+    let result = CURVE_PRECISION / (U256_1 << intpow.to::<usize>());
+    if otherpow.is_zero() {
+        return Ok(result);
+    }
+
+    let x = CURVE_PRECISION / U256_2;
+    let mut term = CURVE_PRECISION;
+    let mut s = CURVE_PRECISION;
+    let mut neg = false;
+
+    for i in 1..256 {
+        let k = U256::from(i) * CURVE_PRECISION;
+        let mut c = k - CURVE_PRECISION;
+        if otherpow > c {
+            c = otherpow - c;
+            neg = !neg;
+        } else {
+            c -= otherpow;
+        }
+
+        term = term * (c * x / CURVE_PRECISION) / k;
+        if neg {
+            s -= term;
+        } else {
+            s += term;
+        }
+
+        if term < EXP_PRECISION {
+            return Ok(result * s / CURVE_PRECISION);
+        }
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+/// Computes `sqrt(x * 1e18)`.
+pub fn sqrt_int(x: U256) -> Result<U256, CurveError> {
+    if x.is_zero() {
+        return Ok(U256::ZERO);
+    }
+
+    let mut z = (x + CURVE_PRECISION) / U256_2;
+    let mut y = x;
+    for _ in 0..256 {
+        if z == y {
+            return Ok(y);
+        }
+        y = z;
+        z = (x * CURVE_PRECISION / z + z) / U256_2;
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+fn g1k0(gamma: U256, k0: U256) -> U256 {
+    let g1k0 = gamma + CURVE_PRECISION;
+    if g1k0 > k0 {
+        g1k0 - k0 + U256_1
+    } else {
+        k0 - g1k0 + U256_1
+    }
+}
+
+fn sort_descending(x: &[U256]) -> Vec<U256> {
+    let mut x = x.to_vec();
+    x.sort_unstable_by(|a, b| b.cmp(a));
+    x
+}
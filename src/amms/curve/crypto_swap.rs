@@ -0,0 +1,1434 @@
+use std::future::Future;
+
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, Bytes, B256, U256},
+    providers::Provider,
+    rpc::types::Log,
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use rug::{float::Round, Float};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::{
+    crypto_math::{fee, geometric_mean, halfpow, newton_d, newton_y, sqrt_int},
+    CurveError,
+};
+use crate::amms::{
+    amm::{check_amount_out, AutomatedMarketMaker, AMM},
+    consts::{CURVE_FEE_DENOMINATOR, CURVE_PRECISION, U256_1, U256_10, U256_2},
+    error::AMMError,
+    factory::{AutomatedMarketMakerFactory, DiscoverySync},
+    float::{float_to_q128, u256_to_float},
+    get_block_timestamp, Token,
+};
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract ICurveCryptoSwap {
+        event TokenExchange(address indexed buyer, uint256 sold_id, uint256 tokens_sold, uint256 bought_id, uint256 tokens_bought);
+        event RemoveLiquidityOne(address indexed provider, uint256 token_amount, uint256 coin_index, uint256 coin_amount);
+        event NewParameters(uint256 admin_fee, uint256 mid_fee, uint256 out_fee, uint256 fee_gamma, uint256 allowed_extra_profit, uint256 adjustment_step, uint256 ma_half_time);
+        event RampAgamma(uint256 initial_A, uint256 future_A, uint256 initial_gamma, uint256 future_gamma, uint256 initial_time, uint256 future_time);
+        event StopRampA(uint256 current_A, uint256 current_gamma, uint256 time);
+
+        function get_dy(uint256 i, uint256 j, uint256 dx) external view returns (uint256);
+        function exchange(uint256 i, uint256 j, uint256 dx, uint256 min_dy) external returns (uint256);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveCryptoSwap2 {
+        event AddLiquidity(address indexed provider, uint256[2] token_amounts, uint256 fee, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[2] token_amounts, uint256 token_supply);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveCryptoSwap3 {
+        event AddLiquidity(address indexed provider, uint256[3] token_amounts, uint256 fee, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[3] token_amounts, uint256 token_supply);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    #[allow(clippy::too_many_arguments)]
+    contract ICurveCryptoSwapFactory {
+        event CryptoPoolDeployed(address token, address[2] coins, uint256 A, uint256 gamma, uint256 mid_fee, uint256 out_fee, uint256 allowed_extra_profit, uint256 fee_gamma, uint256 adjustment_step, uint256 admin_fee, uint256 ma_half_time, uint256 initial_price, address deployer);
+
+        function pool_count() external view returns (uint256);
+        function pool_list(uint256 i) external view returns (address);
+        function get_token(address pool) external view returns (address);
+    }
+
+    #[derive(Debug)]
+    struct CryptoSwapFees {
+        uint256 midFee;
+        uint256 outFee;
+        uint256 feeGamma;
+        uint256 adminFee;
+    }
+
+    #[derive(Debug)]
+    struct CryptoSwapPoolData {
+        address[] coins;
+        uint8[] decimals;
+        uint256[] balances;
+        uint256[] priceScale;
+        uint256[] priceOracle;
+        uint256[] lastPrices;
+        uint256 lastPricesTimestamp;
+        uint256 D;
+        uint256 initialAGamma;
+        uint256 futureAGamma;
+        uint256 initialAGammaTime;
+        uint256 futureAGammaTime;
+        CryptoSwapFees fees;
+        uint256 allowedExtraProfit;
+        uint256 adjustmentStep;
+        uint256 maHalfTime;
+        uint256 xcpProfit;
+        uint256 xcpProfitA;
+        uint256 virtualPrice;
+        bool notAdjusted;
+        uint256 totalSupply;
+        bool isNG;
+    }
+}
+
+sol! {
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IGetCurveCryptoSwapPoolsBatchRequest,
+    "src/amms/abi/GetCurveCryptoSwapPoolsBatchRequest.json",
+}
+
+sol! {
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IGetCurveCryptoSwapPoolDataBatchRequest,
+    "src/amms/abi/GetCurveCryptoSwapPoolDataBatchRequest.json",
+}
+
+// Trades and withdrawals below this size do not update the last traded price
+const MIN_PRICE_AMOUNT: U256 = U256::from_limbs([100000, 0, 0, 0]);
+
+/// A Curve CryptoSwap pool, covering the two coin pools deployed by the crypto factory and
+/// tricrypto pools.
+///
+/// Coins after the first are priced in the first coin through `price_scale`, which repegs towards
+/// the `price_oracle` moving average once the pool has accrued enough profit. Syncing replays the
+/// repegging logic of the pool. Admin fees claimed outside of a repeg are only picked up by the
+/// token supply reported with the next liquidity event.
+///
+/// Pools deployed by the twocrypto NG and tricrypto NG factories are not supported: they log a
+/// seven argument `TokenExchange` and price swaps with different math, so initializing one fails
+/// with [`CurveError::UnsupportedNGPool`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurveCryptoSwapPool {
+    pub address: Address,
+    pub tokens: Vec<Token>,
+    pub balances: Vec<U256>,
+    /// Price of each coin after the first in the first coin used to normalize balances, scaled
+    /// by 1e18
+    pub price_scale: Vec<U256>,
+    /// Exponential moving average of `last_prices`
+    pub price_oracle: Vec<U256>,
+    pub last_prices: Vec<U256>,
+    pub last_prices_timestamp: u64,
+    pub d: U256,
+    /// Amplification coefficient, multiplied by `N^N` and `A_MULTIPLIER`
+    pub initial_a: U256,
+    pub future_a: U256,
+    pub initial_gamma: U256,
+    pub future_gamma: U256,
+    pub initial_a_gamma_time: u64,
+    pub future_a_gamma_time: u64,
+    /// Fee charged on a balanced pool, out of 1e10
+    pub mid_fee: U256,
+    /// Fee charged on an imbalanced pool, out of 1e10
+    pub out_fee: U256,
+    pub fee_gamma: U256,
+    /// Share of the profit minted to the protocol, out of 1e10
+    pub admin_fee: U256,
+    pub allowed_extra_profit: U256,
+    pub adjustment_step: U256,
+    /// Half life of the price oracle, in seconds
+    pub ma_half_time: U256,
+    pub xcp_profit: U256,
+    pub xcp_profit_a: U256,
+    pub virtual_price: U256,
+    pub not_adjusted: bool,
+    /// Total supply of the pool LP token
+    pub total_supply: U256,
+    /// Timestamp of the block the pool is synced to, at which ramps and the price oracle are
+    /// evaluated
+    #[serde(default)]
+    pub block_timestamp: u64,
+}
+
+impl AutomatedMarketMaker for CurveCryptoSwapPool {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        vec![
+            ICurveCryptoSwap::TokenExchange::SIGNATURE_HASH,
+            ICurveCryptoSwap::RemoveLiquidityOne::SIGNATURE_HASH,
+            ICurveCryptoSwap::NewParameters::SIGNATURE_HASH,
+            ICurveCryptoSwap::RampAgamma::SIGNATURE_HASH,
+            ICurveCryptoSwap::StopRampA::SIGNATURE_HASH,
+            ICurveCryptoSwap2::AddLiquidity::SIGNATURE_HASH,
+            ICurveCryptoSwap2::RemoveLiquidity::SIGNATURE_HASH,
+            ICurveCryptoSwap3::AddLiquidity::SIGNATURE_HASH,
+            ICurveCryptoSwap3::RemoveLiquidity::SIGNATURE_HASH,
+        ]
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        let timestamp = log.block_timestamp.unwrap_or(self.block_timestamp);
+        self.block_timestamp = timestamp;
+        let event_signature = log.topics()[0];
+        match event_signature {
+            ICurveCryptoSwap::TokenExchange::SIGNATURE_HASH => {
+                let exchange_event = ICurveCryptoSwap::TokenExchange::decode_log(log.as_ref())?;
+
+                self.apply_exchange(
+                    coin_index(exchange_event.sold_id)?,
+                    coin_index(exchange_event.bought_id)?,
+                    exchange_event.tokens_sold,
+                    exchange_event.tokens_bought,
+                    timestamp,
+                )?;
+            }
+            ICurveCryptoSwap2::AddLiquidity::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap2::AddLiquidity::decode_log(log.as_ref())?;
+                self.sync_add_liquidity(&event.token_amounts, event.token_supply, timestamp)?;
+            }
+            ICurveCryptoSwap3::AddLiquidity::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap3::AddLiquidity::decode_log(log.as_ref())?;
+                self.sync_add_liquidity(&event.token_amounts, event.token_supply, timestamp)?;
+            }
+            ICurveCryptoSwap2::RemoveLiquidity::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap2::RemoveLiquidity::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, event.token_supply);
+            }
+            ICurveCryptoSwap3::RemoveLiquidity::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap3::RemoveLiquidity::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, event.token_supply);
+            }
+            ICurveCryptoSwap::RemoveLiquidityOne::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap::RemoveLiquidityOne::decode_log(log.as_ref())?;
+                let i = coin_index(event.coin_index)?;
+
+                // Balances follow the event, the invariant is recomputed as in the pool
+                let (_, d, xp) = self.withdraw_one_coin(event.token_amount, i, timestamp)?;
+                self.balances[i] -= event.coin_amount;
+                self.total_supply -= event.token_amount;
+
+                let (a, gamma) = self.a_gamma(timestamp);
+                self.end_ramp(timestamp);
+                self.tweak_price(a, gamma, &xp, None, d, timestamp)?;
+            }
+            ICurveCryptoSwap::NewParameters::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap::NewParameters::decode_log(log.as_ref())?;
+
+                self.admin_fee = event.admin_fee;
+                self.mid_fee = event.mid_fee;
+                self.out_fee = event.out_fee;
+                self.fee_gamma = event.fee_gamma;
+                self.allowed_extra_profit = event.allowed_extra_profit;
+                self.adjustment_step = event.adjustment_step;
+                self.ma_half_time = event.ma_half_time;
+            }
+            ICurveCryptoSwap::RampAgamma::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap::RampAgamma::decode_log(log.as_ref())?;
+
+                self.initial_a = event.initial_A;
+                self.future_a = event.future_A;
+                self.initial_gamma = event.initial_gamma;
+                self.future_gamma = event.future_gamma;
+                self.initial_a_gamma_time = event.initial_time.to();
+                self.future_a_gamma_time = event.future_time.to();
+            }
+            ICurveCryptoSwap::StopRampA::SIGNATURE_HASH => {
+                let event = ICurveCryptoSwap::StopRampA::decode_log(log.as_ref())?;
+
+                self.initial_a = event.current_A;
+                self.future_a = event.current_A;
+                self.initial_gamma = event.current_gamma;
+                self.future_gamma = event.current_gamma;
+                self.initial_a_gamma_time = event.time.to();
+                self.future_a_gamma_time = event.time.to();
+            }
+            _ => return Err(AMMError::UnrecognizedEventSignature(event_signature)),
+        }
+
+        info!(
+            target = "amms::curve::crypto_swap::sync",
+            address = ?self.address,
+            balances = ?self.balances,
+            price_scale = ?self.price_scale,
+            d = ?self.d,
+            "Sync"
+        );
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<Address> {
+        self.tokens.iter().map(|t| t.address).collect()
+    }
+
+    /// Calculates the price of `base_token` in `quote_token`, excluding fees.
+    fn calculate_price(&self, base_token: Address, quote_token: Address) -> Result<f64, AMMError> {
+        Ok(self
+            .spot_price(base_token, quote_token)?
+            .to_f64_round(Round::Nearest))
+    }
+
+    /// Calculates the price of `base_token` in `quote_token`, excluding fees, at
+    /// `MPFR_T_PRECISION` bits.
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        float_to_q128(self.spot_price(base_token, quote_token)?)
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let (i, j) = self.coin_indices(base_token, quote_token)?;
+        let amount_out = self.get_dy(i, j, self.tokens[i].after_sell_tax(amount_in))?;
+
+        Ok(self.tokens[j].after_buy_tax(amount_out))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let (i, j) = self.coin_indices(base_token, quote_token)?;
+        let amount_out = self.exchange(i, j, self.tokens[i].after_sell_tax(amount_in))?;
+
+        Ok(self.tokens[j].after_buy_tax(amount_out))
+    }
+
+    /// Encodes `exchange` on the pool. The pool pays the caller, so `recipient` is unused.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        _recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        let (i, j) = self.coin_indices(base_token, quote_token)?;
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        Ok(ICurveCryptoSwap::exchangeCall {
+            i: U256::from(i),
+            j: U256::from(j),
+            dx: amount_in,
+            min_dy: min_out,
+        }
+        .abi_encode()
+        .into())
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        CurveCryptoSwapPool::get_pool_data(vec![self.address], block_number, provider)
+            .await?
+            .pop()
+            .ok_or(CurveError::InitializationError.into())
+    }
+}
+
+impl CurveCryptoSwapPool {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            ..Default::default()
+        }
+    }
+
+    /// Calculates the price of `base_token` in `quote_token`, excluding fees.
+    ///
+    /// The invariant has no closed form derivative, so the price is measured with a swap of a
+    /// millionth of the `base_token` balance, as the pool does to update `last_prices`.
+    pub fn spot_price(&self, base_token: Address, quote_token: Address) -> Result<Float, AMMError> {
+        let (i, j) = self.coin_indices(base_token, quote_token)?;
+        let (a, gamma) = self.a_gamma(self.block_timestamp);
+
+        let mut xp = self.xp();
+        let d = self.current_d(a, gamma, &xp)?;
+        let x_j = xp[j];
+
+        let dx = xp[i] / U256::from(1_000_000);
+        xp[i] += dx;
+        let dy = x_j - newton_y(a, gamma, &xp, d, j)?;
+
+        // Normalized balances are priced in the first coin, scaled by 1e18
+        Ok(
+            u256_to_float(dy)? * u256_to_float(self.coin_price_scale(i))?
+                / (u256_to_float(dx)? * u256_to_float(self.coin_price_scale(j))?),
+        )
+    }
+
+    /// Fetches the state of `pools` at `block_number`, skipping addresses that are not
+    /// CryptoSwap pools.
+    pub async fn get_pool_data<N, P>(
+        pools: Vec<Address>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Self>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let block_timestamp = get_block_timestamp(block_number, provider.clone()).await?;
+        let deployer =
+            IGetCurveCryptoSwapPoolDataBatchRequest::deploy_builder(provider, pools.clone());
+        let res = deployer.block(block_number).call_raw().await?;
+
+        let data = <Vec<CryptoSwapPoolData> as SolValue>::abi_decode(&res)?;
+
+        pools
+            .into_iter()
+            .zip(data)
+            .filter(|(_, pool_data)| !pool_data.coins.is_empty())
+            .map(|(address, pool_data)| {
+                CurveCryptoSwapPool::from_pool_data(address, pool_data, block_timestamp)
+            })
+            .collect()
+    }
+
+    fn from_pool_data(
+        address: Address,
+        pool_data: CryptoSwapPoolData,
+        block_timestamp: u64,
+    ) -> Result<Self, AMMError> {
+        if pool_data.isNG {
+            return Err(CurveError::UnsupportedNGPool(address).into());
+        }
+
+        let n_coins = pool_data.coins.len();
+        if n_coins < 2
+            || pool_data.balances.len() != n_coins
+            || pool_data.price_scale_len() != n_coins - 1
+        {
+            return Err(CurveError::InitializationError.into());
+        }
+
+        // A and gamma are packed into the upper and lower 128 bits
+        let (initial_a, initial_gamma) = unpack_a_gamma(pool_data.initialAGamma);
+        let (future_a, future_gamma) = unpack_a_gamma(pool_data.futureAGamma);
+
+        Ok(Self {
+            address,
+            tokens: pool_data
+                .coins
+                .into_iter()
+                .zip(pool_data.decimals)
+                .map(|(coin, decimals)| Token::new_with_decimals(coin, decimals))
+                .collect(),
+            balances: pool_data.balances,
+            price_scale: pool_data.priceScale,
+            price_oracle: pool_data.priceOracle,
+            last_prices: pool_data.lastPrices,
+            last_prices_timestamp: pool_data.lastPricesTimestamp.to(),
+            d: pool_data.D,
+            initial_a,
+            future_a,
+            initial_gamma,
+            future_gamma,
+            initial_a_gamma_time: pool_data.initialAGammaTime.to(),
+            future_a_gamma_time: pool_data.futureAGammaTime.to(),
+            mid_fee: pool_data.fees.midFee,
+            out_fee: pool_data.fees.outFee,
+            fee_gamma: pool_data.fees.feeGamma,
+            admin_fee: pool_data.fees.adminFee,
+            allowed_extra_profit: pool_data.allowedExtraProfit,
+            adjustment_step: pool_data.adjustmentStep,
+            ma_half_time: pool_data.maHalfTime,
+            xcp_profit: pool_data.xcpProfit,
+            xcp_profit_a: pool_data.xcpProfitA,
+            virtual_price: pool_data.virtualPrice,
+            not_adjusted: pool_data.notAdjusted,
+            total_supply: pool_data.totalSupply,
+            block_timestamp,
+        })
+    }
+
+    /// Returns `A * N^N * A_MULTIPLIER` and `gamma` at `timestamp`, interpolating any ongoing
+    /// ramp.
+    pub fn a_gamma(&self, timestamp: u64) -> (U256, U256) {
+        let (t0, t1) = (self.initial_a_gamma_time, self.future_a_gamma_time);
+        if timestamp >= t1 || t1 <= t0 {
+            return (self.future_a, self.future_gamma);
+        }
+
+        let duration = U256::from(t1 - t0);
+        let elapsed = U256::from(timestamp.saturating_sub(t0));
+        let remaining = duration - elapsed;
+
+        (
+            (self.initial_a * remaining + self.future_a * elapsed) / duration,
+            (self.initial_gamma * remaining + self.future_gamma * elapsed) / duration,
+        )
+    }
+
+    /// Returns the amount of coin `j` received for `dx` of coin `i`, as computed by the pool's
+    /// `get_dy` at `block_timestamp`.
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Result<U256, AMMError> {
+        Ok(self.get_dy_at(i, j, dx, self.block_timestamp)?.0)
+    }
+
+    /// Swaps `dx` of coin `i` for coin `j` at `block_timestamp`, returning the amount received.
+    pub fn exchange(&mut self, i: usize, j: usize, dx: U256) -> Result<U256, AMMError> {
+        let (dy, _) = self.get_dy_at(i, j, dx, self.block_timestamp)?;
+        self.apply_exchange(i, j, dx, dy, self.block_timestamp)?;
+
+        Ok(dy)
+    }
+
+    /// Returns the amount of coin `i` received for burning `token_amount` LP tokens at
+    /// `block_timestamp`.
+    pub fn calc_withdraw_one_coin(&self, token_amount: U256, i: usize) -> Result<U256, AMMError> {
+        Ok(self
+            .withdraw_one_coin(token_amount, i, self.block_timestamp)?
+            .0)
+    }
+
+    /// Returns the amount received for the swap and the normalized balances after it.
+    fn get_dy_at(
+        &self,
+        i: usize,
+        j: usize,
+        dx: U256,
+        timestamp: u64,
+    ) -> Result<(U256, Vec<U256>), AMMError> {
+        let n_coins = self.balances.len();
+        if i == j || i >= n_coins || j >= n_coins {
+            return Err(CurveError::InvalidCoinIndex.into());
+        }
+
+        let (a, gamma) = self.a_gamma(timestamp);
+        let d = self.current_d(a, gamma, &self.xp())?;
+
+        let mut balances = self.balances.clone();
+        balances[i] += dx;
+        let mut xp = xp(&balances, &self.precisions(), &self.price_scale);
+
+        let y = newton_y(a, gamma, &xp, d, j)?;
+        let mut dy = xp[j]
+            .checked_sub(y + U256_1)
+            .ok_or(CurveError::InsufficientLiquidity)?;
+        xp[j] = y;
+
+        // Two coin pools fold the precision into the price scale before dividing
+        let precisions = self.precisions();
+        if j > 0 && n_coins == 2 {
+            dy = dy * CURVE_PRECISION / (self.price_scale[0] * precisions[1]);
+        } else {
+            if j > 0 {
+                dy = dy * CURVE_PRECISION / self.price_scale[j - 1];
+            }
+            dy /= precisions[j];
+        }
+        dy -= fee(&xp, self.mid_fee, self.out_fee, self.fee_gamma) * dy / CURVE_FEE_DENOMINATOR;
+
+        Ok((dy, xp))
+    }
+
+    /// Applies a swap of `dx` of coin `i` for `dy` of coin `j` and updates the price state.
+    fn apply_exchange(
+        &mut self,
+        i: usize,
+        j: usize,
+        dx: U256,
+        dy: U256,
+        timestamp: u64,
+    ) -> Result<(), AMMError> {
+        let n_coins = self.balances.len();
+        if i == j || i >= n_coins || j >= n_coins {
+            return Err(CurveError::InvalidCoinIndex.into());
+        }
+
+        let (a, gamma) = self.a_gamma(timestamp);
+        self.balances[i] += dx;
+        self.balances[j] = self.balances[j]
+            .checked_sub(dy)
+            .ok_or(CurveError::InsufficientLiquidity)?;
+        self.end_ramp(timestamp);
+
+        // The traded price of the coin other than the first, in the first coin
+        let mut last_price = None;
+        if dx > MIN_PRICE_AMOUNT && dy > MIN_PRICE_AMOUNT {
+            let precisions = self.precisions();
+            let dx = dx * precisions[i];
+            let dy = dy * precisions[j];
+
+            last_price = Some(if i != 0 && j != 0 {
+                (j, self.last_prices[i - 1] * dx / dy)
+            } else if i == 0 {
+                (j, dx * CURVE_PRECISION / dy)
+            } else {
+                (i, dy * CURVE_PRECISION / dx)
+            });
+        }
+
+        let xp = self.xp();
+        self.tweak_price(a, gamma, &xp, last_price, U256::ZERO, timestamp)
+    }
+
+    /// Returns the amount of coin `i` withdrawn for `token_amount` LP tokens, along with the
+    /// invariant and normalized balances after the withdrawal.
+    fn withdraw_one_coin(
+        &self,
+        token_amount: U256,
+        i: usize,
+        timestamp: u64,
+    ) -> Result<(U256, U256, Vec<U256>), AMMError> {
+        if i >= self.balances.len() {
+            return Err(CurveError::InvalidCoinIndex.into());
+        }
+        if token_amount > self.total_supply || self.total_supply.is_zero() {
+            return Err(CurveError::InsufficientLiquidity.into());
+        }
+
+        let (a, gamma) = self.a_gamma(timestamp);
+        let mut xp = self.xp();
+        let mut d = self.current_d(a, gamma, &xp)?;
+
+        // The fee is charged on D rather than on y, reducing the invariant less than the user
+        let fee = fee(&xp, self.mid_fee, self.out_fee, self.fee_gamma);
+        let d_d = token_amount * d / self.total_supply;
+        d -= d_d - (fee * d_d / (U256_2 * CURVE_FEE_DENOMINATOR) + U256_1);
+
+        let y = newton_y(a, gamma, &xp, d, i)?;
+        let dy = (xp[i] - y) * CURVE_PRECISION / (self.coin_price_scale(i) * self.precisions()[i]);
+        xp[i] = y;
+
+        Ok((dy, d, xp))
+    }
+
+    fn sync_add_liquidity(
+        &mut self,
+        amounts: &[U256],
+        token_supply: U256,
+        timestamp: u64,
+    ) -> Result<(), AMMError> {
+        let (a, gamma) = self.a_gamma(timestamp);
+        for (balance, amount) in self.balances.iter_mut().zip(amounts) {
+            *balance += amount;
+        }
+        self.end_ramp(timestamp);
+
+        let xp = self.xp();
+        let d = newton_d(a, gamma, &xp)?;
+
+        if self.d.is_zero() || self.total_supply.is_zero() {
+            // The first deposit sets the invariant without touching the prices
+            self.d = d;
+            self.virtual_price = CURVE_PRECISION;
+            self.xcp_profit = CURVE_PRECISION;
+            self.total_supply = token_supply;
+            return Ok(());
+        }
+
+        self.total_supply = token_supply;
+        self.tweak_price(a, gamma, &xp, None, d, timestamp)
+    }
+
+    fn sync_remove_liquidity(&mut self, amounts: &[U256], token_supply: U256) {
+        for (balance, amount) in self.balances.iter_mut().zip(amounts) {
+            *balance -= amount;
+        }
+
+        let token_amount = self.total_supply - token_supply;
+        if !self.total_supply.is_zero() {
+            self.d -= self.d * token_amount / self.total_supply;
+        }
+        self.total_supply = token_supply;
+    }
+
+    /// Replays the pool's `tweak_price`: updates the price oracle, the last prices and the
+    /// profit counters, and moves `price_scale` towards the oracle when the pool can afford it.
+    ///
+    /// `last_price` holds the index of a coin and its traded price in the first coin. Without it,
+    /// the last prices are read from the curve at `xp`.
+    fn tweak_price(
+        &mut self,
+        a: U256,
+        gamma: U256,
+        xp: &[U256],
+        last_price: Option<(usize, U256)>,
+        new_d: U256,
+        timestamp: u64,
+    ) -> Result<(), AMMError> {
+        if self.total_supply.is_zero() {
+            return Err(CurveError::ZeroBalance.into());
+        }
+
+        if self.last_prices_timestamp < timestamp && !self.ma_half_time.is_zero() {
+            let alpha = halfpow(
+                U256::from(timestamp - self.last_prices_timestamp) * CURVE_PRECISION
+                    / self.ma_half_time,
+            )?;
+            for (price_oracle, last_price) in self.price_oracle.iter_mut().zip(&self.last_prices) {
+                *price_oracle = (last_price * (CURVE_PRECISION - alpha) + *price_oracle * alpha)
+                    / CURVE_PRECISION;
+            }
+            self.last_prices_timestamp = timestamp;
+        }
+
+        let d_unadjusted = if new_d.is_zero() {
+            newton_d(a, gamma, xp)?
+        } else {
+            new_d
+        };
+
+        match last_price {
+            Some((ix, price)) if !price.is_zero() => {
+                if ix > 0 {
+                    self.last_prices[ix - 1] = price;
+                } else {
+                    for last_price in self.last_prices.iter_mut() {
+                        *last_price = *last_price * CURVE_PRECISION / price;
+                    }
+                }
+            }
+            _ => {
+                let mut xp_price = xp.to_vec();
+                let dx_price = xp_price[0] / U256::from(1_000_000);
+                xp_price[0] += dx_price;
+
+                for k in 0..self.last_prices.len() {
+                    let dy_price = xp[k + 1] - newton_y(a, gamma, &xp_price, d_unadjusted, k + 1)?;
+                    self.last_prices[k] = self.price_scale[k] * dx_price / dy_price;
+                }
+            }
+        }
+
+        let old_virtual_price = self.virtual_price;
+        let mut xcp_profit = CURVE_PRECISION;
+        let mut virtual_price = CURVE_PRECISION;
+
+        if !old_virtual_price.is_zero() {
+            let xcp = get_xcp(d_unadjusted, &self.price_scale)?;
+            virtual_price = CURVE_PRECISION * xcp / self.total_supply;
+            xcp_profit = self.xcp_profit * virtual_price / old_virtual_price;
+
+            if self.future_a_gamma_time == 1 {
+                self.future_a_gamma_time = 0;
+            }
+        }
+        self.xcp_profit = xcp_profit;
+
+        let norm = self.price_norm()?;
+        let step_divisor = U256::from(if self.price_scale.len() == 1 { 5 } else { 10 });
+        let adjustment_step = self.adjustment_step.max(norm / step_divisor);
+
+        let mut needs_adjustment = self.not_adjusted;
+        if !needs_adjustment
+            && (virtual_price * U256_2).saturating_sub(CURVE_PRECISION)
+                > xcp_profit + U256_2 * self.allowed_extra_profit
+            && norm > adjustment_step
+            && !old_virtual_price.is_zero()
+        {
+            needs_adjustment = true;
+            self.not_adjusted = true;
+        }
+
+        if needs_adjustment && norm > adjustment_step && !old_virtual_price.is_zero() {
+            let p_new = self
+                .price_scale
+                .iter()
+                .zip(&self.price_oracle)
+                .map(|(price_scale, price_oracle)| {
+                    (price_scale * (norm - adjustment_step) + adjustment_step * price_oracle) / norm
+                })
+                .collect::<Vec<_>>();
+
+            let mut xp_new = xp.to_vec();
+            for (k, p_new) in p_new.iter().enumerate() {
+                xp_new[k + 1] = xp[k + 1] * p_new / self.price_scale[k];
+            }
+
+            let d = newton_d(a, gamma, &xp_new)?;
+            let new_virtual_price = CURVE_PRECISION * get_xcp(d, &p_new)? / self.total_supply;
+
+            // Only repeg if at least half of the profit is left afterwards
+            if new_virtual_price > CURVE_PRECISION
+                && U256_2 * new_virtual_price - CURVE_PRECISION > xcp_profit
+            {
+                self.price_scale = p_new;
+                self.d = d;
+                self.virtual_price = new_virtual_price;
+                return Ok(());
+            }
+
+            self.not_adjusted = false;
+            self.d = d_unadjusted;
+            self.virtual_price = virtual_price;
+            return self.claim_admin_fees(a, gamma);
+        }
+
+        self.d = d_unadjusted;
+        self.virtual_price = virtual_price;
+
+        if needs_adjustment {
+            self.not_adjusted = false;
+            self.claim_admin_fees(a, gamma)?;
+        }
+
+        Ok(())
+    }
+
+    /// Mints the admin share of the profit to the fee receiver.
+    fn claim_admin_fees(&mut self, a: U256, gamma: U256) -> Result<(), AMMError> {
+        let xcp_profit_a = self.xcp_profit_a;
+        let virtual_price = self.virtual_price;
+
+        if self.xcp_profit > xcp_profit_a {
+            let fees = (self.xcp_profit - xcp_profit_a) * self.admin_fee
+                / (U256_2 * CURVE_FEE_DENOMINATOR);
+            if !fees.is_zero() {
+                let frac =
+                    virtual_price * CURVE_PRECISION / (virtual_price - fees) - CURVE_PRECISION;
+                self.total_supply += self.total_supply * frac / CURVE_PRECISION;
+                self.xcp_profit -= fees * U256_2;
+            }
+        }
+
+        self.d = newton_d(a, gamma, &self.xp())?;
+        self.virtual_price =
+            CURVE_PRECISION * get_xcp(self.d, &self.price_scale)? / self.total_supply;
+
+        if self.xcp_profit > xcp_profit_a {
+            self.xcp_profit_a = self.xcp_profit;
+        }
+
+        Ok(())
+    }
+
+    /// Distance between the price oracle and the price scale, scaled by 1e18.
+    fn price_norm(&self) -> Result<U256, AMMError> {
+        let ratios = self
+            .price_scale
+            .iter()
+            .zip(&self.price_oracle)
+            .map(|(price_scale, price_oracle)| {
+                (price_oracle * CURVE_PRECISION / price_scale).abs_diff(CURVE_PRECISION)
+            })
+            .collect::<Vec<_>>();
+
+        if ratios.len() == 1 {
+            return Ok(ratios[0]);
+        }
+
+        let norm = ratios.iter().fold(U256::ZERO, |norm, ratio| {
+            norm + ratio * ratio / CURVE_PRECISION
+        });
+        Ok(sqrt_int(norm)?)
+    }
+
+    /// Returns the stored invariant, or recomputes it from `xp` while A and gamma are ramping.
+    fn current_d(&self, a: U256, gamma: U256, xp: &[U256]) -> Result<U256, AMMError> {
+        if self.future_a_gamma_time > 0 {
+            Ok(newton_d(a, gamma, xp)?)
+        } else {
+            Ok(self.d)
+        }
+    }
+
+    /// Flags a finished ramp so that the invariant is no longer recomputed on every call.
+    fn end_ramp(&mut self, timestamp: u64) {
+        if self.future_a_gamma_time > 0 && timestamp >= self.future_a_gamma_time {
+            self.future_a_gamma_time = 1;
+        }
+    }
+
+    /// Multipliers normalizing each coin to 18 decimals.
+    fn precisions(&self) -> Vec<U256> {
+        self.tokens
+            .iter()
+            .map(|token| U256_10.pow(U256::from(18_u8.saturating_sub(token.decimals))))
+            .collect()
+    }
+
+    /// Balances normalized to 18 decimals and priced in the first coin.
+    fn xp(&self) -> Vec<U256> {
+        xp(&self.balances, &self.precisions(), &self.price_scale)
+    }
+
+    /// Price scale of coin `i` in the first coin, scaled by 1e18.
+    fn coin_price_scale(&self, i: usize) -> U256 {
+        if i == 0 {
+            CURVE_PRECISION
+        } else {
+            self.price_scale[i - 1]
+        }
+    }
+
+    fn coin_indices(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+    ) -> Result<(usize, usize), CurveError> {
+        let position = |token| self.tokens.iter().position(|t| t.address == token);
+        match (position(base_token), position(quote_token)) {
+            (Some(i), Some(j)) if i != j => Ok((i, j)),
+            _ => Err(CurveError::TokenNotInPool),
+        }
+    }
+}
+
+impl CryptoSwapPoolData {
+    fn price_scale_len(&self) -> usize {
+        self.priceScale
+            .len()
+            .min(self.priceOracle.len())
+            .min(self.lastPrices.len())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct CurveCryptoSwapFactory {
+    pub address: Address,
+    pub creation_block: u64,
+}
+
+impl CurveCryptoSwapFactory {
+    pub fn new(address: Address, creation_block: u64) -> Self {
+        Self {
+            address,
+            creation_block,
+        }
+    }
+
+    /// Returns the addresses of all pools deployed by the factory, read from its pool list.
+    pub async fn get_all_pools<N, P>(
+        factory_address: Address,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Address>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let factory = ICurveCryptoSwapFactory::new(factory_address, provider.clone());
+        let pool_count = factory
+            .pool_count()
+            .call()
+            .block(block_number)
+            .await?
+            .to::<usize>();
+
+        let step = 766;
+        let mut futures_unordered = FuturesUnordered::new();
+        for i in (0..pool_count).step_by(step) {
+            // The batch contract caps the step at the pool count
+            let deployer = IGetCurveCryptoSwapPoolsBatchRequest::deploy_builder(
+                provider.clone(),
+                U256::from(i),
+                U256::from(step),
+                factory_address,
+            );
+
+            futures_unordered.push(async move {
+                let res = deployer.call_raw().block(block_number).await?;
+                let return_data = <Vec<Address> as SolValue>::abi_decode(&res)?;
+
+                Ok::<Vec<Address>, AMMError>(return_data)
+            });
+        }
+
+        let mut pools = Vec::new();
+        while let Some(res) = futures_unordered.next().await {
+            pools.extend(res?.into_iter().filter(|pool| !pool.is_zero()));
+        }
+
+        Ok(pools)
+    }
+
+    pub async fn sync_all_pools<N, P>(
+        amms: Vec<AMM>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let step = 50;
+        let pools = amms.iter().map(|amm| amm.address()).collect::<Vec<_>>();
+
+        let mut futures_unordered = pools
+            .chunks(step)
+            .map(|group| {
+                CurveCryptoSwapPool::get_pool_data(group.to_vec(), block_number, provider.clone())
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut amms = Vec::new();
+        while let Some(res) = futures_unordered.next().await {
+            amms.extend(res?.into_iter().map(AMM::CurveCryptoSwapPool));
+        }
+
+        Ok(amms)
+    }
+}
+
+impl AutomatedMarketMakerFactory for CurveCryptoSwapFactory {
+    type PoolVariant = CurveCryptoSwapPool;
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn pool_creation_event(&self) -> B256 {
+        ICurveCryptoSwapFactory::CryptoPoolDeployed::SIGNATURE_HASH
+    }
+
+    /// The creation event only logs the LP token, so pools are created with
+    /// [`create_pool_with_provider`](AutomatedMarketMakerFactory::create_pool_with_provider)
+    /// instead.
+    fn create_pool(&self, _log: Log) -> Result<AMM, AMMError> {
+        Err(CurveError::PoolAddressNotLogged.into())
+    }
+
+    /// Looks the pool up among the pools added to the factory's pool list in the block of the
+    /// log, matching it by LP token.
+    async fn create_pool_with_provider<N, P>(&self, log: Log, provider: P) -> Result<AMM, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let event = ICurveCryptoSwapFactory::CryptoPoolDeployed::decode_log(log.as_ref())?;
+        let block_number = log.block_number.ok_or(CurveError::PoolAddressNotLogged)?;
+        let block = BlockId::from(block_number);
+
+        let factory = ICurveCryptoSwapFactory::new(self.address, provider);
+        let first = factory
+            .pool_count()
+            .call()
+            .block(BlockId::from(block_number.saturating_sub(1)))
+            .await?;
+        let last = factory.pool_count().call().block(block).await?;
+
+        let mut i = first;
+        while i < last {
+            let pool = factory.pool_list(i).call().block(block).await?;
+            if factory.get_token(pool).call().block(block).await? == event.token {
+                return Ok(AMM::CurveCryptoSwapPool(CurveCryptoSwapPool::new(pool)));
+            }
+            i += U256_1;
+        }
+
+        Err(CurveError::PoolNotFound(event.token).into())
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+}
+
+impl DiscoverySync for CurveCryptoSwapFactory {
+    fn discover<N, P>(
+        &self,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::curve::crypto_swap::discover",
+            address = ?self.address,
+            "Discovering all pools"
+        );
+
+        let provider = provider.clone();
+        async move {
+            let pools =
+                CurveCryptoSwapFactory::get_all_pools(self.address, to_block, provider).await?;
+
+            Ok(pools
+                .into_iter()
+                .map(|pool| AMM::CurveCryptoSwapPool(CurveCryptoSwapPool::new(pool)))
+                .collect())
+        }
+    }
+
+    fn sync<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::curve::crypto_swap::sync",
+            address = ?self.address,
+            "Syncing all pools"
+        );
+
+        CurveCryptoSwapFactory::sync_all_pools(amms, to_block, provider)
+    }
+}
+
+/// Normalizes `balances` to 18 decimals using `precisions` and prices them in the first coin.
+fn xp(balances: &[U256], precisions: &[U256], price_scale: &[U256]) -> Vec<U256> {
+    balances
+        .iter()
+        .zip(precisions)
+        .enumerate()
+        .map(|(k, (balance, precision))| {
+            if k == 0 {
+                balance * precision
+            } else {
+                balance * precision * price_scale[k - 1] / CURVE_PRECISION
+            }
+        })
+        .collect()
+}
+
+/// Value of the invariant `d` in LP terms, the geometric mean of the balances at `price_scale`.
+fn get_xcp(d: U256, price_scale: &[U256]) -> Result<U256, CurveError> {
+    let n_coins = U256::from(price_scale.len() + 1);
+    let x = std::iter::once(d / n_coins)
+        .chain(
+            price_scale
+                .iter()
+                .map(|price_scale| d * CURVE_PRECISION / (n_coins * price_scale)),
+        )
+        .collect::<Vec<_>>();
+
+    geometric_mean(&x, true)
+}
+
+fn unpack_a_gamma(a_gamma: U256) -> (U256, U256) {
+    (a_gamma >> 128_usize, a_gamma & U256::from(u128::MAX))
+}
+
+fn coin_index(index: U256) -> Result<usize, CurveError> {
+    usize::try_from(index).map_err(|_| CurveError::InvalidCoinIndex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        primitives::address,
+        providers::ProviderBuilder,
+        rpc::client::ClientBuilder,
+        transports::{
+            layers::{RetryBackoffLayer, ThrottleLayer},
+            mock::Asserter,
+        },
+    };
+
+    const A: U256 = U256::from_limbs([400_000, 0, 0, 0]);
+    const GAMMA: U256 = U256::from_limbs([145_000_000_000_000, 0, 0, 0]);
+
+    fn two_crypto() -> CurveCryptoSwapPool {
+        let usdc =
+            Token::new_with_decimals(address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), 6);
+        let weth =
+            Token::new_with_decimals(address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), 18);
+
+        let mut pool = CurveCryptoSwapPool {
+            tokens: vec![usdc, weth],
+            balances: vec![
+                U256::from(20_000_000e6 as u128),
+                U256::from(10_000e18 as u128),
+            ],
+            price_scale: vec![U256::from(2_000e18 as u128)],
+            price_oracle: vec![U256::from(2_000e18 as u128)],
+            last_prices: vec![U256::from(2_000e18 as u128)],
+            initial_a: A,
+            future_a: A,
+            initial_gamma: GAMMA,
+            future_gamma: GAMMA,
+            mid_fee: U256::from(26_000_000),
+            out_fee: U256::from(45_000_000),
+            fee_gamma: U256::from(230_000_000_000_000_u64),
+            admin_fee: U256::from(5_000_000_000_u64),
+            allowed_extra_profit: U256::from(2_000_000_000_000_u64),
+            adjustment_step: U256::from(146_000_000_000_000_u64),
+            ma_half_time: U256::from(600),
+            xcp_profit: CURVE_PRECISION,
+            xcp_profit_a: CURVE_PRECISION,
+            virtual_price: CURVE_PRECISION,
+            ..Default::default()
+        };
+
+        pool.d = newton_d(A, GAMMA, &pool.xp()).unwrap();
+        pool.total_supply = get_xcp(pool.d, &pool.price_scale).unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_newton_d_balanced() {
+        let xp = vec![U256::from(1e24 as u128); 2];
+        let d = newton_d(A, GAMMA, &xp).unwrap();
+        assert!(d.abs_diff(U256::from(2e24 as u128)) <= U256_1);
+
+        let y = newton_y(A, GAMMA, &xp, d, 1).unwrap();
+        assert!(y.abs_diff(xp[1]) < U256::from(1e12 as u128));
+    }
+
+    #[test]
+    fn test_halfpow() {
+        assert_eq!(halfpow(U256::ZERO).unwrap(), CURVE_PRECISION);
+        assert_eq!(halfpow(CURVE_PRECISION).unwrap(), CURVE_PRECISION / U256_2);
+
+        let sqrt_half = halfpow(CURVE_PRECISION / U256_2).unwrap();
+        assert!(sqrt_half.abs_diff(U256::from(707_106_781_186_547_524_u64)) < U256::from(1e10));
+    }
+
+    #[test]
+    fn test_simulate_swap() {
+        let pool = two_crypto();
+        let (usdc, weth) = (pool.tokens[0].address, pool.tokens[1].address);
+
+        // A balanced pool trades at the price scale, minus the 0.26% mid fee
+        let amount_out = pool
+            .simulate_swap(usdc, weth, U256::from(2_000e6 as u128))
+            .unwrap();
+        assert!(amount_out < U256::from(0.9974e18 as u128));
+        assert!(amount_out > U256::from(0.9970e18 as u128));
+
+        let price = pool.calculate_price(weth, usdc).unwrap();
+        assert!((price - 2_000.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_sync_token_exchange() {
+        let mut pool = two_crypto();
+        let mut expected = pool.clone();
+        let amount_in = U256::from(10e18 as u128);
+        let amount_out = expected.exchange(1, 0, amount_in).unwrap();
+
+        let event = ICurveCryptoSwap::TokenExchange {
+            buyer: Address::ZERO,
+            sold_id: U256_1,
+            tokens_sold: amount_in,
+            bought_id: U256::ZERO,
+            tokens_bought: amount_out,
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: event.encode_log_data(),
+            },
+            block_timestamp: Some(expected.last_prices_timestamp),
+            ..Default::default()
+        };
+        pool.sync(&log).unwrap();
+
+        assert_eq!(pool.balances, expected.balances);
+        assert_eq!(pool.d, expected.d);
+        assert_eq!(pool.last_prices, expected.last_prices);
+        assert_eq!(pool.virtual_price, expected.virtual_price);
+        assert!(pool.virtual_price > CURVE_PRECISION);
+    }
+
+    #[test]
+    fn test_simulate_swap_three_coins() {
+        let usdt =
+            Token::new_with_decimals(address!("dAC17F958D2ee523a2206206994597C13D831ec7"), 6);
+        let wbtc =
+            Token::new_with_decimals(address!("2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599"), 8);
+        let weth =
+            Token::new_with_decimals(address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), 18);
+        let a = U256::from(1_707_629);
+        let gamma = U256::from(11_809_167_828_997_u64);
+
+        let mut pool = CurveCryptoSwapPool {
+            tokens: vec![usdt, wbtc, weth],
+            balances: vec![
+                U256::from(30_000_000e6 as u128),
+                U256::from(500e8 as u128),
+                U256::from(15_000e18 as u128),
+            ],
+            price_scale: vec![U256::from(60_000e18 as u128), U256::from(2_000e18 as u128)],
+            initial_a: a,
+            future_a: a,
+            initial_gamma: gamma,
+            future_gamma: gamma,
+            mid_fee: U256::from(3_000_000),
+            out_fee: U256::from(30_000_000),
+            fee_gamma: U256::from(500_000_000_000_000_u64),
+            ..Default::default()
+        };
+        pool.d = newton_d(a, gamma, &pool.xp()).unwrap();
+        let (wbtc, weth) = (pool.tokens[1].address, pool.tokens[2].address);
+
+        let amount_out = pool
+            .simulate_swap(wbtc, weth, U256::from(1e8 as u128))
+            .unwrap();
+        assert!(amount_out < U256::from(30e18 as u128));
+        assert!(amount_out > U256::from(29.9e18 as u128));
+
+        let price = pool.calculate_price(wbtc, weth).unwrap();
+        assert!((price - 30.0).abs() < 1e-4);
+    }
+
+    #[tokio::test]
+    async fn test_create_pool_with_provider() -> eyre::Result<()> {
+        let factory = CurveCryptoSwapFactory::new(
+            address!("F18056Bbd320E96A48e3Fbf8bC061322531aac99"),
+            14005321,
+        );
+        let lp_token = address!("ED4064f376cB8d68F770FB1Ff088a3d0F3FF5c4d");
+        let pools = [
+            address!("0000000000000000000000000000000000000001"),
+            address!("8301AE4fc9c624d1D396cbDAa1ed877821D7C511"),
+        ];
+        let event = ICurveCryptoSwapFactory::CryptoPoolDeployed {
+            token: lp_token,
+            coins: [
+                address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                address!("D533a949740bb3306d119CC777fa900bA034cd52"),
+            ],
+            A: U256::from(400_000),
+            gamma: GAMMA,
+            mid_fee: U256::from(26_000_000),
+            out_fee: U256::from(45_000_000),
+            allowed_extra_profit: U256::from(2_000_000_000_000_u64),
+            fee_gamma: U256::from(230_000_000_000_000_u64),
+            adjustment_step: U256::from(146_000_000_000_000_u64),
+            admin_fee: U256::from(5_000_000_000_u64),
+            ma_half_time: U256::from(600),
+            initial_price: U256::from(1_000e18 as u128),
+            deployer: Address::ZERO,
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: factory.address,
+                data: event.encode_log_data(),
+            },
+            block_number: Some(14_005_400),
+            ..Default::default()
+        };
+
+        // Two pools were added in the block, the second one deployed the LP token
+        let asserter = Asserter::new();
+        asserter.push_success(&Bytes::from(U256::from(7).abi_encode()));
+        asserter.push_success(&Bytes::from(U256::from(9).abi_encode()));
+        for (pool, token) in pools.into_iter().zip([Address::ZERO, lp_token]) {
+            asserter.push_success(&Bytes::from(pool.abi_encode()));
+            asserter.push_success(&Bytes::from(token.abi_encode()));
+        }
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+
+        let amm = factory
+            .create_pool_with_provider(log.clone(), provider.clone())
+            .await?;
+        assert_eq!(amm.address(), pools[1]);
+
+        // No pool of the block deployed the LP token
+        asserter.push_success(&Bytes::from(U256::from(9).abi_encode()));
+        asserter.push_success(&Bytes::from(U256::from(9).abi_encode()));
+        assert!(matches!(
+            factory.create_pool_with_provider(log, provider).await,
+            Err(AMMError::CurveError(CurveError::PoolNotFound(token))) if token == lp_token
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_get_dy() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_PROVIDER")?;
+
+        let client = ClientBuilder::default()
+            .layer(ThrottleLayer::new(250))
+            .layer(RetryBackoffLayer::new(5, 200, 330))
+            .http(rpc_endpoint.parse()?);
+
+        let provider = ProviderBuilder::new().connect_client(client);
+
+        // CRV/ETH, CVX/ETH and tricrypto2, each at two pinned blocks
+        for (address, block_number) in [
+            address!("8301AE4fc9c624d1D396cbDAa1ed877821D7C511"),
+            address!("B576491F1E6e5E62f1d8F26062Ee822B40B0E0d4"),
+            address!("D51a44d3FaE010294C616388b506AcdA1bfAAE46"),
+        ]
+        .into_iter()
+        .flat_map(|address| [(address, 18_000_000), (address, 22_000_000)])
+        {
+            let block_number = BlockId::from(block_number);
+            let pool = CurveCryptoSwapPool::new(address)
+                .init(block_number, provider.clone())
+                .await?;
+            let crypto_pool = ICurveCryptoSwap::new(pool.address, provider.clone());
+
+            for (i, j) in [(0, 1), (1, 0), (pool.tokens.len() - 1, 0)] {
+                let unit = U256_10.pow(U256::from(pool.tokens[i].decimals));
+                for amount_in in [unit / U256::from(1_000), unit, unit * U256::from(1_000)] {
+                    let amount_out = pool.simulate_swap(
+                        pool.tokens[i].address,
+                        pool.tokens[j].address,
+                        amount_in,
+                    )?;
+                    let expected_amount_out = crypto_pool
+                        .get_dy(U256::from(i), U256::from(j), amount_in)
+                        .block(block_number)
+                        .call()
+                        .await?;
+
+                    assert_eq!(amount_out, expected_amount_out);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_init_ng_pool() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_PROVIDER")?;
+
+        let client = ClientBuilder::default()
+            .layer(ThrottleLayer::new(250))
+            .layer(RetryBackoffLayer::new(5, 200, 330))
+            .http(rpc_endpoint.parse()?);
+
+        let provider = ProviderBuilder::new().connect_client(client);
+
+        // TricryptoUSDC, deployed by the tricrypto NG factory
+        let address = address!("7F86Bf177Dd4F3494b841a37e810A34dD56c829B");
+        let res = CurveCryptoSwapPool::new(address)
+            .init(BlockId::from(22_000_000), provider)
+            .await;
+        assert!(matches!(
+            res,
+            Err(AMMError::CurveError(CurveError::UnsupportedNGPool(pool))) if pool == address
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+pub mod crypto_math;
+pub mod crypto_swap;
+pub mod stable_math;
+pub mod stable_swap;
+
+use alloy::primitives::Address;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CurveError {
+    #[error("Error initializing Curve pool")]
+    InitializationError,
+    #[error("Token not in pool")]
+    TokenNotInPool,
+    #[error("Invalid coin index")]
+    InvalidCoinIndex,
+    #[error("Pool is not a meta pool")]
+    NotMetaPool,
+    #[error("Underlying swap logged without a preceding base pool log")]
+    MissingBasePoolLog,
+    #[error("Pool has a zero balance")]
+    ZeroBalance,
+    #[error("Synced balance underflows")]
+    BalanceUnderflow,
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+    #[error("Invariant did not converge")]
+    DidNotConverge,
+    #[error("Unsafe values for the invariant")]
+    UnsafeValues,
+    #[error("Pool address is not logged on creation")]
+    PoolAddressNotLogged,
+    #[error("No pool found for LP token {0}")]
+    PoolNotFound(Address),
+    #[error("Pool {0} was deployed by an NG factory, which is not supported")]
+    UnsupportedNGPool(Address),
+}
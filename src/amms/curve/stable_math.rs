@@ -0,0 +1,114 @@
+use alloy::primitives::U256;
+
+use crate::amms::consts::{CURVE_FEE_DENOMINATOR, CURVE_MAX_ITERATIONS, U256_1, U256_2, U256_4};
+
+use super::CurveError;
+
+/// Computes the invariant `D` for normalized balances `xp`.
+///
+/// `amp` is `A * a_precision`. Pools deployed before `A_PRECISION` was introduced use an
+/// `a_precision` of 1, which reduces the formulas to their original form.
+pub fn get_d(xp: &[U256], amp: U256, a_precision: U256) -> Result<U256, CurveError> {
+    let n_coins = U256::from(xp.len());
+
+    let s = xp.iter().fold(U256::ZERO, |acc, x| acc + x);
+    if s.is_zero() {
+        return Ok(U256::ZERO);
+    }
+    if xp.iter().any(|x| x.is_zero()) {
+        return Err(CurveError::ZeroBalance);
+    }
+
+    let ann = amp * n_coins;
+    let mut d = s;
+    for _ in 0..CURVE_MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp {
+            d_p = d_p * d / (x * n_coins);
+        }
+
+        let d_prev = d;
+        d = (ann * s / a_precision + d_p * n_coins) * d
+            / ((ann - a_precision) * d / a_precision + (n_coins + U256_1) * d_p);
+
+        if d.abs_diff(d_prev) <= U256_1 {
+            return Ok(d);
+        }
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+/// Computes the new balance of coin `j` after the normalized balance of coin `i` is set to `x`,
+/// keeping `D` constant.
+pub fn get_y(
+    i: usize,
+    j: usize,
+    x: U256,
+    xp: &[U256],
+    amp: U256,
+    a_precision: U256,
+) -> Result<U256, CurveError> {
+    if i == j || i >= xp.len() || j >= xp.len() {
+        return Err(CurveError::InvalidCoinIndex);
+    }
+
+    let d = get_d(xp, amp, a_precision)?;
+    let mut balances = xp.to_vec();
+    balances[i] = x;
+
+    get_y_d(j, &balances, d, amp, a_precision)
+}
+
+/// Computes the balance of coin `i` that satisfies the invariant `d` given the other balances.
+pub fn get_y_d(
+    i: usize,
+    xp: &[U256],
+    d: U256,
+    amp: U256,
+    a_precision: U256,
+) -> Result<U256, CurveError> {
+    let n_coins = U256::from(xp.len());
+    let ann = amp * n_coins;
+
+    let mut c = d;
+    let mut s = U256::ZERO;
+    for (k, x) in xp.iter().enumerate() {
+        if k == i {
+            continue;
+        }
+        if x.is_zero() {
+            return Err(CurveError::ZeroBalance);
+        }
+        s += x;
+        c = c * d / (x * n_coins);
+    }
+
+    c = c * d * a_precision / (ann * n_coins);
+    let b = s + d * a_precision / ann;
+
+    let mut y = d;
+    for _ in 0..CURVE_MAX_ITERATIONS {
+        let y_prev = y;
+        y = (y * y + c) / (U256_2 * y + b - d);
+
+        if y.abs_diff(y_prev) <= U256_1 {
+            return Ok(y);
+        }
+    }
+
+    Err(CurveError::DidNotConverge)
+}
+
+/// Fee charged by pools with an off-peg fee multiplier, which increases as the pool moves
+/// away from balance.
+pub fn dynamic_fee(xpi: U256, xpj: U256, fee: U256, offpeg_fee_multiplier: U256) -> U256 {
+    if offpeg_fee_multiplier <= CURVE_FEE_DENOMINATOR {
+        return fee;
+    }
+
+    let xps2 = (xpi + xpj) * (xpi + xpj);
+    offpeg_fee_multiplier * fee
+        / ((offpeg_fee_multiplier - CURVE_FEE_DENOMINATOR) * U256_4 * xpi * xpj / xps2
+            + CURVE_FEE_DENOMINATOR)
+}
@@ -0,0 +1,1389 @@
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, Bytes, B256, U256},
+    providers::Provider,
+    rpc::types::Log,
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use rug::{float::Round, Float};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::{
+    stable_math::{dynamic_fee, get_d, get_y, get_y_d},
+    CurveError,
+};
+use crate::amms::{
+    amm::{check_amount_out, AutomatedMarketMaker},
+    consts::{CURVE_FEE_DENOMINATOR, CURVE_PRECISION, MPFR_T_PRECISION, U256_1, U256_10, U256_4},
+    error::AMMError,
+    float::{float_to_q128, u256_to_float},
+    get_block_timestamp, Token,
+};
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract ICurveStableSwap {
+        event TokenExchange(address indexed buyer, int128 sold_id, uint256 tokens_sold, int128 bought_id, uint256 tokens_bought);
+        event TokenExchangeUnderlying(address indexed buyer, int128 sold_id, uint256 tokens_sold, int128 bought_id, uint256 tokens_bought);
+        event RemoveLiquidityOne(address indexed provider, uint256 token_amount, uint256 coin_amount);
+        event RampA(uint256 old_A, uint256 new_A, uint256 initial_time, uint256 future_time);
+        event StopRampA(uint256 A, uint256 t);
+        event NewFee(uint256 fee, uint256 admin_fee);
+
+        function get_dy(int128 i, int128 j, uint256 dx) external view returns (uint256);
+        function balances(uint256 i) external view returns (uint256);
+        function exchange(int128 i, int128 j, uint256 dx, uint256 min_dy) external returns (uint256);
+        function exchange_underlying(int128 i, int128 j, uint256 dx, uint256 min_dy) external returns (uint256);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveStableSwapFactory {
+        event RemoveLiquidityOne(address indexed provider, uint256 token_amount, uint256 coin_amount, uint256 token_supply);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveStableSwapNG {
+        event AddLiquidity(address indexed provider, uint256[] token_amounts, uint256[] fees, uint256 invariant, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[] token_amounts, uint256[] fees, uint256 token_supply);
+        event RemoveLiquidityOne(address indexed provider, int128 token_id, uint256 token_amount, uint256 coin_amount, uint256 token_supply);
+        event RemoveLiquidityImbalance(address indexed provider, uint256[] token_amounts, uint256[] fees, uint256 invariant, uint256 token_supply);
+        event ApplyNewFee(uint256 fee, uint256 offpeg_fee_multiplier);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveStableSwap2 {
+        event AddLiquidity(address indexed provider, uint256[2] token_amounts, uint256[2] fees, uint256 invariant, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[2] token_amounts, uint256[2] fees, uint256 token_supply);
+        event RemoveLiquidityImbalance(address indexed provider, uint256[2] token_amounts, uint256[2] fees, uint256 invariant, uint256 token_supply);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveStableSwap3 {
+        event AddLiquidity(address indexed provider, uint256[3] token_amounts, uint256[3] fees, uint256 invariant, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[3] token_amounts, uint256[3] fees, uint256 token_supply);
+        event RemoveLiquidityImbalance(address indexed provider, uint256[3] token_amounts, uint256[3] fees, uint256 invariant, uint256 token_supply);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    contract ICurveStableSwap4 {
+        event AddLiquidity(address indexed provider, uint256[4] token_amounts, uint256[4] fees, uint256 invariant, uint256 token_supply);
+        event RemoveLiquidity(address indexed provider, uint256[4] token_amounts, uint256[4] fees, uint256 token_supply);
+        event RemoveLiquidityImbalance(address indexed provider, uint256[4] token_amounts, uint256[4] fees, uint256 invariant, uint256 token_supply);
+    }
+}
+
+sol! {
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IGetCurveStableSwapPoolDataBatchRequest,
+    "src/amms/abi/GetCurveStableSwapPoolDataBatchRequest.json",
+}
+
+type PoolData = (
+    Vec<Address>,
+    Vec<u16>,
+    Vec<U256>,
+    Vec<U256>,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    Address,
+);
+
+/// A Curve StableSwap pool.
+///
+/// Meta pools pair a coin against the LP token of a base pool and hold a snapshot of that base
+/// pool to simulate swaps against its underlying coins. The snapshot is updated by the meta pool's
+/// own underlying swaps and by the logs of the base pool, which the state space routes to the meta
+/// pool as one of its `dependencies`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurveStableSwapPool {
+    pub address: Address,
+    pub tokens: Vec<Token>,
+    pub balances: Vec<U256>,
+    /// Rate multipliers normalizing each coin to 18 decimals, scaled by 1e18
+    pub rates: Vec<U256>,
+    pub initial_a: U256,
+    pub future_a: U256,
+    pub initial_a_time: u64,
+    pub future_a_time: u64,
+    /// Precision of the stored amplification coefficient, 1 for pools predating `A_PRECISION`
+    pub a_precision: U256,
+    /// Swap fee, out of 1e10
+    pub fee: U256,
+    /// Share of the swap fee kept by the protocol, out of 1e10
+    pub admin_fee: U256,
+    pub offpeg_fee_multiplier: U256,
+    /// Total supply of the pool LP token
+    pub total_supply: U256,
+    pub base_pool: Option<Box<CurveStableSwapPool>>,
+    /// Base pool snapshot before its last synced log, against which the meta pool leg of the
+    /// underlying swap emitting that log is priced
+    #[serde(skip)]
+    pub prev_base_pool: Option<Box<CurveStableSwapPool>>,
+    /// Timestamp of the block the pool is synced to, at which A ramps are evaluated
+    #[serde(default)]
+    pub block_timestamp: u64,
+}
+
+impl AutomatedMarketMaker for CurveStableSwapPool {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        // Older pools log fixed size arrays, so the liquidity events differ per coin count
+        vec![
+            ICurveStableSwap::TokenExchange::SIGNATURE_HASH,
+            ICurveStableSwap::TokenExchangeUnderlying::SIGNATURE_HASH,
+            ICurveStableSwap::RemoveLiquidityOne::SIGNATURE_HASH,
+            ICurveStableSwap::RampA::SIGNATURE_HASH,
+            ICurveStableSwap::StopRampA::SIGNATURE_HASH,
+            ICurveStableSwap::NewFee::SIGNATURE_HASH,
+            ICurveStableSwapFactory::RemoveLiquidityOne::SIGNATURE_HASH,
+            ICurveStableSwapNG::AddLiquidity::SIGNATURE_HASH,
+            ICurveStableSwapNG::RemoveLiquidity::SIGNATURE_HASH,
+            ICurveStableSwapNG::RemoveLiquidityOne::SIGNATURE_HASH,
+            ICurveStableSwapNG::RemoveLiquidityImbalance::SIGNATURE_HASH,
+            ICurveStableSwapNG::ApplyNewFee::SIGNATURE_HASH,
+            ICurveStableSwap2::AddLiquidity::SIGNATURE_HASH,
+            ICurveStableSwap2::RemoveLiquidity::SIGNATURE_HASH,
+            ICurveStableSwap2::RemoveLiquidityImbalance::SIGNATURE_HASH,
+            ICurveStableSwap3::AddLiquidity::SIGNATURE_HASH,
+            ICurveStableSwap3::RemoveLiquidity::SIGNATURE_HASH,
+            ICurveStableSwap3::RemoveLiquidityImbalance::SIGNATURE_HASH,
+            ICurveStableSwap4::AddLiquidity::SIGNATURE_HASH,
+            ICurveStableSwap4::RemoveLiquidity::SIGNATURE_HASH,
+            ICurveStableSwap4::RemoveLiquidityImbalance::SIGNATURE_HASH,
+        ]
+    }
+
+    /// Returns the base pool of meta pools, whose logs update the base pool snapshot.
+    fn dependencies(&self) -> Vec<Address> {
+        self.base_pool.iter().map(|pool| pool.address).collect()
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        self.block_timestamp = log.block_timestamp.unwrap_or(self.block_timestamp);
+        if let Some(base_pool) = self.base_pool.as_deref_mut() {
+            base_pool.block_timestamp = self.block_timestamp;
+            if log.address() == base_pool.address {
+                self.prev_base_pool = Some(Box::new(base_pool.clone()));
+                return base_pool.sync(log);
+            }
+        }
+
+        let event_signature = log.topics()[0];
+        match event_signature {
+            ICurveStableSwap::TokenExchange::SIGNATURE_HASH => {
+                let exchange_event = ICurveStableSwap::TokenExchange::decode_log(log.as_ref())?;
+                let i = coin_index(exchange_event.sold_id)?;
+                let j = coin_index(exchange_event.bought_id)?;
+
+                // Balances follow the amounts reported by the event, only the admin fee is simulated
+                let rates = self.stored_rates()?;
+                let xp = xp(&self.balances, &rates);
+                let x = xp[i] + exchange_event.tokens_sold * rates[i] / CURVE_PRECISION;
+                let (_, dy_admin_fee) = self.get_dy_xp(i, j, x, &xp, &rates)?;
+
+                self.balances[i] += exchange_event.tokens_sold;
+                self.balances[j] = self.balances[j]
+                    .checked_sub(exchange_event.tokens_bought + dy_admin_fee)
+                    .ok_or(CurveError::BalanceUnderflow)?;
+            }
+            ICurveStableSwap::TokenExchangeUnderlying::SIGNATURE_HASH => {
+                let exchange_event =
+                    ICurveStableSwap::TokenExchangeUnderlying::decode_log(log.as_ref())?;
+
+                self.sync_exchange_underlying(
+                    coin_index(exchange_event.sold_id)?,
+                    coin_index(exchange_event.bought_id)?,
+                    exchange_event.tokens_sold,
+                    exchange_event.tokens_bought,
+                )?;
+            }
+            ICurveStableSwap2::AddLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwap2::AddLiquidity::decode_log(log.as_ref())?;
+                self.sync_add_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwap3::AddLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwap3::AddLiquidity::decode_log(log.as_ref())?;
+                self.sync_add_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwap4::AddLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwap4::AddLiquidity::decode_log(log.as_ref())?;
+                self.sync_add_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwapNG::AddLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwapNG::AddLiquidity::decode_log(log.as_ref())?;
+                self.sync_add_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwap2::RemoveLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwap2::RemoveLiquidity::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &[], event.token_supply)?;
+            }
+            ICurveStableSwap3::RemoveLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwap3::RemoveLiquidity::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &[], event.token_supply)?;
+            }
+            ICurveStableSwap4::RemoveLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwap4::RemoveLiquidity::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &[], event.token_supply)?;
+            }
+            ICurveStableSwapNG::RemoveLiquidity::SIGNATURE_HASH => {
+                let event = ICurveStableSwapNG::RemoveLiquidity::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &[], event.token_supply)?;
+            }
+            ICurveStableSwap2::RemoveLiquidityImbalance::SIGNATURE_HASH => {
+                let event = ICurveStableSwap2::RemoveLiquidityImbalance::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwap3::RemoveLiquidityImbalance::SIGNATURE_HASH => {
+                let event = ICurveStableSwap3::RemoveLiquidityImbalance::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwap4::RemoveLiquidityImbalance::SIGNATURE_HASH => {
+                let event = ICurveStableSwap4::RemoveLiquidityImbalance::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwapNG::RemoveLiquidityImbalance::SIGNATURE_HASH => {
+                let event = ICurveStableSwapNG::RemoveLiquidityImbalance::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity(&event.token_amounts, &event.fees, event.token_supply)?;
+            }
+            ICurveStableSwap::RemoveLiquidityOne::SIGNATURE_HASH => {
+                let event = ICurveStableSwap::RemoveLiquidityOne::decode_log(log.as_ref())?;
+                let i = self.withdrawn_coin(event.token_amount, event.coin_amount)?;
+                let token_supply = self
+                    .total_supply
+                    .checked_sub(event.token_amount)
+                    .ok_or(CurveError::BalanceUnderflow)?;
+                self.sync_remove_liquidity_one(
+                    i,
+                    event.token_amount,
+                    event.coin_amount,
+                    token_supply,
+                )?;
+            }
+            ICurveStableSwapFactory::RemoveLiquidityOne::SIGNATURE_HASH => {
+                let event = ICurveStableSwapFactory::RemoveLiquidityOne::decode_log(log.as_ref())?;
+                let i = self.withdrawn_coin(event.token_amount, event.coin_amount)?;
+                self.sync_remove_liquidity_one(
+                    i,
+                    event.token_amount,
+                    event.coin_amount,
+                    event.token_supply,
+                )?;
+            }
+            ICurveStableSwapNG::RemoveLiquidityOne::SIGNATURE_HASH => {
+                let event = ICurveStableSwapNG::RemoveLiquidityOne::decode_log(log.as_ref())?;
+                self.sync_remove_liquidity_one(
+                    coin_index(event.token_id)?,
+                    event.token_amount,
+                    event.coin_amount,
+                    event.token_supply,
+                )?;
+            }
+            ICurveStableSwap::RampA::SIGNATURE_HASH => {
+                let ramp_event = ICurveStableSwap::RampA::decode_log(log.as_ref())?;
+
+                self.initial_a = ramp_event.old_A;
+                self.future_a = ramp_event.new_A;
+                self.initial_a_time = ramp_event.initial_time.to();
+                self.future_a_time = ramp_event.future_time.to();
+            }
+            ICurveStableSwap::StopRampA::SIGNATURE_HASH => {
+                let stop_event = ICurveStableSwap::StopRampA::decode_log(log.as_ref())?;
+
+                self.initial_a = stop_event.A;
+                self.future_a = stop_event.A;
+                self.initial_a_time = stop_event.t.to();
+                self.future_a_time = stop_event.t.to();
+            }
+            ICurveStableSwap::NewFee::SIGNATURE_HASH => {
+                let fee_event = ICurveStableSwap::NewFee::decode_log(log.as_ref())?;
+
+                self.fee = fee_event.fee;
+                self.admin_fee = fee_event.admin_fee;
+            }
+            ICurveStableSwapNG::ApplyNewFee::SIGNATURE_HASH => {
+                let fee_event = ICurveStableSwapNG::ApplyNewFee::decode_log(log.as_ref())?;
+
+                self.fee = fee_event.fee;
+                self.offpeg_fee_multiplier = fee_event.offpeg_fee_multiplier;
+            }
+            _ => return Err(AMMError::UnrecognizedEventSignature(event_signature)),
+        }
+
+        info!(
+            target = "amms::curve::stable_swap::sync",
+            address = ?self.address,
+            balances = ?self.balances,
+            total_supply = ?self.total_supply,
+            "Sync"
+        );
+
+        Ok(())
+    }
+
+    /// Returns the pool coins, followed by the base pool coins for meta pools.
+    fn tokens(&self) -> Vec<Address> {
+        let mut tokens = self.tokens.iter().map(|t| t.address).collect::<Vec<_>>();
+        if let Some(base_pool) = &self.base_pool {
+            tokens.extend(base_pool.tokens.iter().map(|t| t.address));
+        }
+        tokens
+    }
+
+    /// Calculates the price of `base_token` in `quote_token`, excluding fees.
+    fn calculate_price(&self, base_token: Address, quote_token: Address) -> Result<f64, AMMError> {
+        Ok(self
+            .spot_price(base_token, quote_token)?
+            .to_f64_round(Round::Nearest))
+    }
+
+    /// Calculates the price of `base_token` in `quote_token`, excluding fees, at
+    /// `MPFR_T_PRECISION` bits.
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        float_to_q128(self.spot_price(base_token, quote_token)?)
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        self.clone()
+            .simulate_swap_mut(base_token, quote_token, amount_in)
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let (token_in, token_out) = match (self.token(base_token), self.token(quote_token)) {
+            (Some(token_in), Some(token_out)) => (token_in.clone(), token_out.clone()),
+            _ => return Err(CurveError::TokenNotInPool.into()),
+        };
+        let amount_in = token_in.after_sell_tax(amount_in);
+
+        let amount_out = if let (Some(i), Some(j)) =
+            (self.coin_index(base_token), self.coin_index(quote_token))
+        {
+            self.exchange(i, j, amount_in)?
+        } else {
+            match (
+                self.underlying_index(base_token),
+                self.underlying_index(quote_token),
+            ) {
+                (Some(i), Some(j)) => self.exchange_underlying(i, j, amount_in)?,
+                _ => return Err(CurveError::TokenNotInPool.into()),
+            }
+        };
+
+        Ok(token_out.after_buy_tax(amount_out))
+    }
+
+    /// Encodes `exchange` between coins of the pool, or `exchange_underlying` between the coins of
+    /// a metapool and its base pool. The pool pays the caller, so `recipient` is unused.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        _recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        if let (Some(i), Some(j)) = (self.coin_index(base_token), self.coin_index(quote_token)) {
+            return Ok(ICurveStableSwap::exchangeCall {
+                i: i as i128,
+                j: j as i128,
+                dx: amount_in,
+                min_dy: min_out,
+            }
+            .abi_encode()
+            .into());
+        }
+
+        match (
+            self.underlying_index(base_token),
+            self.underlying_index(quote_token),
+        ) {
+            (Some(i), Some(j)) => Ok(ICurveStableSwap::exchange_underlyingCall {
+                i: i as i128,
+                j: j as i128,
+                dx: amount_in,
+                min_dy: min_out,
+            }
+            .abi_encode()
+            .into()),
+            _ => Err(CurveError::TokenNotInPool.into()),
+        }
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut pool =
+            CurveStableSwapPool::get_pool_data(vec![self.address], block_number, provider.clone())
+                .await?
+                .pop()
+                .ok_or(CurveError::InitializationError)?;
+
+        if let Some(base_pool) = &pool.base_pool {
+            let base_pool =
+                CurveStableSwapPool::get_pool_data(vec![base_pool.address], block_number, provider)
+                    .await?
+                    .pop()
+                    .ok_or(CurveError::InitializationError)?;
+
+            pool.base_pool = Some(Box::new(base_pool));
+        }
+
+        Ok(pool)
+    }
+}
+
+impl CurveStableSwapPool {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            ..Default::default()
+        }
+    }
+
+    /// Calculates the marginal price of `base_token` in `quote_token`, excluding fees.
+    ///
+    /// For coins `i` and `j` the price follows from the partial derivatives of the invariant,
+    /// `dx_j / dx_i = (Ann + D_P / x_i) / (Ann + D_P / x_j)` with `D_P = D^(n+1) / (n^n * prod(x))`.
+    pub fn spot_price(&self, base_token: Address, quote_token: Address) -> Result<Float, AMMError> {
+        let price = if let (Some(i), Some(j)) =
+            (self.coin_index(base_token), self.coin_index(quote_token))
+        {
+            self.marginal_price(i, j)?
+        } else {
+            let base_pool = self.base_pool.as_ref().ok_or(CurveError::TokenNotInPool)?;
+            let max_coin = self.balances.len() - 1;
+
+            match (
+                self.underlying_index(base_token),
+                self.underlying_index(quote_token),
+            ) {
+                (Some(i), Some(j)) if i >= max_coin && j >= max_coin => {
+                    base_pool.marginal_price(i - max_coin, j - max_coin)?
+                }
+                (Some(i), Some(j)) if i >= max_coin => {
+                    let lp_price = base_pool.lp_marginal_price(i - max_coin)?;
+                    self.marginal_price(max_coin, j)? / lp_price
+                }
+                (Some(i), Some(j)) if j >= max_coin => {
+                    let lp_price = base_pool.lp_marginal_price(j - max_coin)?;
+                    self.marginal_price(i, max_coin)? * lp_price
+                }
+                _ => return Err(CurveError::TokenNotInPool.into()),
+            }
+        };
+
+        Ok(price)
+    }
+
+    /// Fetches the state of `pools` at `block_number`.
+    ///
+    /// Meta pools are returned with an unsynced base pool that only holds its address.
+    pub async fn get_pool_data<N, P>(
+        pools: Vec<Address>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Self>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let block_timestamp = get_block_timestamp(block_number, provider.clone()).await?;
+        let deployer =
+            IGetCurveStableSwapPoolDataBatchRequest::deploy_builder(provider, pools.clone());
+        let res = deployer.block(block_number).call_raw().await?;
+
+        let data = <Vec<PoolData> as SolValue>::abi_decode(&res)?;
+
+        pools
+            .into_iter()
+            .zip(data)
+            .map(|(address, pool_data)| {
+                CurveStableSwapPool::from_pool_data(address, pool_data, block_timestamp)
+            })
+            .collect()
+    }
+
+    fn from_pool_data(
+        address: Address,
+        pool_data: PoolData,
+        block_timestamp: u64,
+    ) -> Result<Self, AMMError> {
+        let (
+            coins,
+            decimals,
+            balances,
+            stored_rates,
+            initial_a,
+            future_a,
+            initial_a_time,
+            future_a_time,
+            a_precision,
+            fee,
+            admin_fee,
+            offpeg_fee_multiplier,
+            total_supply,
+            base_pool,
+        ) = pool_data;
+
+        if coins.is_empty() || coins.len() != balances.len() {
+            return Err(CurveError::InitializationError.into());
+        }
+
+        let rates = if stored_rates.len() == coins.len() {
+            stored_rates
+        } else {
+            decimals
+                .iter()
+                .map(|decimals| U256_10.pow(U256::from(36 - (*decimals).min(36))))
+                .collect()
+        };
+
+        Ok(Self {
+            address,
+            tokens: coins
+                .into_iter()
+                .zip(decimals)
+                .map(|(coin, decimals)| Token::new_with_decimals(coin, decimals as u8))
+                .collect(),
+            balances,
+            rates,
+            initial_a,
+            future_a,
+            initial_a_time: initial_a_time.to(),
+            future_a_time: future_a_time.to(),
+            a_precision: a_precision.max(U256_1),
+            fee,
+            admin_fee,
+            offpeg_fee_multiplier,
+            total_supply,
+            base_pool: (!base_pool.is_zero())
+                .then(|| Box::new(CurveStableSwapPool::new(base_pool))),
+            prev_base_pool: None,
+            block_timestamp,
+        })
+    }
+
+    /// Returns the amplification coefficient multiplied by `a_precision`, interpolating any
+    /// ongoing ramp at `block_timestamp`.
+    pub fn a(&self) -> U256 {
+        let now = self.block_timestamp;
+        if now >= self.future_a_time || self.future_a_time <= self.initial_a_time {
+            return self.future_a;
+        }
+
+        let elapsed = U256::from(now.saturating_sub(self.initial_a_time));
+        let duration = U256::from(self.future_a_time - self.initial_a_time);
+        if self.future_a > self.initial_a {
+            self.initial_a + (self.future_a - self.initial_a) * elapsed / duration
+        } else {
+            self.initial_a - (self.initial_a - self.future_a) * elapsed / duration
+        }
+    }
+
+    /// Returns the rate multipliers of the pool, using the base pool virtual price as the rate of
+    /// the base LP token in meta pools.
+    pub fn stored_rates(&self) -> Result<Vec<U256>, AMMError> {
+        let mut rates = self.rates.clone();
+        if let Some(base_pool) = &self.base_pool {
+            *rates.last_mut().ok_or(CurveError::InitializationError)? =
+                base_pool.get_virtual_price()?;
+        }
+        Ok(rates)
+    }
+
+    /// Returns the value of one LP token in the pool, scaled by 1e18.
+    pub fn get_virtual_price(&self) -> Result<U256, AMMError> {
+        if self.total_supply.is_zero() {
+            return Err(CurveError::ZeroBalance.into());
+        }
+
+        let xp = xp(&self.balances, &self.stored_rates()?);
+        let d = get_d(&xp, self.a(), self.a_precision)?;
+        Ok(d * CURVE_PRECISION / self.total_supply)
+    }
+
+    /// Swaps `dx` of coin `i` for coin `j`, returning the amount received.
+    pub fn exchange(&mut self, i: usize, j: usize, dx: U256) -> Result<U256, AMMError> {
+        let rates = self.stored_rates()?;
+        let xp = xp(&self.balances, &rates);
+        if i >= xp.len() || j >= xp.len() {
+            return Err(CurveError::InvalidCoinIndex.into());
+        }
+
+        let x = xp[i] + dx * rates[i] / CURVE_PRECISION;
+        let (dy, dy_admin_fee) = self.get_dy_xp(i, j, x, &xp, &rates)?;
+
+        self.balances[i] += dx;
+        self.balances[j] -= dy + dy_admin_fee;
+
+        Ok(dy)
+    }
+
+    /// Swaps `dx` of underlying coin `i` for underlying coin `j` in a meta pool. Underlying
+    /// coins are the meta pool coin followed by the base pool coins.
+    pub fn exchange_underlying(&mut self, i: usize, j: usize, dx: U256) -> Result<U256, AMMError> {
+        let max_coin = self.balances.len() - 1;
+        let rates = self.stored_rates()?;
+        let base_pool = self
+            .base_pool
+            .as_deref_mut()
+            .ok_or(CurveError::NotMetaPool)?;
+
+        if i >= max_coin && j >= max_coin {
+            return base_pool.exchange(i - max_coin, j - max_coin, dx);
+        }
+
+        let (meta_i, meta_j) = (i.min(max_coin), j.min(max_coin));
+        let dx = if i < max_coin {
+            dx
+        } else {
+            let mut amounts = vec![U256::ZERO; base_pool.balances.len()];
+            *amounts
+                .get_mut(i - max_coin)
+                .ok_or(CurveError::InvalidCoinIndex)? = dx;
+            base_pool.add_liquidity(&amounts)?
+        };
+
+        let xp = xp(&self.balances, &rates);
+        let x = xp[meta_i] + dx * rates[meta_i] / CURVE_PRECISION;
+        let (dy, dy_admin_fee) = self.get_dy_xp(meta_i, meta_j, x, &xp, &rates)?;
+
+        self.balances[meta_i] += dx;
+        self.balances[meta_j] -= dy + dy_admin_fee;
+
+        if j >= max_coin {
+            let base_pool = self.base_pool.as_deref_mut().unwrap();
+            return base_pool.remove_liquidity_one_coin(dy, j - max_coin);
+        }
+
+        Ok(dy)
+    }
+
+    /// Deposits `amounts` into the pool, returning the amount of LP tokens minted.
+    pub fn add_liquidity(&mut self, amounts: &[U256]) -> Result<U256, AMMError> {
+        let n_coins = self.balances.len();
+        if amounts.len() != n_coins {
+            return Err(CurveError::InvalidCoinIndex.into());
+        }
+
+        let amp = self.a();
+        let rates = self.stored_rates()?;
+        let old_balances = self.balances.clone();
+        let d0 = if self.total_supply.is_zero() {
+            U256::ZERO
+        } else {
+            get_d(&xp(&old_balances, &rates), amp, self.a_precision)?
+        };
+
+        let mut new_balances = old_balances
+            .iter()
+            .zip(amounts)
+            .map(|(balance, amount)| balance + amount)
+            .collect::<Vec<_>>();
+        let d1 = get_d(&xp(&new_balances, &rates), amp, self.a_precision)?;
+        if d1 <= d0 {
+            return Err(CurveError::InsufficientLiquidity.into());
+        }
+
+        if self.total_supply.is_zero() {
+            self.balances = new_balances;
+            self.total_supply = d1;
+            return Ok(d1);
+        }
+
+        // Imbalanced deposits pay a fee on the difference from the ideal balances
+        let fee = self.fee * U256::from(n_coins) / (U256_4 * U256::from(n_coins - 1));
+        for k in 0..n_coins {
+            let ideal_balance = d1 * old_balances[k] / d0;
+            let difference = ideal_balance.abs_diff(new_balances[k]);
+            let coin_fee = fee * difference / CURVE_FEE_DENOMINATOR;
+            self.balances[k] = new_balances[k] - coin_fee * self.admin_fee / CURVE_FEE_DENOMINATOR;
+            new_balances[k] -= coin_fee;
+        }
+        let d2 = get_d(&xp(&new_balances, &rates), amp, self.a_precision)?;
+
+        let mint_amount = self.total_supply * (d2 - d0) / d0;
+        self.total_supply += mint_amount;
+
+        Ok(mint_amount)
+    }
+
+    /// Calculates the amount of coin `i` received for burning `token_amount` LP tokens, along
+    /// with the fee charged.
+    pub fn calc_withdraw_one_coin(
+        &self,
+        token_amount: U256,
+        i: usize,
+    ) -> Result<(U256, U256), AMMError> {
+        let n_coins = self.balances.len();
+        if i >= n_coins {
+            return Err(CurveError::InvalidCoinIndex.into());
+        }
+        if token_amount > self.total_supply {
+            return Err(CurveError::InsufficientLiquidity.into());
+        }
+
+        let amp = self.a();
+        let rates = self.stored_rates()?;
+        let xp = xp(&self.balances, &rates);
+
+        let d0 = get_d(&xp, amp, self.a_precision)?;
+        let d1 = d0 - token_amount * d0 / self.total_supply;
+        let new_y = get_y_d(i, &xp, d1, amp, self.a_precision)?;
+
+        let fee = self.fee * U256::from(n_coins) / (U256_4 * U256::from(n_coins - 1));
+        let mut xp_reduced = xp.clone();
+        for (k, x) in xp.iter().enumerate() {
+            let dx_expected = if k == i {
+                x * d1 / d0 - new_y
+            } else {
+                x - x * d1 / d0
+            };
+            xp_reduced[k] -= fee * dx_expected / CURVE_FEE_DENOMINATOR;
+        }
+
+        let dy = xp_reduced[i] - get_y_d(i, &xp_reduced, d1, amp, self.a_precision)?;
+        let dy = dy.saturating_sub(U256_1) * CURVE_PRECISION / rates[i];
+        let dy_0 = (xp[i] - new_y) * CURVE_PRECISION / rates[i];
+
+        Ok((dy, dy_0.saturating_sub(dy)))
+    }
+
+    /// Burns `token_amount` LP tokens for coin `i`, returning the amount received.
+    pub fn remove_liquidity_one_coin(
+        &mut self,
+        token_amount: U256,
+        i: usize,
+    ) -> Result<U256, AMMError> {
+        let (dy, dy_fee) = self.calc_withdraw_one_coin(token_amount, i)?;
+
+        self.balances[i] -= dy + dy_fee * self.admin_fee / CURVE_FEE_DENOMINATOR;
+        self.total_supply -= token_amount;
+
+        Ok(dy)
+    }
+
+    /// Returns the amount of coin `j` received and the admin fee charged when the normalized
+    /// balance of coin `i` is set to `x`.
+    fn get_dy_xp(
+        &self,
+        i: usize,
+        j: usize,
+        x: U256,
+        xp: &[U256],
+        rates: &[U256],
+    ) -> Result<(U256, U256), AMMError> {
+        let y = get_y(i, j, x, xp, self.a(), self.a_precision)?;
+        let dy = xp[j]
+            .checked_sub(y + U256_1)
+            .ok_or(CurveError::InsufficientLiquidity)?;
+
+        let fee = dynamic_fee(
+            (xp[i] + x) / U256::from(2),
+            (xp[j] + y) / U256::from(2),
+            self.fee,
+            self.offpeg_fee_multiplier,
+        );
+        let dy_fee = dy * fee / CURVE_FEE_DENOMINATOR;
+        let dy_admin_fee =
+            dy_fee * self.admin_fee / CURVE_FEE_DENOMINATOR * CURVE_PRECISION / rates[j];
+
+        Ok(((dy - dy_fee) * CURVE_PRECISION / rates[j], dy_admin_fee))
+    }
+
+    /// Syncs the meta pool leg of an underlying swap. The base pool leg is synced from the base
+    /// pool log emitted before this one, and the base LP tokens exchanged by the meta pool are the
+    /// ones that log minted or burned.
+    fn sync_exchange_underlying(
+        &mut self,
+        i: usize,
+        j: usize,
+        tokens_sold: U256,
+        tokens_bought: U256,
+    ) -> Result<(), AMMError> {
+        let max_coin = self.balances.len() - 1;
+        let prev_base_pool = self.prev_base_pool.take();
+
+        // Swaps between base pool coins go through the base pool only
+        if i >= max_coin && j >= max_coin {
+            return Ok(());
+        }
+
+        let base_pool = self.base_pool.as_deref().ok_or(CurveError::NotMetaPool)?;
+        let prev_base_pool = prev_base_pool.ok_or(CurveError::MissingBasePoolLog)?;
+        let lp_amount = prev_base_pool.total_supply.abs_diff(base_pool.total_supply);
+
+        // The meta pool prices the base LP token at its virtual price before the base pool leg
+        let mut rates = self.rates.clone();
+        *rates.last_mut().ok_or(CurveError::InitializationError)? =
+            prev_base_pool.get_virtual_price()?;
+
+        let (meta_i, meta_j) = (i.min(max_coin), j.min(max_coin));
+        let dx = if i < max_coin { tokens_sold } else { lp_amount };
+        let dy = if j < max_coin {
+            tokens_bought
+        } else {
+            lp_amount
+        };
+
+        // Balances follow the exchanged amounts, only the admin fee is simulated
+        let xp = xp(&self.balances, &rates);
+        let x = xp[meta_i] + dx * rates[meta_i] / CURVE_PRECISION;
+        let (_, dy_admin_fee) = self.get_dy_xp(meta_i, meta_j, x, &xp, &rates)?;
+
+        self.balances[meta_i] += dx;
+        self.balances[meta_j] = self.balances[meta_j]
+            .checked_sub(dy + dy_admin_fee)
+            .ok_or(CurveError::BalanceUnderflow)?;
+
+        Ok(())
+    }
+
+    fn sync_add_liquidity(
+        &mut self,
+        amounts: &[U256],
+        fees: &[U256],
+        token_supply: U256,
+    ) -> Result<(), AMMError> {
+        for (k, balance) in self.balances.iter_mut().enumerate() {
+            let amount = amounts.get(k).copied().unwrap_or_default();
+            let fee = fees.get(k).copied().unwrap_or_default();
+            *balance = (*balance + amount)
+                .checked_sub(fee * self.admin_fee / CURVE_FEE_DENOMINATOR)
+                .ok_or(CurveError::BalanceUnderflow)?;
+        }
+        self.total_supply = token_supply;
+
+        Ok(())
+    }
+
+    fn sync_remove_liquidity(
+        &mut self,
+        amounts: &[U256],
+        fees: &[U256],
+        token_supply: U256,
+    ) -> Result<(), AMMError> {
+        for (k, balance) in self.balances.iter_mut().enumerate() {
+            let amount = amounts.get(k).copied().unwrap_or_default();
+            let fee = fees.get(k).copied().unwrap_or_default();
+            *balance = balance
+                .checked_sub(amount + fee * self.admin_fee / CURVE_FEE_DENOMINATOR)
+                .ok_or(CurveError::BalanceUnderflow)?;
+        }
+        self.total_supply = token_supply;
+
+        Ok(())
+    }
+
+    fn sync_remove_liquidity_one(
+        &mut self,
+        i: usize,
+        token_amount: U256,
+        coin_amount: U256,
+        token_supply: U256,
+    ) -> Result<(), AMMError> {
+        let (_, dy_fee) = self.calc_withdraw_one_coin(token_amount, i)?;
+
+        self.balances[i] = self.balances[i]
+            .checked_sub(coin_amount + dy_fee * self.admin_fee / CURVE_FEE_DENOMINATOR)
+            .ok_or(CurveError::BalanceUnderflow)?;
+        self.total_supply = token_supply;
+
+        Ok(())
+    }
+
+    /// Infers the coin withdrawn by a `RemoveLiquidityOne` event that does not log its index,
+    /// picking the coin whose simulated withdrawal is closest to `coin_amount`.
+    fn withdrawn_coin(&self, token_amount: U256, coin_amount: U256) -> Result<usize, AMMError> {
+        (0..self.balances.len())
+            .filter_map(|i| {
+                self.calc_withdraw_one_coin(token_amount, i)
+                    .ok()
+                    .map(|(dy, _)| (i, dy.abs_diff(coin_amount)))
+            })
+            .min_by_key(|(_, diff)| *diff)
+            .map(|(i, _)| i)
+            .ok_or(CurveError::InvalidCoinIndex.into())
+    }
+
+    /// Returns a coin of the pool or an underlying coin of its base pool.
+    fn token(&self, address: Address) -> Option<&Token> {
+        self.tokens
+            .iter()
+            .chain(self.base_pool.iter().flat_map(|pool| pool.tokens.iter()))
+            .find(|token| token.address == address)
+    }
+
+    fn coin_index(&self, token: Address) -> Option<usize> {
+        self.tokens.iter().position(|t| t.address == token)
+    }
+
+    fn underlying_index(&self, token: Address) -> Option<usize> {
+        let max_coin = self.tokens.len().checked_sub(1)?;
+        if self.tokens.first().map(|t| t.address) == Some(token) {
+            return Some(0);
+        }
+        self.base_pool
+            .as_ref()?
+            .coin_index(token)
+            .map(|i| i + max_coin)
+    }
+
+    /// Marginal price of coin `i` in coin `j`, in whole token units.
+    fn marginal_price(&self, i: usize, j: usize) -> Result<Float, AMMError> {
+        let rates = self.stored_rates()?;
+        let (ann, d_p, _) = self.invariant_terms(&rates)?;
+        let xp = xp(&self.balances, &rates);
+
+        let x_i = u256_to_float(xp[i])?;
+        let x_j = u256_to_float(xp[j])?;
+        let price = (ann.clone() + d_p.clone() / x_i) / (ann + d_p / x_j);
+
+        Ok(price * self.unit_rate(i, &rates)? / self.unit_rate(j, &rates)?)
+    }
+
+    /// Marginal amount of coin `i` received per LP token, in whole token units.
+    fn lp_marginal_price(&self, i: usize) -> Result<Float, AMMError> {
+        let rates = self.stored_rates()?;
+        let (ann, d_p, d) = self.invariant_terms(&rates)?;
+        let xp = xp(&self.balances, &rates);
+        let n_coins = Float::with_val(MPFR_T_PRECISION, self.balances.len());
+
+        // dD/dx_i = (Ann + D_P / x_i) / (Ann - 1 + (n + 1) * D_P / D)
+        let d_d = (ann.clone() + d_p.clone() / u256_to_float(xp[i])?)
+            / (ann - 1 + (n_coins + 1) * d_p / d.clone());
+        let price = d / (u256_to_float(self.total_supply)? * d_d);
+
+        Ok(price / self.unit_rate(i, &rates)?)
+    }
+
+    /// Returns `A * n`, `D^(n+1) / (n^n * prod(x))` and `D` for the current balances.
+    fn invariant_terms(&self, rates: &[U256]) -> Result<(Float, Float, Float), AMMError> {
+        let xp = xp(&self.balances, rates);
+        let amp = self.a();
+        let d = get_d(&xp, amp, self.a_precision)?;
+        let n_coins = U256::from(xp.len());
+
+        let mut d_p = d;
+        for x in &xp {
+            d_p = d_p * d / (x * n_coins);
+        }
+
+        let ann = u256_to_float(amp * n_coins)? / u256_to_float(self.a_precision)?;
+        Ok((ann, u256_to_float(d_p)?, u256_to_float(d)?))
+    }
+
+    /// Value of one whole token of coin `i` in normalized units, relative to 1e18.
+    fn unit_rate(&self, i: usize, rates: &[U256]) -> Result<Float, AMMError> {
+        let decimals = U256_10.pow(U256::from(self.tokens[i].decimals));
+        Ok(u256_to_float(rates[i] * decimals)? / u256_to_float(CURVE_PRECISION * CURVE_PRECISION)?)
+    }
+}
+
+/// Normalizes `balances` to 18 decimals using `rates`.
+fn xp(balances: &[U256], rates: &[U256]) -> Vec<U256> {
+    balances
+        .iter()
+        .zip(rates)
+        .map(|(balance, rate)| rate * balance / CURVE_PRECISION)
+        .collect()
+}
+
+fn coin_index(index: i128) -> Result<usize, CurveError> {
+    usize::try_from(index).map_err(|_| CurveError::InvalidCoinIndex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        primitives::address,
+        providers::ProviderBuilder,
+        rpc::{client::ClientBuilder, types::Filter},
+        transports::layers::{RetryBackoffLayer, ThrottleLayer},
+    };
+
+    fn three_pool() -> CurveStableSwapPool {
+        let dai =
+            Token::new_with_decimals(address!("6B175474E89094C44Da98b954EedeAC495271d0F"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), 6);
+        let usdt =
+            Token::new_with_decimals(address!("dAC17F958D2ee523a2206206994597C13D831ec7"), 6);
+
+        CurveStableSwapPool {
+            address: address!("bEbc44782C7dB0a1A60Cb6fe97d0b483032FF1C7"),
+            tokens: vec![dai, usdc, usdt],
+            balances: vec![
+                U256::from(50_000_000e18 as u128),
+                U256::from(50_000_000e6 as u128),
+                U256::from(50_000_000e6 as u128),
+            ],
+            rates: vec![
+                U256::from(1e18 as u128),
+                U256::from(1e30 as u128),
+                U256::from(1e30 as u128),
+            ],
+            initial_a: U256::from(200_000),
+            future_a: U256::from(200_000),
+            a_precision: U256::from(100),
+            fee: U256::from(1_000_000),
+            admin_fee: U256::from(5_000_000_000_u64),
+            total_supply: U256::from(150_000_000e18 as u128),
+            ..Default::default()
+        }
+    }
+
+    fn lusd_meta_pool() -> CurveStableSwapPool {
+        let lusd =
+            Token::new_with_decimals(address!("5f98805A4E8be255a32880FDeC7F6728C6568bA0"), 18);
+        let lp_token =
+            Token::new_with_decimals(address!("6c3F90f043a72FA612cbac8115EE7e52BDe6E490"), 18);
+
+        CurveStableSwapPool {
+            address: address!("Ed279fDD11cA84bEef15AF5D39BB4d4bEE23F0cA"),
+            tokens: vec![lusd, lp_token],
+            balances: vec![U256::from(10_000_000e18 as u128); 2],
+            rates: vec![U256::from(1e18 as u128); 2],
+            initial_a: U256::from(20_000),
+            future_a: U256::from(20_000),
+            a_precision: U256::from(100),
+            fee: U256::from(4_000_000),
+            admin_fee: U256::from(5_000_000_000_u64),
+            total_supply: U256::from(20_000_000e18 as u128),
+            base_pool: Some(Box::new(three_pool())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_d_balanced() {
+        let xp = vec![U256::from(1e24 as u128); 3];
+        let d = get_d(&xp, U256::from(200_000), U256::from(100)).unwrap();
+        assert!(d.abs_diff(U256::from(3e24 as u128)) <= U256_1);
+    }
+
+    #[test]
+    fn test_simulate_swap() {
+        let pool = three_pool();
+        let (dai, usdc) = (pool.tokens[0].address, pool.tokens[1].address);
+
+        // A balanced pool trades close to 1:1, minus the 0.01% fee
+        let amount_out = pool
+            .simulate_swap(dai, usdc, U256::from(1_000e18 as u128))
+            .unwrap();
+        assert!(amount_out < U256::from(999_900_000_u64));
+        assert!(amount_out > U256::from(999_800_000_u64));
+
+        let price = pool.calculate_price(dai, usdc).unwrap();
+        assert!((price - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sync_token_exchange() {
+        let mut pool = three_pool();
+        let (dai, usdc) = (pool.tokens[0].address, pool.tokens[1].address);
+        let mut expected = pool.clone();
+        let amount_in = U256::from(1_000e18 as u128);
+        let amount_out = expected.simulate_swap_mut(dai, usdc, amount_in).unwrap();
+
+        let event = ICurveStableSwap::TokenExchange {
+            buyer: Address::ZERO,
+            sold_id: 0,
+            tokens_sold: amount_in,
+            bought_id: 1,
+            tokens_bought: amount_out,
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: event.encode_log_data(),
+            },
+            ..Default::default()
+        };
+        pool.sync(&log).unwrap();
+
+        assert_eq!(pool.balances, expected.balances);
+
+        // Amounts bought beyond the pool balance are rejected
+        let drain = ICurveStableSwap::TokenExchange {
+            tokens_bought: pool.balances[1] + U256_1,
+            ..event
+        };
+        let mut drain_log = log;
+        drain_log.inner.data = drain.encode_log_data();
+        assert!(pool.sync(&drain_log).is_err());
+    }
+
+    #[test]
+    fn test_simulate_swap_underlying() {
+        let base_pool = three_pool();
+        let meta_pool = lusd_meta_pool();
+        let lusd = meta_pool.tokens[0].clone();
+        let usdc = base_pool.tokens[1].address;
+
+        let mut pool = meta_pool.clone();
+        let amount_out = pool
+            .simulate_swap_mut(lusd.address, usdc, U256::from(1_000e18 as u128))
+            .unwrap();
+        assert!(amount_out > U256::from(995_000_000_u64));
+        assert!(amount_out < U256::from(1_000_000_000_u64));
+
+        // The LP tokens received by the meta pool are withdrawn from the base pool
+        let base_pool_after = pool.base_pool.as_ref().unwrap();
+        assert!(base_pool_after.total_supply < base_pool.total_supply);
+        assert!(base_pool_after.balances[1] < base_pool.balances[1]);
+
+        let price = meta_pool.calculate_price(lusd.address, usdc).unwrap();
+        assert!((price - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sync_base_pool() {
+        let mut pool = lusd_meta_pool();
+        let base_pool = three_pool();
+        assert_eq!(pool.dependencies(), vec![base_pool.address]);
+
+        // Logs of the base pool update the base pool snapshot only
+        let amount_in = U256::from(1_000e18 as u128);
+        let mut expected = base_pool.clone();
+        let amount_out = expected
+            .simulate_swap_mut(
+                base_pool.tokens[0].address,
+                base_pool.tokens[1].address,
+                amount_in,
+            )
+            .unwrap();
+        let event = ICurveStableSwap::TokenExchange {
+            buyer: Address::ZERO,
+            sold_id: 0,
+            tokens_sold: amount_in,
+            bought_id: 1,
+            tokens_bought: amount_out,
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: base_pool.address,
+                data: event.encode_log_data(),
+            },
+            block_timestamp: Some(1_700_000_000),
+            ..Default::default()
+        };
+        pool.sync(&log).unwrap();
+
+        let base_pool_after = pool.base_pool.as_ref().unwrap();
+        assert_eq!(base_pool_after.balances, expected.balances);
+        assert_eq!(base_pool_after.block_timestamp, 1_700_000_000);
+        assert_eq!(pool.balances, lusd_meta_pool().balances);
+        assert_eq!(pool.block_timestamp, 1_700_000_000);
+    }
+
+    #[test]
+    fn test_sync_token_exchange_underlying() {
+        let mut pool = lusd_meta_pool();
+        let lusd = pool.tokens[0].address;
+        let usdc = pool.base_pool.as_ref().unwrap().tokens[1].address;
+        let mut expected = pool.clone();
+        let amount_in = U256::from(1_000e18 as u128);
+        let amount_out = expected.simulate_swap_mut(lusd, usdc, amount_in).unwrap();
+
+        // The base pool withdrawal is logged before the meta pool swap
+        let lp_amount = pool.base_pool.as_ref().unwrap().total_supply
+            - expected.base_pool.as_ref().unwrap().total_supply;
+        let base_log = Log {
+            inner: alloy::primitives::Log {
+                address: three_pool().address,
+                data: ICurveStableSwap::RemoveLiquidityOne {
+                    provider: pool.address,
+                    token_amount: lp_amount,
+                    coin_amount: amount_out,
+                }
+                .encode_log_data(),
+            },
+            ..Default::default()
+        };
+        let meta_log = Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: ICurveStableSwap::TokenExchangeUnderlying {
+                    buyer: Address::ZERO,
+                    sold_id: 0,
+                    tokens_sold: amount_in,
+                    bought_id: 2,
+                    tokens_bought: amount_out,
+                }
+                .encode_log_data(),
+            },
+            ..Default::default()
+        };
+
+        // Without the base pool leg the exchanged LP amount is unknown
+        assert!(pool.clone().sync(&meta_log).is_err());
+
+        pool.sync(&base_log).unwrap();
+        pool.sync(&meta_log).unwrap();
+
+        assert_eq!(pool.balances, expected.balances);
+        assert_eq!(
+            pool.base_pool.as_ref().unwrap().balances,
+            expected.base_pool.as_ref().unwrap().balances
+        );
+    }
+
+    #[test]
+    fn test_a_ramp() {
+        let mut pool = three_pool();
+        pool.initial_a = U256::from(100_000);
+        pool.future_a = U256::from(200_000);
+        pool.initial_a_time = 1_700_000_000;
+        pool.future_a_time = 1_700_086_400;
+
+        // A is interpolated at the synced block, regardless of the current time
+        pool.block_timestamp = 1_700_021_600;
+        assert_eq!(pool.a(), U256::from(125_000));
+        pool.block_timestamp = 1_700_086_400;
+        assert_eq!(pool.a(), U256::from(200_000));
+    }
+
+    #[test]
+    fn test_remove_liquidity_one_coin() {
+        let mut pool = three_pool();
+        pool.balances[2] = U256::from(40_000_000e6 as u128);
+        let token_amount = U256::from(1_000e18 as u128);
+
+        let (dy, dy_fee) = pool.calc_withdraw_one_coin(token_amount, 1).unwrap();
+        assert!(dy_fee > U256::ZERO);
+        assert_eq!(pool.withdrawn_coin(token_amount, dy).unwrap(), 1);
+
+        let withdrawn = pool.remove_liquidity_one_coin(token_amount, 1).unwrap();
+        assert_eq!(dy, withdrawn);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_swap_three_pool() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_PROVIDER")?;
+
+        let client = ClientBuilder::default()
+            .layer(ThrottleLayer::new(250))
+            .layer(RetryBackoffLayer::new(5, 200, 330))
+            .http(rpc_endpoint.parse()?);
+
+        let provider = ProviderBuilder::new().connect_client(client);
+        let block_number = BlockId::from(22_000_000);
+
+        let pool = CurveStableSwapPool::new(address!("bEbc44782C7dB0a1A60Cb6fe97d0b483032FF1C7"))
+            .init(block_number, provider.clone())
+            .await?;
+
+        let three_pool = ICurveStableSwap::new(pool.address, provider);
+        for (i, j, amount_in) in [
+            (0, 1, U256::from(1_000e18 as u128)),
+            (1, 2, U256::from(1_000_000e6 as u128)),
+            (2, 0, U256::from(10e6 as u128)),
+        ] {
+            let amount_out =
+                pool.simulate_swap(pool.tokens[i].address, pool.tokens[j].address, amount_in)?;
+            let expected_amount_out = three_pool
+                .get_dy(i as i128, j as i128, amount_in)
+                .block(block_number)
+                .call()
+                .await?;
+
+            // get_dy applies the fee before denormalizing while exchange applies it after
+            assert!(amount_out.abs_diff(expected_amount_out) <= U256_1);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_meta_pool_block() -> eyre::Result<()> {
+        let rpc_endpoint = std::env::var("ETHEREUM_PROVIDER")?;
+
+        let client = ClientBuilder::default()
+            .layer(ThrottleLayer::new(250))
+            .layer(RetryBackoffLayer::new(5, 200, 330))
+            .http(rpc_endpoint.parse()?);
+
+        let provider = ProviderBuilder::new().connect_client(client);
+        let meta_pool = address!("Ed279fDD11cA84bEef15AF5D39BB4d4bEE23F0cA");
+        let base_pool = address!("bEbc44782C7dB0a1A60Cb6fe97d0b483032FF1C7");
+
+        // First underlying swap of the LUSD meta pool from block 17,000,000
+        let filter = Filter::new()
+            .address(meta_pool)
+            .event_signature(ICurveStableSwap::TokenExchangeUnderlying::SIGNATURE_HASH)
+            .from_block(17_000_000)
+            .to_block(17_010_000);
+        let block_number = provider
+            .get_logs(&filter)
+            .await?
+            .first()
+            .and_then(|log| log.block_number)
+            .ok_or_else(|| eyre::eyre!("no underlying swap in range"))?;
+
+        let mut pool = CurveStableSwapPool::new(meta_pool)
+            .init(BlockId::from(block_number - 1), provider.clone())
+            .await?;
+
+        // Every log of the meta pool and its base pool in the block, in emission order
+        let filter = Filter::new()
+            .address(vec![meta_pool, base_pool])
+            .from_block(block_number)
+            .to_block(block_number);
+        for log in provider.get_logs(&filter).await? {
+            if pool.sync_events().contains(&log.topics()[0]) {
+                pool.sync(&log)?;
+            }
+        }
+
+        for (pool, address) in [
+            (&pool, meta_pool),
+            (pool.base_pool.as_deref().unwrap(), base_pool),
+        ] {
+            let contract = ICurveStableSwap::new(address, provider.clone());
+            for (i, balance) in pool.balances.iter().enumerate() {
+                let expected_balance = contract
+                    .balances(U256::from(i))
+                    .block(BlockId::from(block_number))
+                    .call()
+                    .await?;
+                assert_eq!(*balance, expected_balance);
+            }
+        }
+
+        Ok(())
+    }
+}
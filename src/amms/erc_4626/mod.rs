@@ -1,18 +1,20 @@
 use super::{
-    amm::AutomatedMarketMaker,
-    consts::{U128_0X10000000000000000, U256_10000, U256_2},
+    amm::{check_amount_out, AutomatedMarketMaker},
+    consts::{Q128, U128_0X10000000000000000, U256_10000, U256_2},
     error::AMMError,
     float::q64_to_float,
+    price::ratio_to_q128,
     uniswap_v2::div_uu,
+    Token, IERC20,
 };
 use alloy::{
     eips::BlockId,
     network::Network,
-    primitives::{Address, B256, U256},
+    primitives::{Address, Bytes, B256, U256},
     providers::Provider,
     rpc::types::Log,
     sol,
-    sol_types::{SolEvent, SolValue},
+    sol_types::{SolCall, SolEvent, SolValue},
 };
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
@@ -28,6 +30,8 @@ sol! {
         event Deposit(address indexed sender,address indexed owner, uint256 assets, uint256 shares);
         function totalAssets() external view returns (uint256);
         function totalSupply() external view returns (uint256);
+        function deposit(uint256 assets, address receiver) external returns (uint256 shares);
+        function redeem(uint256 shares, address receiver, address owner) external returns (uint256 assets);
     }
 }
 
@@ -51,11 +55,9 @@ pub enum ERC4626VaultError {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ERC4626Vault {
     /// Token received from depositing, i.e. shares token
-    pub vault_token: Address,
-    pub vault_token_decimals: u8,
+    pub vault_token: Token,
     /// Token received from withdrawing, i.e. underlying token
-    pub asset_token: Address,
-    pub asset_token_decimals: u8,
+    pub asset_token: Token,
     /// Total supply of vault tokens
     pub vault_reserve: U256,
     /// Total balance of asset tokens held by vault
@@ -68,7 +70,7 @@ pub struct ERC4626Vault {
 
 impl AutomatedMarketMaker for ERC4626Vault {
     fn address(&self) -> Address {
-        self.vault_token
+        self.vault_token.address
     }
 
     fn sync_events(&self) -> Vec<B256> {
@@ -88,7 +90,7 @@ impl AutomatedMarketMaker for ERC4626Vault {
 
                 info!(
                     target = "amms::erc_4626::sync",
-                    address = ?self.vault_token,
+                    address = ?self.vault_token.address,
                     asset_reserve = ?self.asset_reserve,
                     vault_reserve = ?self.vault_reserve,
                     "Deposit"
@@ -102,7 +104,7 @@ impl AutomatedMarketMaker for ERC4626Vault {
 
                 info!(
                     target = "amms::erc_4626::sync",
-                    address = ?self.vault_token,
+                    address = ?self.vault_token.address,
                     asset_reserve = ?self.asset_reserve,
                     vault_reserve = ?self.vault_reserve,
                     "Withdraw"
@@ -118,23 +120,43 @@ impl AutomatedMarketMaker for ERC4626Vault {
     }
 
     fn tokens(&self) -> Vec<Address> {
-        vec![self.vault_token, self.asset_token]
+        vec![self.vault_token.address, self.asset_token.address]
     }
 
     fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
         q64_to_float(self.calculate_price_64_x_64(base_token)?)
     }
 
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        self.calculate_price_128_x_128(base_token)
+    }
+
     fn simulate_swap(
         &self,
         base_token: Address,
         _quote_token: Address,
         amount_in: U256,
     ) -> Result<U256, AMMError> {
-        if self.vault_token == base_token {
-            Ok(self.get_amount_out(amount_in, self.vault_reserve, self.asset_reserve)?)
+        if self.vault_token.address == base_token {
+            let amount_out = self.get_amount_out(
+                self.vault_token.after_sell_tax(amount_in),
+                self.vault_reserve,
+                self.asset_reserve,
+            )?;
+
+            Ok(self.asset_token.after_buy_tax(amount_out))
         } else {
-            Ok(self.get_amount_out(amount_in, self.asset_reserve, self.vault_reserve)?)
+            let amount_out = self.get_amount_out(
+                self.asset_token.after_sell_tax(amount_in),
+                self.asset_reserve,
+                self.vault_reserve,
+            )?;
+
+            Ok(self.vault_token.after_buy_tax(amount_out))
         }
     }
 
@@ -144,33 +166,121 @@ impl AutomatedMarketMaker for ERC4626Vault {
         _quote_token: Address,
         amount_in: U256,
     ) -> Result<U256, AMMError> {
-        if self.vault_token == base_token {
+        if self.vault_token.address == base_token {
+            let amount_in = self.vault_token.after_sell_tax(amount_in);
             let amount_out =
                 self.get_amount_out(amount_in, self.vault_reserve, self.asset_reserve)?;
 
             self.vault_reserve -= amount_in;
             self.asset_reserve -= amount_out;
 
-            Ok(amount_out)
+            Ok(self.asset_token.after_buy_tax(amount_out))
         } else {
+            let amount_in = self.asset_token.after_sell_tax(amount_in);
             let amount_out =
                 self.get_amount_out(amount_in, self.asset_reserve, self.vault_reserve)?;
 
             self.asset_reserve += amount_in;
             self.vault_reserve += amount_out;
 
-            Ok(amount_out)
+            Ok(self.vault_token.after_buy_tax(amount_out))
+        }
+    }
+
+    /// Returns the shares to burn to withdraw `amount_out` assets, or the assets to deposit to mint
+    /// `amount_out` shares.
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        if self.vault_token.address == base_token {
+            let amount_in = self.get_amount_in(
+                self.asset_token.before_buy_tax(amount_out)?,
+                self.vault_reserve,
+                self.asset_reserve,
+            )?;
+
+            self.vault_token.before_sell_tax(amount_in)
+        } else {
+            let amount_in = self.get_amount_in(
+                self.vault_token.before_buy_tax(amount_out)?,
+                self.asset_reserve,
+                self.vault_reserve,
+            )?;
+
+            self.asset_token.before_sell_tax(amount_in)
+        }
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        if self.vault_token.address == base_token {
+            let amount_out = self.asset_token.before_buy_tax(amount_out)?;
+            let amount_in =
+                self.get_amount_in(amount_out, self.vault_reserve, self.asset_reserve)?;
+
+            self.vault_reserve -= amount_in;
+            self.asset_reserve -= amount_out;
+
+            self.vault_token.before_sell_tax(amount_in)
+        } else {
+            let amount_out = self.vault_token.before_buy_tax(amount_out)?;
+            let amount_in =
+                self.get_amount_in(amount_out, self.asset_reserve, self.vault_reserve)?;
+
+            self.asset_reserve += amount_in;
+            self.vault_reserve += amount_out;
+
+            self.asset_token.before_sell_tax(amount_in)
         }
     }
 
     // TODO: clean up this function
+    /// Encodes `deposit` when selling the asset and `redeem` when selling shares. Shares are
+    /// redeemed from `recipient`, which must be the caller or have approved it.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        if base_token == self.vault_token.address {
+            Ok(IERC4626Vault::redeemCall {
+                shares: amount_in,
+                receiver: recipient,
+                owner: recipient,
+            }
+            .abi_encode()
+            .into())
+        } else {
+            Ok(IERC4626Vault::depositCall {
+                assets: amount_in,
+                receiver: recipient,
+            }
+            .abi_encode()
+            .into())
+        }
+    }
+
     async fn init<N, P>(mut self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
     where
         N: Network,
         P: Provider<N> + Clone,
     {
-        let deployer =
-            IGetERC4626VaultDataBatchRequest::deploy_builder(provider, vec![self.vault_token]);
+        let deployer = IGetERC4626VaultDataBatchRequest::deploy_builder(
+            provider,
+            vec![self.vault_token.address],
+        );
         let res = deployer.call_raw().block(block_number).await?;
 
         let data = <Vec<(
@@ -232,23 +342,60 @@ impl AutomatedMarketMaker for ERC4626Vault {
         }
 
         // if above does not error => populate the vault
-        self.vault_token = vault_token;
-        self.vault_token_decimals = vault_token_dec as u8;
-        self.asset_token = asset_token;
-        self.asset_token_decimals = asset_token_dec as u8;
+        // Taxes set before the sync are kept
+        self.vault_token = Token {
+            tax: self.vault_token.tax,
+            ..Token::new_with_decimals(vault_token, vault_token_dec as u8)
+        };
+        self.asset_token = Token {
+            tax: self.asset_token.tax,
+            ..Token::new_with_decimals(asset_token, asset_token_dec as u8)
+        };
         self.vault_reserve = vault_reserve;
         self.asset_reserve = asset_reserve;
 
         Ok(self)
     }
+
+    /// Detects the tax of the asset token by moving a hundredth of the vault's balance of it. Shares
+    /// are minted and burned rather than held by the vault, so their tax is not measured.
+    async fn detect_taxes<N, P>(
+        &mut self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let balance = IERC20::new(self.asset_token.address, provider.clone())
+            .balanceOf(self.vault_token.address)
+            .block(block_number)
+            .call()
+            .await?;
+
+        let amount = balance / U256::from(100);
+        if !amount.is_zero() {
+            let tax = Token::detect_tax(
+                self.asset_token.address,
+                self.vault_token.address,
+                amount,
+                block_number,
+                provider,
+            )
+            .await?;
+            self.asset_token.tax = Some(tax);
+        }
+
+        Ok(())
+    }
 }
 
-// TODO: swap calldata
 impl ERC4626Vault {
     // Returns a new, unsynced ERC4626 vault
     pub fn new(address: Address) -> Self {
         Self {
-            vault_token: address,
+            vault_token: Token::from(address),
             ..Default::default()
         }
     }
@@ -280,8 +427,39 @@ impl ERC4626Vault {
         Ok(amount_in * reserve_out / reserve_in * U256::from(10000 - fee) / U256_10000)
     }
 
+    /// Returns the amount in required to receive `amount_out`, rounding up like `previewMint` and
+    /// `previewWithdraw`.
+    pub fn get_amount_in(
+        &self,
+        amount_out: U256,
+        reserve_in: U256,
+        reserve_out: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_out.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        if self.vault_reserve.is_zero() {
+            return Ok(amount_out);
+        }
+
+        let fee = if reserve_in == self.vault_reserve {
+            self.withdraw_fee
+        } else {
+            self.deposit_fee
+        };
+
+        if reserve_out.is_zero() || 10000 - fee == 0 {
+            return Err(ERC4626VaultError::DivisionByZero.into());
+        }
+
+        // Gross up the amount out by the fee, then convert it at the vault's exchange rate
+        let amount_out = (amount_out * U256_10000).div_ceil(U256::from(10000 - fee));
+        Ok((amount_out * reserve_in).div_ceil(reserve_out))
+    }
+
     pub fn calculate_price_64_x_64(&self, base_token: Address) -> Result<u128, AMMError> {
-        let decimal_shift = self.vault_token_decimals as i8 - self.asset_token_decimals as i8;
+        let decimal_shift = self.vault_token.decimals as i8 - self.asset_token.decimals as i8;
 
         // Normalize reserves by decimal shift
         let (r_v, r_a) = match decimal_shift.cmp(&0) {
@@ -296,7 +474,7 @@ impl ERC4626Vault {
         };
 
         // Withdraw
-        if base_token == self.vault_token {
+        if base_token == self.vault_token.address {
             if r_v.is_zero() {
                 // Return 1 in Q64
                 Ok(U128_0X10000000000000000)
@@ -312,6 +490,36 @@ impl ERC4626Vault {
         }
     }
 
+    /// Calculates the price of the base token in terms of the quote token as a Q128 fixed point
+    /// number, excluding fees.
+    pub fn calculate_price_128_x_128(&self, base_token: Address) -> Result<U256, AMMError> {
+        // Withdraw
+        let (reserve_base, base_decimals, reserve_quote, quote_decimals) =
+            if base_token == self.vault_token.address {
+                (
+                    self.vault_reserve,
+                    self.vault_token.decimals,
+                    self.asset_reserve,
+                    self.asset_token.decimals,
+                )
+            // Deposit
+            } else {
+                (
+                    self.asset_reserve,
+                    self.asset_token.decimals,
+                    self.vault_reserve,
+                    self.vault_token.decimals,
+                )
+            };
+
+        if reserve_base.is_zero() {
+            // Return 1 in Q128
+            return Ok(Q128);
+        }
+
+        ratio_to_q128(reserve_quote, quote_decimals, reserve_base, base_decimals)
+    }
+
     pub async fn get_reserves<N, P>(
         &self,
         provider: P,
@@ -321,7 +529,7 @@ impl ERC4626Vault {
         N: Network,
         P: Provider<N> + Clone + Clone,
     {
-        let vault = IERC4626Vault::new(self.vault_token, provider);
+        let vault = IERC4626Vault::new(self.vault_token.address, provider);
 
         let total_assets = vault.totalAssets().block(block_number).call().await?;
 
@@ -330,3 +538,96 @@ impl ERC4626Vault {
         Ok((total_supply, total_assets))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::TokenTax;
+    use alloy::{primitives::address, providers::ProviderBuilder, transports::mock::Asserter};
+
+    fn taxed_vault() -> ERC4626Vault {
+        ERC4626Vault {
+            vault_token: Token::new_with_decimals(
+                address!("83F20F44975D03b1b09e64809B757c47f942BEeA"),
+                18,
+            ),
+            asset_token: Token::new_with_decimals(
+                address!("6B175474E89094C44Da98b954EedeAC495271d0F"),
+                18,
+            )
+            .with_tax(TokenTax {
+                buy: 500,
+                sell: 1000,
+                transfer: 0,
+            }),
+            vault_reserve: U256::from(10_u128.pow(24)),
+            asset_reserve: U256::from(2 * 10_u128.pow(24)),
+            deposit_fee: 0,
+            withdraw_fee: 0,
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_with_tax() -> eyre::Result<()> {
+        let vault = taxed_vault();
+        let (shares, assets) = (vault.vault_token.address, vault.asset_token.address);
+        let amount = U256::from(10_u128.pow(20));
+
+        // The vault receives 90% of the assets deposited
+        let deposited = U256::from(9 * 10_u128.pow(19));
+        assert_eq!(
+            vault.simulate_swap(assets, shares, amount)?,
+            deposited / U256::from(2)
+        );
+        // and 95% of the assets redeemed arrive
+        assert_eq!(
+            vault.simulate_swap(shares, assets, amount)?,
+            U256::from(19 * 10_u128.pow(19))
+        );
+
+        let mut vault_mut = vault.clone();
+        vault_mut.simulate_swap_mut(assets, shares, amount)?;
+        assert_eq!(vault_mut.asset_reserve, vault.asset_reserve + deposited);
+
+        // Exact out quotes cover the taxes
+        let amount_in = vault.simulate_swap_exact_out(assets, shares, amount)?;
+        assert!(vault.simulate_swap(assets, shares, amount_in)? >= amount);
+        let amount_in = vault.simulate_swap_exact_out(shares, assets, amount)?;
+        assert!(vault.simulate_swap(shares, assets, amount_in)? >= amount);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_taxes() -> eyre::Result<()> {
+        let mut vault = ERC4626Vault {
+            asset_token: Token::new_with_decimals(
+                address!("6B175474E89094C44Da98b954EedeAC495271d0F"),
+                18,
+            ),
+            ..ERC4626Vault::new(address!("83F20F44975D03b1b09e64809B757c47f942BEeA"))
+        };
+
+        // The asset balance of the vault, then the taxes measured by the detector
+        let asserter = Asserter::new();
+        asserter.push_success(&Bytes::from(U256::from(10_u128.pow(24)).abi_encode()));
+        asserter.push_success(&Bytes::from(
+            (U256::from(200), U256::from(300), U256::ZERO).abi_encode(),
+        ));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+
+        vault.detect_taxes(BlockId::latest(), provider).await?;
+        assert_eq!(
+            vault.asset_token.tax,
+            Some(TokenTax {
+                buy: 200,
+                sell: 300,
+                transfer: 0,
+            })
+        );
+        assert_eq!(vault.vault_token.tax, None);
+        assert!(asserter.read_q().is_empty());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,14 @@
 use super::{
-    balancer::BalancerError, erc_4626::ERC4626VaultError, uniswap_v2::UniswapV2Error,
-    uniswap_v3::UniswapV3Error,
+    algebra::AlgebraError, balancer::BalancerError, balancer_v2::BalancerV2Error,
+    curve::CurveError, erc_4626::ERC4626VaultError, liquidity_book::LiquidityBookError,
+    solidly::SolidlyError, uniswap_v2::UniswapV2Error, uniswap_v3::UniswapV3Error,
+    uniswap_v4::UniswapV4Error,
+};
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, FixedBytes, U256},
+    transports::TransportErrorKind,
 };
-use alloy::{primitives::FixedBytes, transports::TransportErrorKind};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -20,8 +26,20 @@ pub enum AMMError {
     #[error(transparent)]
     UniswapV3Error(#[from] UniswapV3Error),
     #[error(transparent)]
+    UniswapV4Error(#[from] UniswapV4Error),
+    #[error(transparent)]
     BalancerError(#[from] BalancerError),
     #[error(transparent)]
+    BalancerV2Error(#[from] BalancerV2Error),
+    #[error(transparent)]
+    CurveError(#[from] CurveError),
+    #[error(transparent)]
+    SolidlyError(#[from] SolidlyError),
+    #[error(transparent)]
+    AlgebraError(#[from] AlgebraError),
+    #[error(transparent)]
+    LiquidityBookError(#[from] LiquidityBookError),
+    #[error(transparent)]
     ERC4626VaultError(#[from] ERC4626VaultError),
     #[error(transparent)]
     BatchContractError(#[from] BatchContractError),
@@ -29,6 +47,26 @@ pub enum AMMError {
     ParseFloatError(#[from] rug::float::ParseFloatError),
     #[error("Unrecognized Event Signature {0}")]
     UnrecognizedEventSignature(FixedBytes<32>),
+    #[error("Price does not fit in a Q128.128 fixed point number")]
+    PriceOverflow,
+    #[error("Token {0} is taxed at 100%")]
+    Untradeable(Address),
+    #[error("Exact prices are not supported by AMM {0}")]
+    ExactPriceNotSupported(Address),
+    #[error("Exact output swaps are not supported by AMM {0}")]
+    ExactOutNotSupported(Address),
+    #[error("Liquidity provision over this range is not supported by AMM {0}")]
+    LiquidityNotSupported(Address),
+    #[error("Expected an amount for each of the {expected} tokens of the AMM, got {actual}")]
+    TokenAmountsMismatch { expected: usize, actual: usize },
+    #[error("AMM {0} does not track its price oracle")]
+    OracleNotTracked(Address),
+    #[error("Swap calldata is not supported by AMM {0}")]
+    SwapCalldataNotSupported(Address),
+    #[error("Amount out {amount_out} is below the minimum of {min_out}")]
+    InsufficientAmountOut { amount_out: U256, min_out: U256 },
+    #[error("Block {0} not found")]
+    BlockNotFound(BlockId),
     #[error(transparent)]
     JoinError(#[from] tokio::task::JoinError),
 }
@@ -1,8 +1,16 @@
-use super::{amm::Variant, uniswap_v2::UniswapV2Factory, uniswap_v3::UniswapV3Factory};
 use super::{
+    algebra::AlgebraFactory,
     amm::{AutomatedMarketMaker, AMM},
     balancer::BalancerFactory,
+    balancer_v2::BalancerV2Vault,
+    curve::crypto_swap::CurveCryptoSwapFactory,
     error::AMMError,
+    liquidity_book::LiquidityBookFactory,
+    solidly::SolidlyFactory,
+};
+use super::{
+    amm::Variant, uniswap_v2::UniswapV2Factory, uniswap_v3::UniswapV3Factory,
+    uniswap_v4::UniswapV4PoolManager,
 };
 use alloy::{
     eips::BlockId,
@@ -48,6 +56,20 @@ pub trait AutomatedMarketMakerFactory: DiscoverySync {
     /// Creates an unsynced pool from a creation log.
     fn create_pool(&self, log: Log) -> Result<AMM, AMMError>;
 
+    /// Creates an unsynced pool from a creation log, fetching what the log does not carry at the
+    /// block of the log. Defaults to [`create_pool`](Self::create_pool).
+    fn create_pool_with_provider<N, P>(
+        &self,
+        log: Log,
+        _provider: P,
+    ) -> impl Future<Output = Result<AMM, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        std::future::ready(self.create_pool(log))
+    }
+
     /// Returns the block number at which the factory was created.
     fn creation_block(&self) -> u64;
 
@@ -90,6 +112,15 @@ macro_rules! factory {
                 }
             }
 
+            /// Whether `other` is the same factory with the same configuration, where `==` only
+            /// compares addresses
+            pub fn same_config(&self, other: &Factory) -> bool {
+                match (self, other) {
+                    $((Factory::$factory_type(factory), Factory::$factory_type(other)) => factory == other,)+
+                    _ => false,
+                }
+            }
+
              pub fn creation_block(&self) -> u64 {
                 match self {
                     $(Factory::$factory_type(factory) => factory.creation_block(),)+
@@ -135,6 +166,16 @@ macro_rules! factory {
                 }
             }
 
+            pub async fn create_pool_with_provider<N, P>(&self, log: Log, provider: P) -> Result<AMM, AMMError>
+            where
+                N: Network,
+                P: Provider<N> + Clone,
+            {
+                match self {
+                    $(Factory::$factory_type(factory) => factory.create_pool_with_provider(log, provider).await,)+
+                }
+            }
+
             pub async fn sync< N, P>(&self, amms: Vec<AMM>, to_block: BlockId, provider: P) -> Result<Vec<AMM>, AMMError>
             where
                                 N: Network,
@@ -156,7 +197,17 @@ macro_rules! factory {
     };
 }
 
-factory!(UniswapV2Factory, UniswapV3Factory, BalancerFactory);
+factory!(
+    UniswapV2Factory,
+    UniswapV3Factory,
+    UniswapV4PoolManager,
+    BalancerFactory,
+    BalancerV2Vault,
+    CurveCryptoSwapFactory,
+    SolidlyFactory,
+    AlgebraFactory,
+    LiquidityBookFactory
+);
 
 #[derive(Default)]
 pub struct NoopAMM;
@@ -198,6 +249,14 @@ impl AutomatedMarketMaker for NoopAMM {
         unreachable!()
     }
 
+    fn calculate_price_exact(
+        &self,
+        _base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        unreachable!()
+    }
+
     fn tokens(&self) -> Vec<Address> {
         unreachable!()
     }
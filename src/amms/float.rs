@@ -2,7 +2,7 @@ use alloy::primitives::U256;
 use rug::Float;
 
 use super::{
-    consts::{MPFR_T_PRECISION, U128_0X10000000000000000},
+    consts::{MPFR_T_PRECISION, Q128, U128_0X10000000000000000},
     error::AMMError,
 };
 
@@ -23,3 +23,28 @@ pub fn u256_to_float(num: U256) -> Result<Float, AMMError> {
     let parsed_value = Float::parse_radix(value_string, 10)?;
     Ok(Float::with_val(MPFR_T_PRECISION, parsed_value))
 }
+
+/// Converts a price to a Q128.128 fixed point number, rounding down. The result carries the
+/// precision of the float, `MPFR_T_PRECISION` bits.
+pub fn float_to_q128(num: Float) -> Result<U256, AMMError> {
+    let scaled = num * u256_to_float(Q128)?;
+    let integer = scaled
+        .floor()
+        .to_integer()
+        .filter(|integer| *integer >= 0)
+        .ok_or(AMMError::PriceOverflow)?;
+
+    U256::from_str_radix(&integer.to_string_radix(16), 16).map_err(|_| AMMError::PriceOverflow)
+}
+
+/// Converts an amount to an integer, rounding down and saturating at zero and `U256::MAX`.
+pub fn float_to_u256_saturating(num: Float) -> U256 {
+    if num.is_nan() || num <= 0 {
+        return U256::ZERO;
+    }
+
+    num.floor()
+        .to_integer()
+        .and_then(|integer| U256::from_str_radix(&integer.to_string_radix(16), 16).ok())
+        .unwrap_or(U256::MAX)
+}
@@ -0,0 +1,163 @@
+//! Port of the Liquidity Book v2.1 price, fee and bin helpers, rounding in the direction the pairs
+//! do.
+//!
+//! Reference:
+//! https://github.com/traderjoe-xyz/joe-v2/tree/main/src/libraries
+
+use alloy::primitives::U256;
+use uniswap_v3_math::full_math::{mul_div, mul_div_rounding_up};
+
+use super::{Bin, LiquidityBookError, StaticFeeParameters, VariableFeeParameters};
+use crate::amms::consts::{
+    LIQUIDITY_BOOK_BASIS_POINT_MAX, LIQUIDITY_BOOK_PRECISION, LIQUIDITY_BOOK_REAL_ID_SHIFT, Q128,
+    U256_1,
+};
+
+/// Returns the price of token X in token Y of the bin `id` as a 128.128 fixed point number.
+pub fn get_price_from_id(id: u32, bin_step: u16) -> Result<U256, LiquidityBookError> {
+    let base = Q128 + (U256::from(bin_step) << 128) / LIQUIDITY_BOOK_BASIS_POINT_MAX;
+    let exponent = id as i32 - LIQUIDITY_BOOK_REAL_ID_SHIFT;
+
+    pow(base, exponent)
+}
+
+/// Raises the 128.128 fixed point number `x` to the power `y`.
+fn pow(x: U256, y: i32) -> Result<U256, LiquidityBookError> {
+    if y == 0 {
+        return Ok(Q128);
+    }
+
+    let mut invert = y < 0;
+    let abs_y = y.unsigned_abs();
+
+    let mut result = U256::ZERO;
+    if abs_y < 0x100000 {
+        result = Q128;
+
+        // Work with a base below one so the squares do not overflow
+        let mut squared = x;
+        if x > U256::from(u128::MAX) {
+            squared = U256::MAX / squared;
+            invert = !invert;
+        }
+
+        for bit in 0..20 {
+            if abs_y & (1 << bit) != 0 {
+                result = result.wrapping_mul(squared) >> 128;
+            }
+            squared = squared.wrapping_mul(squared) >> 128;
+        }
+    }
+
+    if result.is_zero() {
+        return Err(LiquidityBookError::PowUnderflow);
+    }
+
+    Ok(if invert { U256::MAX / result } else { result })
+}
+
+/// Returns the total fee of a swap in 1e18 precision, the base fee plus the variable fee charged
+/// on the volatility accumulator.
+pub fn get_total_fee(
+    static_fee_parameters: &StaticFeeParameters,
+    volatility_accumulator: u32,
+    bin_step: u16,
+) -> U256 {
+    let base_fee = U256::from(static_fee_parameters.base_factor)
+        * U256::from(bin_step)
+        * U256::from(10_000_000_000_u64);
+
+    let variable_fee = if static_fee_parameters.variable_fee_control != 0 {
+        let prod = U256::from(volatility_accumulator) * U256::from(bin_step);
+        (prod * prod * U256::from(static_fee_parameters.variable_fee_control) + U256::from(99))
+            / U256::from(100)
+    } else {
+        U256::ZERO
+    };
+
+    base_fee + variable_fee
+}
+
+/// Returns the fee included in `amount_with_fees`.
+pub fn get_fee_amount_from(amount_with_fees: U256, total_fee: U256) -> U256 {
+    (amount_with_fees * total_fee + LIQUIDITY_BOOK_PRECISION - U256_1) / LIQUIDITY_BOOK_PRECISION
+}
+
+/// Returns the fee to add on top of `amount`.
+pub fn get_fee_amount(amount: U256, total_fee: U256) -> U256 {
+    let denominator = LIQUIDITY_BOOK_PRECISION - total_fee;
+    (amount * total_fee + denominator - U256_1) / denominator
+}
+
+/// The result of swapping through a single bin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BinSwap {
+    /// Amount taken from the amount left to swap, fees included
+    pub amount_in_with_fees: u128,
+    pub amount_out: u128,
+    pub fee: u128,
+}
+
+/// Swaps up to `amount_in_left` through `bin`, taking at most all of its out token reserve.
+pub fn get_amounts(
+    bin: &Bin,
+    static_fee_parameters: &StaticFeeParameters,
+    variable_fee_parameters: &VariableFeeParameters,
+    bin_step: u16,
+    swap_for_y: bool,
+    id: u32,
+    amount_in_left: u128,
+) -> Result<BinSwap, LiquidityBookError> {
+    let price = get_price_from_id(id, bin_step)?;
+    let bin_reserve_out = if swap_for_y {
+        bin.reserve_y
+    } else {
+        bin.reserve_x
+    };
+
+    let max_amount_in = if swap_for_y {
+        mul_div_rounding_up(U256::from(bin_reserve_out), Q128, price)
+    } else {
+        mul_div_rounding_up(U256::from(bin_reserve_out), price, Q128)
+    }
+    .map_err(|_| LiquidityBookError::AmountOverflow)?;
+    let max_amount_in = safe_128(max_amount_in)?;
+
+    let total_fee = get_total_fee(
+        static_fee_parameters,
+        variable_fee_parameters.volatility_accumulator,
+        bin_step,
+    );
+    let max_fee = safe_128(get_fee_amount(U256::from(max_amount_in), total_fee))?;
+    let max_amount_in = max_amount_in
+        .checked_add(max_fee)
+        .ok_or(LiquidityBookError::AmountOverflow)?;
+
+    if amount_in_left >= max_amount_in {
+        return Ok(BinSwap {
+            amount_in_with_fees: max_amount_in,
+            amount_out: bin_reserve_out,
+            fee: max_fee,
+        });
+    }
+
+    let fee = safe_128(get_fee_amount_from(U256::from(amount_in_left), total_fee))?;
+    let amount_in = U256::from(amount_in_left - fee);
+
+    let amount_out = if swap_for_y {
+        mul_div(amount_in, price, Q128)
+    } else {
+        mul_div(amount_in, Q128, price)
+    }
+    .map_err(|_| LiquidityBookError::AmountOverflow)?;
+
+    Ok(BinSwap {
+        amount_in_with_fees: amount_in_left,
+        amount_out: safe_128(amount_out)?.min(bin_reserve_out),
+        fee,
+    })
+}
+
+fn safe_128(x: U256) -> Result<u128, LiquidityBookError> {
+    u128::try_from(x).map_err(|_| LiquidityBookError::AmountOverflow)
+}
@@ -0,0 +1,1341 @@
+pub mod bin_math;
+
+use std::{collections::BTreeMap, future::Future};
+
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, Bytes, B256, U256},
+    providers::Provider,
+    rpc::types::{Filter, FilterSet, Log},
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use rug::float::Round;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::info;
+
+use super::{
+    amm::{check_amount_out, AutomatedMarketMaker, SwapOutcome, AMM},
+    consts::{LIQUIDITY_BOOK_BASIS_POINT_MAX, LIQUIDITY_BOOK_BINS_PER_SIDE, Q128, U256_10},
+    error::AMMError,
+    factory::{AutomatedMarketMakerFactory, DiscoverySync},
+    float::u256_to_float,
+    get_block_timestamp,
+    price::{adjust_decimals, invert_q128},
+    Token,
+};
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract ILBFactory {
+        event LBPairCreated(
+            address indexed tokenX,
+            address indexed tokenY,
+            uint256 indexed binStep,
+            address LBPair,
+            uint256 pid
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract ILBPair {
+        event Swap(
+            address indexed sender,
+            address indexed to,
+            uint24 id,
+            bytes32 amountsIn,
+            bytes32 amountsOut,
+            uint24 volatilityAccumulator,
+            bytes32 totalFees,
+            bytes32 protocolFees
+        );
+
+        function swap(bool swapForY, address to) external returns (bytes32 amountsOut);
+
+        event DepositedToBins(address indexed sender, address indexed to, uint256[] ids, bytes32[] amounts);
+
+        event WithdrawnFromBins(address indexed sender, address indexed to, uint256[] ids, bytes32[] amounts);
+
+        event CompositionFees(address indexed sender, uint24 id, bytes32 totalFees, bytes32 protocolFees);
+
+        event FlashLoan(
+            address indexed sender,
+            address indexed receiver,
+            uint24 activeId,
+            bytes32 amounts,
+            bytes32 totalFees,
+            bytes32 protocolFees
+        );
+
+        event StaticFeeParametersSet(
+            address indexed sender,
+            uint16 baseFactor,
+            uint16 filterPeriod,
+            uint16 decayPeriod,
+            uint16 reductionFactor,
+            uint24 variableFeeControl,
+            uint16 protocolShare,
+            uint24 maxVolatilityAccumulator
+        );
+
+        event ForcedDecay(address indexed sender, uint24 idReference, uint24 volatilityReference);
+    }
+
+    #[derive(Debug)]
+    struct LiquidityBookPairData {
+        address tokenX;
+        uint8 tokenXDecimals;
+        address tokenY;
+        uint8 tokenYDecimals;
+        uint16 binStep;
+        uint24 activeId;
+        uint16 baseFactor;
+        uint16 filterPeriod;
+        uint16 decayPeriod;
+        uint16 reductionFactor;
+        uint24 variableFeeControl;
+        uint16 protocolShare;
+        uint24 maxVolatilityAccumulator;
+        uint24 volatilityAccumulator;
+        uint24 volatilityReference;
+        uint24 idReference;
+        uint40 timeOfLastUpdate;
+        uint24[] binIds;
+        uint128[] reserveX;
+        uint128[] reserveY;
+    }
+}
+
+sol! {
+    #[sol(rpc)]
+    GetLiquidityBookPairDataBatchRequest,
+    "src/amms/abi/GetLiquidityBookPairDataBatchRequest.json",
+}
+
+#[derive(Error, Debug)]
+pub enum LiquidityBookError {
+    #[error("Error initializing Liquidity Book pair")]
+    InitializationError,
+    #[error("Token not in pair")]
+    TokenNotInPool,
+    #[error("Not enough liquidity in the synced bins")]
+    OutOfLiquidity,
+    #[error("Amount overflow")]
+    AmountOverflow,
+    #[error("Price underflow")]
+    PowUnderflow,
+    #[error("Reserves of bin {0} underflow")]
+    ReserveUnderflow(u32),
+}
+
+/// The reserves of a single bin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bin {
+    pub reserve_x: u128,
+    pub reserve_y: u128,
+}
+
+impl Bin {
+    pub fn is_empty(&self) -> bool {
+        self.reserve_x == 0 && self.reserve_y == 0
+    }
+
+    fn add(&mut self, (x, y): (u128, u128)) -> Result<(), LiquidityBookError> {
+        self.reserve_x = self
+            .reserve_x
+            .checked_add(x)
+            .ok_or(LiquidityBookError::AmountOverflow)?;
+        self.reserve_y = self
+            .reserve_y
+            .checked_add(y)
+            .ok_or(LiquidityBookError::AmountOverflow)?;
+        Ok(())
+    }
+
+    fn sub(&mut self, id: u32, (x, y): (u128, u128)) -> Result<(), LiquidityBookError> {
+        self.reserve_x = self
+            .reserve_x
+            .checked_sub(x)
+            .ok_or(LiquidityBookError::ReserveUnderflow(id))?;
+        self.reserve_y = self
+            .reserve_y
+            .checked_sub(y)
+            .ok_or(LiquidityBookError::ReserveUnderflow(id))?;
+        Ok(())
+    }
+}
+
+/// Fee parameters set by the factory owner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticFeeParameters {
+    pub base_factor: u16,
+    pub filter_period: u16,
+    pub decay_period: u16,
+    pub reduction_factor: u16,
+    pub variable_fee_control: u32,
+    pub protocol_share: u16,
+    pub max_volatility_accumulator: u32,
+}
+
+/// The volatility accumulator and its references, which drive the variable fee.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariableFeeParameters {
+    pub volatility_accumulator: u32,
+    pub volatility_reference: u32,
+    pub id_reference: u32,
+    pub time_of_last_update: u64,
+}
+
+impl VariableFeeParameters {
+    /// Moves the references to the active bin once the filter period has passed since the last
+    /// update, decaying the volatility reference or resetting it after the decay period.
+    pub fn update_references(
+        &mut self,
+        static_fee_parameters: &StaticFeeParameters,
+        active_id: u32,
+        timestamp: u64,
+    ) {
+        let dt = timestamp.saturating_sub(self.time_of_last_update);
+
+        if dt >= static_fee_parameters.filter_period as u64 {
+            self.id_reference = active_id;
+            self.volatility_reference = if dt < static_fee_parameters.decay_period as u64 {
+                (self.volatility_accumulator as u64 * static_fee_parameters.reduction_factor as u64
+                    / LIQUIDITY_BOOK_BASIS_POINT_MAX.to::<u64>()) as u32
+            } else {
+                0
+            };
+        }
+
+        self.time_of_last_update = timestamp;
+    }
+
+    /// Accumulates the distance between `active_id` and the reference bin, capped at the max
+    /// volatility accumulator.
+    pub fn update_volatility_accumulator(
+        &mut self,
+        static_fee_parameters: &StaticFeeParameters,
+        active_id: u32,
+    ) {
+        let delta_id = active_id.abs_diff(self.id_reference) as u64;
+        let volatility_accumulator = self.volatility_reference as u64
+            + delta_id * LIQUIDITY_BOOK_BASIS_POINT_MAX.to::<u64>();
+
+        self.volatility_accumulator = volatility_accumulator
+            .min(static_fee_parameters.max_volatility_accumulator as u64)
+            as u32;
+    }
+}
+
+/// A Trader Joe Liquidity Book v2.1 pair, holding liquidity in discrete bins of constant price.
+///
+/// Only the non-empty bins within `LIQUIDITY_BOOK_BINS_PER_SIDE` of the active bin are fetched when
+/// the pair is synced. Logs touching bins outside of them are not applied to the reserves, and swaps
+/// that would walk past them fail with `OutOfLiquidity`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LiquidityBookPair {
+    pub address: Address,
+    /// Token X of the pair
+    pub token_a: Token,
+    /// Token Y of the pair
+    pub token_b: Token,
+    pub bin_step: u16,
+    pub active_id: u32,
+    /// Non-empty bins by id
+    pub bins: BTreeMap<u32, Bin>,
+    /// Lowest bin id whose reserves are synced
+    #[serde(default)]
+    pub lower_bin_id: u32,
+    /// Highest bin id whose reserves are synced
+    #[serde(default = "max_bin_id")]
+    pub upper_bin_id: u32,
+    pub static_fee_parameters: StaticFeeParameters,
+    pub variable_fee_parameters: VariableFeeParameters,
+    /// Timestamp of the block the pair is synced to, at which the variable fee is evaluated
+    #[serde(default)]
+    pub block_timestamp: u64,
+    /// Protocol share of the composition fees of the deposit being synced, which is logged before
+    /// the deposit and taken out of the amounts it adds to the active bin
+    #[serde(skip)]
+    composition_fees: Option<(u32, (u128, u128))>,
+}
+
+/// The state of a pair after a simulated swap.
+#[derive(Debug, Clone, Default)]
+pub struct LiquidityBookSwap {
+    pub amount_in_left: u128,
+    pub amount_out: u128,
+    /// Total fee paid in the input token
+    pub fee: u128,
+    /// Part of the fee taken by the protocol
+    pub protocol_fee: u128,
+    pub active_id: u32,
+    pub variable_fee_parameters: VariableFeeParameters,
+    /// Bins whose reserves changed during the swap
+    pub bins: Vec<(u32, Bin)>,
+}
+
+impl AutomatedMarketMaker for LiquidityBookPair {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        vec![
+            ILBPair::Swap::SIGNATURE_HASH,
+            ILBPair::DepositedToBins::SIGNATURE_HASH,
+            ILBPair::WithdrawnFromBins::SIGNATURE_HASH,
+            ILBPair::CompositionFees::SIGNATURE_HASH,
+            ILBPair::FlashLoan::SIGNATURE_HASH,
+            ILBPair::StaticFeeParametersSet::SIGNATURE_HASH,
+            ILBPair::ForcedDecay::SIGNATURE_HASH,
+        ]
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        self.block_timestamp = log.block_timestamp.unwrap_or(self.block_timestamp);
+
+        let event_signature = log.topics()[0];
+        match event_signature {
+            ILBPair::Swap::SIGNATURE_HASH => {
+                let swap_event = ILBPair::Swap::decode_log(log.as_ref())?;
+
+                // A swap emits one event per bin, the references only move on the first of them
+                self.variable_fee_parameters.update_references(
+                    &self.static_fee_parameters,
+                    self.active_id,
+                    self.block_timestamp,
+                );
+
+                self.active_id = swap_event.id.to();
+                self.variable_fee_parameters.volatility_accumulator =
+                    swap_event.volatilityAccumulator.to();
+
+                self.update_bin(self.active_id, |bin, id| {
+                    bin.add(decode_amounts(swap_event.amountsIn))?;
+                    bin.sub(id, decode_amounts(swap_event.protocolFees))?;
+                    bin.sub(id, decode_amounts(swap_event.amountsOut))
+                })?;
+
+                info!(
+                    target = "amms::liquidity_book::sync",
+                    address = ?self.address,
+                    active_id = ?self.active_id,
+                    volatility_accumulator = ?self.variable_fee_parameters.volatility_accumulator,
+                    "Swap"
+                );
+            }
+            ILBPair::DepositedToBins::SIGNATURE_HASH => {
+                let deposit_event = ILBPair::DepositedToBins::decode_log(log.as_ref())?;
+
+                let composition_fees = self.composition_fees.take();
+                for (id, amounts) in deposit_event.ids.iter().zip(deposit_event.amounts.iter()) {
+                    let id = id.to();
+                    self.update_bin(id, |bin, id| {
+                        bin.add(decode_amounts(*amounts))?;
+                        match composition_fees {
+                            Some((fees_id, protocol_fees)) if fees_id == id => {
+                                bin.sub(id, protocol_fees)
+                            }
+                            _ => Ok(()),
+                        }
+                    })?;
+                }
+
+                info!(
+                    target = "amms::liquidity_book::sync",
+                    address = ?self.address,
+                    ids = ?deposit_event.ids,
+                    "DepositedToBins"
+                );
+            }
+            ILBPair::WithdrawnFromBins::SIGNATURE_HASH => {
+                let withdraw_event = ILBPair::WithdrawnFromBins::decode_log(log.as_ref())?;
+
+                for (id, amounts) in withdraw_event.ids.iter().zip(withdraw_event.amounts.iter()) {
+                    self.update_bin(id.to(), |bin, id| bin.sub(id, decode_amounts(*amounts)))?;
+                }
+
+                info!(
+                    target = "amms::liquidity_book::sync",
+                    address = ?self.address,
+                    ids = ?withdraw_event.ids,
+                    "WithdrawnFromBins"
+                );
+            }
+            ILBPair::CompositionFees::SIGNATURE_HASH => {
+                let composition_fees_event = ILBPair::CompositionFees::decode_log(log.as_ref())?;
+
+                // The protocol share of the fee is not added to the bin by the following deposit
+                self.composition_fees = Some((
+                    composition_fees_event.id.to(),
+                    decode_amounts(composition_fees_event.protocolFees),
+                ));
+            }
+            ILBPair::FlashLoan::SIGNATURE_HASH => {
+                let flash_loan_event = ILBPair::FlashLoan::decode_log(log.as_ref())?;
+
+                // Flash loan fees are paid to the active bin
+                self.update_bin(flash_loan_event.activeId.to(), |bin, id| {
+                    bin.add(decode_amounts(flash_loan_event.totalFees))?;
+                    bin.sub(id, decode_amounts(flash_loan_event.protocolFees))
+                })?;
+            }
+            ILBPair::StaticFeeParametersSet::SIGNATURE_HASH => {
+                let parameters_event = ILBPair::StaticFeeParametersSet::decode_log(log.as_ref())?;
+
+                self.static_fee_parameters = StaticFeeParameters {
+                    base_factor: parameters_event.baseFactor,
+                    filter_period: parameters_event.filterPeriod,
+                    decay_period: parameters_event.decayPeriod,
+                    reduction_factor: parameters_event.reductionFactor,
+                    variable_fee_control: parameters_event.variableFeeControl.to(),
+                    protocol_share: parameters_event.protocolShare,
+                    max_volatility_accumulator: parameters_event.maxVolatilityAccumulator.to(),
+                };
+
+                info!(
+                    target = "amms::liquidity_book::sync",
+                    address = ?self.address,
+                    static_fee_parameters = ?self.static_fee_parameters,
+                    "StaticFeeParametersSet"
+                );
+            }
+            ILBPair::ForcedDecay::SIGNATURE_HASH => {
+                let decay_event = ILBPair::ForcedDecay::decode_log(log.as_ref())?;
+
+                self.variable_fee_parameters.id_reference = decay_event.idReference.to();
+                self.variable_fee_parameters.volatility_reference =
+                    decay_event.volatilityReference.to();
+            }
+            _ => {
+                return Err(AMMError::UnrecognizedEventSignature(event_signature));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<Address> {
+        vec![self.token_a.address, self.token_b.address]
+    }
+
+    /// Returns the price of the active bin, excluding fees.
+    fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
+        self.price_at(self.active_id, base_token)
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        // Price of token X in token Y, in the smallest units of each token
+        let price = bin_math::get_price_from_id(self.active_id, self.bin_step)?;
+
+        if self.swap_for_y(base_token)? {
+            adjust_decimals(price, self.token_a.decimals, self.token_b.decimals)
+        } else {
+            adjust_decimals(
+                invert_q128(price)?,
+                self.token_b.decimals,
+                self.token_a.decimals,
+            )
+        }
+    }
+
+    fn simulate_swap_outcome(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let swap_for_y = self.swap_for_y(base_token)?;
+        let (token_in, token_out) = self.swap_tokens(swap_for_y);
+        let amount_received = u128::try_from(token_in.after_sell_tax(amount_in))
+            .map_err(|_| LiquidityBookError::AmountOverflow)?;
+
+        let swap = self.swap_partial(amount_received, swap_for_y, self.block_timestamp)?;
+
+        let outcome = SwapOutcome {
+            amount_in: U256::from(amount_received - swap.amount_in_left),
+            amount_out: U256::from(swap.amount_out),
+            fee: Some(U256::from(swap.fee)),
+            protocol_fee: Some(U256::from(swap.protocol_fee)),
+            price_before: self.calculate_price(base_token, quote_token)?,
+            price_after: self.price_at(swap.active_id, base_token)?,
+            ticks_crossed: swap.bins.len().saturating_sub(1) as u32,
+            liquidity_exhausted: swap.amount_in_left != 0,
+        };
+
+        Ok(outcome.with_taxes(amount_in, token_in, token_out))
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let swap_for_y = self.swap_for_y(base_token)?;
+        let (token_in, token_out) = self.swap_tokens(swap_for_y);
+        let amount_received = u128::try_from(token_in.after_sell_tax(amount_in))
+            .map_err(|_| LiquidityBookError::AmountOverflow)?;
+
+        let swap = self.swap(amount_received, swap_for_y, self.block_timestamp)?;
+
+        Ok(token_out.after_buy_tax(U256::from(swap.amount_out)))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let swap_for_y = self.swap_for_y(base_token)?;
+        let (token_in, token_out) = self.swap_tokens(swap_for_y);
+        let amount_received = u128::try_from(token_in.after_sell_tax(amount_in))
+            .map_err(|_| LiquidityBookError::AmountOverflow)?;
+
+        let swap = self.swap(amount_received, swap_for_y, self.block_timestamp)?;
+        let amount_out = token_out.after_buy_tax(U256::from(swap.amount_out));
+
+        // Update the pair state
+        self.active_id = swap.active_id;
+        self.variable_fee_parameters = swap.variable_fee_parameters;
+        for (id, bin) in swap.bins {
+            self.bins.insert(id, bin);
+            self.remove_if_empty(id);
+        }
+
+        Ok(amount_out)
+    }
+
+    /// Encodes `swap` on the pair, which swaps the tokens transferred to it beforehand.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        let swap_for_y = self.swap_for_y(base_token)?;
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        Ok(ILBPair::swapCall {
+            swapForY: swap_for_y,
+            to: recipient,
+        }
+        .abi_encode()
+        .into())
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        LiquidityBookPair::get_pair_data(vec![self.address], block_number, provider)
+            .await?
+            .pop()
+            .ok_or(LiquidityBookError::InitializationError.into())
+    }
+}
+
+impl LiquidityBookPair {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            ..Default::default()
+        }
+    }
+
+    /// Fetches the state and the bins around the active bin of `pairs` at `block_number`, skipping
+    /// addresses that are not Liquidity Book pairs.
+    pub async fn get_pair_data<N, P>(
+        pairs: Vec<Address>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Self>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let block_timestamp = get_block_timestamp(block_number, provider.clone()).await?;
+        let deployer = GetLiquidityBookPairDataBatchRequest::deploy_builder(
+            provider,
+            pairs.clone(),
+            U256::from(LIQUIDITY_BOOK_BINS_PER_SIDE),
+        );
+        let res = deployer.block(block_number).call_raw().await?;
+
+        let data = <Vec<LiquidityBookPairData> as SolValue>::abi_decode(&res)?;
+
+        Ok(pairs
+            .into_iter()
+            .zip(data)
+            .filter(|(_, pair_data)| !pair_data.tokenX.is_zero())
+            .map(|(address, pair_data)| {
+                let active_id = pair_data.activeId.to();
+                let (lower_bin_id, upper_bin_id) =
+                    synced_bin_ids(active_id, pair_data.binIds.iter().map(|id| id.to()));
+                let bins = pair_data
+                    .binIds
+                    .iter()
+                    .zip(pair_data.reserveX)
+                    .zip(pair_data.reserveY)
+                    .map(|((id, reserve_x), reserve_y)| {
+                        (
+                            id.to(),
+                            Bin {
+                                reserve_x,
+                                reserve_y,
+                            },
+                        )
+                    })
+                    .filter(|(_, bin)| !bin.is_empty())
+                    .collect();
+
+                LiquidityBookPair {
+                    address,
+                    token_a: Token::new_with_decimals(pair_data.tokenX, pair_data.tokenXDecimals),
+                    token_b: Token::new_with_decimals(pair_data.tokenY, pair_data.tokenYDecimals),
+                    bin_step: pair_data.binStep,
+                    active_id,
+                    bins,
+                    lower_bin_id,
+                    upper_bin_id,
+                    static_fee_parameters: StaticFeeParameters {
+                        base_factor: pair_data.baseFactor,
+                        filter_period: pair_data.filterPeriod,
+                        decay_period: pair_data.decayPeriod,
+                        reduction_factor: pair_data.reductionFactor,
+                        variable_fee_control: pair_data.variableFeeControl.to(),
+                        protocol_share: pair_data.protocolShare,
+                        max_volatility_accumulator: pair_data.maxVolatilityAccumulator.to(),
+                    },
+                    variable_fee_parameters: VariableFeeParameters {
+                        volatility_accumulator: pair_data.volatilityAccumulator.to(),
+                        volatility_reference: pair_data.volatilityReference.to(),
+                        id_reference: pair_data.idReference.to(),
+                        time_of_last_update: pair_data.timeOfLastUpdate.to(),
+                    },
+                    block_timestamp,
+                    composition_fees: None,
+                }
+            })
+            .collect())
+    }
+
+    /// Calculates the price of `base_token` in terms of the other token of the pair in bin `id`.
+    pub fn price_at(&self, id: u32, base_token: Address) -> Result<f64, AMMError> {
+        let swap_for_y = self.swap_for_y(base_token)?;
+
+        // Price of token X in token Y, in whole tokens
+        let price = u256_to_float(bin_math::get_price_from_id(id, self.bin_step)?)?
+            / u256_to_float(Q128)?
+            * u256_to_float(U256_10.pow(U256::from(self.token_a.decimals)))?
+            / u256_to_float(U256_10.pow(U256::from(self.token_b.decimals)))?;
+
+        let price = if swap_for_y { price } else { 1 / price };
+        Ok(price.to_f64_round(Round::Nearest))
+    }
+
+    /// Swaps `amount_in` of token X for token Y when `swap_for_y` is true, at `timestamp`, walking
+    /// from the active bin to the next non-empty bin until the amount in is exhausted.
+    pub fn swap(
+        &self,
+        amount_in: u128,
+        swap_for_y: bool,
+        timestamp: u64,
+    ) -> Result<LiquidityBookSwap, LiquidityBookError> {
+        let swap = self.swap_partial(amount_in, swap_for_y, timestamp)?;
+        if swap.amount_in_left != 0 {
+            return Err(LiquidityBookError::OutOfLiquidity);
+        }
+
+        Ok(swap)
+    }
+
+    /// Like [`Self::swap`], but stops in the last non-empty bin when the pair runs out of
+    /// liquidity, leaving the unswapped amount in `amount_in_left`.
+    pub fn swap_partial(
+        &self,
+        amount_in: u128,
+        swap_for_y: bool,
+        timestamp: u64,
+    ) -> Result<LiquidityBookSwap, LiquidityBookError> {
+        let mut swap = LiquidityBookSwap {
+            amount_in_left: amount_in,
+            active_id: self.active_id,
+            variable_fee_parameters: self.variable_fee_parameters,
+            ..Default::default()
+        };
+
+        swap.variable_fee_parameters.update_references(
+            &self.static_fee_parameters,
+            swap.active_id,
+            timestamp,
+        );
+
+        while swap.amount_in_left != 0 && self.is_synced(swap.active_id) {
+            let mut bin = self.bins.get(&swap.active_id).copied().unwrap_or_default();
+            let bin_reserve_out = if swap_for_y {
+                bin.reserve_y
+            } else {
+                bin.reserve_x
+            };
+
+            if bin_reserve_out != 0 {
+                swap.variable_fee_parameters
+                    .update_volatility_accumulator(&self.static_fee_parameters, swap.active_id);
+
+                let bin_swap = bin_math::get_amounts(
+                    &bin,
+                    &self.static_fee_parameters,
+                    &swap.variable_fee_parameters,
+                    self.bin_step,
+                    swap_for_y,
+                    swap.active_id,
+                    swap.amount_in_left,
+                )?;
+
+                if bin_swap.amount_in_with_fees > 0 {
+                    swap.amount_in_left -= bin_swap.amount_in_with_fees;
+                    swap.amount_out += bin_swap.amount_out;
+                    swap.fee += bin_swap.fee;
+
+                    // The protocol share of the fee is taken out of the bin
+                    let protocol_fee = (U256::from(bin_swap.fee)
+                        * U256::from(self.static_fee_parameters.protocol_share)
+                        / LIQUIDITY_BOOK_BASIS_POINT_MAX)
+                        .to::<u128>();
+                    let amount_in_to_bin = bin_swap.amount_in_with_fees - protocol_fee;
+                    swap.protocol_fee += protocol_fee;
+
+                    if swap_for_y {
+                        bin.add((amount_in_to_bin, 0))?;
+                        bin.sub(swap.active_id, (0, bin_swap.amount_out))?;
+                    } else {
+                        bin.add((0, amount_in_to_bin))?;
+                        bin.sub(swap.active_id, (bin_swap.amount_out, 0))?;
+                    }
+                    swap.bins.push((swap.active_id, bin));
+                }
+            }
+
+            if swap.amount_in_left != 0 {
+                match self.next_non_empty_bin(swap_for_y, swap.active_id) {
+                    Some(id) => swap.active_id = id,
+                    None => break,
+                }
+            }
+        }
+
+        Ok(swap)
+    }
+
+    /// Returns the next non-empty bin in the direction of the swap, bins below the active bin
+    /// holding token Y and bins above it holding token X.
+    pub fn next_non_empty_bin(&self, swap_for_y: bool, id: u32) -> Option<u32> {
+        if swap_for_y {
+            self.bins.range(..id).next_back().map(|(id, _)| *id)
+        } else {
+            self.bins.range(id + 1..).next().map(|(id, _)| *id)
+        }
+    }
+
+    /// Returns true if the reserves of bin `id` are synced.
+    pub fn is_synced(&self, id: u32) -> bool {
+        (self.lower_bin_id..=self.upper_bin_id).contains(&id)
+    }
+
+    /// Applies `update` to the reserves of bin `id`, unless they are not synced.
+    fn update_bin(
+        &mut self,
+        id: u32,
+        update: impl FnOnce(&mut Bin, u32) -> Result<(), LiquidityBookError>,
+    ) -> Result<(), LiquidityBookError> {
+        if !self.is_synced(id) {
+            return Ok(());
+        }
+
+        let mut bin = self.bins.get(&id).copied().unwrap_or_default();
+        update(&mut bin, id)?;
+        self.bins.insert(id, bin);
+        self.remove_if_empty(id);
+
+        Ok(())
+    }
+
+    fn remove_if_empty(&mut self, id: u32) {
+        if self.bins.get(&id).is_some_and(Bin::is_empty) {
+            self.bins.remove(&id);
+        }
+    }
+
+    fn swap_for_y(&self, base_token: Address) -> Result<bool, LiquidityBookError> {
+        if base_token == self.token_a.address {
+            Ok(true)
+        } else if base_token == self.token_b.address {
+            Ok(false)
+        } else {
+            Err(LiquidityBookError::TokenNotInPool)
+        }
+    }
+
+    /// Returns the token sold into and the token bought from the pair.
+    fn swap_tokens(&self, swap_for_y: bool) -> (&Token, &Token) {
+        if swap_for_y {
+            (&self.token_a, &self.token_b)
+        } else {
+            (&self.token_b, &self.token_a)
+        }
+    }
+}
+
+/// Splits packed amounts into the token X amount, held in the lower 128 bits, and the token Y
+/// amount.
+fn decode_amounts(amounts: B256) -> (u128, u128) {
+    let amounts = U256::from_be_bytes(amounts.0);
+    (
+        (amounts & U256::from(u128::MAX)).to::<u128>(),
+        (amounts >> 128_usize).to::<u128>(),
+    )
+}
+
+/// Returns the bounds of the bins synced with the fetched `bin_ids`, which span every bin up to the
+/// last one fetched on each side of the active bin, or every bin on that side if fewer than
+/// `LIQUIDITY_BOOK_BINS_PER_SIDE` are non-empty.
+fn synced_bin_ids(active_id: u32, bin_ids: impl Iterator<Item = u32>) -> (u32, u32) {
+    let (below, above): (Vec<u32>, Vec<u32>) = bin_ids
+        .filter(|id| *id != active_id)
+        .partition(|id| *id < active_id);
+
+    let lower_bin_id = match below.iter().min() {
+        Some(id) if below.len() >= LIQUIDITY_BOOK_BINS_PER_SIDE => *id,
+        _ => 0,
+    };
+    let upper_bin_id = match above.iter().max() {
+        Some(id) if above.len() >= LIQUIDITY_BOOK_BINS_PER_SIDE => *id,
+        _ => max_bin_id(),
+    };
+
+    (lower_bin_id, upper_bin_id)
+}
+
+fn max_bin_id() -> u32 {
+    u32::MAX
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LiquidityBookFactory {
+    pub address: Address,
+    pub creation_block: u64,
+}
+
+impl LiquidityBookFactory {
+    pub fn new(address: Address, creation_block: u64) -> Self {
+        Self {
+            address,
+            creation_block,
+        }
+    }
+
+    pub async fn get_all_pools<N, P>(
+        &self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let disc_filter = Filter::new()
+            .event_signature(FilterSet::from(vec![self.pool_creation_event()]))
+            .address(vec![self.address()]);
+
+        let sync_provider = provider.clone();
+        let mut futures = FuturesUnordered::new();
+
+        let sync_step = 100_000;
+        let mut latest_block = self.creation_block;
+        while latest_block < block_number.as_u64().unwrap_or_default() {
+            let mut block_filter = disc_filter.clone();
+            let from_block = latest_block;
+            let to_block = (from_block + sync_step).min(block_number.as_u64().unwrap_or_default());
+
+            block_filter = block_filter.from_block(from_block);
+            block_filter = block_filter.to_block(to_block);
+
+            let sync_provider = sync_provider.clone();
+
+            futures.push(async move { sync_provider.get_logs(&block_filter).await });
+
+            latest_block = to_block + 1;
+        }
+
+        let mut pools = vec![];
+        while let Some(res) = futures.next().await {
+            let logs = res?;
+
+            for log in logs {
+                pools.push(self.create_pool(log)?);
+            }
+        }
+
+        Ok(pools)
+    }
+
+    pub async fn sync_all_pools<N, P>(
+        amms: Vec<AMM>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        // Every pair walks up to 2 * LIQUIDITY_BOOK_BINS_PER_SIDE bins, so keep the groups small
+        let step = 10;
+        let pairs = amms.iter().map(|amm| amm.address()).collect::<Vec<_>>();
+
+        let mut futures_unordered = pairs
+            .chunks(step)
+            .map(|group| {
+                LiquidityBookPair::get_pair_data(group.to_vec(), block_number, provider.clone())
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut amms = Vec::new();
+        while let Some(res) = futures_unordered.next().await {
+            amms.extend(
+                res?.into_iter()
+                    .filter(|pair| {
+                        !pair.bins.is_empty()
+                            && pair.token_a.decimals > 0
+                            && pair.token_b.decimals > 0
+                    })
+                    .map(AMM::LiquidityBookPair),
+            );
+        }
+
+        Ok(amms)
+    }
+}
+
+impl AutomatedMarketMakerFactory for LiquidityBookFactory {
+    type PoolVariant = LiquidityBookPair;
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn pool_creation_event(&self) -> B256 {
+        ILBFactory::LBPairCreated::SIGNATURE_HASH
+    }
+
+    fn create_pool(&self, log: Log) -> Result<AMM, AMMError> {
+        let event = ILBFactory::LBPairCreated::decode_log(&log.inner)?;
+
+        Ok(AMM::LiquidityBookPair(LiquidityBookPair {
+            address: event.LBPair,
+            token_a: event.tokenX.into(),
+            token_b: event.tokenY.into(),
+            bin_step: event.binStep.saturating_to(),
+            ..Default::default()
+        }))
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+}
+
+impl DiscoverySync for LiquidityBookFactory {
+    fn discover<N, P>(
+        &self,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::liquidity_book::discover",
+            address = ?self.address,
+            "Discovering all pools"
+        );
+
+        self.get_all_pools(to_block, provider)
+    }
+
+    fn sync<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::liquidity_book::sync",
+            address = ?self.address,
+            "Syncing all pools"
+        );
+
+        LiquidityBookFactory::sync_all_pools(amms, to_block, provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, aliases::U24, LogData};
+
+    const ACTIVE_ID: u32 = 8376278;
+
+    fn wavax_usdc_pair() -> LiquidityBookPair {
+        let e18 = 10_u128.pow(18);
+        let e6 = 10_u128.pow(6);
+
+        LiquidityBookPair {
+            address: address!("D446eb1660F766d533BeCeEf890Df7A69d26f7d1"),
+            token_a: Token::new_with_decimals(
+                address!("B31f66AA3C1e785363F0875A1B74E27b85FD66c7"),
+                18,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("B97EF9Ef8734C71904D8002F8b6Bc66Dd9c48a6E"),
+                6,
+            ),
+            bin_step: 20,
+            active_id: ACTIVE_ID,
+            bins: BTreeMap::from([
+                (ACTIVE_ID - 2, Bin::new(0, 50_000 * e6)),
+                (ACTIVE_ID - 1, Bin::new(0, 40_000 * e6)),
+                (ACTIVE_ID, Bin::new(100 * e18, 1_000 * e6)),
+                (ACTIVE_ID + 1, Bin::new(300 * e18, 0)),
+                (ACTIVE_ID + 2, Bin::new(200 * e18, 0)),
+            ]),
+            lower_bin_id: ACTIVE_ID - 10,
+            upper_bin_id: ACTIVE_ID + 10,
+            static_fee_parameters: StaticFeeParameters {
+                base_factor: 5000,
+                filter_period: 30,
+                decay_period: 600,
+                reduction_factor: 5000,
+                variable_fee_control: 40000,
+                protocol_share: 1000,
+                max_volatility_accumulator: 350000,
+            },
+            variable_fee_parameters: VariableFeeParameters {
+                id_reference: ACTIVE_ID,
+                time_of_last_update: 1_700_000_000,
+                ..Default::default()
+            },
+            // Past the decay period, so the references are reset before a swap
+            block_timestamp: 1_700_001_000,
+            composition_fees: None,
+        }
+    }
+
+    fn pair_log(pair: Address, data: LogData) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address: pair,
+                data,
+            },
+            block_timestamp: Some(1_700_000_010),
+            ..Default::default()
+        }
+    }
+
+    fn encode_amounts(x: u128, y: u128) -> B256 {
+        B256::from((U256::from(y) << 128) | U256::from(x))
+    }
+
+    impl Bin {
+        fn new(reserve_x: u128, reserve_y: u128) -> Self {
+            Self {
+                reserve_x,
+                reserve_y,
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_price_from_id() -> eyre::Result<()> {
+        assert_eq!(bin_math::get_price_from_id(1 << 23, 20)?, Q128);
+
+        let pair = wavax_usdc_pair();
+        let price = pair.calculate_price(pair.token_a.address, Address::default())?;
+        assert!((price - 19.998320302854044).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_within_bin() -> eyre::Result<()> {
+        let mut pair = wavax_usdc_pair();
+
+        let amount_out = pair.simulate_swap_mut(
+            pair.token_a.address,
+            pair.token_b.address,
+            U256::from(10_u128.pow(19)),
+        )?;
+
+        assert_eq!(amount_out, U256::from(199_783_219_u128));
+        assert_eq!(pair.active_id, ACTIVE_ID);
+        assert_eq!(
+            pair.bins[&ACTIVE_ID],
+            Bin::new(109_999_000_000_000_000_000, 800_216_781)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_across_bins() -> eyre::Result<()> {
+        let mut pair = wavax_usdc_pair();
+
+        let amount_out = pair.simulate_swap_mut(
+            pair.token_a.address,
+            pair.token_b.address,
+            U256::from(10_u128.pow(20)),
+        )?;
+
+        assert_eq!(amount_out, U256::from(1_995_824_567_u128));
+        assert_eq!(pair.active_id, ACTIVE_ID - 1);
+        assert_eq!(pair.variable_fee_parameters.volatility_accumulator, 10000);
+        assert_eq!(
+            pair.bins[&ACTIVE_ID],
+            Bin::new(150_049_248_424_031_803_954, 0)
+        );
+        assert_eq!(
+            pair.bins[&(ACTIVE_ID - 1)],
+            Bin::new(49_940_671_662_774_355_114, 39_004_175_433)
+        );
+
+        let amount_out = pair.simulate_swap(
+            pair.token_b.address,
+            pair.token_a.address,
+            U256::from(10_000_000_000_u128),
+        )?;
+        assert!(!amount_out.is_zero());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_outcome() -> eyre::Result<()> {
+        let pair = wavax_usdc_pair();
+        let (wavax, usdc) = (pair.token_a.address, pair.token_b.address);
+
+        let outcome = pair.simulate_swap_outcome(wavax, usdc, U256::from(10_u128.pow(20)))?;
+        assert_eq!(outcome.amount_in, U256::from(10_u128.pow(20)));
+        assert_eq!(outcome.amount_out, U256::from(1_995_824_567_u128));
+        assert!(outcome.fee.is_some_and(|fee| !fee.is_zero()));
+        assert_eq!(outcome.ticks_crossed, 1);
+        assert!(!outcome.liquidity_exhausted);
+        assert!(outcome.price_after < outcome.price_before);
+
+        // Drains every bin holding token Y
+        let amount_in = U256::from(10_u128.pow(30));
+        let outcome = pair.simulate_swap_outcome(wavax, usdc, amount_in)?;
+        assert!(outcome.liquidity_exhausted);
+        assert!(outcome.amount_in < amount_in);
+        assert_eq!(outcome.amount_out, U256::from(91_000_000_000_u128));
+        assert_eq!(outcome.ticks_crossed, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_for_x_within_filter_period() -> eyre::Result<()> {
+        let pair = wavax_usdc_pair();
+
+        let swap = pair.swap(10_000_000_000, false, 2_000_000_000)?;
+        assert_eq!(swap.amount_out, 498_536_261_846_773_163_954);
+        assert_eq!(swap.active_id, ACTIVE_ID + 2);
+        assert_eq!(swap.variable_fee_parameters.volatility_accumulator, 20000);
+
+        // Within the filter period the references are kept, so the accumulator builds on them
+        let mut pair = wavax_usdc_pair();
+        pair.variable_fee_parameters = VariableFeeParameters {
+            volatility_accumulator: 20000,
+            volatility_reference: 10000,
+            id_reference: ACTIVE_ID - 1,
+            time_of_last_update: 1_700_000_000,
+        };
+
+        let swap = pair.swap(10_u128.pow(20), true, 1_700_000_010)?;
+        assert_eq!(swap.amount_out, 1_995_760_628);
+        assert_eq!(swap.variable_fee_parameters.id_reference, ACTIVE_ID - 1);
+        assert_eq!(swap.variable_fee_parameters.volatility_accumulator, 10000);
+
+        // Swapping past the synced bins runs out of liquidity
+        assert!(matches!(
+            pair.swap(10_u128.pow(30), true, 1_700_000_010),
+            Err(LiquidityBookError::OutOfLiquidity)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync() -> eyre::Result<()> {
+        let mut pair = wavax_usdc_pair();
+
+        let swap_event = ILBPair::Swap {
+            sender: Address::default(),
+            to: Address::default(),
+            id: U24::from(ACTIVE_ID - 1),
+            amountsIn: encode_amounts(1_000, 0),
+            amountsOut: encode_amounts(0, 500),
+            volatilityAccumulator: U24::from(10000),
+            totalFees: encode_amounts(10, 0),
+            protocolFees: encode_amounts(1, 0),
+        };
+        pair.sync(&pair_log(pair.address, swap_event.encode_log_data()))?;
+
+        assert_eq!(pair.active_id, ACTIVE_ID - 1);
+        assert_eq!(pair.variable_fee_parameters.volatility_accumulator, 10000);
+        assert_eq!(
+            pair.variable_fee_parameters.time_of_last_update,
+            1_700_000_010
+        );
+        assert_eq!(
+            pair.bins[&(ACTIVE_ID - 1)],
+            Bin::new(999, 40_000_000_000 - 500)
+        );
+
+        let deposit_event = ILBPair::DepositedToBins {
+            sender: Address::default(),
+            to: Address::default(),
+            ids: vec![U256::from(ACTIVE_ID + 3)],
+            amounts: vec![encode_amounts(5_000, 0)],
+        };
+        pair.sync(&pair_log(pair.address, deposit_event.encode_log_data()))?;
+        assert_eq!(pair.bins[&(ACTIVE_ID + 3)], Bin::new(5_000, 0));
+
+        let withdraw_event = ILBPair::WithdrawnFromBins {
+            sender: Address::default(),
+            to: Address::default(),
+            ids: vec![U256::from(ACTIVE_ID + 3)],
+            amounts: vec![encode_amounts(5_000, 0)],
+        };
+        pair.sync(&pair_log(pair.address, withdraw_event.encode_log_data()))?;
+        assert!(!pair.bins.contains_key(&(ACTIVE_ID + 3)));
+        assert_eq!(
+            pair.next_non_empty_bin(false, ACTIVE_ID + 1),
+            Some(ACTIVE_ID + 2)
+        );
+        assert_eq!(pair.next_non_empty_bin(false, ACTIVE_ID + 2), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_synced_bins() -> eyre::Result<()> {
+        let mut pair = wavax_usdc_pair();
+        let bins = pair.bins.clone();
+
+        // Logs touching bins outside of the synced ones leave the reserves untouched
+        let withdraw_event = ILBPair::WithdrawnFromBins {
+            sender: Address::default(),
+            to: Address::default(),
+            ids: vec![U256::from(ACTIVE_ID + 20)],
+            amounts: vec![encode_amounts(5_000, 0)],
+        };
+        pair.sync(&pair_log(pair.address, withdraw_event.encode_log_data()))?;
+        let deposit_event = ILBPair::DepositedToBins {
+            sender: Address::default(),
+            to: Address::default(),
+            ids: vec![U256::from(ACTIVE_ID - 20)],
+            amounts: vec![encode_amounts(0, 5_000)],
+        };
+        pair.sync(&pair_log(pair.address, deposit_event.encode_log_data()))?;
+        assert_eq!(pair.bins, bins);
+        assert_eq!(pair.block_timestamp, 1_700_000_010);
+
+        // The protocol share of the composition fees is taken out of the deposit that follows
+        let composition_fees_event = ILBPair::CompositionFees {
+            sender: Address::default(),
+            id: U24::from(ACTIVE_ID + 3),
+            totalFees: encode_amounts(10, 0),
+            protocolFees: encode_amounts(1, 0),
+        };
+        pair.sync(&pair_log(
+            pair.address,
+            composition_fees_event.encode_log_data(),
+        ))?;
+        let deposit_event = ILBPair::DepositedToBins {
+            sender: Address::default(),
+            to: Address::default(),
+            ids: vec![U256::from(ACTIVE_ID + 3)],
+            amounts: vec![encode_amounts(100, 0)],
+        };
+        pair.sync(&pair_log(pair.address, deposit_event.encode_log_data()))?;
+        assert_eq!(pair.bins[&(ACTIVE_ID + 3)], Bin::new(99, 0));
+
+        let withdraw_event = ILBPair::WithdrawnFromBins {
+            sender: Address::default(),
+            to: Address::default(),
+            ids: vec![U256::from(ACTIVE_ID + 3)],
+            amounts: vec![encode_amounts(100, 0)],
+        };
+        assert!(pair
+            .sync(&pair_log(pair.address, withdraw_event.encode_log_data()))
+            .is_err());
+
+        // Quotes are unavailable while the active bin is outside of the synced bins
+        let swap_event = ILBPair::Swap {
+            sender: Address::default(),
+            to: Address::default(),
+            id: U24::from(ACTIVE_ID + 20),
+            amountsIn: encode_amounts(0, 1_000),
+            amountsOut: encode_amounts(500, 0),
+            volatilityAccumulator: U24::from(200000),
+            totalFees: encode_amounts(0, 10),
+            protocolFees: encode_amounts(0, 1),
+        };
+        pair.sync(&pair_log(pair.address, swap_event.encode_log_data()))?;
+        assert_eq!(pair.active_id, ACTIVE_ID + 20);
+        assert!(!pair.bins.contains_key(&(ACTIVE_ID + 20)));
+        assert!(matches!(
+            pair.swap(1_000, false, pair.block_timestamp),
+            Err(LiquidityBookError::OutOfLiquidity)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_synced_bin_ids() {
+        let below = (1..=LIQUIDITY_BOOK_BINS_PER_SIDE as u32).map(|i| ACTIVE_ID - 2 * i);
+
+        assert_eq!(
+            synced_bin_ids(ACTIVE_ID, std::iter::once(ACTIVE_ID).chain(below.clone())),
+            (
+                ACTIVE_ID - 2 * LIQUIDITY_BOOK_BINS_PER_SIDE as u32,
+                u32::MAX
+            )
+        );
+        assert_eq!(
+            synced_bin_ids(ACTIVE_ID, below.skip(1).chain([ACTIVE_ID + 1])),
+            (0, u32::MAX)
+        );
+    }
+}
@@ -3,20 +3,37 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-use alloy::{dyn_abi::DynSolType, network::Network, primitives::Address, providers::Provider, sol};
+use alloy::{
+    consensus::BlockHeader,
+    dyn_abi::DynSolType,
+    eips::BlockId,
+    network::{BlockResponse, Network},
+    primitives::{address, Address, U256},
+    providers::Provider,
+    rpc::types::state::StateOverridesBuilder,
+    sol,
+};
+use consts::{U256_1, U256_10000};
 use error::{AMMError, BatchContractError};
 use futures::{stream::FuturesUnordered, StreamExt};
 use serde::{Deserialize, Serialize};
 
+pub mod algebra;
 pub mod amm;
 pub mod balancer;
+pub mod balancer_v2;
 pub mod consts;
+pub mod curve;
 pub mod erc_4626;
 pub mod error;
 pub mod factory;
 pub mod float;
+pub mod liquidity_book;
+pub mod price;
+pub mod solidly;
 pub mod uniswap_v2;
 pub mod uniswap_v3;
+pub mod uniswap_v4;
 
 sol! {
     #[sol(rpc)]
@@ -24,18 +41,66 @@ sol! {
     "src/amms/abi/GetTokenDecimalsBatchRequest.json",
 }
 
+sol! {
+    #[sol(rpc)]
+    TokenTaxDetector,
+    "src/amms/abi/TokenTaxDetector.json",
+}
+
 sol!(
 #[derive(Debug, PartialEq, Eq)]
 #[sol(rpc)]
 contract IERC20 {
     function decimals() external view returns (uint8);
+    function balanceOf(address account) external view returns (uint256);
 });
 
+/// Wallets the tax detector moves tokens between. They must hold no code and be excluded from no
+/// fees.
+const TAX_DETECTOR_WALLET: Address = address!("7a5c0de7a5c0de7a5c0de7a5c0de7a5c0de70001");
+const TAX_DETECTOR_RECIPIENT: Address = address!("7a5c0de7a5c0de7a5c0de7a5c0de7a5c0de70002");
+
+/// Taxes charged by fee on transfer tokens, in basis points of the amount transferred.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenTax {
+    /// Charged when the token is transferred out of a pool
+    pub buy: u16,
+    /// Charged when the token is transferred into a pool
+    pub sell: u16,
+    /// Charged when the token is transferred between wallets
+    pub transfer: u16,
+}
+
+impl TokenTax {
+    /// Returns the amount that arrives when `amount` is sent and `tax` is taken.
+    fn deduct(amount: U256, tax: u16) -> U256 {
+        let tax = U256::from(tax);
+        // Split the amount so the product cannot overflow
+        let taken = amount / U256_10000 * tax + amount % U256_10000 * tax / U256_10000;
+
+        amount.saturating_sub(taken)
+    }
+
+    /// Returns the smallest amount to send so that at least `amount` arrives once `tax` is taken.
+    fn gross_up(amount: U256, tax: u16) -> Option<U256> {
+        if amount.is_zero() || tax == 0 {
+            return Some(amount);
+        }
+        let kept = U256_10000
+            .checked_sub(U256::from(tax))
+            .filter(|kept| !kept.is_zero())?;
+
+        // The tax rounds down, so `deduct(gross)` is `ceil(gross * kept / 10000)`
+        ((amount - U256_1).checked_mul(U256_10000)? / kept).checked_add(U256_1)
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Token {
     pub address: Address,
     pub decimals: u8,
-    // TODO: add optional tax
+    #[serde(default)]
+    pub tax: Option<TokenTax>,
 }
 
 impl Token {
@@ -46,11 +111,20 @@ impl Token {
     {
         let decimals = IERC20::new(address, provider).decimals().call().await?;
 
-        Ok(Self { address, decimals })
+        Ok(Self::new_with_decimals(address, decimals))
     }
 
     pub const fn new_with_decimals(address: Address, decimals: u8) -> Self {
-        Self { address, decimals }
+        Self {
+            address,
+            decimals,
+            tax: None,
+        }
+    }
+
+    pub const fn with_tax(mut self, tax: TokenTax) -> Self {
+        self.tax = Some(tax);
+        self
     }
 
     pub const fn address(&self) -> &Address {
@@ -60,14 +134,101 @@ impl Token {
     pub const fn decimals(&self) -> u8 {
         self.decimals
     }
+
+    /// Returns the amount a pool receives when `amount` is sold into it.
+    pub fn after_sell_tax(&self, amount: U256) -> U256 {
+        match self.tax {
+            Some(tax) => TokenTax::deduct(amount, tax.sell),
+            None => amount,
+        }
+    }
+
+    /// Returns the amount received when a pool sends out `amount`.
+    pub fn after_buy_tax(&self, amount: U256) -> U256 {
+        match self.tax {
+            Some(tax) => TokenTax::deduct(amount, tax.buy),
+            None => amount,
+        }
+    }
+
+    /// Returns the marginal rate a pool must quote before taxes for a swap of `self` into
+    /// `token_out` to return `marginal_rate` once the sell and buy taxes are taken.
+    pub fn untaxed_marginal_rate(&self, token_out: &Token, marginal_rate: f64) -> f64 {
+        let kept = |tax: u16| f64::from(10_000_u16.saturating_sub(tax)) / 10_000.0;
+        let sell = self.tax.map_or(0, |tax| tax.sell);
+        let buy = token_out.tax.map_or(0, |tax| tax.buy);
+
+        marginal_rate / (kept(sell) * kept(buy))
+    }
+
+    /// Returns the amount to sell so that a pool receives `amount`.
+    pub fn before_sell_tax(&self, amount: U256) -> Result<U256, AMMError> {
+        match self.tax {
+            Some(tax) => {
+                TokenTax::gross_up(amount, tax.sell).ok_or(AMMError::Untradeable(self.address))
+            }
+            None => Ok(amount),
+        }
+    }
+
+    /// Returns the amount a pool must send out so that `amount` is received.
+    pub fn before_buy_tax(&self, amount: U256) -> Result<U256, AMMError> {
+        match self.tax {
+            Some(tax) => {
+                TokenTax::gross_up(amount, tax.buy).ok_or(AMMError::Untradeable(self.address))
+            }
+            None => Ok(amount),
+        }
+    }
+
+    /// Measures the taxes of `token` by simulating transfers of `amount` out of `pool`, between two
+    /// wallets and back into `pool`, which must hold at least `amount` of the token.
+    ///
+    /// The detector code is placed at the pool and the wallets with `eth_call` state overrides, so
+    /// the node must support them. Tokens that call back into the pool during a transfer can not
+    /// be measured this way.
+    pub async fn detect_tax<N, P>(
+        token: Address,
+        pool: Address,
+        amount: U256,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<TokenTax, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let overrides = StateOverridesBuilder::default()
+            .with_code(pool, TokenTaxDetector::DEPLOYED_BYTECODE.clone())
+            .with_code(
+                TAX_DETECTOR_WALLET,
+                TokenTaxDetector::DEPLOYED_BYTECODE.clone(),
+            )
+            .with_code(
+                TAX_DETECTOR_RECIPIENT,
+                TokenTaxDetector::DEPLOYED_BYTECODE.clone(),
+            )
+            .build();
+
+        let taxes = TokenTaxDetector::new(pool, provider)
+            .detectTax(token, amount, TAX_DETECTOR_WALLET, TAX_DETECTOR_RECIPIENT)
+            .state(overrides)
+            .block(block_number)
+            .call()
+            .await?;
+
+        // Taxes are capped at 100% on chain
+        Ok(TokenTax {
+            buy: taxes.buyTax.to::<u16>(),
+            sell: taxes.sellTax.to::<u16>(),
+            transfer: taxes.transferTax.to::<u16>(),
+        })
+    }
 }
 
 impl From<Address> for Token {
     fn from(address: Address) -> Self {
-        Self {
-            address,
-            decimals: 0,
-        }
+        Self::new_with_decimals(address, 0)
     }
 }
 
@@ -77,6 +238,20 @@ impl Hash for Token {
     }
 }
 
+/// Fetches the timestamp of `block_number`, at which AMMs whose state moves with time are
+/// evaluated.
+pub async fn get_block_timestamp<N, P>(block_number: BlockId, provider: P) -> Result<u64, AMMError>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    provider
+        .get_block(block_number)
+        .await?
+        .map(|block| block.header().timestamp())
+        .ok_or(AMMError::BlockNotFound(block_number))
+}
+
 /// Fetches the decimal precision for a list of ERC-20 tokens.
 ///
 /// # Returns
@@ -0,0 +1,80 @@
+//! Exact prices as Q128.128 fixed point numbers, adjusted for token decimals.
+
+use alloy::primitives::U256;
+use uniswap_v3_math::full_math::mul_div;
+
+use super::{
+    consts::{Q128, U256_10},
+    error::AMMError,
+};
+
+/// Returns the price of the base token in whole quote tokens, given that `amount_base` of the base
+/// token trades for `amount_quote` of the quote token, both in their smallest units.
+pub fn ratio_to_q128(
+    amount_quote: U256,
+    quote_decimals: u8,
+    amount_base: U256,
+    base_decimals: u8,
+) -> Result<U256, AMMError> {
+    let numerator = amount_quote
+        .checked_mul(U256_10.pow(U256::from(base_decimals)))
+        .ok_or(AMMError::PriceOverflow)?;
+    let denominator = amount_base
+        .checked_mul(U256_10.pow(U256::from(quote_decimals)))
+        .ok_or(AMMError::PriceOverflow)?;
+
+    if denominator.is_zero() {
+        return Err(AMMError::PriceOverflow);
+    }
+
+    mul_div(numerator, Q128, denominator).map_err(|_| AMMError::PriceOverflow)
+}
+
+/// Converts a price between the smallest units of two tokens into a price between whole tokens.
+pub fn adjust_decimals(
+    price: U256,
+    base_decimals: u8,
+    quote_decimals: u8,
+) -> Result<U256, AMMError> {
+    mul_div(
+        price,
+        U256_10.pow(U256::from(base_decimals)),
+        U256_10.pow(U256::from(quote_decimals)),
+    )
+    .map_err(|_| AMMError::PriceOverflow)
+}
+
+/// Returns the reciprocal of a Q128.128 price.
+pub fn invert_q128(price: U256) -> Result<U256, AMMError> {
+    if price.is_zero() {
+        return Err(AMMError::PriceOverflow);
+    }
+
+    mul_div(Q128, Q128, price).map_err(|_| AMMError::PriceOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_to_q128() -> eyre::Result<()> {
+        // 1 WETH for 2500 USDC
+        let price = ratio_to_q128(
+            U256::from(2_500_000_000_u128),
+            6,
+            U256::from(10_u128.pow(18)),
+            18,
+        )?;
+        assert_eq!(price, Q128 * U256::from(2500));
+        assert_eq!(invert_q128(price)?, Q128 / U256::from(2500));
+
+        assert_eq!(
+            adjust_decimals(Q128, 18, 6)?,
+            Q128 * U256::from(10_u128.pow(12))
+        );
+        assert!(ratio_to_q128(U256::from(1), 18, U256::ZERO, 18).is_err());
+
+        Ok(())
+    }
+}
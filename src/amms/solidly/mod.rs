@@ -0,0 +1,871 @@
+use std::future::Future;
+
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, Bytes, B256, U256},
+    providers::Provider,
+    rpc::types::{Filter, FilterSet, Log},
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use rug::{float::Round, Float};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use super::{
+    amm::{check_amount_out, AMMKey, AutomatedMarketMaker, AMM},
+    consts::{
+        SOLIDLY_FEE_DENOMINATOR, SOLIDLY_MAX_ITERATIONS, SOLIDLY_PRECISION,
+        SOLIDLY_ZERO_FEE_INDICATOR, U256_1, U256_10,
+    },
+    error::AMMError,
+    factory::{AutomatedMarketMakerFactory, DiscoverySync},
+    float::{float_to_q128, u256_to_float},
+    price::ratio_to_q128,
+    Token,
+};
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract ISolidlyPool {
+        event Sync(uint256 reserve0, uint256 reserve1);
+
+        function getAmountOut(uint256 amountIn, address tokenIn) external view returns (uint256);
+        function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes calldata data) external;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract ISolidlyFactory {
+        event PoolCreated(address indexed token0, address indexed token1, bool indexed stable, address pool, uint256);
+        event SetCustomFee(address indexed pool, uint256 fee);
+
+        function getFee(address pool, bool stable) external view returns (uint256);
+        function stableFee() external view returns (uint256);
+        function volatileFee() external view returns (uint256);
+    }
+
+    #[derive(Debug)]
+    struct SolidlyPoolData {
+        address tokenA;
+        address tokenB;
+        uint8 tokenADecimals;
+        uint8 tokenBDecimals;
+        uint256 reserve0;
+        uint256 reserve1;
+        bool stable;
+        uint256 fee;
+    }
+}
+
+sol! {
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    IGetSolidlyPoolDataBatchRequest,
+    "src/amms/abi/GetSolidlyPoolDataBatchRequest.json",
+}
+
+#[derive(Error, Debug)]
+pub enum SolidlyError {
+    #[error("Error initializing Solidly pool")]
+    InitializationError,
+    #[error("Token not in pool")]
+    TokenNotInPool,
+    #[error("Stable invariant did not converge")]
+    DidNotConverge,
+}
+
+/// A Solidly pool as deployed by Velodrome V2 and Aerodrome, pricing either the `x * y = k`
+/// volatile curve or the `x^3 * y + y^3 * x = k` stable curve.
+///
+/// The swap fee is read from the factory when the pool is synced and follows the custom fees the
+/// factory sets for the pool afterwards. Changes to the factory's default fees emit no event, so
+/// they are only picked up on the next sync.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SolidlyPool {
+    pub address: Address,
+    pub factory: Address,
+    pub token_a: Token,
+    pub token_b: Token,
+    pub reserve_0: U256,
+    pub reserve_1: U256,
+    pub stable: bool,
+    /// Swap fee in basis points
+    pub fee: u32,
+    /// Fee in basis points the factory charges pools of this type without a custom fee, if the
+    /// factory exposes it
+    pub default_fee: Option<u32>,
+}
+
+impl AutomatedMarketMaker for SolidlyPool {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        vec![
+            ISolidlyPool::Sync::SIGNATURE_HASH,
+            ISolidlyFactory::SetCustomFee::SIGNATURE_HASH,
+        ]
+    }
+
+    fn log_key(log: &Log) -> Option<AMMKey> {
+        let topics = log.topics();
+        match topics.first() {
+            Some(&ISolidlyFactory::SetCustomFee::SIGNATURE_HASH) => {
+                topics.get(1).map(|pool| Address::from_word(*pool).into())
+            }
+            _ => None,
+        }
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        match log.topic0() {
+            Some(&ISolidlyPool::Sync::SIGNATURE_HASH) => {
+                let sync_event = ISolidlyPool::Sync::decode_log(&log.inner)?;
+
+                info!(
+                    target = "amm::solidly::sync",
+                    address = ?self.address,
+                    reserve_0 = ?sync_event.reserve0,
+                    reserve_1 = ?sync_event.reserve1, "Sync"
+                );
+
+                self.reserve_0 = sync_event.reserve0;
+                self.reserve_1 = sync_event.reserve1;
+            }
+            Some(&ISolidlyFactory::SetCustomFee::SIGNATURE_HASH)
+                if log.address() == self.factory =>
+            {
+                let fee_event = ISolidlyFactory::SetCustomFee::decode_log(&log.inner)?;
+                self.sync_custom_fee(fee_event.fee.saturating_to());
+            }
+            // Pools also emit events sharing their signature with other AMMs' sync events, such as
+            // the Uniswap V2 `Mint`, which are routed here by address and skipped
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn tokens(&self) -> Vec<Address> {
+        vec![self.token_a.address, self.token_b.address]
+    }
+
+    /// Returns the spot price of the base token in the quote token, excluding fees.
+    fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
+        Ok(self.spot_price(base_token)?.to_f64_round(Round::Nearest))
+    }
+
+    /// Returns the spot price of the base token in the quote token, excluding fees.
+    ///
+    /// Stable pools are priced through the invariant at `MPFR_T_PRECISION` bits.
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        if self.stable {
+            return float_to_q128(self.spot_price(base_token)?);
+        }
+
+        if self.zero_for_one(base_token)? {
+            ratio_to_q128(
+                self.reserve_1,
+                self.token_b.decimals,
+                self.reserve_0,
+                self.token_a.decimals,
+            )
+        } else {
+            ratio_to_q128(
+                self.reserve_0,
+                self.token_a.decimals,
+                self.reserve_1,
+                self.token_b.decimals,
+            )
+        }
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token)?;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+
+        let amount_out = self.get_amount_out(token_in.after_sell_tax(amount_in), zero_for_one)?;
+        Ok(token_out.after_buy_tax(amount_out))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token)?;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+
+        let amount_in = token_in.after_sell_tax(amount_in);
+        let amount_out = self.get_amount_out(amount_in, zero_for_one)?;
+        let amount_received = token_out.after_buy_tax(amount_out);
+
+        // The fee is sent to a separate fees contract and does not accrue to the reserves
+        let amount_in = amount_in - self.fee_amount(amount_in);
+        if zero_for_one {
+            self.reserve_0 += amount_in;
+            self.reserve_1 -= amount_out;
+        } else {
+            self.reserve_0 -= amount_out;
+            self.reserve_1 += amount_in;
+        }
+
+        Ok(amount_received)
+    }
+
+    /// Encodes `swap` on the pool, requesting the amount out before the buy tax of the token out.
+    /// The amount in must be transferred to the pool first.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token)?;
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        let (token_in, _) = self.swap_tokens(zero_for_one);
+        let amount_out = self.get_amount_out(token_in.after_sell_tax(amount_in), zero_for_one)?;
+        let (amount_0_out, amount_1_out) = if zero_for_one {
+            (U256::ZERO, amount_out)
+        } else {
+            (amount_out, U256::ZERO)
+        };
+
+        Ok(ISolidlyPool::swapCall {
+            amount0Out: amount_0_out,
+            amount1Out: amount_1_out,
+            to: recipient,
+            data: Bytes::new(),
+        }
+        .abi_encode()
+        .into())
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        SolidlyPool::get_pool_data(self.factory, vec![self.address], block_number, provider)
+            .await?
+            .pop()
+            .ok_or(SolidlyError::InitializationError.into())
+    }
+}
+
+impl SolidlyPool {
+    pub fn new(factory: Address, address: Address) -> Self {
+        Self {
+            address,
+            factory,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the spot price of the base token in the quote token, the ratio of the partial
+    /// derivatives of the invariant.
+    pub fn spot_price(&self, base_token: Address) -> Result<Float, AMMError> {
+        let zero_for_one = self.zero_for_one(base_token)?;
+
+        // Reserves in whole tokens
+        let x = u256_to_float(self.reserve_0)?
+            / u256_to_float(U256_10.pow(U256::from(self.token_a.decimals)))?;
+        let y = u256_to_float(self.reserve_1)?
+            / u256_to_float(U256_10.pow(U256::from(self.token_b.decimals)))?;
+
+        // Price of token 0 in token 1, the ratio of the partial derivatives of the invariant
+        let price = if self.stable {
+            let x2 = x.clone() * x.clone();
+            let y2 = y.clone() * y.clone();
+            (x2.clone() * y.clone() * 3 + y2.clone() * y.clone()) / (x2 * x.clone() + y2 * x * 3)
+        } else {
+            y / x
+        };
+
+        Ok(if zero_for_one { price } else { 1 / price })
+    }
+
+    /// Applies a custom fee set by the factory, where zero resets the pool to the default fee
+    fn sync_custom_fee(&mut self, fee: u32) {
+        match fee {
+            SOLIDLY_ZERO_FEE_INDICATOR => self.fee = 0,
+            0 => match self.default_fee {
+                Some(default_fee) => self.fee = default_fee,
+                None => warn!(
+                    target = "amm::solidly::sync",
+                    address = ?self.address,
+                    "Custom fee reset without a known default fee"
+                ),
+            },
+            fee => self.fee = fee,
+        }
+
+        info!(
+            target = "amm::solidly::sync",
+            address = ?self.address,
+            fee = self.fee, "SetCustomFee"
+        );
+    }
+
+    /// Fetches the state of `pools` at `block_number`, skipping addresses that are not Solidly
+    /// pools.
+    pub async fn get_pool_data<N, P>(
+        factory: Address,
+        pools: Vec<Address>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<Self>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let deployer = IGetSolidlyPoolDataBatchRequest::deploy_builder(
+            provider.clone(),
+            factory,
+            pools.clone(),
+        );
+        let res = deployer.block(block_number).call_raw().await?;
+
+        let data = <Vec<SolidlyPoolData> as SolValue>::abi_decode(&res)?;
+
+        // Not every Solidly fork exposes its default fees
+        let factory_contract = ISolidlyFactory::new(factory, provider);
+        let stable_fee = factory_contract
+            .stableFee()
+            .block(block_number)
+            .call()
+            .await;
+        let volatile_fee = factory_contract
+            .volatileFee()
+            .block(block_number)
+            .call()
+            .await;
+
+        Ok(pools
+            .into_iter()
+            .zip(data)
+            .filter(|(_, pool_data)| !pool_data.tokenA.is_zero())
+            .map(|(address, pool_data)| SolidlyPool {
+                address,
+                factory,
+                token_a: Token::new_with_decimals(pool_data.tokenA, pool_data.tokenADecimals),
+                token_b: Token::new_with_decimals(pool_data.tokenB, pool_data.tokenBDecimals),
+                reserve_0: pool_data.reserve0,
+                reserve_1: pool_data.reserve1,
+                stable: pool_data.stable,
+                fee: pool_data.fee.saturating_to(),
+                default_fee: if pool_data.stable {
+                    stable_fee.as_ref().ok()
+                } else {
+                    volatile_fee.as_ref().ok()
+                }
+                .map(|fee| fee.saturating_to()),
+            })
+            .collect())
+    }
+
+    /// Calculates the amount received for `amount_in`, swapping token 0 for token 1 when
+    /// `zero_for_one` is true.
+    pub fn get_amount_out(
+        &self,
+        amount_in: U256,
+        zero_for_one: bool,
+    ) -> Result<U256, SolidlyError> {
+        let amount_in = amount_in - self.fee_amount(amount_in);
+
+        let (reserve_in, reserve_out) = if zero_for_one {
+            (self.reserve_0, self.reserve_1)
+        } else {
+            (self.reserve_1, self.reserve_0)
+        };
+
+        if reserve_in.is_zero() || reserve_out.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        if !self.stable {
+            return Ok(amount_in * reserve_out / (reserve_in + amount_in));
+        }
+
+        let (decimals_in, decimals_out) = if zero_for_one {
+            (self.decimals_0(), self.decimals_1())
+        } else {
+            (self.decimals_1(), self.decimals_0())
+        };
+
+        let xy = self.k(self.reserve_0, self.reserve_1);
+        let reserve_in = reserve_in * SOLIDLY_PRECISION / decimals_in;
+        let reserve_out = reserve_out * SOLIDLY_PRECISION / decimals_out;
+        let amount_in = amount_in * SOLIDLY_PRECISION / decimals_in;
+
+        let y = reserve_out - self.get_y(amount_in + reserve_in, xy, reserve_out)?;
+        Ok(y * decimals_out / SOLIDLY_PRECISION)
+    }
+
+    fn fee_amount(&self, amount_in: U256) -> U256 {
+        amount_in * U256::from(self.fee) / SOLIDLY_FEE_DENOMINATOR
+    }
+
+    /// The pool invariant, computed over balances normalized to 18 decimals for stable pools.
+    fn k(&self, x: U256, y: U256) -> U256 {
+        if self.stable {
+            let x = x * SOLIDLY_PRECISION / self.decimals_0();
+            let y = y * SOLIDLY_PRECISION / self.decimals_1();
+            let a = x * y / SOLIDLY_PRECISION;
+            let b = x * x / SOLIDLY_PRECISION + y * y / SOLIDLY_PRECISION;
+            a * b / SOLIDLY_PRECISION
+        } else {
+            x * y
+        }
+    }
+
+    /// Solves the stable invariant for `y` given `x0` through Newton's method, rounding so that
+    /// the invariant does not decrease.
+    fn get_y(&self, x0: U256, xy: U256, mut y: U256) -> Result<U256, SolidlyError> {
+        for _ in 0..SOLIDLY_MAX_ITERATIONS {
+            let k = f(x0, y);
+            if k < xy {
+                let mut dy = (xy - k) * SOLIDLY_PRECISION / d(x0, y);
+                if dy.is_zero() {
+                    if k == xy {
+                        return Ok(y);
+                    }
+                    // The pool normalizes the balances a second time here, which is replicated
+                    // to match its rounding
+                    if self.k(x0, y + U256_1) > xy {
+                        return Ok(y + U256_1);
+                    }
+                    dy = U256_1;
+                }
+                y += dy;
+            } else {
+                let mut dy = (k - xy) * SOLIDLY_PRECISION / d(x0, y);
+                if dy.is_zero() {
+                    if k == xy || f(x0, y - U256_1) < xy {
+                        return Ok(y);
+                    }
+                    dy = U256_1;
+                }
+                y -= dy;
+            }
+        }
+
+        Err(SolidlyError::DidNotConverge)
+    }
+
+    fn decimals_0(&self) -> U256 {
+        U256_10.pow(U256::from(self.token_a.decimals))
+    }
+
+    fn decimals_1(&self) -> U256 {
+        U256_10.pow(U256::from(self.token_b.decimals))
+    }
+
+    fn zero_for_one(&self, base_token: Address) -> Result<bool, SolidlyError> {
+        if base_token == self.token_a.address {
+            Ok(true)
+        } else if base_token == self.token_b.address {
+            Ok(false)
+        } else {
+            Err(SolidlyError::TokenNotInPool)
+        }
+    }
+
+    /// Returns the token sold into and the token bought from the pool.
+    fn swap_tokens(&self, zero_for_one: bool) -> (&Token, &Token) {
+        if zero_for_one {
+            (&self.token_a, &self.token_b)
+        } else {
+            (&self.token_b, &self.token_a)
+        }
+    }
+}
+
+/// The stable invariant `x0^3 * y + y^3 * x0` for normalized balances.
+fn f(x0: U256, y: U256) -> U256 {
+    let a = x0 * y / SOLIDLY_PRECISION;
+    let b = x0 * x0 / SOLIDLY_PRECISION + y * y / SOLIDLY_PRECISION;
+    a * b / SOLIDLY_PRECISION
+}
+
+/// Derivative of the stable invariant with respect to `y`.
+fn d(x0: U256, y: U256) -> U256 {
+    U256::from(3) * x0 * (y * y / SOLIDLY_PRECISION) / SOLIDLY_PRECISION
+        + x0 * x0 / SOLIDLY_PRECISION * x0 / SOLIDLY_PRECISION
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SolidlyFactory {
+    pub address: Address,
+    pub creation_block: u64,
+}
+
+impl SolidlyFactory {
+    pub fn new(address: Address, creation_block: u64) -> Self {
+        Self {
+            address,
+            creation_block,
+        }
+    }
+
+    pub async fn get_all_pools<N, P>(
+        &self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let disc_filter = Filter::new()
+            .event_signature(FilterSet::from(vec![self.pool_creation_event()]))
+            .address(vec![self.address()]);
+
+        let sync_provider = provider.clone();
+        let mut futures = FuturesUnordered::new();
+
+        let sync_step = 100_000;
+        let mut latest_block = self.creation_block;
+        while latest_block < block_number.as_u64().unwrap_or_default() {
+            let mut block_filter = disc_filter.clone();
+            let from_block = latest_block;
+            let to_block = (from_block + sync_step).min(block_number.as_u64().unwrap_or_default());
+
+            block_filter = block_filter.from_block(from_block);
+            block_filter = block_filter.to_block(to_block);
+
+            let sync_provider = sync_provider.clone();
+
+            futures.push(async move { sync_provider.get_logs(&block_filter).await });
+
+            latest_block = to_block + 1;
+        }
+
+        let mut pools = vec![];
+        while let Some(res) = futures.next().await {
+            let logs = res?;
+
+            for log in logs {
+                pools.push(self.create_pool(log)?);
+            }
+        }
+
+        Ok(pools)
+    }
+
+    pub async fn sync_all_pools<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let step = 120;
+        let pools = amms.iter().map(|amm| amm.address()).collect::<Vec<_>>();
+
+        let mut futures_unordered = pools
+            .chunks(step)
+            .map(|group| {
+                SolidlyPool::get_pool_data(
+                    self.address,
+                    group.to_vec(),
+                    block_number,
+                    provider.clone(),
+                )
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut amms = Vec::new();
+        while let Some(res) = futures_unordered.next().await {
+            amms.extend(res?.into_iter().map(AMM::SolidlyPool));
+        }
+
+        Ok(amms)
+    }
+}
+
+impl AutomatedMarketMakerFactory for SolidlyFactory {
+    type PoolVariant = SolidlyPool;
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn pool_creation_event(&self) -> B256 {
+        ISolidlyFactory::PoolCreated::SIGNATURE_HASH
+    }
+
+    fn create_pool(&self, log: Log) -> Result<AMM, AMMError> {
+        let event = ISolidlyFactory::PoolCreated::decode_log(&log.inner)?;
+
+        Ok(AMM::SolidlyPool(SolidlyPool {
+            address: event.pool,
+            factory: self.address,
+            token_a: event.token0.into(),
+            token_b: event.token1.into(),
+            stable: event.stable,
+            ..Default::default()
+        }))
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+}
+
+impl DiscoverySync for SolidlyFactory {
+    fn discover<N, P>(
+        &self,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::solidly::discover",
+            address = ?self.address,
+            "Discovering all pools"
+        );
+
+        self.get_all_pools(to_block, provider)
+    }
+
+    fn sync<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::solidly::sync",
+            address = ?self.address,
+            "Syncing all pools"
+        );
+
+        self.sync_all_pools(amms, to_block, provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        amms::{
+            consts::Q128,
+            uniswap_v2::{IUniswapV2Pair, UniswapV2Pool},
+        },
+        state_space::StateSpace,
+    };
+    use alloy::primitives::address;
+
+    fn usdc_dai_pool() -> SolidlyPool {
+        SolidlyPool {
+            address: address!("19715771E30c93915A5bbDa134d782b81A820076"),
+            token_a: Token::new_with_decimals(
+                address!("0b2C639c533813f4Aa9D7837CAf62653d097Ff85"),
+                6,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("DA10009cBd5D07dd0CeCc66161FC93D7c9000da1"),
+                18,
+            ),
+            reserve_0: U256::from(1_000_000e6),
+            reserve_1: U256::from(1_200_000_000_000_000_000_000_000_u128),
+            stable: true,
+            fee: 5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_amount_out_volatile() {
+        let pool = SolidlyPool {
+            token_a: Token::new_with_decimals(
+                address!("4200000000000000000000000000000000000006"),
+                18,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("0b2C639c533813f4Aa9D7837CAf62653d097Ff85"),
+                6,
+            ),
+            reserve_0: U256::from(1_000e18),
+            reserve_1: U256::from(2_000_000e6),
+            fee: 30,
+            ..Default::default()
+        };
+
+        let amount_out = pool.get_amount_out(U256::from(1e18), true).unwrap();
+        assert_eq!(amount_out, U256::from(1_992_013_962_u64));
+    }
+
+    #[test]
+    fn test_get_amount_out_stable() {
+        let pool = usdc_dai_pool();
+
+        let amount_out = pool.get_amount_out(U256::from(1_000e6), true).unwrap();
+        assert_eq!(amount_out, U256::from(1_000_980_591_889_939_735_031_u128));
+
+        let amount_out = pool.get_amount_out(U256::from(1_000e18), false).unwrap();
+        assert_eq!(amount_out, U256::from(997_976_635_u64));
+    }
+
+    #[test]
+    fn test_sync_and_calculate_price() {
+        let mut pool = usdc_dai_pool();
+
+        let event = ISolidlyPool::Sync {
+            reserve0: U256::from(1_000_000e6),
+            reserve1: U256::from(1_000_000_000_000_000_000_000_000_u128),
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: event.encode_log_data(),
+            },
+            ..Default::default()
+        };
+        pool.sync(&log).unwrap();
+
+        assert_eq!(
+            pool.reserve_1,
+            U256::from(1_000_000_000_000_000_000_000_000_u128)
+        );
+        assert_eq!(
+            pool.calculate_price(pool.token_a.address, pool.token_b.address)
+                .unwrap(),
+            1.0
+        );
+
+        // The stable curve is flatter than the constant product curve around the peg
+        pool.reserve_1 = U256::from(1_200_000_000_000_000_000_000_000_u128);
+        let stable_price = pool
+            .calculate_price(pool.token_a.address, pool.token_b.address)
+            .unwrap();
+        pool.stable = false;
+        let volatile_price = pool
+            .calculate_price(pool.token_a.address, pool.token_b.address)
+            .unwrap();
+        assert!(stable_price > 1.0 && stable_price < volatile_price);
+        assert_eq!(volatile_price, 1.2);
+
+        // 1.2 in Q128
+        assert_eq!(
+            pool.calculate_price_exact(pool.token_a.address, pool.token_b.address)
+                .unwrap(),
+            U256::from_str_radix("408338840305126156156049528918121853747", 10).unwrap()
+        );
+        pool.reserve_1 = U256::from(1_000_000_000_000_000_000_000_000_u128);
+        pool.stable = true;
+        assert_eq!(
+            pool.calculate_price_exact(pool.token_a.address, pool.token_b.address)
+                .unwrap(),
+            Q128
+        );
+    }
+
+    #[test]
+    fn test_sync_skips_uniswap_v2_mint() -> eyre::Result<()> {
+        let pool = usdc_dai_pool();
+        let (reserve_0, reserve_1) = (pool.reserve_0, pool.reserve_1);
+
+        // Solidly pools emit the Uniswap V2 `Mint` event, which is in the block filter whenever the
+        // state space also tracks Uniswap V2 pools
+        let mint_event = IUniswapV2Pair::Mint {
+            sender: Address::ZERO,
+            amount0: U256::from(1e6),
+            amount1: U256::from(1e18),
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: mint_event.encode_log_data(),
+            },
+            block_number: Some(1),
+            ..Default::default()
+        };
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool.clone().into());
+        state_space.insert(UniswapV2Pool::default().into());
+        state_space.sync(&[log])?;
+
+        let Some(AMM::SolidlyPool(pool)) = state_space.get(pool.address) else {
+            panic!("the pool is in the state space");
+        };
+        assert_eq!((pool.reserve_0, pool.reserve_1), (reserve_0, reserve_1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_custom_fee() -> eyre::Result<()> {
+        let pool = SolidlyPool {
+            factory: address!("F1046053aa5682b4F9a81b5481394DA16BE5FF5a"),
+            default_fee: Some(5),
+            ..usdc_dai_pool()
+        };
+        let set_custom_fee = |factory, fee: u32| Log {
+            inner: alloy::primitives::Log {
+                address: factory,
+                data: ISolidlyFactory::SetCustomFee {
+                    pool: pool.address,
+                    fee: U256::from(fee),
+                }
+                .encode_log_data(),
+            },
+            block_number: Some(1),
+            ..Default::default()
+        };
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool.clone().into());
+        let fee = |state_space: &StateSpace| match state_space.get(pool.address) {
+            Some(AMM::SolidlyPool(pool)) => pool.fee,
+            _ => panic!("the pool is in the state space"),
+        };
+
+        // The factory emits the event, which is routed to the pool it names
+        state_space.sync(&[set_custom_fee(pool.factory, 30)])?;
+        assert_eq!(fee(&state_space), 30);
+
+        state_space.sync(&[set_custom_fee(pool.factory, SOLIDLY_ZERO_FEE_INDICATOR)])?;
+        assert_eq!(fee(&state_space), 0);
+
+        state_space.sync(&[set_custom_fee(pool.factory, 0)])?;
+        assert_eq!(fee(&state_space), 5);
+
+        // Events from another factory are skipped
+        state_space.sync(&[set_custom_fee(Address::ZERO, 30)])?;
+        assert_eq!(fee(&state_space), 5);
+
+        Ok(())
+    }
+}
@@ -1,21 +1,24 @@
+pub mod oracle;
+
 use super::{
-    amm::{AutomatedMarketMaker, AMM},
+    amm::{check_amount_out, AutomatedMarketMaker, LiquidityOutcome, LiquidityRange, AMM},
     consts::{
-        MPFR_T_PRECISION, U128_0X10000000000000000, U256_0X100, U256_0X10000, U256_0X100000000,
-        U256_0XFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF,
-        U256_0XFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF, U256_1, U256_100000, U256_128,
-        U256_16, U256_191, U256_192, U256_2, U256_255, U256_32, U256_4, U256_64, U256_8,
+        MPFR_T_PRECISION, Q128, U128_0X10000000000000000, U256_0X100, U256_0X10000,
+        U256_0X100000000, U256_0XFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF,
+        U256_0XFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF, U256_1, U256_1000, U256_100000,
+        U256_128, U256_16, U256_191, U256_192, U256_2, U256_255, U256_32, U256_4, U256_64, U256_8,
     },
     error::AMMError,
     factory::{AutomatedMarketMakerFactory, DiscoverySync},
-    float::q64_to_float,
+    float::{float_to_u256_saturating, q64_to_float},
+    price::{adjust_decimals, ratio_to_q128},
     Token,
 };
 
 use alloy::{
     eips::BlockId,
-    network::Network,
-    primitives::{Address, Bytes, B256, U256},
+    network::{Network, TransactionBuilder},
+    primitives::{Address, Bytes, FixedBytes, B256, U256},
     providers::Provider,
     rpc::types::Log,
     sol,
@@ -23,6 +26,7 @@ use alloy::{
 };
 use futures::{stream::FuturesUnordered, StreamExt};
 use itertools::Itertools;
+use oracle::{Observation, Oracle};
 use rug::Float;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, future::Future, hash::Hash};
@@ -31,6 +35,9 @@ use tracing::info;
 use IGetUniswapV2PoolDataBatchRequest::IGetUniswapV2PoolDataBatchRequestInstance;
 use IUniswapV2Factory::IUniswapV2FactoryInstance;
 
+/// Maximum number of fee discovery calls in flight while syncing the pools of a factory
+const FEE_DISCOVERY_CONCURRENCY: usize = 64;
+
 sol!(
 // UniswapV2Factory
 #[allow(missing_docs)]
@@ -47,10 +54,18 @@ contract IUniswapV2Factory {
 #[sol(rpc)]
 contract IUniswapV2Pair {
     event Sync(uint112 reserve0, uint112 reserve1);
+    event Mint(address indexed sender, uint amount0, uint amount1);
+    event Burn(address indexed sender, uint amount0, uint amount1, address indexed to);
     function token0() external view returns (address);
     function token1() external view returns (address);
     function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes calldata data);
     function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[sol(rpc)]
+contract IUniswapV2Router {
+    function getAmountsOut(uint256 amountIn, address[] calldata path) external view returns (uint256[] memory amounts);
 });
 
 sol!(
@@ -73,6 +88,97 @@ pub enum UniswapV2Error {
     DivisionByZero,
     #[error("Rounding Error")]
     RoundingError,
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+    #[error("Could not discover the fee of pool {0}")]
+    FeeDiscoveryError(Address),
+    #[error("Insufficient liquidity minted")]
+    InsufficientLiquidityMinted,
+    #[error("Insufficient liquidity burned")]
+    InsufficientLiquidityBurned,
+    #[error("Reserves overflow")]
+    ReserveOverflow,
+    #[error("Observation is older than the oldest one in the oracle")]
+    OracleTooOld,
+}
+
+/// How the fee of a Uniswap V2 pool is discovered when the pool is synced.
+///
+/// Forks charge anything from 0.17% to 0.3%, and some let each pair set its own fee, so a factory
+/// declares the strategy that fits its deployment and passes it on to the pools it creates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UniswapV2FeeDiscovery {
+    /// Keep the fee the pool was created with
+    #[default]
+    Fixed,
+    /// Read the fee from a getter on the pool, such as `swapFee()`, that returns the fee out of
+    /// `denominator`
+    Getter {
+        selector: FixedBytes<4>,
+        denominator: u32,
+    },
+    /// Infer the fee from a quote of the fork's router, which must route through the pool's
+    /// factory
+    RouterQuote { router: Address },
+}
+
+impl UniswapV2FeeDiscovery {
+    /// Returns the fee of `pool` at `block_number` in units of 0.001%, or `None` if the strategy
+    /// keeps the fee the pool was created with.
+    pub async fn discover_fee<N, P>(
+        &self,
+        pool: &UniswapV2Pool,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Option<usize>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        match *self {
+            UniswapV2FeeDiscovery::Fixed => Ok(None),
+            UniswapV2FeeDiscovery::Getter {
+                selector,
+                denominator,
+            } => {
+                let tx = N::TransactionRequest::default()
+                    .with_to(pool.address)
+                    .with_input(selector);
+                let res = provider.call(tx).block(block_number).await?;
+
+                let fee = <U256 as SolValue>::abi_decode(&res)
+                    .ok()
+                    .filter(|_| denominator != 0)
+                    .and_then(|fee| fee.checked_mul(U256_100000))
+                    .map(|fee| fee / U256::from(denominator))
+                    .filter(|fee| *fee < U256_100000)
+                    .ok_or(UniswapV2Error::FeeDiscoveryError(pool.address))?;
+
+                Ok(Some(fee.to::<usize>()))
+            }
+            UniswapV2FeeDiscovery::RouterQuote { router } => {
+                let (reserve_in, reserve_out) =
+                    (U256::from(pool.reserve_0), U256::from(pool.reserve_1));
+                // Small enough to barely move the price, large enough to resolve 0.001% steps
+                let amount_in = reserve_in / U256_1000;
+                if amount_in.is_zero() || reserve_out.is_zero() {
+                    return Ok(None);
+                }
+
+                let amounts = IUniswapV2Router::new(router, provider)
+                    .getAmountsOut(amount_in, vec![pool.token_a.address, pool.token_b.address])
+                    .call()
+                    .block(block_number)
+                    .await?;
+                let quote = amounts
+                    .last()
+                    .ok_or(UniswapV2Error::FeeDiscoveryError(pool.address))?;
+
+                pool.calibrate_fee(amount_in, reserve_in, reserve_out, *quote)
+                    .map(Some)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -82,34 +188,75 @@ pub struct UniswapV2Pool {
     pub token_b: Token,
     pub reserve_0: u128,
     pub reserve_1: u128,
+    /// Swap fee in units of 0.001%
     pub fee: usize,
+    #[serde(default)]
+    pub fee_discovery: UniswapV2FeeDiscovery,
+    /// Total supply of LP shares, including the minimum liquidity locked by the first mint
+    #[serde(default)]
+    pub total_supply: U256,
+    /// Timestamp of the pair's last update, truncated to 32 bits as the pair stores it
+    #[serde(default)]
+    pub block_timestamp_last: u32,
+    /// UQ112x112 prices of token0 and token1 accumulated every second as of the last update
+    #[serde(default)]
+    pub price_0_cumulative_last: U256,
+    #[serde(default)]
+    pub price_1_cumulative_last: U256,
+    /// Cumulative prices of the last updates, only kept for pools that track them
+    #[serde(default)]
+    pub oracle: Option<Oracle>,
 }
 
+/// Shares permanently locked by the first mint of a pair
+pub const MINIMUM_LIQUIDITY: U256 = U256_1000;
+
 impl AutomatedMarketMaker for UniswapV2Pool {
     fn address(&self) -> Address {
         self.address
     }
 
     fn sync_events(&self) -> Vec<B256> {
-        vec![IUniswapV2Pair::Sync::SIGNATURE_HASH]
+        vec![
+            IUniswapV2Pair::Sync::SIGNATURE_HASH,
+            IUniswapV2Pair::Mint::SIGNATURE_HASH,
+            IUniswapV2Pair::Burn::SIGNATURE_HASH,
+        ]
     }
 
     fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
-        let sync_event = IUniswapV2Pair::Sync::decode_log(&log.inner)?;
-
-        let (reserve_0, reserve_1) = (
-            sync_event.reserve0.to::<u128>(),
-            sync_event.reserve1.to::<u128>(),
-        );
-
-        info!(
-            target = "amm::uniswap_v2::sync",
-            address = ?self.address,
-            reserve_0, reserve_1, "Sync"
-        );
+        let event_signature = log.topics()[0];
+        match event_signature {
+            IUniswapV2Pair::Sync::SIGNATURE_HASH => {
+                let sync_event = IUniswapV2Pair::Sync::decode_log(&log.inner)?;
+
+                let (reserve_0, reserve_1) = (
+                    sync_event.reserve0.to::<u128>(),
+                    sync_event.reserve1.to::<u128>(),
+                );
+
+                info!(
+                    target = "amm::uniswap_v2::sync",
+                    address = ?self.address,
+                    reserve_0, reserve_1, "Sync"
+                );
+
+                self.sync_cumulative_prices(log);
+                self.reserve_0 = reserve_0;
+                self.reserve_1 = reserve_1;
+            }
+            // The pair emits `Sync` before `Mint` and `Burn`, so the reserves are already up to date
+            IUniswapV2Pair::Mint::SIGNATURE_HASH => {
+                let mint_event = IUniswapV2Pair::Mint::decode_log(&log.inner)?;
+                self.sync_mint(mint_event.amount0, mint_event.amount1);
+            }
+            IUniswapV2Pair::Burn::SIGNATURE_HASH => {
+                let burn_event = IUniswapV2Pair::Burn::decode_log(&log.inner)?;
+                self.sync_burn(burn_event.amount0, burn_event.amount1);
+            }
+            _ => return Err(AMMError::UnrecognizedEventSignature(event_signature)),
+        }
 
-        self.reserve_0 = reserve_0;
-        self.reserve_1 = reserve_1;
         Ok(())
     }
 
@@ -120,17 +267,21 @@ impl AutomatedMarketMaker for UniswapV2Pool {
         amount_in: U256,
     ) -> Result<U256, AMMError> {
         if self.token_a.address == base_token {
-            Ok(self.get_amount_out(
-                amount_in,
+            let amount_out = self.get_amount_out(
+                self.token_a.after_sell_tax(amount_in),
                 U256::from(self.reserve_0),
                 U256::from(self.reserve_1),
-            ))
+            );
+
+            Ok(self.token_b.after_buy_tax(amount_out))
         } else {
-            Ok(self.get_amount_out(
-                amount_in,
+            let amount_out = self.get_amount_out(
+                self.token_b.after_sell_tax(amount_in),
                 U256::from(self.reserve_1),
                 U256::from(self.reserve_0),
-            ))
+            );
+
+            Ok(self.token_a.after_buy_tax(amount_out))
         }
     }
 
@@ -141,6 +292,7 @@ impl AutomatedMarketMaker for UniswapV2Pool {
         amount_in: U256,
     ) -> Result<U256, AMMError> {
         if self.token_a.address == base_token {
+            let amount_in = self.token_a.after_sell_tax(amount_in);
             let amount_out = self.get_amount_out(
                 amount_in,
                 U256::from(self.reserve_0),
@@ -150,8 +302,9 @@ impl AutomatedMarketMaker for UniswapV2Pool {
             self.reserve_0 += amount_in.to::<u128>();
             self.reserve_1 -= amount_out.to::<u128>();
 
-            Ok(amount_out)
+            Ok(self.token_b.after_buy_tax(amount_out))
         } else {
+            let amount_in = self.token_b.after_sell_tax(amount_in);
             let amount_out = self.get_amount_out(
                 amount_in,
                 U256::from(self.reserve_1),
@@ -161,7 +314,65 @@ impl AutomatedMarketMaker for UniswapV2Pool {
             self.reserve_0 -= amount_out.to::<u128>();
             self.reserve_1 += amount_in.to::<u128>();
 
-            Ok(amount_out)
+            Ok(self.token_a.after_buy_tax(amount_out))
+        }
+    }
+
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        if self.token_a.address == base_token {
+            let amount_in = self.get_amount_in(
+                self.token_b.before_buy_tax(amount_out)?,
+                U256::from(self.reserve_0),
+                U256::from(self.reserve_1),
+            )?;
+
+            self.token_a.before_sell_tax(amount_in)
+        } else {
+            let amount_in = self.get_amount_in(
+                self.token_a.before_buy_tax(amount_out)?,
+                U256::from(self.reserve_1),
+                U256::from(self.reserve_0),
+            )?;
+
+            self.token_b.before_sell_tax(amount_in)
+        }
+    }
+
+    fn simulate_swap_exact_out_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        if self.token_a.address == base_token {
+            let amount_out = self.token_b.before_buy_tax(amount_out)?;
+            let amount_in = self.get_amount_in(
+                amount_out,
+                U256::from(self.reserve_0),
+                U256::from(self.reserve_1),
+            )?;
+
+            self.reserve_0 += amount_in.to::<u128>();
+            self.reserve_1 -= amount_out.to::<u128>();
+
+            self.token_a.before_sell_tax(amount_in)
+        } else {
+            let amount_out = self.token_a.before_buy_tax(amount_out)?;
+            let amount_in = self.get_amount_in(
+                amount_out,
+                U256::from(self.reserve_1),
+                U256::from(self.reserve_0),
+            )?;
+
+            self.reserve_0 -= amount_out.to::<u128>();
+            self.reserve_1 += amount_in.to::<u128>();
+
+            self.token_b.before_sell_tax(amount_in)
         }
     }
 
@@ -174,6 +385,143 @@ impl AutomatedMarketMaker for UniswapV2Pool {
         q64_to_float(price)
     }
 
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        self.calculate_price_128_x_128(base_token)
+    }
+
+    fn twap(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        seconds_ago: u32,
+        block_timestamp: u64,
+    ) -> Result<U256, AMMError> {
+        let oracle = self
+            .oracle
+            .as_ref()
+            .filter(|oracle| !oracle.observations.is_empty())
+            .ok_or(AMMError::OracleNotTracked(self.address))?;
+        if seconds_ago == 0 {
+            return self.calculate_price_exact(base_token, quote_token);
+        }
+
+        let time = block_timestamp as u32;
+        let (price_0, price_1) = self.prices_x_112();
+        let now = oracle.observe(time, 0, price_0, price_1)?;
+        let then = oracle.observe(time, seconds_ago, price_0, price_1)?;
+
+        let seconds_ago = U256::from(seconds_ago);
+        let (average_price, base_decimals, quote_decimals) = if base_token == self.token_a.address {
+            (
+                now.0.wrapping_sub(then.0) / seconds_ago,
+                self.token_a.decimals,
+                self.token_b.decimals,
+            )
+        } else {
+            (
+                now.1.wrapping_sub(then.1) / seconds_ago,
+                self.token_b.decimals,
+                self.token_a.decimals,
+            )
+        };
+
+        // UQ112x112 to Q128.128
+        adjust_decimals(average_price << 16, base_decimals, quote_decimals)
+    }
+
+    fn simulate_add_liquidity(
+        &self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        self.add_liquidity(range, amounts)
+    }
+
+    fn simulate_add_liquidity_mut(
+        &mut self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        let outcome = self.add_liquidity(range, amounts)?;
+
+        // The pair reverts when a reserve no longer fits in 112 bits
+        let add_reserve = |reserve: u128, amount: U256| {
+            u128::try_from(amount)
+                .ok()
+                .and_then(|amount| reserve.checked_add(amount))
+                .filter(|reserve| *reserve < 1 << 112)
+                .ok_or(UniswapV2Error::ReserveOverflow)
+        };
+        let reserve_0 = add_reserve(self.reserve_0, outcome.amounts[0])?;
+        let reserve_1 = add_reserve(self.reserve_1, outcome.amounts[1])?;
+
+        if self.total_supply.is_zero() {
+            self.total_supply = MINIMUM_LIQUIDITY;
+        }
+        self.total_supply += outcome.liquidity;
+        self.reserve_0 = reserve_0;
+        self.reserve_1 = reserve_1;
+
+        Ok(outcome)
+    }
+
+    fn simulate_remove_liquidity(
+        &self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        self.remove_liquidity(range, liquidity)
+    }
+
+    fn simulate_remove_liquidity_mut(
+        &mut self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        let outcome = self.remove_liquidity(range, liquidity)?;
+
+        self.total_supply -= outcome.liquidity;
+        self.reserve_0 -= outcome.amounts[0].to::<u128>();
+        self.reserve_1 -= outcome.amounts[1].to::<u128>();
+
+        Ok(outcome)
+    }
+
+    /// Encodes `swap` on the pair, requesting the amount out before the buy tax of the token out.
+    /// The amount in must be transferred to the pair first.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        let (amount_0_out, amount_1_out) = if base_token == self.token_a.address {
+            let amount_out = self.get_amount_out(
+                self.token_a.after_sell_tax(amount_in),
+                U256::from(self.reserve_0),
+                U256::from(self.reserve_1),
+            );
+            (U256::ZERO, amount_out)
+        } else {
+            let amount_out = self.get_amount_out(
+                self.token_b.after_sell_tax(amount_in),
+                U256::from(self.reserve_1),
+                U256::from(self.reserve_0),
+            );
+            (amount_out, U256::ZERO)
+        };
+
+        UniswapV2Pool::swap_calldata(self, amount_0_out, amount_1_out, recipient, vec![])
+    }
+
     async fn init<N, P>(mut self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
     where
         N: Network,
@@ -186,8 +534,18 @@ impl AutomatedMarketMaker for UniswapV2Pool {
 
         let res = deployer.call_raw().block(block_number).await?;
 
-        let pool_data =
-            <Vec<(Address, Address, u128, u128, u32, u32)> as SolValue>::abi_decode(&res)?[0];
+        let pool_data = <Vec<(
+            Address,
+            Address,
+            u128,
+            u128,
+            u32,
+            u32,
+            U256,
+            u32,
+            U256,
+            U256,
+        )> as SolValue>::abi_decode(&res)?[0];
 
         if pool_data.0.is_zero() {
             todo!("Return error");
@@ -197,11 +555,53 @@ impl AutomatedMarketMaker for UniswapV2Pool {
         self.token_b = Token::new_with_decimals(pool_data.1, pool_data.5 as u8);
         self.reserve_0 = pool_data.2;
         self.reserve_1 = pool_data.3;
-
-        // TODO: populate fee?
+        self.total_supply = pool_data.6;
+        self.block_timestamp_last = pool_data.7;
+        self.price_0_cumulative_last = pool_data.8;
+        self.price_1_cumulative_last = pool_data.9;
+        self.write_observation();
+
+        if let Some(fee) = self
+            .fee_discovery
+            .discover_fee(&self, block_number, provider)
+            .await?
+        {
+            self.fee = fee;
+        }
 
         Ok(self)
     }
+
+    /// Detects the taxes of both tokens by moving a hundredth of the pair's reserves of each.
+    async fn detect_taxes<N, P>(
+        &mut self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        for (token, reserve) in [
+            (&mut self.token_a, self.reserve_0),
+            (&mut self.token_b, self.reserve_1),
+        ] {
+            let amount = U256::from(reserve / 100);
+            if !amount.is_zero() {
+                let tax = Token::detect_tax(
+                    token.address,
+                    self.address,
+                    amount,
+                    block_number,
+                    provider.clone(),
+                )
+                .await?;
+                token.tax = Some(tax);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 pub fn u128_to_float(num: u128) -> Result<Float, AMMError> {
@@ -212,7 +612,6 @@ pub fn u128_to_float(num: u128) -> Result<Float, AMMError> {
 
 impl UniswapV2Pool {
     // Create a new, unsynced UniswapV2 pool
-    // TODO: update the init function to derive the fee
     pub fn new(address: Address, fee: usize) -> Self {
         Self {
             address,
@@ -221,6 +620,241 @@ impl UniswapV2Pool {
         }
     }
 
+    /// Creates a new, unsynced pool whose fee is discovered on `init`.
+    pub fn new_with_fee_discovery(address: Address, fee_discovery: UniswapV2FeeDiscovery) -> Self {
+        Self {
+            address,
+            fee_discovery,
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the cumulative prices of the pool's last `cardinality` updates once it is synced, so
+    /// that its TWAPs can be computed locally.
+    pub fn with_oracle(mut self, cardinality: usize) -> Self {
+        self.oracle = Some(Oracle::new(cardinality));
+        self
+    }
+
+    /// Returns the fee, in units of 0.001%, at which swapping `amount_in` against the reserves
+    /// yields `quote`.
+    pub fn calibrate_fee(
+        &self,
+        amount_in: U256,
+        reserve_in: U256,
+        reserve_out: U256,
+        quote: U256,
+    ) -> Result<usize, AMMError> {
+        let amount_out = |fee| {
+            UniswapV2Pool {
+                fee,
+                ..Default::default()
+            }
+            .get_amount_out(amount_in, reserve_in, reserve_out)
+        };
+
+        if quote.is_zero() || amount_out(0) < quote {
+            return Err(UniswapV2Error::FeeDiscoveryError(self.address).into());
+        }
+
+        // The amount out falls as the fee rises, so search for the highest fee meeting the quote
+        let (mut low, mut high) = (0, 100_000);
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if amount_out(mid) >= quote {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// Mints shares for at most `amounts` of token0 and token1, depositing them in the ratio of the
+    /// reserves as the router does.
+    fn add_liquidity(
+        &self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        if range != LiquidityRange::Full {
+            return Err(AMMError::LiquidityNotSupported(self.address));
+        }
+        let &[amount_0, amount_1] = amounts else {
+            return Err(AMMError::TokenAmountsMismatch {
+                expected: 2,
+                actual: amounts.len(),
+            });
+        };
+
+        let (reserve_0, reserve_1) = (U256::from(self.reserve_0), U256::from(self.reserve_1));
+        let (amount_0, amount_1, liquidity) = if self.total_supply.is_zero() {
+            let liquidity = (amount_0 * amount_1)
+                .root(2)
+                .saturating_sub(MINIMUM_LIQUIDITY);
+
+            (amount_0, amount_1, liquidity)
+        } else {
+            if reserve_0.is_zero() || reserve_1.is_zero() {
+                return Err(UniswapV2Error::InsufficientLiquidity.into());
+            }
+
+            let amount_1_optimal = amount_0 * reserve_1 / reserve_0;
+            let (amount_0, amount_1) = if amount_1_optimal <= amount_1 {
+                (amount_0, amount_1_optimal)
+            } else {
+                (amount_1 * reserve_0 / reserve_1, amount_1)
+            };
+            let liquidity = (amount_0 * self.total_supply / reserve_0)
+                .min(amount_1 * self.total_supply / reserve_1);
+
+            (amount_0, amount_1, liquidity)
+        };
+
+        if liquidity.is_zero() {
+            return Err(UniswapV2Error::InsufficientLiquidityMinted.into());
+        }
+
+        Ok(LiquidityOutcome {
+            liquidity,
+            amounts: vec![amount_0, amount_1],
+        })
+    }
+
+    /// Burns `liquidity` shares for their pro rata part of the reserves.
+    fn remove_liquidity(
+        &self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        if range != LiquidityRange::Full {
+            return Err(AMMError::LiquidityNotSupported(self.address));
+        }
+        if liquidity > self.total_supply {
+            return Err(UniswapV2Error::InsufficientLiquidity.into());
+        }
+
+        let amount_0 = liquidity * U256::from(self.reserve_0) / self.total_supply;
+        let amount_1 = liquidity * U256::from(self.reserve_1) / self.total_supply;
+        if amount_0.is_zero() || amount_1.is_zero() {
+            return Err(UniswapV2Error::InsufficientLiquidityBurned.into());
+        }
+
+        Ok(LiquidityOutcome {
+            liquidity,
+            amounts: vec![amount_0, amount_1],
+        })
+    }
+
+    /// Accounts for the shares minted by a `Mint` event, given the reserves after the mint.
+    ///
+    /// Shares minted to the protocol when the factory's `feeTo` is set are not observed, so the
+    /// total supply of such pairs drifts until they are synced again.
+    fn sync_mint(&mut self, amount_0: U256, amount_1: U256) {
+        let reserve_0 = U256::from(self.reserve_0).saturating_sub(amount_0);
+        let reserve_1 = U256::from(self.reserve_1).saturating_sub(amount_1);
+
+        if reserve_0.is_zero() || reserve_1.is_zero() {
+            self.total_supply = (amount_0 * amount_1).root(2);
+        } else {
+            self.total_supply += (amount_0 * self.total_supply / reserve_0)
+                .min(amount_1 * self.total_supply / reserve_1);
+        }
+
+        info!(
+            target = "amm::uniswap_v2::sync",
+            address = ?self.address,
+            total_supply = ?self.total_supply, "Mint"
+        );
+    }
+
+    /// Accounts for the shares burned by a `Burn` event, given the reserves after the burn.
+    ///
+    /// The pair rounds the amounts it pays out down, so the shares burned are the fewest that pay
+    /// out both amounts.
+    fn sync_burn(&mut self, amount_0: U256, amount_1: U256) {
+        let liquidity = [
+            (amount_0, U256::from(self.reserve_0) + amount_0),
+            (amount_1, U256::from(self.reserve_1) + amount_1),
+        ]
+        .into_iter()
+        .filter(|(_, balance)| !balance.is_zero())
+        .map(|(amount, balance)| (amount * self.total_supply).div_ceil(balance))
+        .max()
+        .unwrap_or_default();
+
+        self.total_supply = self.total_supply.saturating_sub(liquidity);
+
+        info!(
+            target = "amm::uniswap_v2::sync",
+            address = ?self.address,
+            total_supply = ?self.total_supply, "Burn"
+        );
+    }
+
+    /// Accrues the cumulative prices at the current reserves up to the block of a `Sync` log, as the
+    /// pair does on its first update in a block.
+    ///
+    /// Logs without a block timestamp leave the cumulative prices out of date, so the oracle stops
+    /// being tracked until the pool is synced again.
+    fn sync_cumulative_prices(&mut self, log: &Log) {
+        let Some(block_timestamp) = log.block_timestamp else {
+            if self.oracle.take().is_some() {
+                tracing::warn!(
+                    target = "amm::uniswap_v2::sync",
+                    address = ?self.address,
+                    "Log has no block timestamp, the oracle is no longer tracked"
+                );
+            }
+            return;
+        };
+
+        let block_timestamp = block_timestamp as u32;
+        let time_elapsed = U256::from(block_timestamp.wrapping_sub(self.block_timestamp_last));
+        if time_elapsed.is_zero() {
+            return;
+        }
+
+        let (price_0, price_1) = self.prices_x_112();
+        self.price_0_cumulative_last = self
+            .price_0_cumulative_last
+            .wrapping_add(price_0 * time_elapsed);
+        self.price_1_cumulative_last = self
+            .price_1_cumulative_last
+            .wrapping_add(price_1 * time_elapsed);
+        self.block_timestamp_last = block_timestamp;
+
+        self.write_observation();
+    }
+
+    /// Records the cumulative prices as of the last update in the oracle, if the pool tracks it.
+    fn write_observation(&mut self) {
+        let observation = Observation {
+            block_timestamp: self.block_timestamp_last,
+            price_0_cumulative: self.price_0_cumulative_last,
+            price_1_cumulative: self.price_1_cumulative_last,
+        };
+
+        if let Some(oracle) = &mut self.oracle {
+            oracle.write(observation);
+        }
+    }
+
+    /// Returns the prices of token0 and token1 at the current reserves as UQ112x112 numbers, or
+    /// zero when the pool is empty, as the pair accumulates them.
+    pub fn prices_x_112(&self) -> (U256, U256) {
+        if self.reserve_0 == 0 || self.reserve_1 == 0 {
+            return (U256::ZERO, U256::ZERO);
+        }
+
+        let (reserve_0, reserve_1) = (U256::from(self.reserve_0), U256::from(self.reserve_1));
+        (
+            (reserve_1 << 112) / reserve_0,
+            (reserve_0 << 112) / reserve_1,
+        )
+    }
+
     /// Calculates the amount received for a given `amount_in` `reserve_in` and `reserve_out`.
     pub fn get_amount_out(&self, amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
         if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
@@ -234,6 +868,55 @@ impl UniswapV2Pool {
         numerator / denominator
     }
 
+    /// Calculates the amount required to receive `amount_out` for a given `reserve_in` and `reserve_out`.
+    pub fn get_amount_in(
+        &self,
+        amount_out: U256,
+        reserve_in: U256,
+        reserve_out: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_out.is_zero() {
+            return Ok(U256::ZERO);
+        }
+        if reserve_in.is_zero() || amount_out >= reserve_out {
+            return Err(UniswapV2Error::InsufficientLiquidity.into());
+        }
+        let numerator = reserve_in * amount_out * U256_100000;
+        let denominator = (reserve_out - amount_out) * (U256_100000 - U256::from(self.fee));
+
+        Ok(numerator / denominator + U256_1)
+    }
+
+    /// Calculates the amount of `base_token` to sell for the marginal rate of the swap, the rate at
+    /// which its amount out grows with its amount in, to fall to `marginal_rate`. The rate is in raw
+    /// token units, after fees and taxes.
+    ///
+    /// Returns zero if the marginal rate is already at or below `marginal_rate`.
+    pub fn amount_in_to_marginal_rate(
+        &self,
+        base_token: Address,
+        marginal_rate: f64,
+    ) -> Result<U256, AMMError> {
+        let (token_in, token_out, reserve_in, reserve_out) = if self.token_a.address == base_token {
+            (&self.token_a, &self.token_b, self.reserve_0, self.reserve_1)
+        } else {
+            (&self.token_b, &self.token_a, self.reserve_1, self.reserve_0)
+        };
+        let marginal_rate = token_in.untaxed_marginal_rate(token_out, marginal_rate);
+
+        // The marginal rate after the pool receives x is fee * r_in * r_out / (r_in + fee * x)^2
+        let fee: Float = Float::with_val(MPFR_T_PRECISION, 100_000 - self.fee) / 100_000;
+        let reserve_in = u128_to_float(reserve_in)?;
+        let reserve_out = u128_to_float(reserve_out)?;
+        let amount_received =
+            ((fee.clone() * &reserve_in * reserve_out / marginal_rate).sqrt() - reserve_in) / fee;
+
+        // Grossing up an amount past `U256::MAX` for the sell tax saturates as well
+        Ok(token_in
+            .before_sell_tax(float_to_u256_saturating(amount_received))
+            .unwrap_or(U256::MAX))
+    }
+
     /// Calculates the price of the base token in terms of the quote token.
     ///
     /// Returned as a Q64 fixed point number.
@@ -266,6 +949,39 @@ impl UniswapV2Pool {
         }
     }
 
+    /// Calculates the price of the base token in terms of the quote token.
+    ///
+    /// Returned as a Q128 fixed point number.
+    pub fn calculate_price_128_x_128(&self, base_token: Address) -> Result<U256, AMMError> {
+        let (reserve_base, base_decimals, reserve_quote, quote_decimals) =
+            if base_token == self.token_a.address {
+                (
+                    self.reserve_0,
+                    self.token_a.decimals,
+                    self.reserve_1,
+                    self.token_b.decimals,
+                )
+            } else {
+                (
+                    self.reserve_1,
+                    self.token_b.decimals,
+                    self.reserve_0,
+                    self.token_a.decimals,
+                )
+            };
+
+        if reserve_base == 0 {
+            return Ok(Q128);
+        }
+
+        ratio_to_q128(
+            U256::from(reserve_quote),
+            quote_decimals,
+            U256::from(reserve_base),
+            base_decimals,
+        )
+    }
+
     pub fn swap_calldata(
         &self,
         amount_0_out: U256,
@@ -368,8 +1084,14 @@ pub fn div_uu(x: U256, y: U256) -> Result<u128, AMMError> {
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct UniswapV2Factory {
     pub address: Address,
+    /// Fee of the factory's pools in units of 0.001%, kept unless the fee discovery finds another
     pub fee: usize,
     pub creation_block: u64,
+    #[serde(default)]
+    pub fee_discovery: UniswapV2FeeDiscovery,
+    /// Number of updates whose cumulative prices the factory's pools keep, or zero to not track them
+    #[serde(default)]
+    pub oracle_cardinality: usize,
 }
 
 impl UniswapV2Factory {
@@ -378,9 +1100,34 @@ impl UniswapV2Factory {
             address,
             creation_block,
             fee,
+            fee_discovery: UniswapV2FeeDiscovery::default(),
+            oracle_cardinality: 0,
         }
     }
 
+    /// Creates a factory whose pools discover their fee when synced, starting from `fee`.
+    pub fn new_with_fee_discovery(
+        address: Address,
+        fee: usize,
+        creation_block: u64,
+        fee_discovery: UniswapV2FeeDiscovery,
+    ) -> Self {
+        Self {
+            address,
+            creation_block,
+            fee,
+            fee_discovery,
+            oracle_cardinality: 0,
+        }
+    }
+
+    /// Keeps the cumulative prices of the last `cardinality` updates of the factory's pools, so
+    /// that their TWAPs can be computed locally.
+    pub fn with_oracle(mut self, cardinality: usize) -> Self {
+        self.oracle_cardinality = cardinality;
+        self
+    }
+
     pub async fn get_all_pairs<N, P>(
         factory_address: Address,
         block_number: BlockId,
@@ -458,13 +1205,37 @@ impl UniswapV2Factory {
             futures_unordered.push(async move {
                 let res = deployer.call_raw().block(block_number).await?;
 
-                let return_data =
-                    <Vec<(Address, Address, u128, u128, u32, u32)> as SolValue>::abi_decode(&res)?;
-
-                Ok::<(Vec<Address>, Vec<(Address, Address, u128, u128, u32, u32)>), AMMError>((
-                    group,
-                    return_data,
-                ))
+                let return_data = <Vec<(
+                    Address,
+                    Address,
+                    u128,
+                    u128,
+                    u32,
+                    u32,
+                    U256,
+                    u32,
+                    U256,
+                    U256,
+                )> as SolValue>::abi_decode(&res)?;
+
+                Ok::<
+                    (
+                        Vec<Address>,
+                        Vec<(
+                            Address,
+                            Address,
+                            u128,
+                            u128,
+                            u32,
+                            u32,
+                            U256,
+                            u32,
+                            U256,
+                            U256,
+                        )>,
+                    ),
+                    AMMError,
+                >((group, return_data))
             });
         }
 
@@ -493,19 +1264,53 @@ impl UniswapV2Factory {
                 pool.token_b = Token::new_with_decimals(pool_data.1, pool_data.5 as u8);
                 pool.reserve_0 = pool_data.2;
                 pool.reserve_1 = pool_data.3;
+                pool.total_supply = pool_data.6;
+                pool.block_timestamp_last = pool_data.7;
+                pool.price_0_cumulative_last = pool_data.8;
+                pool.price_1_cumulative_last = pool_data.9;
+                pool.write_observation();
             }
         }
 
-        let amms = amms
-            .into_iter()
-            .filter_map(|(_, amm)| {
-                if amm.tokens().iter().any(|t| t.is_zero()) {
-                    None
-                } else {
-                    Some(amm)
-                }
-            })
-            .collect();
+        let mut amms = amms
+            .into_values()
+            .filter(|amm| !amm.tokens().iter().any(|t| t.is_zero()))
+            .collect::<Vec<_>>();
+
+        let mut futures = vec![];
+        for (i, amm) in amms.iter().enumerate() {
+            let AMM::UniswapV2Pool(pool) = amm else {
+                continue;
+            };
+            if pool.fee_discovery == UniswapV2FeeDiscovery::Fixed {
+                continue;
+            }
+
+            let provider = provider.clone();
+            futures.push(async move {
+                let fee = pool
+                    .fee_discovery
+                    .discover_fee(pool, block_number, provider)
+                    .await?;
+
+                Ok::<(usize, Option<usize>), AMMError>((i, fee))
+            });
+        }
+
+        // Fee discovery takes a call per pool, so only a bounded number are in flight at once
+        let mut fee_stream =
+            futures::stream::iter(futures).buffer_unordered(FEE_DISCOVERY_CONCURRENCY);
+        let mut fees = vec![];
+        while let Some(res) = fee_stream.next().await {
+            fees.push(res?);
+        }
+        drop(fee_stream);
+
+        for (i, fee) in fees {
+            if let (Some(fee), AMM::UniswapV2Pool(pool)) = (fee, &mut amms[i]) {
+                pool.fee = fee;
+            }
+        }
 
         Ok(amms)
     }
@@ -531,6 +1336,10 @@ impl AutomatedMarketMakerFactory for UniswapV2Factory {
             reserve_0: 0,
             reserve_1: 0,
             fee: self.fee,
+            fee_discovery: self.fee_discovery,
+            total_supply: U256::ZERO,
+            oracle: (self.oracle_cardinality > 0).then(|| Oracle::new(self.oracle_cardinality)),
+            ..Default::default()
         }))
     }
 
@@ -570,6 +1379,11 @@ impl DiscoverySync for UniswapV2Factory {
                         reserve_0: 0,
                         reserve_1: 0,
                         fee: self.fee,
+                        fee_discovery: self.fee_discovery,
+                        total_supply: U256::ZERO,
+                        oracle: (self.oracle_cardinality > 0)
+                            .then(|| Oracle::new(self.oracle_cardinality)),
+                        ..Default::default()
                     })
                 })
                 .collect())
@@ -599,9 +1413,17 @@ impl DiscoverySync for UniswapV2Factory {
 #[cfg(test)]
 mod tests {
     use crate::amms::{
-        amm::AutomatedMarketMaker, consts::U256_100000, uniswap_v2::UniswapV2Pool, Token,
+        amm::{AutomatedMarketMaker, LiquidityRange},
+        consts::{Q128, U256_100000},
+        error::AMMError,
+        uniswap_v2::{IUniswapV2Pair, UniswapV2Pool},
+        Token, TokenTax,
+    };
+    use alloy::{
+        primitives::{address, aliases::U112, Address, U256},
+        rpc::types::Log,
+        sol_types::{SolCall, SolEvent},
     };
-    use alloy::primitives::{address, Address, U256};
 
     #[test]
     fn test_get_amount_out() {
@@ -621,6 +1443,343 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_amount_in() {
+        let fees = [125, 150, 300, 1000]; // 0.125%, 0.15%, 0.3%, 1%
+        let reserve_in = U256::from(100).pow(U256::from(18));
+        let reserve_out = U256::from(100).pow(U256::from(18));
+        for fee in fees {
+            let pool = UniswapV2Pool {
+                fee,
+                ..Default::default()
+            };
+
+            for amount_out in [U256::from(1), U256::from(10).pow(U256::from(18))] {
+                let amount_in = pool
+                    .get_amount_in(amount_out, reserve_in, reserve_out)
+                    .unwrap();
+
+                // The smallest amount in that covers the amount out
+                assert!(pool.get_amount_out(amount_in, reserve_in, reserve_out) >= amount_out);
+                assert!(
+                    pool.get_amount_out(amount_in - U256::from(1), reserve_in, reserve_out)
+                        < amount_out
+                );
+            }
+        }
+
+        let pool = UniswapV2Pool::default();
+        assert!(pool
+            .get_amount_in(reserve_out, reserve_in, reserve_out)
+            .is_err());
+    }
+
+    #[test]
+    fn test_amount_in_to_marginal_rate() -> eyre::Result<()> {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let pool = UniswapV2Pool {
+            token_a: Token::new_with_decimals(dai, 18),
+            token_b: Token::new_with_decimals(weth, 18).with_tax(TokenTax {
+                sell: 100,
+                ..Default::default()
+            }),
+            reserve_0: 2 * 10_u128.pow(24),
+            reserve_1: 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        };
+
+        // The marginal rate of a swap of `amount_in` after taxes, measured over a small increment
+        let probe = U256::from(10_u64.pow(12));
+        let marginal_rate = |amount_in: U256| -> eyre::Result<f64> {
+            let amount_out = pool.simulate_swap(weth, dai, amount_in)?;
+            let probed_out = pool.simulate_swap(weth, dai, amount_in + probe)?;
+            Ok(f64::from(probed_out - amount_out) / f64::from(probe))
+        };
+        let spot_rate = marginal_rate(U256::ZERO)?;
+
+        assert_eq!(
+            pool.amount_in_to_marginal_rate(weth, spot_rate * 1.1)?,
+            U256::ZERO
+        );
+
+        let target_rate = spot_rate * 0.9;
+        let amount_in = pool.amount_in_to_marginal_rate(weth, target_rate)?;
+        assert!((marginal_rate(amount_in)? / target_rate - 1.0).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_with_tax() -> eyre::Result<()> {
+        let tax = TokenTax {
+            buy: 500,
+            sell: 1000,
+            transfer: 0,
+        };
+        let pool = UniswapV2Pool {
+            token_a: Token::new_with_decimals(
+                address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                18,
+            )
+            .with_tax(tax),
+            token_b: Token::new_with_decimals(
+                address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                18,
+            ),
+            reserve_0: 10_u128.pow(24),
+            reserve_1: 10_u128.pow(24),
+            fee: 300,
+            ..Default::default()
+        };
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let (reserve_0, reserve_1) = (U256::from(pool.reserve_0), U256::from(pool.reserve_1));
+        let amount = U256::from(10_u128.pow(20));
+
+        // The pool receives 90% of what is sold
+        assert_eq!(
+            pool.simulate_swap(token_a, token_b, amount)?,
+            pool.get_amount_out(U256::from(9 * 10_u128.pow(19)), reserve_0, reserve_1)
+        );
+        // and 95% of what it sends arrives
+        let amount_out = pool.get_amount_out(amount, reserve_1, reserve_0);
+        assert_eq!(
+            pool.simulate_swap(token_b, token_a, amount)?,
+            amount_out - amount_out * U256::from(5) / U256::from(100)
+        );
+
+        for (base_token, quote_token) in [(token_a, token_b), (token_b, token_a)] {
+            let amount_in = pool.simulate_swap_exact_out(base_token, quote_token, amount)?;
+            assert!(pool.simulate_swap(base_token, quote_token, amount_in)? >= amount);
+            assert!(
+                pool.simulate_swap(base_token, quote_token, amount_in - U256::from(1))? < amount
+            );
+        }
+
+        let mut pool = pool;
+        pool.token_a.tax = Some(TokenTax {
+            sell: 10_000,
+            ..tax
+        });
+        assert!(pool
+            .simulate_swap_exact_out(token_a, token_b, amount)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap_calldata() -> eyre::Result<()> {
+        let pool = UniswapV2Pool {
+            token_a: Token::new_with_decimals(
+                address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                6,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                18,
+            ),
+            reserve_0: 10_u128.pow(12),
+            reserve_1: 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        };
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let recipient = address!("00000000000000000000000000000000000000aa");
+        let amount_in = U256::from(10_u128.pow(9));
+        let amount_out = pool.simulate_swap(token_a, token_b, amount_in)?;
+
+        let calldata = AutomatedMarketMaker::swap_calldata(
+            &pool, token_a, token_b, amount_in, amount_out, recipient,
+        )?;
+        let call = IUniswapV2Pair::swapCall::abi_decode(&calldata)?;
+        assert_eq!(call.amount0Out, U256::ZERO);
+        assert_eq!(call.amount1Out, amount_out);
+        assert_eq!(call.to, recipient);
+
+        assert!(matches!(
+            AutomatedMarketMaker::swap_calldata(
+                &pool,
+                token_a,
+                token_b,
+                amount_in,
+                amount_out + U256::from(1),
+                recipient,
+            ),
+            Err(AMMError::InsufficientAmountOut { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_twap() -> eyre::Result<()> {
+        let mut pool = UniswapV2Pool {
+            token_a: Token::new_with_decimals(Address::with_last_byte(1), 18),
+            token_b: Token::new_with_decimals(Address::with_last_byte(2), 18),
+            reserve_0: 10_u128.pow(18),
+            reserve_1: 2 * 10_u128.pow(18),
+            block_timestamp_last: 1_000,
+            ..Default::default()
+        };
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        assert!(matches!(
+            pool.twap(token_a, token_b, 10, 1_000),
+            Err(AMMError::OracleNotTracked(_))
+        ));
+
+        pool = pool.with_oracle(8);
+        pool.write_observation();
+
+        // The price of token0 moves from 2 to 4 after 10 seconds
+        let sync_event = IUniswapV2Pair::Sync {
+            reserve0: U112::from(10_u128.pow(18)),
+            reserve1: U112::from(4 * 10_u128.pow(18)),
+        };
+        pool.sync(&Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: sync_event.encode_log_data(),
+            },
+            block_timestamp: Some(1_010),
+            ..Default::default()
+        })?;
+        assert_eq!(pool.block_timestamp_last, 1_010);
+        assert_eq!(pool.price_0_cumulative_last, U256::from(20) << 112);
+
+        assert_eq!(
+            pool.twap(token_a, token_b, 30, 1_030)?,
+            ((U256::from(100) << 112) / U256::from(30)) << 16
+        );
+        assert_eq!(
+            pool.twap(token_a, token_b, 20, 1_030)?,
+            Q128 * U256::from(4)
+        );
+        assert_eq!(
+            pool.twap(token_b, token_a, 20, 1_030)?,
+            Q128 / U256::from(4)
+        );
+        assert!(pool.twap(token_a, token_b, 31, 1_030).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_liquidity() -> eyre::Result<()> {
+        let pool = UniswapV2Pool {
+            reserve_0: 10_u128.pow(24),
+            reserve_1: 2 * 10_u128.pow(24),
+            total_supply: U256::from(10_u128.pow(24)),
+            fee: 300,
+            ..Default::default()
+        };
+
+        // Deposits in the ratio of the reserves, leaving the excess token1 unused
+        let amounts = [U256::from(10_u128.pow(20)), U256::from(5 * 10_u128.pow(20))];
+        let outcome = pool.simulate_add_liquidity(LiquidityRange::Full, &amounts)?;
+        assert_eq!(outcome.liquidity, U256::from(10_u128.pow(20)));
+        assert_eq!(
+            outcome.amounts,
+            vec![U256::from(10_u128.pow(20)), U256::from(2 * 10_u128.pow(20))]
+        );
+
+        let mut minted = pool.clone();
+        assert_eq!(
+            minted.simulate_add_liquidity_mut(LiquidityRange::Full, &amounts)?,
+            outcome
+        );
+        assert_eq!(minted.total_supply, pool.total_supply + outcome.liquidity);
+
+        // The shares minted are reconstructed from the `Sync` and `Mint` events of the pair
+        let mut synced = pool.clone();
+        for log_data in [
+            IUniswapV2Pair::Sync {
+                reserve0: U112::from(minted.reserve_0),
+                reserve1: U112::from(minted.reserve_1),
+            }
+            .encode_log_data(),
+            IUniswapV2Pair::Mint {
+                sender: Address::default(),
+                amount0: outcome.amounts[0],
+                amount1: outcome.amounts[1],
+            }
+            .encode_log_data(),
+        ] {
+            synced.sync(&Log {
+                inner: alloy::primitives::Log {
+                    address: pool.address,
+                    data: log_data,
+                },
+                ..Default::default()
+            })?;
+        }
+        assert_eq!(synced.total_supply, minted.total_supply);
+
+        let removed =
+            minted.simulate_remove_liquidity_mut(LiquidityRange::Full, outcome.liquidity)?;
+        assert_eq!(removed.amounts, outcome.amounts);
+        assert_eq!(
+            (minted.reserve_0, minted.reserve_1, minted.total_supply),
+            (pool.reserve_0, pool.reserve_1, pool.total_supply)
+        );
+
+        assert!(pool
+            .simulate_remove_liquidity(LiquidityRange::Full, pool.total_supply + U256::from(1))
+            .is_err());
+
+        // Deposits pushing a reserve past 112 bits revert and leave the pool untouched
+        let mut overflowed = pool.clone();
+        let amounts = [U256::from(1_u128 << 111), U256::from(1_u128 << 112)];
+        assert!(overflowed
+            .simulate_add_liquidity_mut(LiquidityRange::Full, &amounts)
+            .is_err());
+        assert_eq!(
+            (overflowed.reserve_0, overflowed.reserve_1),
+            (pool.reserve_0, pool.reserve_1)
+        );
+        assert!(pool
+            .simulate_add_liquidity(
+                LiquidityRange::Ticks {
+                    tick_lower: -60,
+                    tick_upper: 60
+                },
+                &amounts
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calibrate_fee() {
+        let pool = UniswapV2Pool::default();
+        let reserve_in = U256::from(47092140895915_u128);
+        let reserve_out = U256::from(28396598565590008529300_u128);
+        let amount_in = reserve_in / U256::from(1000);
+
+        // 0.17%, 0.25%, 0.3% and a per-pair 0.1%
+        for fee in [170, 250, 300, 100] {
+            let quote = UniswapV2Pool {
+                fee,
+                ..Default::default()
+            }
+            .get_amount_out(amount_in, reserve_in, reserve_out);
+
+            assert_eq!(
+                pool.calibrate_fee(amount_in, reserve_in, reserve_out, quote)
+                    .unwrap(),
+                fee
+            );
+        }
+
+        // A quote above the constant product amount can not come from a fee
+        let quote = pool.get_amount_out(amount_in, reserve_in, reserve_out) + U256::from(1);
+        assert!(pool
+            .calibrate_fee(amount_in, reserve_in, reserve_out, quote)
+            .is_err());
+    }
+
     #[test]
     fn test_calculate_price_edge_case() {
         let token_a = address!("0d500b1d8e8ef31e21c99d1db9a6444d3adf1270");
@@ -638,6 +1797,7 @@ mod tests {
             reserve_0: 23595096345912178729927,
             reserve_1: 154664232014390554564,
             fee: 300,
+            ..Default::default()
         };
 
         assert!(pool.calculate_price(token_a, Address::default()).unwrap() != 0.0);
@@ -659,6 +1819,7 @@ mod tests {
             reserve_0: 47092140895915,
             reserve_1: 28396598565590008529300,
             fee: 300,
+            ..Default::default()
         };
 
         let price_a_64_x = pool
@@ -689,6 +1850,7 @@ mod tests {
             reserve_0: 47092140895915,
             reserve_1: 28396598565590008529300,
             fee: 300,
+            ..Default::default()
         };
 
         let price_a_64_x = pool.calculate_price_64_x_64(pool.token_a.address).unwrap();
@@ -697,4 +1859,49 @@ mod tests {
         assert_eq!(30591574867092394336528, price_b_64_x);
         assert_eq!(11123401407064628, price_a_64_x);
     }
+
+    #[test]
+    fn test_calculate_price_128_x_128() {
+        let pool = UniswapV2Pool {
+            address: address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"),
+            token_a: Token::new_with_decimals(
+                address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                6,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                18,
+            ),
+            reserve_0: 47092140895915,
+            reserve_1: 28396598565590008529300,
+            fee: 300,
+            ..Default::default()
+        };
+
+        let price_a_128_x = pool
+            .calculate_price_128_x_128(pool.token_a.address)
+            .unwrap();
+        let price_b_128_x = pool
+            .calculate_price_exact(pool.token_b.address, Address::default())
+            .unwrap();
+
+        assert_eq!(
+            U256::from_str_radix("564314952384978689353671347908274706391168", 10).unwrap(),
+            price_b_128_x
+        );
+        assert_eq!(
+            U256::from(205190538985261922773315857767409556_u128),
+            price_a_128_x
+        );
+
+        // Truncating to Q64 gives the Q64 price
+        assert_eq!(
+            U256::from(pool.calculate_price_64_x_64(pool.token_b.address).unwrap()),
+            price_b_128_x >> 64
+        );
+        assert_eq!(
+            U256::from(pool.calculate_price_64_x_64(pool.token_a.address).unwrap()),
+            price_a_128_x >> 64
+        );
+    }
 }
@@ -0,0 +1,127 @@
+//! Cumulative prices of a Uniswap V2 pair, kept to compute time weighted average prices locally.
+//!
+//! The pair accumulates the price of each token every second, on its first update in a block.
+//! Keeping the cumulative prices of the last updates lets a TWAP over any window they cover be
+//! computed without querying a historical state.
+
+use std::collections::VecDeque;
+
+use alloy::primitives::U256;
+use serde::{Deserialize, Serialize};
+
+use super::UniswapV2Error;
+
+/// The cumulative prices of a pair after an update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Observation {
+    pub block_timestamp: u32,
+    pub price_0_cumulative: U256,
+    pub price_1_cumulative: U256,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Oracle {
+    /// Number of observations kept
+    pub cardinality: usize,
+    /// Observations of consecutive updates, oldest first
+    pub observations: VecDeque<Observation>,
+}
+
+impl Oracle {
+    pub fn new(cardinality: usize) -> Self {
+        Self {
+            cardinality,
+            observations: VecDeque::with_capacity(cardinality),
+        }
+    }
+
+    /// Records the cumulative prices after an update, dropping the oldest observation once the
+    /// cardinality is reached.
+    pub fn write(&mut self, observation: Observation) {
+        if self.cardinality == 0 {
+            return;
+        }
+
+        if self.observations.len() == self.cardinality {
+            self.observations.pop_front();
+        }
+        self.observations.push_back(observation);
+    }
+
+    /// Returns the cumulative prices of token0 and token1 `seconds_ago` seconds before `time`,
+    /// given the UQ112x112 prices since the last update.
+    pub fn observe(
+        &self,
+        time: u32,
+        seconds_ago: u32,
+        price_0: U256,
+        price_1: U256,
+    ) -> Result<(U256, U256), UniswapV2Error> {
+        let age = |observation: &Observation| time.wrapping_sub(observation.block_timestamp);
+
+        let Some(last) = self.observations.back() else {
+            return Err(UniswapV2Error::OracleTooOld);
+        };
+
+        // The prices since the last update hold until `time`
+        if seconds_ago <= age(last) {
+            let elapsed = U256::from(age(last) - seconds_ago);
+            return Ok((
+                last.price_0_cumulative.wrapping_add(price_0 * elapsed),
+                last.price_1_cumulative.wrapping_add(price_1 * elapsed),
+            ));
+        }
+
+        // Otherwise the prices between the two updates surrounding the target held throughout
+        let (before, after) = self
+            .observations
+            .iter()
+            .zip(self.observations.iter().skip(1))
+            .rev()
+            .find(|(before, _)| age(before) >= seconds_ago)
+            .ok_or(UniswapV2Error::OracleTooOld)?;
+
+        let observation_time_delta = U256::from(age(before) - age(after));
+        let target_delta = U256::from(age(before) - seconds_ago);
+        let interpolate = |before: U256, after: U256| {
+            before.wrapping_add(after.wrapping_sub(before) / observation_time_delta * target_delta)
+        };
+
+        Ok((
+            interpolate(before.price_0_cumulative, after.price_0_cumulative),
+            interpolate(before.price_1_cumulative, after.price_1_cumulative),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observe() -> eyre::Result<()> {
+        let mut oracle = Oracle::new(2);
+        for (block_timestamp, price_0_cumulative) in [(1_000, 0), (1_010, 20), (1_030, 80)] {
+            oracle.write(Observation {
+                block_timestamp,
+                price_0_cumulative: U256::from(price_0_cumulative),
+                price_1_cumulative: U256::ZERO,
+            });
+        }
+
+        // The first observation is dropped, then the price moves from 3 to 5
+        assert_eq!(oracle.observations.len(), 2);
+        let observe = |seconds_ago| {
+            oracle
+                .observe(1_040, seconds_ago, U256::from(5), U256::ZERO)
+                .map(|(price_0_cumulative, _)| price_0_cumulative.to::<u64>())
+        };
+        assert_eq!(observe(0)?, 130);
+        assert_eq!(observe(10)?, 80);
+        assert_eq!(observe(20)?, 50);
+        assert_eq!(observe(30)?, 20);
+        assert!(observe(31).is_err());
+
+        Ok(())
+    }
+}
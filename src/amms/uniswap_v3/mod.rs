@@ -1,8 +1,16 @@
+pub mod oracle;
+
 use super::{
-    amm::{AutomatedMarketMaker, AMM},
+    amm::{
+        check_amount_out, AutomatedMarketMaker, LiquidityOutcome, LiquidityRange, SwapOutcome, AMM,
+    },
+    consts::{MPFR_T_PRECISION, Q128, Q64, Q96, U256_10000},
     error::{AMMError, BatchContractError},
     factory::{AutomatedMarketMakerFactory, DiscoverySync},
-    get_token_decimals, Token,
+    float::{float_to_u256_saturating, u256_to_float},
+    get_token_decimals,
+    price::adjust_decimals,
+    Token,
 };
 use crate::amms::{
     consts::U256_1, uniswap_v3::GetUniswapV3PoolTickBitmapBatchRequest::TickBitmapInfo,
@@ -18,7 +26,9 @@ use alloy::{
     transports::BoxFuture,
 };
 use futures::{stream::FuturesUnordered, StreamExt};
+use oracle::{Observation, Oracle};
 use rayon::iter::{IntoParallelRefIterator, ParallelDrainRange, ParallelIterator};
+use rug::Float;
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering,
@@ -30,7 +40,9 @@ use std::{
 use thiserror::Error;
 use tracing::info;
 use uniswap_v3_math::error::UniswapV3MathError;
+use uniswap_v3_math::full_math::mul_div;
 use uniswap_v3_math::tick_math::{MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK};
+use GetUniswapV3PoolObservationsBatchRequest::ObservationsInfo;
 use GetUniswapV3PoolTickDataBatchRequest::TickDataInfo;
 
 sol! {
@@ -83,6 +95,15 @@ sol! {
             uint128 liquidity,
             int24 tick
         );
+
+        /// @notice Emitted when the protocol fee is changed by the pool
+        event SetFeeProtocol(uint8 feeProtocol0Old, uint8 feeProtocol1Old, uint8 feeProtocol0New, uint8 feeProtocol1New);
+
+        /// @notice Emitted by the pool for increases to the number of observations that can be stored
+        event IncreaseObservationCardinalityNext(
+            uint16 observationCardinalityNextOld,
+            uint16 observationCardinalityNextNew
+        );
     }
 
 
@@ -96,6 +117,28 @@ sol! {
         function token1() external view returns (address);
 
     }
+
+    #[allow(clippy::too_many_arguments)]
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract IPancakeV3PoolEvents {
+        /// @notice Emitted by the pool for any swaps between token0 and token1, including the protocol fees taken
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint128 protocolFeesToken0,
+            uint128 protocolFeesToken1
+        );
+
+        /// @notice Emitted when the protocol fee is changed by the pool, in units of 0.01% of the swap fee
+        event SetFeeProtocol(uint32 feeProtocol0Old, uint32 feeProtocol1Old, uint32 feeProtocol0New, uint32 feeProtocol1New);
+    }
+
 }
 
 sol! {
@@ -116,12 +159,110 @@ sol! {
     "src/amms/abi/GetUniswapV3PoolTickDataBatchRequest.json"
 }
 
+sol! {
+    #[sol(rpc)]
+    GetUniswapV3PoolObservationsBatchRequest,
+    "src/amms/abi/GetUniswapV3PoolObservationsBatchRequest.json"
+}
+
 #[derive(Error, Debug)]
 pub enum UniswapV3Error {
     #[error(transparent)]
     UniswapV3MathError(#[from] UniswapV3MathError),
     #[error("Liquidity Underflow")]
     LiquidityUnderflow,
+    #[error("Insufficient liquidity to fill the amount out")]
+    InsufficientLiquidity,
+    #[error("Invalid sqrt price limit")]
+    InvalidSqrtPriceLimit,
+    #[error("Invalid tick range")]
+    InvalidTickRange,
+    #[error("Liquidity overflow")]
+    LiquidityOverflow,
+    #[error("Observation is older than the oldest one in the oracle")]
+    OracleTooOld,
+}
+
+/// The Uniswap V3 deployment a factory and its pools belong to.
+///
+/// Forks share the Uniswap V3 tick model but differ in their event layouts and protocol fee
+/// encoding, so the fork decides which logs a pool is synced from and how its protocol fee is read. A state space can track
+/// several forks by adding one `UniswapV3Factory` per deployment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UniswapV3Fork {
+    #[default]
+    UniswapV3,
+    /// Emits an extended `Swap` event carrying the protocol fees taken by the swap
+    PancakeSwapV3,
+    SushiSwapV3,
+}
+
+impl UniswapV3Fork {
+    /// Returns the signature of the event emitted by pools for every swap.
+    pub fn swap_event(&self) -> B256 {
+        match self {
+            UniswapV3Fork::PancakeSwapV3 => IPancakeV3PoolEvents::Swap::SIGNATURE_HASH,
+            _ => IUniswapV3PoolEvents::Swap::SIGNATURE_HASH,
+        }
+    }
+
+    /// Returns the signature of the event emitted by pools when the protocol fee changes.
+    pub fn set_fee_protocol_event(&self) -> B256 {
+        match self {
+            UniswapV3Fork::PancakeSwapV3 => IPancakeV3PoolEvents::SetFeeProtocol::SIGNATURE_HASH,
+            _ => IUniswapV3PoolEvents::SetFeeProtocol::SIGNATURE_HASH,
+        }
+    }
+
+    /// Packs the protocol fees of token0 and token1 as slot0 stores them.
+    pub fn pack_fee_protocol(&self, fee_protocol_0: u32, fee_protocol_1: u32) -> u32 {
+        match self {
+            UniswapV3Fork::PancakeSwapV3 => fee_protocol_0 + (fee_protocol_1 << 16),
+            _ => fee_protocol_0 + (fee_protocol_1 << 4),
+        }
+    }
+
+    /// Decodes the protocol fee charged on swaps in the given direction from the packed
+    /// `fee_protocol` of slot0.
+    pub fn protocol_fee(&self, fee_protocol: u32, zero_for_one: bool) -> ProtocolFee {
+        match self {
+            UniswapV3Fork::PancakeSwapV3 => ProtocolFee::BasisPoints(if zero_for_one {
+                fee_protocol % 65536
+            } else {
+                fee_protocol >> 16
+            }),
+            _ => ProtocolFee::Fraction(if zero_for_one {
+                fee_protocol % 16
+            } else {
+                fee_protocol >> 4
+            }),
+        }
+    }
+}
+
+/// The share of each swap step's fee that the protocol takes before the rest accrues to liquidity
+/// providers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProtocolFee {
+    #[default]
+    None,
+    /// `1 / denominator` of the fee, or nothing for a zero denominator
+    Fraction(u32),
+    /// `share / 10000` of the fee
+    BasisPoints(u32),
+}
+
+impl ProtocolFee {
+    /// Returns the part of `fee_amount` taken by the protocol.
+    pub fn take(&self, fee_amount: U256) -> U256 {
+        match *self {
+            ProtocolFee::Fraction(denominator) if denominator > 0 => {
+                fee_amount / U256::from(denominator)
+            }
+            ProtocolFee::BasisPoints(share) => fee_amount * U256::from(share) / U256_10000,
+            _ => U256::ZERO,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -136,6 +277,19 @@ pub struct UniswapV3Pool {
     pub tick_spacing: i32, // TODO: we can make this a u8, tick spacing will never exceed 200
     pub tick_bitmap: HashMap<i16, U256>,
     pub ticks: HashMap<i32, Info>,
+    #[serde(default)]
+    pub fork: UniswapV3Fork,
+    /// Protocol fees of token0 and token1, packed as the fork stores them in slot0
+    #[serde(default)]
+    pub fee_protocol: u32,
+    /// Fees earned per unit of liquidity over the life of the pool, as Q128.128 numbers
+    #[serde(default)]
+    pub fee_growth_global_0_x_128: U256,
+    #[serde(default)]
+    pub fee_growth_global_1_x_128: U256,
+    /// Observations of the pool's oracle, only synced for pools that track it
+    #[serde(default)]
+    pub oracle: Option<Oracle>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -143,6 +297,12 @@ pub struct Info {
     pub liquidity_gross: u128,
     pub liquidity_net: i128,
     pub initialized: bool,
+    /// Fee growth on the other side of the tick relative to the current tick. Only differences
+    /// between these values are meaningful, as they depend on when the tick was initialized.
+    #[serde(default)]
+    pub fee_growth_outside_0_x_128: U256,
+    #[serde(default)]
+    pub fee_growth_outside_1_x_128: U256,
 }
 
 impl Info {
@@ -151,16 +311,67 @@ impl Info {
             liquidity_gross,
             liquidity_net,
             initialized,
+            ..Default::default()
         }
     }
 }
 
+/// A liquidity position in a tick range, as the pool or the position manager stores it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Fee growth inside the range when the position was last updated
+    pub fee_growth_inside_0_last_x_128: U256,
+    pub fee_growth_inside_1_last_x_128: U256,
+}
+
+/// The token amounts a position's liquidity is worth and the fees it has not collected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PositionValue {
+    pub amount_0: U256,
+    pub amount_1: U256,
+    pub fees_0: U256,
+    pub fees_1: U256,
+}
+
 pub struct CurrentState {
-    amount_specified_remaining: I256,
-    amount_calculated: I256,
-    sqrt_price_x_96: U256,
-    tick: i32,
-    liquidity: u128,
+    pub amount_specified_remaining: I256,
+    pub amount_calculated: I256,
+    pub sqrt_price_x_96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    /// Total fee paid in the input token
+    pub fee_amount: U256,
+    /// Part of the fee taken by the protocol
+    pub protocol_fee: U256,
+    /// Number of initialized ticks crossed
+    pub ticks_crossed: u32,
+    /// Fee growth of the input token per unit of liquidity, starting from the pool's global value
+    pub fee_growth_global_x_128: U256,
+    /// Initialized ticks crossed, with the fee growth of the input token when they were crossed
+    pub crossed_ticks: Vec<(i32, U256)>,
+}
+
+impl CurrentState {
+    /// Summarizes an exact input swap of `amount_in` that ended in this state.
+    pub fn outcome(&self, amount_in: U256, price_before: f64, price_after: f64) -> SwapOutcome {
+        SwapOutcome {
+            amount_in: amount_in - self.amount_specified_remaining.into_raw(),
+            amount_out: (-self.amount_calculated).into_raw(),
+            fee: Some(self.fee_amount),
+            protocol_fee: Some(self.protocol_fee),
+            price_before,
+            price_after,
+            ticks_crossed: self.ticks_crossed,
+            // Without a price limit, the loop only stops short of the amount specified at the min or
+            // max price
+            liquidity_exhausted: !self.amount_specified_remaining.is_zero()
+                && (self.sqrt_price_x_96 == default_sqrt_price_limit(true)
+                    || self.sqrt_price_x_96 == default_sqrt_price_limit(false)),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -174,28 +385,23 @@ pub struct StepComputations {
     pub fee_amount: U256,
 }
 
-pub struct Tick {
-    pub liquidity_gross: u128,
-    pub liquidity_net: i128,
-    pub fee_growth_outside_0_x_128: U256,
-    pub fee_growth_outside_1_x_128: U256,
-    pub tick_cumulative_outside: U256,
-    pub seconds_per_liquidity_outside_x_128: U256,
-    pub seconds_outside: u32,
-    pub initialized: bool,
-}
-
 impl AutomatedMarketMaker for UniswapV3Pool {
     fn address(&self) -> Address {
         self.address
     }
 
     fn sync_events(&self) -> Vec<B256> {
-        vec![
+        let mut events = vec![
             IUniswapV3PoolEvents::Mint::SIGNATURE_HASH,
             IUniswapV3PoolEvents::Burn::SIGNATURE_HASH,
-            IUniswapV3PoolEvents::Swap::SIGNATURE_HASH,
-        ]
+            self.fork.swap_event(),
+            self.fork.set_fee_protocol_event(),
+        ];
+        if self.oracle.is_some() {
+            events.push(IUniswapV3PoolEvents::IncreaseObservationCardinalityNext::SIGNATURE_HASH);
+        }
+
+        events
     }
 
     fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
@@ -203,11 +409,47 @@ impl AutomatedMarketMaker for UniswapV3Pool {
         match event_signature {
             IUniswapV3PoolEvents::Swap::SIGNATURE_HASH => {
                 let swap_event = IUniswapV3PoolEvents::Swap::decode_log(log.as_ref())?;
+                let (tick, liquidity) = (self.tick, self.liquidity);
 
+                self.sync_swap(
+                    swap_event.amount0,
+                    swap_event.amount1,
+                    swap_event.sqrtPriceX96.to(),
+                );
+                self.sqrt_price = swap_event.sqrtPriceX96.to();
+                self.liquidity = swap_event.liquidity;
+                self.tick = swap_event.tick.unchecked_into();
+
+                if self.tick != tick {
+                    self.write_observation(log, tick, liquidity);
+                }
+
+                info!(
+                    target = "amms::uniswap_v3::sync",
+                    address = ?self.address,
+                    sqrt_price = ?self.sqrt_price,
+                    liquidity = ?self.liquidity,
+                    tick = ?self.tick,
+                    "Swap"
+                );
+            }
+            IPancakeV3PoolEvents::Swap::SIGNATURE_HASH => {
+                let swap_event = IPancakeV3PoolEvents::Swap::decode_log(log.as_ref())?;
+                let (tick, liquidity) = (self.tick, self.liquidity);
+
+                self.sync_swap(
+                    swap_event.amount0,
+                    swap_event.amount1,
+                    swap_event.sqrtPriceX96.to(),
+                );
                 self.sqrt_price = swap_event.sqrtPriceX96.to();
                 self.liquidity = swap_event.liquidity;
                 self.tick = swap_event.tick.unchecked_into();
 
+                if self.tick != tick {
+                    self.write_observation(log, tick, liquidity);
+                }
+
                 info!(
                     target = "amms::uniswap_v3::sync",
                     address = ?self.address,
@@ -219,12 +461,15 @@ impl AutomatedMarketMaker for UniswapV3Pool {
             }
             IUniswapV3PoolEvents::Mint::SIGNATURE_HASH => {
                 let mint_event = IUniswapV3PoolEvents::Mint::decode_log(log.as_ref())?;
-
-                self.modify_position(
+                let (tick_lower, tick_upper): (i32, i32) = (
                     mint_event.tickLower.unchecked_into(),
                     mint_event.tickUpper.unchecked_into(),
-                    mint_event.amount as i128,
-                )?;
+                );
+
+                if mint_event.amount > 0 && self.tick >= tick_lower && self.tick < tick_upper {
+                    self.write_observation(log, self.tick, self.liquidity);
+                }
+                self.modify_position(tick_lower, tick_upper, mint_event.amount as i128)?;
 
                 info!(
                     target = "amms::uniswap_v3::sync",
@@ -237,12 +482,15 @@ impl AutomatedMarketMaker for UniswapV3Pool {
             }
             IUniswapV3PoolEvents::Burn::SIGNATURE_HASH => {
                 let burn_event = IUniswapV3PoolEvents::Burn::decode_log(log.as_ref())?;
-
-                self.modify_position(
+                let (tick_lower, tick_upper): (i32, i32) = (
                     burn_event.tickLower.unchecked_into(),
                     burn_event.tickUpper.unchecked_into(),
-                    -(burn_event.amount as i128),
-                )?;
+                );
+
+                if burn_event.amount > 0 && self.tick >= tick_lower && self.tick < tick_upper {
+                    self.write_observation(log, self.tick, self.liquidity);
+                }
+                self.modify_position(tick_lower, tick_upper, -(burn_event.amount as i128))?;
 
                 info!(
                     target = "amms::uniswap_v3::sync",
@@ -253,6 +501,55 @@ impl AutomatedMarketMaker for UniswapV3Pool {
                     "Burn"
                 );
             }
+            IUniswapV3PoolEvents::SetFeeProtocol::SIGNATURE_HASH => {
+                let fee_protocol_event =
+                    IUniswapV3PoolEvents::SetFeeProtocol::decode_log(log.as_ref())?;
+
+                self.fee_protocol = self.fork.pack_fee_protocol(
+                    fee_protocol_event.feeProtocol0New as u32,
+                    fee_protocol_event.feeProtocol1New as u32,
+                );
+
+                info!(
+                    target = "amms::uniswap_v3::sync",
+                    address = ?self.address,
+                    fee_protocol = self.fee_protocol,
+                    "SetFeeProtocol"
+                );
+            }
+            IPancakeV3PoolEvents::SetFeeProtocol::SIGNATURE_HASH => {
+                let fee_protocol_event =
+                    IPancakeV3PoolEvents::SetFeeProtocol::decode_log(log.as_ref())?;
+
+                self.fee_protocol = self.fork.pack_fee_protocol(
+                    fee_protocol_event.feeProtocol0New,
+                    fee_protocol_event.feeProtocol1New,
+                );
+
+                info!(
+                    target = "amms::uniswap_v3::sync",
+                    address = ?self.address,
+                    fee_protocol = self.fee_protocol,
+                    "SetFeeProtocol"
+                );
+            }
+            IUniswapV3PoolEvents::IncreaseObservationCardinalityNext::SIGNATURE_HASH => {
+                let cardinality_event =
+                    IUniswapV3PoolEvents::IncreaseObservationCardinalityNext::decode_log(
+                        log.as_ref(),
+                    )?;
+
+                if let Some(oracle) = &mut self.oracle {
+                    oracle.grow(cardinality_event.observationCardinalityNextNew);
+                }
+
+                info!(
+                    target = "amms::uniswap_v3::sync",
+                    address = ?self.address,
+                    observation_cardinality_next = cardinality_event.observationCardinalityNextNew,
+                    "IncreaseObservationCardinalityNext"
+                );
+            }
             _ => {
                 return Err(AMMError::UnrecognizedEventSignature(event_signature));
             }
@@ -272,311 +569,248 @@ impl AutomatedMarketMaker for UniswapV3Pool {
         }
 
         let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
 
-        // Set sqrt_price_limit_x_96 to the max or min sqrt price in the pool depending on zero_for_one
-        let sqrt_price_limit_x_96 = if zero_for_one {
-            MIN_SQRT_RATIO + U256_1
-        } else {
-            MAX_SQRT_RATIO - U256_1
-        };
+        let amount_out = token_out.after_buy_tax((-current_state.amount_calculated).into_raw());
 
-        // Initialize a mutable state state struct to hold the dynamic simulated state of the pool
-        let mut current_state = CurrentState {
-            sqrt_price_x_96: self.sqrt_price, // Active price on the pool
-            amount_calculated: I256::ZERO,    // Amount of token_out that has been calculated
-            amount_specified_remaining: I256::from_raw(amount_in), // Amount of token_in that has not been swapped
-            tick: self.tick,                                       // Current i24 tick of the pool
-            liquidity: self.liquidity, // Current available liquidity in the tick range
-        };
-
-        while current_state.amount_specified_remaining != I256::ZERO
-            && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
-        {
-            // Initialize a new step struct to hold the dynamic state of the pool at each step
-            let mut step = StepComputations {
-                // Set the sqrt_price_start_x_96 to the current sqrt_price_x_96
-                sqrt_price_start_x_96: current_state.sqrt_price_x_96,
-                ..Default::default()
-            };
-
-            // Get the next tick from the current tick
-            (step.tick_next, step.initialized) =
-                uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
-                    &self.tick_bitmap,
-                    current_state.tick,
-                    self.tick_spacing,
-                    zero_for_one,
-                )
-                .map_err(UniswapV3Error::from)?;
-
-            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
-            // Note: this could be removed as we are clamping in the batch contract
-            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+        tracing::trace!(?amount_out);
 
-            // Get the next sqrt price from the input amount
-            step.sqrt_price_next_x96 =
-                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)
-                    .map_err(UniswapV3Error::from)?;
+        Ok(amount_out)
+    }
 
-            // Target spot price
-            let swap_target_sqrt_ratio = if zero_for_one {
-                if step.sqrt_price_next_x96 < sqrt_price_limit_x_96 {
-                    sqrt_price_limit_x_96
-                } else {
-                    step.sqrt_price_next_x96
-                }
-            } else if step.sqrt_price_next_x96 > sqrt_price_limit_x_96 {
-                sqrt_price_limit_x_96
-            } else {
-                step.sqrt_price_next_x96
-            };
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_in.is_zero() {
+            return Ok(U256::ZERO);
+        }
 
-            // Compute swap step and update the current state
-            (
-                current_state.sqrt_price_x_96,
-                step.amount_in,
-                step.amount_out,
-                step.fee_amount,
-            ) = uniswap_v3_math::swap_math::compute_swap_step(
-                current_state.sqrt_price_x_96,
-                swap_target_sqrt_ratio,
-                current_state.liquidity,
-                current_state.amount_specified_remaining,
-                self.fee,
-            )
-            .map_err(UniswapV3Error::from)?;
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
 
-            // Decrement the amount remaining to be swapped and amount received from the step
-            current_state.amount_specified_remaining = current_state
-                .amount_specified_remaining
-                .overflowing_sub(I256::from_raw(
-                    step.amount_in.overflowing_add(step.fee_amount).0,
-                ))
-                .0;
+        let amount_out = token_out.after_buy_tax((-current_state.amount_calculated).into_raw());
 
-            current_state.amount_calculated -= I256::from_raw(step.amount_out);
+        self.apply_swap(zero_for_one, &current_state);
 
-            // TODO: adjust for fee protocol
+        tracing::trace!(?amount_out);
 
-            // If the price moved all the way to the next price, recompute the liquidity change for the next iteration
-            if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
-                if step.initialized {
-                    let mut liquidity_net = if let Some(info) = self.ticks.get(&step.tick_next) {
-                        info.liquidity_net
-                    } else {
-                        0
-                    };
+        Ok(amount_out)
+    }
 
-                    // we are on a tick boundary, and the next tick is initialized, so we must charge a protocol fee
-                    if zero_for_one {
-                        liquidity_net = -liquidity_net;
-                    }
+    fn simulate_swap_exact_out(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_out: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_out.is_zero() {
+            return Ok(U256::ZERO);
+        }
 
-                    current_state.liquidity = if liquidity_net < 0 {
-                        if current_state.liquidity < (-liquidity_net as u128) {
-                            return Err(UniswapV3Error::LiquidityUnderflow.into());
-                        } else {
-                            current_state.liquidity - (-liquidity_net as u128)
-                        }
-                    } else {
-                        current_state.liquidity + (liquidity_net as u128)
-                    };
-                }
-                // Increment the current tick
-                current_state.tick = if zero_for_one {
-                    step.tick_next.wrapping_sub(1)
-                } else {
-                    step.tick_next
-                }
-                // If the current_state sqrt price is not equal to the step sqrt price, then we are not on the same tick.
-                // Update the current_state.tick to the tick at the current_state.sqrt_price_x_96
-            } else if current_state.sqrt_price_x_96 != step.sqrt_price_start_x_96 {
-                current_state.tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(
-                    current_state.sqrt_price_x_96,
-                )
-                .map_err(UniswapV3Error::from)?;
-            }
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            -I256::from_raw(token_out.before_buy_tax(amount_out)?),
+        )?;
+
+        if !current_state.amount_specified_remaining.is_zero() {
+            return Err(UniswapV3Error::InsufficientLiquidity.into());
         }
 
-        let amount_out = (-current_state.amount_calculated).into_raw();
+        let amount_in = token_in.before_sell_tax(current_state.amount_calculated.into_raw())?;
 
-        tracing::trace!(?amount_out);
+        tracing::trace!(?amount_in);
 
-        Ok(amount_out)
+        Ok(amount_in)
     }
 
-    fn simulate_swap_mut(
+    fn simulate_swap_exact_out_mut(
         &mut self,
         base_token: Address,
         _quote_token: Address,
-        amount_in: U256,
+        amount_out: U256,
     ) -> Result<U256, AMMError> {
-        if amount_in.is_zero() {
+        if amount_out.is_zero() {
             return Ok(U256::ZERO);
         }
 
         let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            -I256::from_raw(token_out.before_buy_tax(amount_out)?),
+        )?;
+
+        if !current_state.amount_specified_remaining.is_zero() {
+            return Err(UniswapV3Error::InsufficientLiquidity.into());
+        }
 
-        // Set sqrt_price_limit_x_96 to the max or min sqrt price in the pool depending on zero_for_one
-        let sqrt_price_limit_x_96 = if zero_for_one {
-            MIN_SQRT_RATIO + U256_1
-        } else {
-            MAX_SQRT_RATIO - U256_1
-        };
-
-        // Initialize a mutable state state struct to hold the dynamic simulated state of the pool
-        let mut current_state = CurrentState {
-            // Active price on the pool
-            sqrt_price_x_96: self.sqrt_price,
-            // Amount of token_out that has been calculated
-            amount_calculated: I256::ZERO,
-            // Amount of token_in that has not been swapped
-            amount_specified_remaining: I256::from_raw(amount_in),
-            // Current i24 tick of the pool
-            tick: self.tick,
-            // Current available liquidity in the tick range
-            liquidity: self.liquidity,
-        };
+        let amount_in = token_in.before_sell_tax(current_state.amount_calculated.into_raw())?;
 
-        while current_state.amount_specified_remaining != I256::ZERO
-            && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
-        {
-            // Initialize a new step struct to hold the dynamic state of the pool at each step
-            let mut step = StepComputations {
-                // Set the sqrt_price_start_x_96 to the current sqrt_price_x_96
-                sqrt_price_start_x_96: current_state.sqrt_price_x_96,
-                ..Default::default()
-            };
+        self.apply_swap(zero_for_one, &current_state);
 
-            // Get the next tick from the current tick
-            (step.tick_next, step.initialized) =
-                uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
-                    &self.tick_bitmap,
-                    current_state.tick,
-                    self.tick_spacing,
-                    zero_for_one,
-                )
-                .map_err(UniswapV3Error::from)?;
+        tracing::trace!(?amount_in);
 
-            // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
-            // Note: this could be removed as we are clamping in the batch contract
-            step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+        Ok(amount_in)
+    }
 
-            // Get the next sqrt price from the input amount
-            step.sqrt_price_next_x96 =
-                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)
-                    .map_err(UniswapV3Error::from)?;
+    fn tokens(&self) -> Vec<Address> {
+        vec![self.token_a.address, self.token_b.address]
+    }
 
-            // Target spot price
-            let swap_target_sqrt_ratio = if zero_for_one {
-                if step.sqrt_price_next_x96 < sqrt_price_limit_x_96 {
-                    sqrt_price_limit_x_96
-                } else {
-                    step.sqrt_price_next_x96
-                }
-            } else if step.sqrt_price_next_x96 > sqrt_price_limit_x_96 {
-                sqrt_price_limit_x_96
-            } else {
-                step.sqrt_price_next_x96
-            };
+    fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
+        price_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
 
-            // Compute swap step and update the current state
-            (
-                current_state.sqrt_price_x_96,
-                step.amount_in,
-                step.amount_out,
-                step.fee_amount,
-            ) = uniswap_v3_math::swap_math::compute_swap_step(
-                current_state.sqrt_price_x_96,
-                swap_target_sqrt_ratio,
-                current_state.liquidity,
-                current_state.amount_specified_remaining,
-                self.fee,
-            )
-            .map_err(UniswapV3Error::from)?;
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        price_exact_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
 
-            // Decrement the amount remaining to be swapped and amount received from the step
-            current_state.amount_specified_remaining = current_state
-                .amount_specified_remaining
-                .overflowing_sub(I256::from_raw(
-                    step.amount_in.overflowing_add(step.fee_amount).0,
-                ))
-                .0;
+    fn twap(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        seconds_ago: u32,
+        block_timestamp: u64,
+    ) -> Result<U256, AMMError> {
+        let oracle = self
+            .oracle
+            .as_ref()
+            .filter(|oracle| oracle.observation_cardinality > 0)
+            .ok_or(AMMError::OracleNotTracked(self.address))?;
+        if seconds_ago == 0 {
+            return self.calculate_price_exact(base_token, quote_token);
+        }
 
-            current_state.amount_calculated -= I256::from_raw(step.amount_out);
+        let time = block_timestamp as u32;
+        let (tick_cumulative_then, _) =
+            oracle.observe(time, seconds_ago, self.tick, self.liquidity)?;
+        let (tick_cumulative_now, _) = oracle.observe(time, 0, self.tick, self.liquidity)?;
 
-            // If the price moved all the way to the next price, recompute the liquidity change for the next iteration
-            if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
-                if step.initialized {
-                    let mut liquidity_net = if let Some(info) = self.ticks.get(&step.tick_next) {
-                        info.liquidity_net
-                    } else {
-                        0
-                    };
+        // Round the mean tick towards negative infinity, as the periphery's `OracleLibrary`
+        let tick_cumulative_delta = tick_cumulative_now - tick_cumulative_then;
+        let mut mean_tick = tick_cumulative_delta / seconds_ago as i64;
+        if tick_cumulative_delta < 0 && tick_cumulative_delta % seconds_ago as i64 != 0 {
+            mean_tick -= 1;
+        }
 
-                    // we are on a tick boundary, and the next tick is initialized, so we must charge a protocol fee
-                    if zero_for_one {
-                        liquidity_net = -liquidity_net;
-                    }
+        let sqrt_price = uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(mean_tick as i32)
+            .map_err(UniswapV3Error::from)?;
+        price_exact_at_sqrt_price(sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
 
-                    current_state.liquidity = if liquidity_net < 0 {
-                        if current_state.liquidity < (-liquidity_net as u128) {
-                            return Err(AMMError::from(UniswapV3Error::LiquidityUnderflow));
-                        } else {
-                            current_state.liquidity - (-liquidity_net as u128)
-                        }
-                    } else {
-                        current_state.liquidity + (liquidity_net as u128)
-                    };
-                }
-                // Increment the current tick
-                current_state.tick = if zero_for_one {
-                    step.tick_next.wrapping_sub(1)
-                } else {
-                    step.tick_next
-                }
-                // If the current_state sqrt price is not equal to the step sqrt price, then we are not on the same tick.
-                // Update the current_state.tick to the tick at the current_state.sqrt_price_x_96
-            } else if current_state.sqrt_price_x_96 != step.sqrt_price_start_x_96 {
-                current_state.tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(
-                    current_state.sqrt_price_x_96,
-                )
-                .map_err(UniswapV3Error::from)?;
-            }
+    fn simulate_swap_outcome(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let price_before = self.calculate_price(base_token, quote_token)?;
+        if amount_in.is_zero() {
+            return Ok(SwapOutcome::empty(price_before));
         }
 
-        // Update the pool state
-        self.liquidity = current_state.liquidity;
-        self.sqrt_price = current_state.sqrt_price_x_96;
-        self.tick = current_state.tick;
-
-        let amount_out = (-current_state.amount_calculated).into_raw();
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+        let price_after = price_at_sqrt_price(
+            current_state.sqrt_price_x_96,
+            &self.token_a,
+            &self.token_b,
+            base_token,
+        )?;
+
+        Ok(current_state
+            .outcome(
+                token_in.after_sell_tax(amount_in),
+                price_before,
+                price_after,
+            )
+            .with_taxes(amount_in, token_in, token_out))
+    }
 
-        tracing::trace!(?amount_out);
+    fn simulate_add_liquidity(
+        &self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        Ok(self.add_liquidity(range, amounts)?.2)
+    }
 
-        Ok(amount_out)
+    fn simulate_add_liquidity_mut(
+        &mut self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<LiquidityOutcome, AMMError> {
+        let (tick_lower, tick_upper, outcome) = self.add_liquidity(range, amounts)?;
+        let liquidity_delta =
+            i128::try_from(outcome.liquidity).map_err(|_| UniswapV3Error::LiquidityOverflow)?;
+        self.modify_position(tick_lower, tick_upper, liquidity_delta)?;
+
+        Ok(outcome)
     }
 
-    fn tokens(&self) -> Vec<Address> {
-        vec![self.token_a.address, self.token_b.address]
+    fn simulate_remove_liquidity(
+        &self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        Ok(self.remove_liquidity(range, liquidity)?.2)
     }
 
-    fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
-        let tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(self.sqrt_price)
-            .map_err(UniswapV3Error::from)?;
-        let shift = self.token_a.decimals as i8 - self.token_b.decimals as i8;
+    fn simulate_remove_liquidity_mut(
+        &mut self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<LiquidityOutcome, AMMError> {
+        let (tick_lower, tick_upper, outcome) = self.remove_liquidity(range, liquidity)?;
+        let liquidity_delta =
+            i128::try_from(outcome.liquidity).map_err(|_| UniswapV3Error::LiquidityOverflow)?;
+        self.modify_position(tick_lower, tick_upper, -liquidity_delta)?;
+
+        Ok(outcome)
+    }
 
-        let price = match shift.cmp(&0) {
-            Ordering::Less => 1.0001_f64.powi(tick) / 10_f64.powi(-shift as i32),
-            Ordering::Greater => 1.0001_f64.powi(tick) * 10_f64.powi(shift as i32),
-            Ordering::Equal => 1.0001_f64.powi(tick),
-        };
+    /// Encodes `swap` on the pool as an exact input swap without a price limit. The amount in is
+    /// paid in the swap callback.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
 
-        if base_token == self.token_a.address {
-            Ok(price)
-        } else {
-            Ok(1.0 / price)
-        }
+        let zero_for_one = base_token == self.token_a.address;
+        UniswapV3Pool::swap_calldata(
+            self,
+            recipient,
+            zero_for_one,
+            I256::from_raw(amount_in),
+            default_sqrt_price_limit(zero_for_one),
+            vec![],
+        )
     }
 
     async fn init<N, P>(mut self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
@@ -599,6 +833,7 @@ impl AutomatedMarketMaker for UniswapV3Pool {
         UniswapV3Factory::sync_token_decimals(&mut pool, provider.clone()).await?;
         UniswapV3Factory::sync_tick_bitmaps(&mut pool, block_number, provider.clone()).await?;
         UniswapV3Factory::sync_tick_data(&mut pool, block_number, provider.clone()).await?;
+        UniswapV3Factory::sync_observations(&mut pool, block_number, provider.clone()).await?;
 
         let AMM::UniswapV3Pool(pool) = pool[0].to_owned() else {
             unreachable!()
@@ -617,6 +852,13 @@ impl UniswapV3Pool {
         }
     }
 
+    /// Tracks the pool's oracle observations once it is synced, so that its TWAPs can be computed
+    /// locally.
+    pub fn with_oracle(mut self) -> Self {
+        self.oracle = Some(Oracle::default());
+        self
+    }
+
     /// Modifies a positions liquidity in the pool.
     pub fn modify_position(
         &mut self,
@@ -648,27 +890,31 @@ impl UniswapV3Pool {
         tick_upper: i32,
         liquidity_delta: i128,
     ) -> Result<(), AMMError> {
-        let mut flipped_lower = false;
-        let mut flipped_upper = false;
-
-        if liquidity_delta != 0 {
-            flipped_lower = self.update_tick(tick_lower, liquidity_delta, false)?;
-            flipped_upper = self.update_tick(tick_upper, liquidity_delta, true)?;
-            if flipped_lower {
-                self.flip_tick(tick_lower, self.tick_spacing);
-            }
-            if flipped_upper {
-                self.flip_tick(tick_upper, self.tick_spacing);
-            }
-        }
-
-        if liquidity_delta < 0 {
-            if flipped_lower {
-                self.ticks.remove(&tick_lower);
-            }
+        let uninitialized = [tick_lower, tick_upper]
+            .into_iter()
+            .filter(|tick| {
+                self.ticks
+                    .get(tick)
+                    .is_none_or(|info| info.liquidity_gross == 0)
+            })
+            .collect::<Vec<_>>();
+
+        update_position(
+            &mut self.ticks,
+            &mut self.tick_bitmap,
+            self.tick_spacing,
+            tick_lower,
+            tick_upper,
+            liquidity_delta,
+        );
 
-            if flipped_upper {
-                self.ticks.remove(&tick_upper);
+        // By convention, all fee growth before a tick was initialized happened below it
+        for tick in uninitialized {
+            if tick <= self.tick {
+                if let Some(info) = self.ticks.get_mut(&tick) {
+                    info.fee_growth_outside_0_x_128 = self.fee_growth_global_0_x_128;
+                    info.fee_growth_outside_1_x_128 = self.fee_growth_global_1_x_128;
+                }
             }
         }
 
@@ -681,46 +927,499 @@ impl UniswapV3Pool {
         liquidity_delta: i128,
         upper: bool,
     ) -> Result<bool, AMMError> {
-        let info = self.ticks.entry(tick).or_default();
+        Ok(update_tick(&mut self.ticks, tick, liquidity_delta, upper))
+    }
+
+    pub fn flip_tick(&mut self, tick: i32, tick_spacing: i32) {
+        flip_tick(&mut self.tick_bitmap, tick, tick_spacing);
+    }
+
+    /// Runs the swap loop from the current pool state without mutating the pool.
+    ///
+    /// A positive `amount_specified` is an exact input swap, a negative one an exact output swap.
+    pub fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+    ) -> Result<CurrentState, AMMError> {
+        self.swap_to_limit(
+            zero_for_one,
+            amount_specified,
+            default_sqrt_price_limit(zero_for_one),
+        )
+    }
+
+    /// Runs the swap loop from the current pool state without mutating the pool, stopping once the
+    /// price reaches `sqrt_price_limit_x_96`.
+    pub fn swap_to_limit(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<CurrentState, AMMError> {
+        // Initialize a mutable state state struct to hold the dynamic simulated state of the pool
+        let current_state = CurrentState {
+            sqrt_price_x_96: self.sqrt_price, // Active price on the pool
+            amount_calculated: I256::ZERO,    // Amount of token_out that has been calculated
+            amount_specified_remaining: amount_specified, // Amount of token_in that has not been swapped
+            tick: self.tick,                              // Current i24 tick of the pool
+            liquidity: self.liquidity, // Current available liquidity in the tick range
+            fee_amount: U256::ZERO,
+            protocol_fee: U256::ZERO,
+            ticks_crossed: 0,
+            fee_growth_global_x_128: if zero_for_one {
+                self.fee_growth_global_0_x_128
+            } else {
+                self.fee_growth_global_1_x_128
+            },
+            crossed_ticks: vec![],
+        };
+
+        compute_swap(
+            &self.tick_bitmap,
+            &self.ticks,
+            self.tick_spacing,
+            self.fee,
+            self.fork.protocol_fee(self.fee_protocol, zero_for_one),
+            zero_for_one,
+            sqrt_price_limit_x_96,
+            current_state,
+        )
+    }
+
+    /// Simulates swapping `amount_in` of `base_token` with a price limit, as the pool does when
+    /// called with `sqrt_price_limit_x_96`.
+    ///
+    /// The swap stops once the price reaches the limit, so the outcome may be a partial fill that
+    /// consumes less than `amount_in`.
+    pub fn simulate_swap_with_limit(
+        &self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        Ok(self
+            .swap_with_limit(base_token, amount_in, sqrt_price_limit_x_96)?
+            .1)
+    }
+
+    /// Simulates swapping `amount_in` of `base_token` with a price limit, mutating the pool state.
+    pub fn simulate_swap_with_limit_mut(
+        &mut self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let (current_state, outcome) =
+            self.swap_with_limit(base_token, amount_in, sqrt_price_limit_x_96)?;
+        self.apply_swap(base_token == self.token_a.address, &current_state);
+
+        Ok(outcome)
+    }
+
+    /// Calculates the amount of `base_token` to sell for the marginal rate of the swap, the rate at
+    /// which its amount out grows with its amount in, to fall to `marginal_rate`. The rate is in raw
+    /// token units, after fees and taxes.
+    ///
+    /// Returns zero if the marginal rate is already at or below `marginal_rate`.
+    pub fn amount_in_to_marginal_rate(
+        &self,
+        base_token: Address,
+        marginal_rate: f64,
+    ) -> Result<U256, AMMError> {
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let marginal_rate = token_in.untaxed_marginal_rate(token_out, marginal_rate);
+
+        // The marginal rate is fee * price selling token 0 and fee / price selling token 1, with the
+        // price in raw token 1 per token 0
+        let fee = Float::with_val(MPFR_T_PRECISION, 1_000_000 - self.fee) / 1_000_000;
+        let price: Float = if zero_for_one {
+            marginal_rate / fee
+        } else {
+            fee / marginal_rate
+        };
+        let sqrt_price_x_96 = float_to_u256_saturating(price.sqrt() * u256_to_float(Q96)?);
+
+        let sqrt_price_limit_x_96 = if zero_for_one {
+            sqrt_price_x_96.max(default_sqrt_price_limit(true))
+        } else {
+            sqrt_price_x_96.min(default_sqrt_price_limit(false))
+        };
+        if check_sqrt_price_limit(self.sqrt_price, sqrt_price_limit_x_96, zero_for_one).is_err() {
+            return Ok(U256::ZERO);
+        }
+
+        // An exact input swap of an unbounded amount stops at the price limit
+        let current_state = self.swap_to_limit(zero_for_one, I256::MAX, sqrt_price_limit_x_96)?;
+        let amount_received = (I256::MAX - current_state.amount_specified_remaining).into_raw();
+
+        // Grossing up an amount past `U256::MAX` for the sell tax saturates as well
+        Ok(token_in
+            .before_sell_tax(amount_received)
+            .unwrap_or(U256::MAX))
+    }
+
+    /// Moves the pool to the state a swap ended in, accruing the fee growth of the input token and
+    /// flipping the fee growth outside of every tick crossed.
+    fn apply_swap(&mut self, zero_for_one: bool, current_state: &CurrentState) {
+        let fee_growth_global_out_x_128 = if zero_for_one {
+            self.fee_growth_global_1_x_128
+        } else {
+            self.fee_growth_global_0_x_128
+        };
+
+        for (tick, fee_growth_global_in_x_128) in &current_state.crossed_ticks {
+            if let Some(info) = self.ticks.get_mut(tick) {
+                let (outside_in, outside_out) = if zero_for_one {
+                    (
+                        &mut info.fee_growth_outside_0_x_128,
+                        &mut info.fee_growth_outside_1_x_128,
+                    )
+                } else {
+                    (
+                        &mut info.fee_growth_outside_1_x_128,
+                        &mut info.fee_growth_outside_0_x_128,
+                    )
+                };
+
+                *outside_in = fee_growth_global_in_x_128.wrapping_sub(*outside_in);
+                *outside_out = fee_growth_global_out_x_128.wrapping_sub(*outside_out);
+            }
+        }
+
+        if zero_for_one {
+            self.fee_growth_global_0_x_128 = current_state.fee_growth_global_x_128;
+        } else {
+            self.fee_growth_global_1_x_128 = current_state.fee_growth_global_x_128;
+        }
+
+        self.liquidity = current_state.liquidity;
+        self.sqrt_price = current_state.sqrt_price_x_96;
+        self.tick = current_state.tick;
+    }
+
+    /// Accrues the fees of a swap observed on chain by replaying it against the local state up to
+    /// the price it ended at. The caller then takes the price, liquidity and tick from the event.
+    fn sync_swap(&mut self, amount_0: I256, amount_1: I256, sqrt_price_x_96: U256) {
+        let zero_for_one = amount_0 > I256::ZERO;
+        let amount_in = if zero_for_one { amount_0 } else { amount_1 };
+        if amount_in <= I256::ZERO {
+            return;
+        }
+
+        match self.swap_to_limit(zero_for_one, amount_in, sqrt_price_x_96) {
+            Ok(current_state) => self.apply_swap(zero_for_one, &current_state),
+            Err(err) => tracing::warn!(
+                target = "amms::uniswap_v3::sync",
+                address = ?self.address,
+                ?err,
+                "Could not replay swap, fee growth is out of date"
+            ),
+        }
+    }
+
+    /// Writes an observation of the tick and liquidity that held until the block of `log`.
+    ///
+    /// Logs without a block timestamp leave a gap in the observations, so the oracle stops being
+    /// tracked until the pool is synced again.
+    fn write_observation(&mut self, log: &Log, tick: i32, liquidity: u128) {
+        let Some(oracle) = &mut self.oracle else {
+            return;
+        };
+
+        if let Some(block_timestamp) = log.block_timestamp {
+            oracle.write(block_timestamp as u32, tick, liquidity);
+        } else {
+            tracing::warn!(
+                target = "amms::uniswap_v3::sync",
+                address = ?self.address,
+                "Log has no block timestamp, the oracle is no longer tracked"
+            );
+            self.oracle = None;
+        }
+    }
+
+    /// Returns the fee growth per unit of liquidity of token0 and token1 inside a tick range, as
+    /// the pool computes it in `getFeeGrowthInside`.
+    pub fn fee_growth_inside(&self, tick_lower: i32, tick_upper: i32) -> (U256, U256) {
+        let fee_growth_outside = |tick| {
+            self.ticks
+                .get(&tick)
+                .map(|info| {
+                    (
+                        info.fee_growth_outside_0_x_128,
+                        info.fee_growth_outside_1_x_128,
+                    )
+                })
+                .unwrap_or_default()
+        };
+        let global = (
+            self.fee_growth_global_0_x_128,
+            self.fee_growth_global_1_x_128,
+        );
 
-        let liquidity_gross_before = info.liquidity_gross;
+        let (lower_0, lower_1) = fee_growth_outside(tick_lower);
+        let (below_0, below_1) = if self.tick >= tick_lower {
+            (lower_0, lower_1)
+        } else {
+            (
+                global.0.wrapping_sub(lower_0),
+                global.1.wrapping_sub(lower_1),
+            )
+        };
 
-        let liquidity_gross_after = if liquidity_delta < 0 {
-            liquidity_gross_before - ((-liquidity_delta) as u128)
+        let (upper_0, upper_1) = fee_growth_outside(tick_upper);
+        let (above_0, above_1) = if self.tick < tick_upper {
+            (upper_0, upper_1)
         } else {
-            liquidity_gross_before + (liquidity_delta as u128)
+            (
+                global.0.wrapping_sub(upper_0),
+                global.1.wrapping_sub(upper_1),
+            )
         };
 
-        // we do not need to check if liqudity_gross_after > maxLiquidity because we are only calling update tick on a burn or mint log.
-        // this should already be validated when a log is
-        let flipped = (liquidity_gross_after == 0) != (liquidity_gross_before == 0);
+        (
+            global.0.wrapping_sub(below_0).wrapping_sub(above_0),
+            global.1.wrapping_sub(below_1).wrapping_sub(above_1),
+        )
+    }
 
-        if liquidity_gross_before == 0 {
-            info.initialized = true;
+    /// Opens a position of `liquidity` in a tick range at the current state, so that it starts
+    /// without uncollected fees. This does not add the liquidity to the pool.
+    pub fn open_position(&self, tick_lower: i32, tick_upper: i32, liquidity: u128) -> Position {
+        let (fee_growth_inside_0_last_x_128, fee_growth_inside_1_last_x_128) =
+            self.fee_growth_inside(tick_lower, tick_upper);
+
+        Position {
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_0_last_x_128,
+            fee_growth_inside_1_last_x_128,
         }
+    }
+
+    /// Values a position at the current state of the pool: the token amounts its liquidity is
+    /// worth and the fees it earned since its last update, rounded down as the pool pays them out.
+    pub fn position_value(&self, position: &Position) -> Result<PositionValue, AMMError> {
+        let (amount_0, amount_1) = self.position_amounts(
+            position.tick_lower,
+            position.tick_upper,
+            position.liquidity,
+            false,
+        )?;
+
+        let (fee_growth_inside_0_x_128, fee_growth_inside_1_x_128) =
+            self.fee_growth_inside(position.tick_lower, position.tick_upper);
+        let fees = |fee_growth_inside_x_128: U256, fee_growth_inside_last_x_128| {
+            mul_div(
+                fee_growth_inside_x_128.wrapping_sub(fee_growth_inside_last_x_128),
+                U256::from(position.liquidity),
+                Q128,
+            )
+            .map_err(UniswapV3Error::from)
+        };
+
+        Ok(PositionValue {
+            amount_0,
+            amount_1,
+            fees_0: fees(
+                fee_growth_inside_0_x_128,
+                position.fee_growth_inside_0_last_x_128,
+            )?,
+            fees_1: fees(
+                fee_growth_inside_1_x_128,
+                position.fee_growth_inside_1_last_x_128,
+            )?,
+        })
+    }
+
+    /// Returns the amounts of token0 and token1 that `liquidity` in a tick range is worth at the
+    /// current state, rounded up when minting and down when burning, as the pool rounds them.
+    pub fn position_amounts(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        round_up: bool,
+    ) -> Result<(U256, U256), AMMError> {
+        let sqrt_price_lower = uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(tick_lower)
+            .map_err(UniswapV3Error::from)?;
+        let sqrt_price_upper = uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(tick_upper)
+            .map_err(UniswapV3Error::from)?;
 
-        info.liquidity_gross = liquidity_gross_after;
+        let amount_0 = |sqrt_price_a, sqrt_price_b| {
+            uniswap_v3_math::sqrt_price_math::_get_amount_0_delta(
+                sqrt_price_a,
+                sqrt_price_b,
+                liquidity,
+                round_up,
+            )
+            .map_err(UniswapV3Error::from)
+        };
+        let amount_1 = |sqrt_price_a, sqrt_price_b| {
+            uniswap_v3_math::sqrt_price_math::_get_amount_1_delta(
+                sqrt_price_a,
+                sqrt_price_b,
+                liquidity,
+                round_up,
+            )
+            .map_err(UniswapV3Error::from)
+        };
 
-        info.liquidity_net = if upper {
-            info.liquidity_net - liquidity_delta
+        Ok(if self.tick < tick_lower {
+            (amount_0(sqrt_price_lower, sqrt_price_upper)?, U256::ZERO)
+        } else if self.tick < tick_upper {
+            (
+                amount_0(self.sqrt_price, sqrt_price_upper)?,
+                amount_1(sqrt_price_lower, self.sqrt_price)?,
+            )
         } else {
-            info.liquidity_net + liquidity_delta
+            (U256::ZERO, amount_1(sqrt_price_lower, sqrt_price_upper)?)
+        })
+    }
+
+    /// Returns the tick range of `range`, checking that the pool accepts positions over it.
+    ///
+    /// The full range spans the lowest to the highest tick usable at the pool's tick spacing.
+    fn tick_range(&self, range: LiquidityRange) -> Result<(i32, i32), UniswapV3Error> {
+        if self.tick_spacing <= 0 {
+            return Err(UniswapV3Error::InvalidTickRange);
+        }
+
+        let (tick_lower, tick_upper) = match range {
+            LiquidityRange::Full => (
+                MIN_TICK / self.tick_spacing * self.tick_spacing,
+                MAX_TICK / self.tick_spacing * self.tick_spacing,
+            ),
+            LiquidityRange::Ticks {
+                tick_lower,
+                tick_upper,
+            } => (tick_lower, tick_upper),
         };
 
-        Ok(flipped)
+        if tick_lower >= tick_upper
+            || tick_lower < MIN_TICK
+            || tick_upper > MAX_TICK
+            || tick_lower % self.tick_spacing != 0
+            || tick_upper % self.tick_spacing != 0
+        {
+            return Err(UniswapV3Error::InvalidTickRange);
+        }
+
+        Ok((tick_lower, tick_upper))
     }
 
-    pub fn flip_tick(&mut self, tick: i32, tick_spacing: i32) {
-        let (word_pos, bit_pos) = uniswap_v3_math::tick_bitmap::position(tick / tick_spacing);
-        let mask = U256::from(1) << bit_pos;
+    /// Mints the most liquidity that at most `amounts` of token0 and token1 buy over `range`, as
+    /// the position manager does.
+    fn add_liquidity(
+        &self,
+        range: LiquidityRange,
+        amounts: &[U256],
+    ) -> Result<(i32, i32, LiquidityOutcome), AMMError> {
+        let &[amount_0, amount_1] = amounts else {
+            return Err(AMMError::TokenAmountsMismatch {
+                expected: 2,
+                actual: amounts.len(),
+            });
+        };
+        let (tick_lower, tick_upper) = self.tick_range(range)?;
+
+        let liquidity = liquidity_for_amounts(
+            self.sqrt_price,
+            uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(tick_lower)
+                .map_err(UniswapV3Error::from)?,
+            uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(tick_upper)
+                .map_err(UniswapV3Error::from)?,
+            amount_0,
+            amount_1,
+        )?;
+        let (amount_0, amount_1) =
+            self.position_amounts(tick_lower, tick_upper, liquidity, true)?;
+
+        Ok((
+            tick_lower,
+            tick_upper,
+            LiquidityOutcome {
+                liquidity: U256::from(liquidity),
+                amounts: vec![amount_0, amount_1],
+            },
+        ))
+    }
+
+    /// Burns `liquidity` from a position over `range`, which must be held by the pool's positions.
+    fn remove_liquidity(
+        &self,
+        range: LiquidityRange,
+        liquidity: U256,
+    ) -> Result<(i32, i32, LiquidityOutcome), AMMError> {
+        let (tick_lower, tick_upper) = self.tick_range(range)?;
+        let liquidity =
+            u128::try_from(liquidity).map_err(|_| UniswapV3Error::InsufficientLiquidity)?;
+
+        // Every position over the range references both of its ticks
+        let liquidity_gross = |tick| self.ticks.get(&tick).map_or(0, |info| info.liquidity_gross);
+        if liquidity_gross(tick_lower) < liquidity || liquidity_gross(tick_upper) < liquidity {
+            return Err(UniswapV3Error::InsufficientLiquidity.into());
+        }
 
-        if let Some(word) = self.tick_bitmap.get_mut(&word_pos) {
-            *word ^= mask;
+        let (amount_0, amount_1) =
+            self.position_amounts(tick_lower, tick_upper, liquidity, false)?;
+
+        Ok((
+            tick_lower,
+            tick_upper,
+            LiquidityOutcome {
+                liquidity: U256::from(liquidity),
+                amounts: vec![amount_0, amount_1],
+            },
+        ))
+    }
+
+    /// Returns the token sold into and the token bought from the pool.
+    fn swap_tokens(&self, zero_for_one: bool) -> (&Token, &Token) {
+        if zero_for_one {
+            (&self.token_a, &self.token_b)
         } else {
-            self.tick_bitmap.insert(word_pos, mask);
+            (&self.token_b, &self.token_a)
         }
     }
 
+    fn swap_with_limit(
+        &self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<(CurrentState, SwapOutcome), AMMError> {
+        let zero_for_one = base_token == self.token_a.address;
+        check_sqrt_price_limit(self.sqrt_price, sqrt_price_limit_x_96, zero_for_one)?;
+
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let amount_received = token_in.after_sell_tax(amount_in);
+
+        let current_state = self.swap_to_limit(
+            zero_for_one,
+            I256::from_raw(amount_received),
+            sqrt_price_limit_x_96,
+        )?;
+        let outcome = current_state.outcome(
+            amount_received,
+            price_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)?,
+            price_at_sqrt_price(
+                current_state.sqrt_price_x_96,
+                &self.token_a,
+                &self.token_b,
+                base_token,
+            )?,
+        );
+
+        Ok((
+            current_state,
+            outcome.with_taxes(amount_in, token_in, token_out),
+        ))
+    }
+
     pub fn swap_calldata(
         &self,
         recipient: Address,
@@ -745,6 +1444,11 @@ impl UniswapV3Pool {
 pub struct UniswapV3Factory {
     pub address: Address,
     pub creation_block: u64,
+    #[serde(default)]
+    pub fork: UniswapV3Fork,
+    /// Whether the factory's pools track their oracle observations
+    #[serde(default)]
+    pub oracle: bool,
 }
 
 impl UniswapV3Factory {
@@ -752,9 +1456,29 @@ impl UniswapV3Factory {
         UniswapV3Factory {
             address,
             creation_block,
+            fork: UniswapV3Fork::default(),
+            oracle: false,
         }
     }
 
+    /// Creates a factory for a Uniswap V3 fork, whose pools are synced with the fork's event
+    /// layouts and fee units.
+    pub fn new_with_fork(address: Address, creation_block: u64, fork: UniswapV3Fork) -> Self {
+        UniswapV3Factory {
+            address,
+            creation_block,
+            fork,
+            oracle: false,
+        }
+    }
+
+    /// Syncs the oracle observations of the factory's pools, so that their TWAPs can be computed
+    /// locally.
+    pub fn with_oracle(mut self) -> Self {
+        self.oracle = true;
+        self
+    }
+
     pub async fn get_all_pools<N, P>(
         &self,
         block_number: BlockId,
@@ -801,7 +1525,7 @@ impl UniswapV3Factory {
     }
 
     pub async fn sync_all_pools<N, P>(
-        mut pools: Vec<AMM>,
+        pools: Vec<AMM>,
         block_number: BlockId,
         provider: P,
     ) -> Result<Vec<AMM>, AMMError>
@@ -809,7 +1533,9 @@ impl UniswapV3Factory {
         N: Network,
         P: Provider<N> + Clone,
     {
+        let mut pools = pools;
         UniswapV3Factory::sync_slot_0(&mut pools, block_number, provider.clone()).await?;
+
         UniswapV3Factory::sync_token_decimals(&mut pools, provider.clone()).await?;
 
         pools = pools
@@ -826,6 +1552,7 @@ impl UniswapV3Factory {
 
         UniswapV3Factory::sync_tick_bitmaps(&mut pools, block_number, provider.clone()).await?;
         UniswapV3Factory::sync_tick_data(&mut pools, block_number, provider.clone()).await?;
+        UniswapV3Factory::sync_observations(&mut pools, block_number, provider.clone()).await?;
 
         Ok(pools)
     }
@@ -897,7 +1624,10 @@ impl UniswapV3Factory {
 
         while let Some(res) = futures.next().await {
             let (pools, return_data) = res?;
-            let return_data = <Vec<(i32, u128, U256)> as SolValue>::abi_decode(&return_data)?;
+            let return_data =
+                <Vec<(i32, u128, U256, u32, U256, U256, u16, u16, u16)> as SolValue>::abi_decode(
+                    &return_data,
+                )?;
 
             for (slot_0_data, pool) in return_data.iter().zip(pools.iter_mut()) {
                 let AMM::UniswapV3Pool(ref mut uv3_pool) = pool else {
@@ -907,6 +1637,15 @@ impl UniswapV3Factory {
                 uv3_pool.tick = slot_0_data.0;
                 uv3_pool.liquidity = slot_0_data.1;
                 uv3_pool.sqrt_price = slot_0_data.2;
+                uv3_pool.fee_protocol = slot_0_data.3;
+                uv3_pool.fee_growth_global_0_x_128 = slot_0_data.4;
+                uv3_pool.fee_growth_global_1_x_128 = slot_0_data.5;
+
+                if let Some(oracle) = &mut uv3_pool.oracle {
+                    oracle.observation_index = slot_0_data.6;
+                    oracle.observation_cardinality = slot_0_data.7;
+                    oracle.observation_cardinality_next = slot_0_data.8;
+                }
             }
         }
 
@@ -1113,19 +1852,116 @@ impl UniswapV3Factory {
             }
         }
 
-        if !group.is_empty() {
+        if !group.is_empty() {
+            let provider = provider.clone();
+            let calldata = std::mem::take(&mut group);
+
+            futures.push(Box::pin(async move {
+                Ok::<(Vec<TickDataInfo>, Bytes), AMMError>((
+                    calldata.clone(),
+                    GetUniswapV3PoolTickDataBatchRequest::deploy_builder(provider, calldata)
+                        .call_raw()
+                        .block(block_number)
+                        .await?,
+                ))
+            }));
+        }
+
+        let mut pool_set = pools
+            .iter_mut()
+            .map(|pool| (pool.address(), pool))
+            .collect::<HashMap<Address, &mut AMM>>();
+
+        while let Some(res) = futures.next().await {
+            let (tick_info, return_data) = res?;
+            let return_data =
+                <Vec<Vec<(bool, u128, i128, U256, U256)>> as SolValue>::abi_decode(&return_data)?;
+
+            for (tick_bitmaps, tick_info) in return_data.iter().zip(tick_info.iter()) {
+                let pool = pool_set.get_mut(&tick_info.pool).unwrap();
+
+                let AMM::UniswapV3Pool(ref mut uv3_pool) = pool else {
+                    unreachable!()
+                };
+
+                for (tick, tick_idx) in tick_bitmaps.iter().zip(tick_info.ticks.iter()) {
+                    let info = Info {
+                        liquidity_gross: tick.1,
+                        liquidity_net: tick.2,
+                        initialized: tick.0,
+                        fee_growth_outside_0_x_128: tick.3,
+                        fee_growth_outside_1_x_128: tick.4,
+                    };
+
+                    uv3_pool.ticks.insert(tick_idx.as_i32(), info);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Syncs the observations in use by pools that track their oracle, after their slot0 is synced.
+    async fn sync_observations<N, P>(
+        pools: &mut [AMM],
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let max_observations = 500;
+        let mut groups: Vec<Vec<ObservationsInfo>> = vec![];
+        let mut group_observations = max_observations;
+
+        for pool in pools.iter_mut() {
+            let AMM::UniswapV3Pool(UniswapV3Pool {
+                address,
+                oracle: Some(oracle),
+                ..
+            }) = pool
+            else {
+                continue;
+            };
+
+            // Slots beyond the cardinality in use hold the placeholder the pool writes on growth
+            oracle.observations = vec![
+                Observation {
+                    block_timestamp: 1,
+                    ..Default::default()
+                };
+                oracle.observation_cardinality_next as usize
+            ];
+
+            let indices = (0..oracle.observation_cardinality).collect::<Vec<_>>();
+            for indices in indices.chunks(max_observations) {
+                if group_observations + indices.len() > max_observations {
+                    groups.push(vec![]);
+                    group_observations = 0;
+                }
+                group_observations += indices.len();
+
+                if let Some(group) = groups.last_mut() {
+                    group.push(ObservationsInfo {
+                        pool: *address,
+                        indices: indices.to_vec(),
+                    });
+                }
+            }
+        }
+
+        let mut futures = FuturesUnordered::new();
+        for group in groups {
             let provider = provider.clone();
-            let calldata = std::mem::take(&mut group);
-
-            futures.push(Box::pin(async move {
-                Ok::<(Vec<TickDataInfo>, Bytes), AMMError>((
-                    calldata.clone(),
-                    GetUniswapV3PoolTickDataBatchRequest::deploy_builder(provider, calldata)
+            futures.push(async move {
+                Ok::<(Vec<ObservationsInfo>, Bytes), AMMError>((
+                    group.clone(),
+                    GetUniswapV3PoolObservationsBatchRequest::deploy_builder(provider, group)
                         .call_raw()
                         .block(block_number)
                         .await?,
                 ))
-            }));
+            });
         }
 
         let mut pool_set = pools
@@ -1134,32 +1970,391 @@ impl UniswapV3Factory {
             .collect::<HashMap<Address, &mut AMM>>();
 
         while let Some(res) = futures.next().await {
-            let (tick_info, return_data) = res?;
-            let return_data = <Vec<Vec<(bool, u128, i128)>> as SolValue>::abi_decode(&return_data)?;
-
-            for (tick_bitmaps, tick_info) in return_data.iter().zip(tick_info.iter()) {
-                let pool = pool_set.get_mut(&tick_info.pool).unwrap();
-
-                let AMM::UniswapV3Pool(ref mut uv3_pool) = pool else {
+            let (observations_info, return_data) = res?;
+            let return_data =
+                <Vec<Vec<(u32, i64, U256, bool)>> as SolValue>::abi_decode(&return_data)?;
+
+            for (observations, info) in return_data.iter().zip(observations_info.iter()) {
+                let Some(AMM::UniswapV3Pool(UniswapV3Pool {
+                    oracle: Some(oracle),
+                    ..
+                })) = pool_set.get_mut(&info.pool).map(|pool| &mut **pool)
+                else {
                     unreachable!()
                 };
 
-                for (tick, tick_idx) in tick_bitmaps.iter().zip(tick_info.ticks.iter()) {
-                    let info = Info {
-                        liquidity_gross: tick.1,
-                        liquidity_net: tick.2,
-                        initialized: tick.0,
+                for (observation, index) in observations.iter().zip(info.indices.iter()) {
+                    oracle.observations[*index as usize] = Observation {
+                        block_timestamp: observation.0,
+                        tick_cumulative: observation.1,
+                        seconds_per_liquidity_cumulative_x_128: observation.2,
+                        initialized: observation.3,
                     };
+                }
+            }
+        }
 
-                    uv3_pool.ticks.insert(tick_idx.as_i32(), info);
+        Ok(())
+    }
+}
+
+/// Runs the concentrated liquidity swap loop over the given tick state, starting from `state`.
+///
+/// A positive `state.amount_specified_remaining` is an exact input swap and a negative one an exact
+/// output swap, in which case `amount_calculated` accumulates the amount in. The loop stops once the
+/// amount specified is exhausted or the price reaches `sqrt_price_limit_x_96`. This is shared by
+/// every AMM in the crate that follows the Uniswap V3 tick model.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_swap(
+    tick_bitmap: &HashMap<i16, U256>,
+    ticks: &HashMap<i32, Info>,
+    tick_spacing: i32,
+    fee: u32,
+    protocol_fee: ProtocolFee,
+    zero_for_one: bool,
+    sqrt_price_limit_x_96: U256,
+    mut current_state: CurrentState,
+) -> Result<CurrentState, AMMError> {
+    let exact_input = current_state.amount_specified_remaining > I256::ZERO;
+
+    while current_state.amount_specified_remaining != I256::ZERO
+        && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
+    {
+        // Initialize a new step struct to hold the dynamic state of the pool at each step
+        let mut step = StepComputations {
+            // Set the sqrt_price_start_x_96 to the current sqrt_price_x_96
+            sqrt_price_start_x_96: current_state.sqrt_price_x_96,
+            ..Default::default()
+        };
+
+        // Get the next tick from the current tick
+        (step.tick_next, step.initialized) =
+            uniswap_v3_math::tick_bitmap::next_initialized_tick_within_one_word(
+                tick_bitmap,
+                current_state.tick,
+                tick_spacing,
+                zero_for_one,
+            )
+            .map_err(UniswapV3Error::from)?;
+
+        // ensure that we do not overshoot the min/max tick, as the tick bitmap is not aware of these bounds
+        // Note: this could be removed as we are clamping in the batch contract
+        step.tick_next = step.tick_next.clamp(MIN_TICK, MAX_TICK);
+
+        // Get the next sqrt price from the input amount
+        step.sqrt_price_next_x96 =
+            uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(step.tick_next)
+                .map_err(UniswapV3Error::from)?;
+
+        // Target spot price
+        let swap_target_sqrt_ratio = if zero_for_one {
+            if step.sqrt_price_next_x96 < sqrt_price_limit_x_96 {
+                sqrt_price_limit_x_96
+            } else {
+                step.sqrt_price_next_x96
+            }
+        } else if step.sqrt_price_next_x96 > sqrt_price_limit_x_96 {
+            sqrt_price_limit_x_96
+        } else {
+            step.sqrt_price_next_x96
+        };
+
+        // Compute swap step and update the current state
+        (
+            current_state.sqrt_price_x_96,
+            step.amount_in,
+            step.amount_out,
+            step.fee_amount,
+        ) = uniswap_v3_math::swap_math::compute_swap_step(
+            current_state.sqrt_price_x_96,
+            swap_target_sqrt_ratio,
+            current_state.liquidity,
+            current_state.amount_specified_remaining,
+            fee,
+        )
+        .map_err(UniswapV3Error::from)?;
+
+        if exact_input {
+            // Decrement the amount remaining to be swapped and amount received from the step
+            current_state.amount_specified_remaining = current_state
+                .amount_specified_remaining
+                .overflowing_sub(I256::from_raw(
+                    step.amount_in.overflowing_add(step.fee_amount).0,
+                ))
+                .0;
+
+            current_state.amount_calculated -= I256::from_raw(step.amount_out);
+        } else {
+            // Increment the amount remaining to be received and the amount paid for the step
+            current_state.amount_specified_remaining += I256::from_raw(step.amount_out);
+
+            current_state.amount_calculated +=
+                I256::from_raw(step.amount_in.overflowing_add(step.fee_amount).0);
+        }
+
+        let step_protocol_fee = protocol_fee.take(step.fee_amount);
+        current_state.fee_amount += step.fee_amount;
+        current_state.protocol_fee += step_protocol_fee;
+
+        // Fees left to liquidity providers accrue to the liquidity in range
+        if current_state.liquidity > 0 {
+            current_state.fee_growth_global_x_128 =
+                current_state.fee_growth_global_x_128.wrapping_add(
+                    mul_div(
+                        step.fee_amount - step_protocol_fee,
+                        Q128,
+                        U256::from(current_state.liquidity),
+                    )
+                    .map_err(UniswapV3Error::from)?,
+                );
+        }
+
+        // If the price moved all the way to the next price, recompute the liquidity change for the next iteration
+        if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
+            if step.initialized {
+                let mut liquidity_net = if let Some(info) = ticks.get(&step.tick_next) {
+                    info.liquidity_net
+                } else {
+                    0
+                };
+
+                // we are on a tick boundary, and the next tick is initialized, so we must charge a protocol fee
+                if zero_for_one {
+                    liquidity_net = -liquidity_net;
                 }
+
+                current_state.liquidity = if liquidity_net < 0 {
+                    if current_state.liquidity < (-liquidity_net as u128) {
+                        return Err(UniswapV3Error::LiquidityUnderflow.into());
+                    } else {
+                        current_state.liquidity - (-liquidity_net as u128)
+                    }
+                } else {
+                    current_state.liquidity + (liquidity_net as u128)
+                };
+
+                current_state.ticks_crossed += 1;
+                current_state
+                    .crossed_ticks
+                    .push((step.tick_next, current_state.fee_growth_global_x_128));
             }
+            // Increment the current tick
+            current_state.tick = if zero_for_one {
+                step.tick_next.wrapping_sub(1)
+            } else {
+                step.tick_next
+            }
+            // If the current_state sqrt price is not equal to the step sqrt price, then we are not on the same tick.
+            // Update the current_state.tick to the tick at the current_state.sqrt_price_x_96
+        } else if current_state.sqrt_price_x_96 != step.sqrt_price_start_x_96 {
+            current_state.tick =
+                uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(current_state.sqrt_price_x_96)
+                    .map_err(UniswapV3Error::from)?;
         }
+    }
+
+    Ok(current_state)
+}
+
+/// Returns the most liquidity that `amount_0` of token0 and `amount_1` of token1 buy between
+/// `sqrt_price_a_x_96` and `sqrt_price_b_x_96` at `sqrt_price_x_96`, as `LiquidityAmounts` computes it.
+pub fn liquidity_for_amounts(
+    sqrt_price_x_96: U256,
+    sqrt_price_a_x_96: U256,
+    sqrt_price_b_x_96: U256,
+    amount_0: U256,
+    amount_1: U256,
+) -> Result<u128, AMMError> {
+    let (sqrt_price_a_x_96, sqrt_price_b_x_96) = if sqrt_price_a_x_96 > sqrt_price_b_x_96 {
+        (sqrt_price_b_x_96, sqrt_price_a_x_96)
+    } else {
+        (sqrt_price_a_x_96, sqrt_price_b_x_96)
+    };
+
+    let liquidity_for_amount_0 = |sqrt_price_a_x_96: U256, sqrt_price_b_x_96: U256| {
+        let intermediate = mul_div(sqrt_price_a_x_96, sqrt_price_b_x_96, Q96)?;
+        mul_div(
+            amount_0,
+            intermediate,
+            sqrt_price_b_x_96 - sqrt_price_a_x_96,
+        )
+    };
+    let liquidity_for_amount_1 = |sqrt_price_a_x_96: U256, sqrt_price_b_x_96: U256| {
+        mul_div(amount_1, Q96, sqrt_price_b_x_96 - sqrt_price_a_x_96)
+    };
+
+    let liquidity = if sqrt_price_x_96 <= sqrt_price_a_x_96 {
+        liquidity_for_amount_0(sqrt_price_a_x_96, sqrt_price_b_x_96)
+    } else if sqrt_price_x_96 < sqrt_price_b_x_96 {
+        liquidity_for_amount_0(sqrt_price_x_96, sqrt_price_b_x_96).and_then(|liquidity_0| {
+            Ok(liquidity_0.min(liquidity_for_amount_1(sqrt_price_a_x_96, sqrt_price_x_96)?))
+        })
+    } else {
+        liquidity_for_amount_1(sqrt_price_a_x_96, sqrt_price_b_x_96)
+    }
+    .map_err(UniswapV3Error::from)?;
+
+    Ok(u128::try_from(liquidity).map_err(|_| UniswapV3Error::LiquidityOverflow)?)
+}
+
+/// Returns the price limit of a swap that is only bounded by the price range of the pool.
+pub fn default_sqrt_price_limit(zero_for_one: bool) -> U256 {
+    if zero_for_one {
+        MIN_SQRT_RATIO + U256_1
+    } else {
+        MAX_SQRT_RATIO - U256_1
+    }
+}
+
+/// Checks that `sqrt_price_limit_x_96` lies in the direction of the swap and within the price
+/// range of the pool, as the pool does before swapping.
+pub fn check_sqrt_price_limit(
+    sqrt_price_x_96: U256,
+    sqrt_price_limit_x_96: U256,
+    zero_for_one: bool,
+) -> Result<(), UniswapV3Error> {
+    let valid = if zero_for_one {
+        sqrt_price_limit_x_96 < sqrt_price_x_96 && sqrt_price_limit_x_96 > MIN_SQRT_RATIO
+    } else {
+        sqrt_price_limit_x_96 > sqrt_price_x_96 && sqrt_price_limit_x_96 < MAX_SQRT_RATIO
+    };
+
+    if valid {
         Ok(())
+    } else {
+        Err(UniswapV3Error::InvalidSqrtPriceLimit)
+    }
+}
+
+/// Calculates the price of `base_token` in terms of the other token of the pair at `sqrt_price`
+/// as a Q128.128 fixed point number, adjusted for token decimals.
+pub fn price_exact_at_sqrt_price(
+    sqrt_price: U256,
+    token_a: &Token,
+    token_b: &Token,
+    base_token: Address,
+) -> Result<U256, AMMError> {
+    if base_token == token_a.address {
+        // sqrt_price^2 / 2^192 in Q128 is sqrt_price^2 / 2^64
+        let price = mul_div(sqrt_price, sqrt_price, Q64).map_err(|_| AMMError::PriceOverflow)?;
+        adjust_decimals(price, token_a.decimals, token_b.decimals)
+    } else {
+        // 2^192 / sqrt_price^2 in Q128, split to keep the intermediate product within 512 bits
+        let price = mul_div(Q128, Q96, sqrt_price)
+            .and_then(|price| mul_div(price, Q96, sqrt_price))
+            .map_err(|_| AMMError::PriceOverflow)?;
+        adjust_decimals(price, token_b.decimals, token_a.decimals)
+    }
+}
+
+/// Calculates the price of `base_token` in terms of the other token of the pair at `sqrt_price`.
+pub fn price_at_sqrt_price(
+    sqrt_price: U256,
+    token_a: &Token,
+    token_b: &Token,
+    base_token: Address,
+) -> Result<f64, AMMError> {
+    let tick = uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio(sqrt_price)
+        .map_err(UniswapV3Error::from)?;
+    let shift = token_a.decimals as i8 - token_b.decimals as i8;
+
+    let price = match shift.cmp(&0) {
+        Ordering::Less => 1.0001_f64.powi(tick) / 10_f64.powi(-shift as i32),
+        Ordering::Greater => 1.0001_f64.powi(tick) * 10_f64.powi(shift as i32),
+        Ordering::Equal => 1.0001_f64.powi(tick),
+    };
+
+    if base_token == token_a.address {
+        Ok(price)
+    } else {
+        Ok(1.0 / price)
+    }
+}
+
+/// Updates the ticks and tick bitmap for a change in a position's liquidity.
+pub fn update_position(
+    ticks: &mut HashMap<i32, Info>,
+    tick_bitmap: &mut HashMap<i16, U256>,
+    tick_spacing: i32,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity_delta: i128,
+) {
+    let mut flipped_lower = false;
+    let mut flipped_upper = false;
+
+    if liquidity_delta != 0 {
+        flipped_lower = update_tick(ticks, tick_lower, liquidity_delta, false);
+        flipped_upper = update_tick(ticks, tick_upper, liquidity_delta, true);
+        if flipped_lower {
+            flip_tick(tick_bitmap, tick_lower, tick_spacing);
+        }
+        if flipped_upper {
+            flip_tick(tick_bitmap, tick_upper, tick_spacing);
+        }
+    }
+
+    if liquidity_delta < 0 {
+        if flipped_lower {
+            ticks.remove(&tick_lower);
+        }
+
+        if flipped_upper {
+            ticks.remove(&tick_upper);
+        }
+    }
+}
+
+/// Updates a tick's gross and net liquidity, returning true if the tick was flipped from
+/// initialized to uninitialized or vice versa.
+pub fn update_tick(
+    ticks: &mut HashMap<i32, Info>,
+    tick: i32,
+    liquidity_delta: i128,
+    upper: bool,
+) -> bool {
+    let info = ticks.entry(tick).or_default();
+
+    let liquidity_gross_before = info.liquidity_gross;
+
+    let liquidity_gross_after = if liquidity_delta < 0 {
+        liquidity_gross_before - ((-liquidity_delta) as u128)
+    } else {
+        liquidity_gross_before + (liquidity_delta as u128)
+    };
+
+    // we do not need to check if liqudity_gross_after > maxLiquidity because we are only calling update tick on a burn or mint log.
+    // this should already be validated when a log is
+    let flipped = (liquidity_gross_after == 0) != (liquidity_gross_before == 0);
+
+    if liquidity_gross_before == 0 {
+        info.initialized = true;
+    }
+
+    info.liquidity_gross = liquidity_gross_after;
+
+    info.liquidity_net = if upper {
+        info.liquidity_net - liquidity_delta
+    } else {
+        info.liquidity_net + liquidity_delta
+    };
+
+    flipped
+}
+
+/// Flips the initialized state of a tick in the tick bitmap.
+pub fn flip_tick(tick_bitmap: &mut HashMap<i16, U256>, tick: i32, tick_spacing: i32) {
+    let (word_pos, bit_pos) = uniswap_v3_math::tick_bitmap::position(tick / tick_spacing);
+    let mask = U256::from(1) << bit_pos;
+
+    if let Some(word) = tick_bitmap.get_mut(&word_pos) {
+        *word ^= mask;
+    } else {
+        tick_bitmap.insert(word_pos, mask);
     }
 }
 
-fn tick_to_word(tick: i32, tick_spacing: i32) -> i32 {
+pub fn tick_to_word(tick: i32, tick_spacing: i32) -> i32 {
     let mut compressed = tick / tick_spacing;
     if tick < 0 && tick % tick_spacing != 0 {
         compressed -= 1;
@@ -1189,6 +2384,8 @@ impl AutomatedMarketMakerFactory for UniswapV3Factory {
             token_b: pool_created_event.token1.into(),
             fee: pool_created_event.fee.to::<u32>(),
             tick_spacing: pool_created_event.tickSpacing.unchecked_into(),
+            fork: self.fork,
+            oracle: self.oracle.then(Oracle::default),
             ..Default::default()
         }))
     }
@@ -1196,6 +2393,15 @@ impl AutomatedMarketMakerFactory for UniswapV3Factory {
     fn creation_block(&self) -> u64 {
         self.creation_block
     }
+
+    fn pool_events(&self) -> Vec<B256> {
+        UniswapV3Pool {
+            fork: self.fork,
+            oracle: self.oracle.then(Oracle::default),
+            ..Default::default()
+        }
+        .sync_events()
+    }
 }
 
 impl DiscoverySync for UniswapV3Factory {
@@ -1612,4 +2818,528 @@ mod test {
 
         Ok(())
     }
+
+    /// A pool at price 1 with a full range position and a position concentrated around the price.
+    fn concentrated_pool() -> eyre::Result<UniswapV3Pool> {
+        let mut pool = UniswapV3Pool {
+            token_a: Token::new_with_decimals(
+                address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                18,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                18,
+            ),
+            sqrt_price: U256::from(1_u128 << 96),
+            fee: 3000,
+            tick_spacing: 60,
+            ..Default::default()
+        };
+        pool.modify_position(-887220, 887220, 10_i128.pow(21))?;
+        pool.modify_position(-600, 600, 10_i128.pow(22))?;
+
+        Ok(pool)
+    }
+
+    #[test]
+    fn test_simulate_swap_exact_out() -> eyre::Result<()> {
+        let pool = concentrated_pool()?;
+
+        for (base_token, quote_token) in [
+            (pool.token_a.address, pool.token_b.address),
+            (pool.token_b.address, pool.token_a.address),
+        ] {
+            // Crosses the upper bound of the concentrated position
+            let amount_out = U256::from(5 * 10_u128.pow(20));
+            let amount_in = pool.simulate_swap_exact_out(base_token, quote_token, amount_out)?;
+
+            let exact_in_out = pool.simulate_swap(base_token, quote_token, amount_in)?;
+            assert!(exact_in_out >= amount_out);
+            assert!(exact_in_out - amount_out <= U256::from(1));
+
+            let mut pool = pool.clone();
+            let amount_in_mut =
+                pool.simulate_swap_exact_out_mut(base_token, quote_token, amount_out)?;
+            assert_eq!(amount_in_mut, amount_in);
+            assert_ne!(pool.sqrt_price, U256::from(1_u128 << 96));
+        }
+
+        assert!(pool
+            .simulate_swap_exact_out(
+                pool.token_a.address,
+                pool.token_b.address,
+                U256::from(10_u128.pow(23))
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_outcome() -> eyre::Result<()> {
+        let pool = concentrated_pool()?;
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+
+        // Stays within the concentrated position
+        let amount_in = U256::from(10_u128.pow(20));
+        let outcome = pool.simulate_swap_outcome(token_a, token_b, amount_in)?;
+        assert_eq!(outcome.amount_in, amount_in);
+        assert_eq!(
+            outcome.amount_out,
+            pool.simulate_swap(token_a, token_b, amount_in)?
+        );
+        assert_eq!(outcome.fee, Some(U256::from(3 * 10_u128.pow(17))));
+        assert_eq!(outcome.ticks_crossed, 0);
+        assert!(!outcome.liquidity_exhausted);
+        assert!(outcome.price_after < outcome.price_before);
+
+        // Crosses the lower bound of the concentrated position
+        let outcome = pool.simulate_swap_outcome(token_a, token_b, U256::from(10_u128.pow(21)))?;
+        assert_eq!(outcome.ticks_crossed, 1);
+        assert!(!outcome.liquidity_exhausted);
+
+        // Runs through every position to the min price
+        let amount_in = U256::from(10).pow(U256::from(45));
+        let outcome = pool.simulate_swap_outcome(token_a, token_b, amount_in)?;
+        assert!(outcome.liquidity_exhausted);
+        assert!(outcome.amount_in < amount_in);
+        assert_eq!(outcome.ticks_crossed, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_protocol_fee() -> eyre::Result<()> {
+        let mut pool = concentrated_pool()?;
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let amount_in = U256::from(10_u128.pow(20));
+
+        let outcome = pool.simulate_swap_outcome(token_a, token_b, amount_in)?;
+        assert_eq!(outcome.protocol_fee, Some(U256::ZERO));
+
+        // Take 1/4 of the token0 fees and 1/5 of the token1 fees
+        let event = IUniswapV3PoolEvents::SetFeeProtocol {
+            feeProtocol0Old: 0,
+            feeProtocol1Old: 0,
+            feeProtocol0New: 4,
+            feeProtocol1New: 5,
+        };
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: event.encode_log_data(),
+            },
+            ..Default::default()
+        };
+        pool.sync(&log)?;
+        assert_eq!(pool.fee_protocol, 4 + (5 << 4));
+
+        // The protocol fee is carved out of the fee and leaves the amounts unchanged
+        let with_protocol_fee = pool.simulate_swap_outcome(token_a, token_b, amount_in)?;
+        assert_eq!(with_protocol_fee.amount_out, outcome.amount_out);
+        assert_eq!(with_protocol_fee.fee, outcome.fee);
+        assert_eq!(
+            with_protocol_fee.protocol_fee,
+            Some(U256::from(75 * 10_u128.pow(15)))
+        );
+        let with_protocol_fee = pool.simulate_swap_outcome(token_b, token_a, amount_in)?;
+        assert_eq!(
+            with_protocol_fee.protocol_fee,
+            Some(U256::from(6 * 10_u128.pow(16)))
+        );
+
+        // PancakeSwap V3 takes a share of the fee out of 10000
+        pool.fork = UniswapV3Fork::PancakeSwapV3;
+        pool.fee_protocol = pool.fork.pack_fee_protocol(3300, 3200);
+        let with_protocol_fee = pool.simulate_swap_outcome(token_a, token_b, amount_in)?;
+        assert_eq!(
+            with_protocol_fee.protocol_fee,
+            Some(U256::from(99 * 10_u128.pow(15)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_position_value() -> eyre::Result<()> {
+        let mut pool = concentrated_pool()?;
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let full_range = pool.open_position(-887220, 887220, 10_u128.pow(21));
+        let concentrated = pool.open_position(-600, 600, 10_u128.pow(22));
+
+        // At price 1 the range is symmetric, so the position holds as much of each token
+        let value = pool.position_value(&concentrated)?;
+        assert!(value.amount_0.abs_diff(value.amount_1) <= U256::from(1));
+        assert_eq!((value.fees_0, value.fees_1), (U256::ZERO, U256::ZERO));
+
+        // Crosses the lower bound of the concentrated position, then back through its whole range
+        let mut fees = (U256::ZERO, U256::ZERO);
+        for (base_token, quote_token, amount_in) in [
+            (token_a, token_b, 10_u128.pow(21)),
+            (token_b, token_a, 2 * 10_u128.pow(21)),
+        ] {
+            let outcome =
+                pool.simulate_swap_outcome(base_token, quote_token, U256::from(amount_in))?;
+            assert!(outcome.ticks_crossed > 0);
+            if base_token == token_a {
+                fees.0 += outcome.fee.unwrap();
+            } else {
+                fees.1 += outcome.fee.unwrap();
+            }
+
+            pool.simulate_swap_mut(base_token, quote_token, U256::from(amount_in))?;
+        }
+
+        // Every fee is paid out to one of the positions, less rounding
+        let full_range_value = pool.position_value(&full_range)?;
+        let concentrated_value = pool.position_value(&concentrated)?;
+        let fees_0 = full_range_value.fees_0 + concentrated_value.fees_0;
+        let fees_1 = full_range_value.fees_1 + concentrated_value.fees_1;
+        assert!(fees_0 <= fees.0 && fees.0 - fees_0 <= U256::from(4));
+        assert!(fees_1 <= fees.1 && fees.1 - fees_1 <= U256::from(4));
+
+        // The concentrated position earned nothing while the price was below its range
+        assert!(concentrated_value.fees_0 < fees_0 * U256::from(10) / U256::from(11));
+
+        // A position opened now starts without fees
+        let value = pool.position_value(&pool.open_position(-600, 600, 10_u128.pow(22)))?;
+        assert_eq!((value.fees_0, value.fees_1), (U256::ZERO, U256::ZERO));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_swap_fee_growth() -> eyre::Result<()> {
+        let pool = concentrated_pool()?;
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let amount_in = U256::from(10_u128.pow(21));
+
+        let mut simulated = pool.clone();
+        let amount_out = simulated.simulate_swap_mut(token_a, token_b, amount_in)?;
+
+        let mut synced = pool.clone();
+        let swap_event = IUniswapV3PoolEvents::Swap {
+            sender: Address::default(),
+            recipient: Address::default(),
+            amount0: I256::from_raw(amount_in),
+            amount1: -I256::from_raw(amount_out),
+            sqrtPriceX96: simulated.sqrt_price.to(),
+            liquidity: simulated.liquidity,
+            tick: Signed::unchecked_from(simulated.tick),
+        };
+        synced.sync(&Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: swap_event.encode_log_data(),
+            },
+            ..Default::default()
+        })?;
+
+        assert_ne!(synced.fee_growth_global_0_x_128, U256::ZERO);
+        assert_eq!(
+            synced.fee_growth_global_0_x_128,
+            simulated.fee_growth_global_0_x_128
+        );
+        assert_eq!(
+            synced.fee_growth_inside(-600, 600),
+            simulated.fee_growth_inside(-600, 600)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_twap() -> eyre::Result<()> {
+        let mut pool = concentrated_pool()?;
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        assert!(matches!(
+            pool.twap(token_a, token_b, 60, 1_000),
+            Err(AMMError::OracleNotTracked(_))
+        ));
+
+        pool.oracle = Some(Oracle {
+            observations: vec![Observation {
+                block_timestamp: 1_000,
+                initialized: true,
+                ..Default::default()
+            }],
+            observation_cardinality: 1,
+            observation_cardinality_next: 1,
+            ..Default::default()
+        });
+
+        let address = pool.address;
+        let log = |data, block_timestamp| Log {
+            inner: alloy::primitives::Log { address, data },
+            block_timestamp: Some(block_timestamp),
+            ..Default::default()
+        };
+        let cardinality_event = IUniswapV3PoolEvents::IncreaseObservationCardinalityNext {
+            observationCardinalityNextOld: 1,
+            observationCardinalityNextNew: 10,
+        };
+        pool.sync(&log(cardinality_event.encode_log_data(), 1_006))?;
+
+        // The price holds at tick 0 for 12 seconds before a swap moves it
+        let mut simulated = pool.clone();
+        let amount_in = U256::from(10_u128.pow(21));
+        let amount_out = simulated.simulate_swap_mut(token_a, token_b, amount_in)?;
+        let swap_event = IUniswapV3PoolEvents::Swap {
+            sender: Address::default(),
+            recipient: Address::default(),
+            amount0: I256::from_raw(amount_in),
+            amount1: -I256::from_raw(amount_out),
+            sqrtPriceX96: simulated.sqrt_price.to(),
+            liquidity: simulated.liquidity,
+            tick: Signed::unchecked_from(simulated.tick),
+        };
+        pool.sync(&log(swap_event.encode_log_data(), 1_012))?;
+
+        let oracle = pool.oracle.as_ref().unwrap();
+        assert_eq!(oracle.observation_cardinality, 10);
+        assert_eq!(oracle.observation_index, 1);
+
+        assert_eq!(pool.twap(token_a, token_b, 12, 1_012)?, Q128);
+        assert_eq!(
+            pool.twap(token_a, token_b, 0, 1_024)?,
+            pool.calculate_price_exact(token_a, token_b)?
+        );
+
+        let mean_tick = (simulated.tick * 12).div_euclid(24);
+        assert_eq!(
+            pool.twap(token_a, token_b, 24, 1_024)?,
+            price_exact_at_sqrt_price(
+                uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(mean_tick)?,
+                &pool.token_a,
+                &pool.token_b,
+                token_a,
+            )?
+        );
+        assert!(pool.twap(token_a, token_b, 25, 1_024).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_liquidity() -> eyre::Result<()> {
+        let pool = concentrated_pool()?;
+        let range = LiquidityRange::Ticks {
+            tick_lower: -600,
+            tick_upper: 600,
+        };
+
+        // At price 1 the range takes as much of each token, so the excess token1 is left unused
+        let amounts = [U256::from(10_u128.pow(20)), U256::from(2 * 10_u128.pow(20))];
+        let outcome = pool.simulate_add_liquidity(range, &amounts)?;
+        assert!(
+            outcome.amounts[0] <= amounts[0] && amounts[0] - outcome.amounts[0] <= U256::from(1)
+        );
+        assert!(outcome.amounts[1].abs_diff(outcome.amounts[0]) <= U256::from(1));
+
+        let mut minted = pool.clone();
+        assert_eq!(minted.simulate_add_liquidity_mut(range, &amounts)?, outcome);
+        assert_eq!(
+            U256::from(minted.liquidity),
+            U256::from(pool.liquidity) + outcome.liquidity
+        );
+
+        // Burning rounds the amounts down, minting rounds them up
+        let removed = minted.simulate_remove_liquidity_mut(range, outcome.liquidity)?;
+        assert!(removed.amounts[0] <= outcome.amounts[0]);
+        assert!(outcome.amounts[0] - removed.amounts[0] <= U256::from(1));
+        assert_eq!(minted.liquidity, pool.liquidity);
+        assert_eq!(
+            minted.ticks[&-600].liquidity_gross,
+            pool.ticks[&-600].liquidity_gross
+        );
+
+        // Out of range positions take a single token
+        let outcome = pool.simulate_add_liquidity(
+            LiquidityRange::Ticks {
+                tick_lower: 600,
+                tick_upper: 1200,
+            },
+            &amounts,
+        )?;
+        assert_eq!(outcome.amounts[1], U256::ZERO);
+
+        assert!(
+            pool.simulate_add_liquidity(LiquidityRange::Full, &amounts)?
+                .liquidity
+                > U256::ZERO
+        );
+        assert!(pool
+            .simulate_remove_liquidity(range, U256::from(10_u128.pow(23)))
+            .is_err());
+        assert!(pool
+            .simulate_add_liquidity(
+                LiquidityRange::Ticks {
+                    tick_lower: -601,
+                    tick_upper: 600
+                },
+                &amounts
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_swap_with_limit() -> eyre::Result<()> {
+        let mut pool = concentrated_pool()?;
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+
+        let sqrt_price_limit_x_96 = uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(-300)?;
+        let amount_in = U256::from(10_u128.pow(21));
+
+        // The limit binds before the amount in is consumed
+        let outcome = pool.simulate_swap_with_limit(token_a, amount_in, sqrt_price_limit_x_96)?;
+        assert!(outcome.amount_in < amount_in);
+        assert!(!outcome.liquidity_exhausted);
+        assert_eq!(outcome.ticks_crossed, 0);
+        assert_eq!(
+            outcome.amount_out,
+            pool.simulate_swap(token_a, token_b, outcome.amount_in)?
+        );
+
+        // Without a binding limit the fill matches an unlimited swap
+        let small_amount_in = U256::from(10_u128.pow(18));
+        let unbound_outcome =
+            pool.simulate_swap_with_limit(token_a, small_amount_in, sqrt_price_limit_x_96)?;
+        assert_eq!(
+            unbound_outcome,
+            pool.simulate_swap_outcome(token_a, token_b, small_amount_in)?
+        );
+
+        assert_eq!(
+            pool.simulate_swap_with_limit_mut(token_a, amount_in, sqrt_price_limit_x_96)?,
+            outcome
+        );
+        assert_eq!(pool.sqrt_price, sqrt_price_limit_x_96);
+        assert_eq!(pool.tick, -300);
+
+        // The limit must lie in the direction of the swap
+        assert!(matches!(
+            pool.simulate_swap_with_limit(token_b, amount_in, sqrt_price_limit_x_96 - U256_1),
+            Err(AMMError::UniswapV3Error(
+                UniswapV3Error::InvalidSqrtPriceLimit
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_amount_in_to_marginal_rate() -> eyre::Result<()> {
+        let pool = concentrated_pool()?;
+
+        for (base_token, quote_token) in [
+            (pool.token_a.address, pool.token_b.address),
+            (pool.token_b.address, pool.token_a.address),
+        ] {
+            // The marginal rate of a swap of `amount_in`, measured over a small increment
+            let probe = U256::from(10_u64.pow(12));
+            let marginal_rate = |amount_in: U256| -> eyre::Result<f64> {
+                let amount_out = pool.simulate_swap(base_token, quote_token, amount_in)?;
+                let probed_out = pool.simulate_swap(base_token, quote_token, amount_in + probe)?;
+                Ok(f64::from(probed_out - amount_out) / f64::from(probe))
+            };
+            let spot_rate = marginal_rate(U256::ZERO)?;
+
+            assert_eq!(
+                pool.amount_in_to_marginal_rate(base_token, spot_rate * 1.1)?,
+                U256::ZERO
+            );
+
+            // Crosses the bound of the concentrated position
+            let target_rate = spot_rate * 0.9;
+            let amount_in = pool.amount_in_to_marginal_rate(base_token, target_rate)?;
+            assert!((marginal_rate(amount_in)? / target_rate - 1.0).abs() < 1e-6);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calculate_price_exact() -> eyre::Result<()> {
+        let mut pool = UniswapV3Pool {
+            token_a: Token::new_with_decimals(
+                address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                6,
+            ),
+            token_b: Token::new_with_decimals(
+                address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                18,
+            ),
+            sqrt_price: U256::from(1_u128 << 96),
+            ..Default::default()
+        };
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let e12 = U256::from(10_u128.pow(12));
+
+        assert_eq!(pool.calculate_price_exact(token_a, token_b)?, Q128 / e12);
+        assert_eq!(pool.calculate_price_exact(token_b, token_a)?, Q128 * e12);
+
+        // Prices between ticks are not rounded to the tick below
+        pool.sqrt_price =
+            uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(100)? + U256::from(1_u128 << 80);
+        let price_at_tick = price_exact_at_sqrt_price(
+            uniswap_v3_math::tick_math::get_sqrt_ratio_at_tick(100)?,
+            &pool.token_a,
+            &pool.token_b,
+            token_a,
+        )?;
+        assert!(pool.calculate_price_exact(token_a, token_b)? > price_at_tick);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_pancake_swap() -> eyre::Result<()> {
+        let mut pool = UniswapV3Pool {
+            address: address!("36696169C63e42cd08ce11f5deeBbCeBae652050"),
+            fork: UniswapV3Fork::PancakeSwapV3,
+            ..Default::default()
+        };
+
+        assert!(pool
+            .sync_events()
+            .contains(&IPancakeV3PoolEvents::Swap::SIGNATURE_HASH));
+
+        // The factory subscribes to the fork's events for the pools it discovers
+        let factory = UniswapV3Factory::new_with_fork(
+            address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865"),
+            0,
+            UniswapV3Fork::PancakeSwapV3,
+        );
+        assert_eq!(factory.pool_events(), pool.sync_events());
+        assert!(!factory
+            .pool_events()
+            .contains(&IUniswapV3PoolEvents::Swap::SIGNATURE_HASH));
+
+        let swap_event = IPancakeV3PoolEvents::Swap {
+            sender: Address::default(),
+            recipient: Address::default(),
+            amount0: I256::ZERO,
+            amount1: I256::ZERO,
+            sqrtPriceX96: U160::from(1_u128 << 96),
+            liquidity: 1_000_000,
+            tick: Signed::unchecked_from(10),
+            protocolFeesToken0: 1,
+            protocolFeesToken1: 0,
+        };
+        pool.sync(&Log {
+            inner: alloy::primitives::Log {
+                address: pool.address,
+                data: swap_event.encode_log_data(),
+            },
+            ..Default::default()
+        })?;
+
+        assert_eq!(pool.sqrt_price, U256::from(1_u128 << 96));
+        assert_eq!(pool.liquidity, 1_000_000);
+        assert_eq!(pool.tick, 10);
+
+        Ok(())
+    }
 }
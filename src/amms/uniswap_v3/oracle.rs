@@ -0,0 +1,339 @@
+//! The price and liquidity oracle of a Uniswap V3 pool, ported from `Oracle.sol`.
+//!
+//! The pool writes at most one observation per block into a ring buffer of `cardinality`
+//! observations, so a time weighted average price can be computed from any two observations
+//! still in the buffer.
+
+use alloy::primitives::U256;
+use serde::{Deserialize, Serialize};
+
+use super::UniswapV3Error;
+
+/// Mask of the 160 bits the pool stores the seconds per liquidity cumulative in
+const U160_MAX: U256 = U256::from_limbs([u64::MAX, u64::MAX, u32::MAX as u64, 0]);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Observation {
+    pub block_timestamp: u32,
+    /// Tick multiplied by the seconds elapsed over the life of the pool, as of `block_timestamp`
+    pub tick_cumulative: i64,
+    /// Seconds elapsed divided by the in range liquidity over the life of the pool, as a Q128.128
+    /// number truncated to 160 bits
+    pub seconds_per_liquidity_cumulative_x_128: U256,
+    pub initialized: bool,
+}
+
+impl Observation {
+    /// Returns the observation that follows `self` at `block_timestamp`, given the tick and
+    /// liquidity in between.
+    pub fn transform(&self, block_timestamp: u32, tick: i32, liquidity: u128) -> Observation {
+        let delta = block_timestamp.wrapping_sub(self.block_timestamp);
+
+        Observation {
+            block_timestamp,
+            tick_cumulative: self
+                .tick_cumulative
+                .wrapping_add(tick as i64 * delta as i64),
+            seconds_per_liquidity_cumulative_x_128: self
+                .seconds_per_liquidity_cumulative_x_128
+                .wrapping_add((U256::from(delta) << 128) / U256::from(liquidity.max(1)))
+                & U160_MAX,
+            initialized: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Oracle {
+    /// Observations in the order the pool stores them, `observation_cardinality_next` of them once
+    /// synced
+    pub observations: Vec<Observation>,
+    /// Index of the most recent observation
+    pub observation_index: u16,
+    /// Number of observations in use
+    pub observation_cardinality: u16,
+    /// Number of observations the buffer grows to once the last one in use is written
+    pub observation_cardinality_next: u16,
+}
+
+impl Oracle {
+    /// Records the tick and liquidity that held until `block_timestamp`, unless an observation was
+    /// already written in this block.
+    pub fn write(&mut self, block_timestamp: u32, tick: i32, liquidity: u128) {
+        let last = self.observation(self.observation_index);
+        if self.observation_cardinality == 0 || last.block_timestamp == block_timestamp {
+            return;
+        }
+
+        if self.observation_cardinality_next > self.observation_cardinality
+            && self.observation_index == self.observation_cardinality - 1
+        {
+            self.observation_cardinality = self.observation_cardinality_next;
+        }
+
+        self.observation_index = (self.observation_index + 1) % self.observation_cardinality;
+        let observation = last.transform(block_timestamp, tick, liquidity);
+        self.set_observation(self.observation_index, observation);
+    }
+
+    /// Prepares the buffer to hold `next` observations, as `increaseObservationCardinalityNext`.
+    pub fn grow(&mut self, next: u16) {
+        if next <= self.observation_cardinality_next {
+            return;
+        }
+
+        // The pool writes a timestamp into new slots to pay for their storage up front
+        for index in self.observation_cardinality_next..next {
+            self.set_observation(
+                index,
+                Observation {
+                    block_timestamp: 1,
+                    ..Default::default()
+                },
+            );
+        }
+
+        self.observation_cardinality_next = next;
+    }
+
+    /// Returns the tick cumulative and seconds per liquidity cumulative `seconds_ago` seconds
+    /// before `time`, given the current tick and liquidity.
+    pub fn observe(
+        &self,
+        time: u32,
+        seconds_ago: u32,
+        tick: i32,
+        liquidity: u128,
+    ) -> Result<(i64, U256), UniswapV3Error> {
+        if self.observation_cardinality == 0 {
+            return Err(UniswapV3Error::OracleTooOld);
+        }
+
+        if seconds_ago == 0 {
+            let mut last = self.observation(self.observation_index);
+            if last.block_timestamp != time {
+                last = last.transform(time, tick, liquidity);
+            }
+
+            return Ok((
+                last.tick_cumulative,
+                last.seconds_per_liquidity_cumulative_x_128,
+            ));
+        }
+
+        let target = time.wrapping_sub(seconds_ago);
+        let (before_or_at, at_or_after) =
+            self.surrounding_observations(time, target, tick, liquidity)?;
+
+        if target == before_or_at.block_timestamp {
+            Ok((
+                before_or_at.tick_cumulative,
+                before_or_at.seconds_per_liquidity_cumulative_x_128,
+            ))
+        } else if target == at_or_after.block_timestamp {
+            Ok((
+                at_or_after.tick_cumulative,
+                at_or_after.seconds_per_liquidity_cumulative_x_128,
+            ))
+        } else {
+            // Interpolate between the observations surrounding the target
+            let observation_time_delta = at_or_after
+                .block_timestamp
+                .wrapping_sub(before_or_at.block_timestamp);
+            let target_delta = target.wrapping_sub(before_or_at.block_timestamp);
+
+            let tick_cumulative = before_or_at.tick_cumulative
+                + (at_or_after.tick_cumulative - before_or_at.tick_cumulative)
+                    / observation_time_delta as i64
+                    * target_delta as i64;
+            let seconds_per_liquidity_cumulative_x_128 = before_or_at
+                .seconds_per_liquidity_cumulative_x_128
+                .wrapping_add(
+                    (at_or_after
+                        .seconds_per_liquidity_cumulative_x_128
+                        .wrapping_sub(before_or_at.seconds_per_liquidity_cumulative_x_128)
+                        & U160_MAX)
+                        * U256::from(target_delta)
+                        / U256::from(observation_time_delta),
+                )
+                & U160_MAX;
+
+            Ok((tick_cumulative, seconds_per_liquidity_cumulative_x_128))
+        }
+    }
+
+    /// Returns the observations at or before and at or after `target`, transforming the most
+    /// recent observation if `target` is after it.
+    fn surrounding_observations(
+        &self,
+        time: u32,
+        target: u32,
+        tick: i32,
+        liquidity: u128,
+    ) -> Result<(Observation, Observation), UniswapV3Error> {
+        let before_or_at = self.observation(self.observation_index);
+        if lte(time, before_or_at.block_timestamp, target) {
+            return if before_or_at.block_timestamp == target {
+                Ok((before_or_at, Observation::default()))
+            } else {
+                Ok((
+                    before_or_at,
+                    before_or_at.transform(target, tick, liquidity),
+                ))
+            };
+        }
+
+        // The oldest observation is the next one, unless the buffer has not filled up yet
+        let mut oldest =
+            self.observation((self.observation_index + 1) % self.observation_cardinality);
+        if !oldest.initialized {
+            oldest = self.observation(0);
+        }
+
+        if !lte(time, oldest.block_timestamp, target) {
+            return Err(UniswapV3Error::OracleTooOld);
+        }
+
+        Ok(self.binary_search(time, target))
+    }
+
+    /// Finds the observations surrounding `target`, which must be between the oldest and the most
+    /// recent observation.
+    fn binary_search(&self, time: u32, target: u32) -> (Observation, Observation) {
+        let cardinality = self.observation_cardinality as usize;
+        let mut l = (self.observation_index as usize + 1) % cardinality;
+        let mut r = l + cardinality - 1;
+
+        loop {
+            let i = (l + r) / 2;
+            let before_or_at = self.observation((i % cardinality) as u16);
+
+            // Slots that have not been written yet hold no observation
+            if !before_or_at.initialized {
+                l = i + 1;
+                continue;
+            }
+
+            let at_or_after = self.observation(((i + 1) % cardinality) as u16);
+            let target_at_or_after = lte(time, before_or_at.block_timestamp, target);
+
+            if target_at_or_after && lte(time, target, at_or_after.block_timestamp) {
+                return (before_or_at, at_or_after);
+            }
+
+            if !target_at_or_after {
+                r = i - 1;
+            } else {
+                l = i + 1;
+            }
+        }
+    }
+
+    fn observation(&self, index: u16) -> Observation {
+        self.observations
+            .get(index as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn set_observation(&mut self, index: u16, observation: Observation) {
+        let index = index as usize;
+        if index >= self.observations.len() {
+            self.observations.resize(index + 1, Observation::default());
+        }
+
+        self.observations[index] = observation;
+    }
+}
+
+/// Compares two timestamps at or before `time`, accounting for them having wrapped around 2^32.
+fn lte(time: u32, a: u32, b: u32) -> bool {
+    if a <= time && b <= time {
+        return a <= b;
+    }
+
+    let a_adjusted = if a > time {
+        a as u64
+    } else {
+        a as u64 + (1 << 32)
+    };
+    let b_adjusted = if b > time {
+        b as u64
+    } else {
+        b as u64 + (1 << 32)
+    };
+
+    a_adjusted <= b_adjusted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oracle(cardinality: u16) -> Oracle {
+        let mut oracle = Oracle {
+            observations: vec![Observation {
+                block_timestamp: 1_000,
+                initialized: true,
+                ..Default::default()
+            }],
+            observation_index: 0,
+            observation_cardinality: 1,
+            observation_cardinality_next: 1,
+        };
+        oracle.grow(cardinality);
+
+        oracle
+    }
+
+    #[test]
+    fn test_write_and_observe() -> eyre::Result<()> {
+        let mut oracle = oracle(4);
+
+        // Tick 10 held for 12 seconds, then tick -20 for 24 seconds
+        oracle.write(1_012, 10, 1_000);
+        oracle.write(1_012, 50, 1_000);
+        oracle.write(1_036, -20, 1_000);
+
+        assert_eq!(oracle.observation_cardinality, 4);
+        assert_eq!(oracle.observation_index, 2);
+        assert_eq!(oracle.observations[2].tick_cumulative, 120 - 480);
+
+        // Current tick 5 since the last observation
+        assert_eq!(oracle.observe(1_040, 0, 5, 1_000)?.0, 120 - 480 + 20);
+        assert_eq!(oracle.observe(1_040, 4, 5, 1_000)?.0, 120 - 480);
+        assert_eq!(oracle.observe(1_040, 34, 5, 1_000)?.0, 60);
+        assert_eq!(oracle.observe(1_040, 40, 5, 1_000)?.0, 0);
+        assert!(matches!(
+            oracle.observe(1_040, 41, 5, 1_000),
+            Err(UniswapV3Error::OracleTooOld)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_wraps_around() -> eyre::Result<()> {
+        let mut oracle = oracle(2);
+        for i in 1..=5 {
+            oracle.write(1_000 + i * 10, 1, 1);
+        }
+
+        // Only the two most recent observations are kept
+        assert_eq!(oracle.observation_index, 1);
+        assert_eq!(oracle.observe(1_050, 10, 1, 1)?.0, 40);
+        assert_eq!(oracle.observe(1_050, 5, 1, 1)?.0, 45);
+        assert!(oracle.observe(1_050, 11, 1, 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lte() {
+        assert!(lte(100, 10, 20));
+        assert!(!lte(100, 20, 10));
+        // `a` is before the timestamp wrapped around, `b` after
+        assert!(lte(100, u32::MAX - 5, 20));
+        assert!(!lte(100, 20, u32::MAX - 5));
+    }
+}
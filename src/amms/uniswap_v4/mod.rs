@@ -0,0 +1,1174 @@
+use super::{
+    amm::{check_amount_out, AMMKey, AutomatedMarketMaker, SwapOutcome, AMM},
+    consts::U256_1,
+    error::{AMMError, BatchContractError},
+    factory::{AutomatedMarketMakerFactory, DiscoverySync},
+    get_token_decimals,
+    uniswap_v3::{
+        check_sqrt_price_limit, compute_swap, default_sqrt_price_limit, price_at_sqrt_price,
+        price_exact_at_sqrt_price, tick_to_word, update_position, CurrentState, Info, ProtocolFee,
+        UniswapV3Error,
+    },
+    Token,
+};
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{
+        aliases::U24, keccak256, Address, BigIntConversionError, Bytes, Signed, B256, I256, U256,
+    },
+    providers::Provider,
+    rpc::types::{Filter, FilterSet, Log},
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+};
+use thiserror::Error;
+use tracing::info;
+use uniswap_v3_math::tick_math::{MAX_TICK, MIN_TICK};
+use GetUniswapV4PoolTickBitmapBatchRequest::TickBitmapInfo;
+
+sol! {
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct PoolKey {
+        address currency0;
+        address currency1;
+        uint24 fee;
+        int24 tickSpacing;
+        address hooks;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[derive(Debug, PartialEq, Eq)]
+    #[sol(rpc)]
+    contract IPoolManager {
+        /// @notice Emitted when a new pool is initialized
+        event Initialize(
+            bytes32 indexed id,
+            address indexed currency0,
+            address indexed currency1,
+            uint24 fee,
+            int24 tickSpacing,
+            address hooks,
+            uint160 sqrtPriceX96,
+            int24 tick
+        );
+
+        /// @notice Emitted when a liquidity position is modified
+        event ModifyLiquidity(
+            bytes32 indexed id,
+            address indexed sender,
+            int24 tickLower,
+            int24 tickUpper,
+            int256 liquidityDelta,
+            bytes32 salt
+        );
+
+        /// @notice Emitted for swaps between currency0 and currency1
+        event Swap(
+            bytes32 indexed id,
+            address indexed sender,
+            int128 amount0,
+            int128 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint24 fee
+        );
+
+        struct SwapParams {
+            bool zeroForOne;
+            int256 amountSpecified;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        function swap(PoolKey memory key, SwapParams memory params, bytes calldata hookData) external returns (int256 swapDelta);
+        function extsload(bytes32[] calldata slots) external view returns (bytes32[] memory);
+    }
+}
+
+sol! {
+    #[sol(rpc)]
+    GetUniswapV4PoolTickBitmapBatchRequest,
+    "src/amms/abi/GetUniswapV4PoolTickBitmapBatchRequest.json",
+}
+
+/// Storage slot of the `pools` mapping in the `PoolManager`
+const POOLS_SLOT: u64 = 6;
+/// Offset of `liquidity` within `Pool.State`
+const LIQUIDITY_OFFSET: u64 = 3;
+/// Offset of the `ticks` mapping within `Pool.State`
+const TICKS_OFFSET: u64 = 4;
+/// Maximum number of storage slots read in a single `extsload` call
+const EXTSLOAD_STEP: usize = 2000;
+/// Maximum number of tick bitmap words scanned in a single batch request
+const TICK_BITMAP_STEP: i32 = 6900;
+
+/// Fee value signalling that the LP fee of the pool is managed by its hook
+pub const DYNAMIC_FEE_FLAG: u32 = 0x800000;
+
+// Hook permission flags, encoded in the lowest bits of the hook address
+pub const BEFORE_SWAP_FLAG: u16 = 1 << 7;
+pub const AFTER_SWAP_FLAG: u16 = 1 << 6;
+pub const BEFORE_SWAP_RETURNS_DELTA_FLAG: u16 = 1 << 3;
+pub const AFTER_SWAP_RETURNS_DELTA_FLAG: u16 = 1 << 2;
+
+#[derive(Error, Debug)]
+pub enum UniswapV4Error {
+    #[error("Pool hooks modify swap behavior")]
+    HookModifiesSwap,
+    #[error(transparent)]
+    BigIntConversionError(#[from] BigIntConversionError),
+}
+
+/// A Uniswap V4 pool living in the singleton `PoolManager`.
+///
+/// V4 pools have no contract address of their own. Their address is the `PoolManager` and they are
+/// keyed in the state space by the `PoolManager` and their `PoolId`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UniswapV4Pool {
+    pub pool_manager: Address,
+    pub pool_id: B256,
+    /// Currency 0 of the pool, the zero address denotes the native currency
+    pub token_a: Token,
+    /// Currency 1 of the pool
+    pub token_b: Token,
+    /// Fee from the pool key, equal to [`DYNAMIC_FEE_FLAG`] for dynamic fee pools
+    pub fee: u32,
+    /// LP fee currently charged by the pool
+    pub lp_fee: u32,
+    /// Packed protocol fee, the lower 12 bits apply to zero for one swaps
+    pub protocol_fee: u32,
+    pub tick_spacing: i32,
+    pub hooks: Address,
+    pub liquidity: u128,
+    pub sqrt_price: U256,
+    pub tick: i32,
+    pub tick_bitmap: HashMap<i16, U256>,
+    pub ticks: HashMap<i32, Info>,
+    /// Simulate swaps through hooks that modify swap behavior, treating the result as approximate.
+    /// When false, simulating a swap on such a pool returns [`UniswapV4Error::HookModifiesSwap`].
+    pub approximate_hooks: bool,
+}
+
+impl AutomatedMarketMaker for UniswapV4Pool {
+    fn address(&self) -> Address {
+        self.pool_manager
+    }
+
+    fn key(&self) -> AMMKey {
+        AMMKey::Singleton {
+            singleton: self.pool_manager,
+            id: self.pool_id,
+        }
+    }
+
+    fn sync_events(&self) -> Vec<B256> {
+        vec![
+            IPoolManager::Swap::SIGNATURE_HASH,
+            IPoolManager::ModifyLiquidity::SIGNATURE_HASH,
+        ]
+    }
+
+    /// Routes `Swap` and `ModifyLiquidity` logs to the pool with their `PoolId` in the
+    /// `PoolManager` emitting them. Pools created by `Initialize` logs are found by discovery.
+    fn log_key(log: &Log) -> Option<AMMKey> {
+        let topics = log.topics();
+        match topics.first() {
+            Some(&IPoolManager::Swap::SIGNATURE_HASH)
+            | Some(&IPoolManager::ModifyLiquidity::SIGNATURE_HASH) => {
+                topics.get(1).map(|pool_id| AMMKey::Singleton {
+                    singleton: log.address(),
+                    id: *pool_id,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn sync(&mut self, log: &Log) -> Result<(), AMMError> {
+        // Events of other contracts sharing their signature with the `PoolManager` events are
+        // skipped
+        if log.address() != self.pool_manager {
+            return Ok(());
+        }
+
+        let event_signature = log.topics()[0];
+        match event_signature {
+            IPoolManager::Swap::SIGNATURE_HASH => {
+                let swap_event = IPoolManager::Swap::decode_log(log.as_ref())?;
+
+                self.sqrt_price = swap_event.sqrtPriceX96.to();
+                self.liquidity = swap_event.liquidity;
+                self.tick = swap_event.tick.unchecked_into();
+
+                // The event reports the total swap fee, which is the LP fee when no protocol fee is set
+                if self.is_dynamic_fee() && self.protocol_fee == 0 {
+                    self.lp_fee = swap_event.fee.to::<u32>();
+                }
+
+                info!(
+                    target = "amms::uniswap_v4::sync",
+                    pool_id = ?self.pool_id,
+                    sqrt_price = ?self.sqrt_price,
+                    liquidity = ?self.liquidity,
+                    tick = ?self.tick,
+                    "Swap"
+                );
+            }
+            IPoolManager::ModifyLiquidity::SIGNATURE_HASH => {
+                let modify_event = IPoolManager::ModifyLiquidity::decode_log(log.as_ref())?;
+
+                self.modify_position(
+                    modify_event.tickLower.unchecked_into(),
+                    modify_event.tickUpper.unchecked_into(),
+                    i128::try_from(modify_event.liquidityDelta).map_err(UniswapV4Error::from)?,
+                )?;
+
+                info!(
+                    target = "amms::uniswap_v4::sync",
+                    pool_id = ?self.pool_id,
+                    sqrt_price = ?self.sqrt_price,
+                    liquidity = ?self.liquidity,
+                    tick = ?self.tick,
+                    "ModifyLiquidity"
+                );
+            }
+            _ => {
+                return Err(AMMError::UnrecognizedEventSignature(event_signature));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn simulate_swap(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_in.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+
+        Ok(token_out.after_buy_tax((-current_state.amount_calculated).into_raw()))
+    }
+
+    fn simulate_swap_mut(
+        &mut self,
+        base_token: Address,
+        _quote_token: Address,
+        amount_in: U256,
+    ) -> Result<U256, AMMError> {
+        if amount_in.is_zero() {
+            return Ok(U256::ZERO);
+        }
+
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+
+        let amount_out = token_out.after_buy_tax((-current_state.amount_calculated).into_raw());
+
+        self.liquidity = current_state.liquidity;
+        self.sqrt_price = current_state.sqrt_price_x_96;
+        self.tick = current_state.tick;
+
+        Ok(amount_out)
+    }
+
+    fn tokens(&self) -> Vec<Address> {
+        vec![self.token_a.address, self.token_b.address]
+    }
+
+    fn calculate_price(&self, base_token: Address, _quote_token: Address) -> Result<f64, AMMError> {
+        price_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
+
+    fn calculate_price_exact(
+        &self,
+        base_token: Address,
+        _quote_token: Address,
+    ) -> Result<U256, AMMError> {
+        price_exact_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)
+    }
+
+    fn simulate_swap_outcome(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let price_before = self.calculate_price(base_token, quote_token)?;
+        if amount_in.is_zero() {
+            return Ok(SwapOutcome::empty(price_before));
+        }
+
+        let zero_for_one = base_token == self.token_a.address;
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let current_state = self.swap(
+            zero_for_one,
+            I256::from_raw(token_in.after_sell_tax(amount_in)),
+        )?;
+        let price_after = price_at_sqrt_price(
+            current_state.sqrt_price_x_96,
+            &self.token_a,
+            &self.token_b,
+            base_token,
+        )?;
+
+        Ok(current_state
+            .outcome(
+                token_in.after_sell_tax(amount_in),
+                price_before,
+                price_after,
+            )
+            .with_taxes(amount_in, token_in, token_out))
+    }
+
+    /// Encodes `swap` on the `PoolManager` as an exact input swap without a price limit. The call
+    /// must be made while the `PoolManager` is unlocked, with the caller settling the amount in and
+    /// taking the amount out, so `recipient` is unused.
+    fn swap_calldata(
+        &self,
+        base_token: Address,
+        quote_token: Address,
+        amount_in: U256,
+        min_out: U256,
+        _recipient: Address,
+    ) -> Result<Bytes, AMMError> {
+        check_amount_out(self, base_token, quote_token, amount_in, min_out)?;
+
+        let zero_for_one = base_token == self.token_a.address;
+        Ok(IPoolManager::swapCall {
+            key: self.pool_key(),
+            params: IPoolManager::SwapParams {
+                zeroForOne: zero_for_one,
+                // Negative amounts are exact input swaps
+                amountSpecified: -I256::from_raw(amount_in),
+                sqrtPriceLimitX96: default_sqrt_price_limit(zero_for_one).to(),
+            },
+            hookData: Bytes::new(),
+        }
+        .abi_encode()
+        .into())
+    }
+
+    async fn init<N, P>(self, block_number: BlockId, provider: P) -> Result<Self, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut pool = vec![self.into()];
+        UniswapV4PoolManager::sync_token_decimals(&mut pool, provider.clone()).await?;
+        UniswapV4PoolManager::sync_pool_state(&mut pool, block_number, provider.clone()).await?;
+        UniswapV4PoolManager::sync_tick_bitmaps(&mut pool, block_number, provider.clone()).await?;
+        UniswapV4PoolManager::sync_tick_data(&mut pool, block_number, provider.clone()).await?;
+
+        let AMM::UniswapV4Pool(pool) = pool.remove(0) else {
+            unreachable!()
+        };
+
+        Ok(pool)
+    }
+}
+
+impl UniswapV4Pool {
+    /// Creates a new, unsynced Uniswap V4 pool from its pool key.
+    pub fn new(pool_manager: Address, key: PoolKey) -> Self {
+        let fee = key.fee.to::<u32>();
+        Self {
+            pool_manager,
+            pool_id: keccak256(key.abi_encode()),
+            token_a: key.currency0.into(),
+            token_b: key.currency1.into(),
+            fee,
+            lp_fee: if fee == DYNAMIC_FEE_FLAG { 0 } else { fee },
+            tick_spacing: key.tickSpacing.unchecked_into(),
+            hooks: key.hooks,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the pool key identifying the pool in the `PoolManager`.
+    pub fn pool_key(&self) -> PoolKey {
+        PoolKey {
+            currency0: self.token_a.address,
+            currency1: self.token_b.address,
+            fee: U24::from(self.fee),
+            tickSpacing: Signed::<24, 1>::unchecked_from(self.tick_spacing),
+            hooks: self.hooks,
+        }
+    }
+
+    /// Returns true if the LP fee of the pool is managed by its hook.
+    pub fn is_dynamic_fee(&self) -> bool {
+        self.fee == DYNAMIC_FEE_FLAG
+    }
+
+    /// Returns true if the pool hooks can change the outcome of a swap, either by returning a
+    /// delta from `beforeSwap`/`afterSwap` or by overriding the LP fee of a dynamic fee pool.
+    pub fn hooks_modify_swap(&self) -> bool {
+        let flags = u16::from_be_bytes([self.hooks[18], self.hooks[19]]);
+
+        flags & (BEFORE_SWAP_RETURNS_DELTA_FLAG | AFTER_SWAP_RETURNS_DELTA_FLAG) != 0
+            || (self.is_dynamic_fee() && flags & BEFORE_SWAP_FLAG != 0)
+    }
+
+    /// Returns the total fee charged on a swap in the given direction, in hundredths of a bip.
+    pub fn swap_fee(&self, zero_for_one: bool) -> u32 {
+        let protocol_fee = if zero_for_one {
+            self.protocol_fee & 0xfff
+        } else {
+            self.protocol_fee >> 12
+        };
+
+        if protocol_fee == 0 {
+            self.lp_fee
+        } else {
+            protocol_fee + self.lp_fee - protocol_fee * self.lp_fee / 1_000_000
+        }
+    }
+
+    /// Modifies a positions liquidity in the pool.
+    pub fn modify_position(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: i128,
+    ) -> Result<(), AMMError> {
+        update_position(
+            &mut self.ticks,
+            &mut self.tick_bitmap,
+            self.tick_spacing,
+            tick_lower,
+            tick_upper,
+            liquidity_delta,
+        );
+
+        if liquidity_delta != 0 && self.tick >= tick_lower && self.tick < tick_upper {
+            self.liquidity = if liquidity_delta < 0 {
+                self.liquidity
+                    .checked_sub((-liquidity_delta) as u128)
+                    .ok_or(UniswapV3Error::LiquidityUnderflow)?
+            } else {
+                self.liquidity + (liquidity_delta as u128)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the swap loop from the current pool state without mutating the pool.
+    ///
+    /// A positive `amount_specified` is an exact input swap.
+    pub fn swap(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+    ) -> Result<CurrentState, AMMError> {
+        self.swap_to_limit(
+            zero_for_one,
+            amount_specified,
+            default_sqrt_price_limit(zero_for_one),
+        )
+    }
+
+    /// Runs the swap loop from the current pool state without mutating the pool, stopping once the
+    /// price reaches `sqrt_price_limit_x_96`.
+    pub fn swap_to_limit(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<CurrentState, AMMError> {
+        if self.hooks_modify_swap() && !self.approximate_hooks {
+            return Err(UniswapV4Error::HookModifiesSwap.into());
+        }
+
+        let current_state = CurrentState {
+            sqrt_price_x_96: self.sqrt_price,
+            amount_calculated: I256::ZERO,
+            amount_specified_remaining: amount_specified,
+            tick: self.tick,
+            liquidity: self.liquidity,
+            fee_amount: U256::ZERO,
+            protocol_fee: U256::ZERO,
+            ticks_crossed: 0,
+            fee_growth_global_x_128: U256::ZERO,
+            crossed_ticks: vec![],
+        };
+
+        compute_swap(
+            &self.tick_bitmap,
+            &self.ticks,
+            self.tick_spacing,
+            self.swap_fee(zero_for_one),
+            ProtocolFee::None,
+            zero_for_one,
+            sqrt_price_limit_x_96,
+            current_state,
+        )
+    }
+
+    /// Simulates swapping `amount_in` of `base_token` with a price limit, as the pool does when
+    /// called with `sqrt_price_limit_x_96`.
+    ///
+    /// The swap stops once the price reaches the limit, so the outcome may be a partial fill that
+    /// consumes less than `amount_in`.
+    pub fn simulate_swap_with_limit(
+        &self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        Ok(self
+            .swap_with_limit(base_token, amount_in, sqrt_price_limit_x_96)?
+            .1)
+    }
+
+    /// Simulates swapping `amount_in` of `base_token` with a price limit, mutating the pool state.
+    pub fn simulate_swap_with_limit_mut(
+        &mut self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<SwapOutcome, AMMError> {
+        let (current_state, outcome) =
+            self.swap_with_limit(base_token, amount_in, sqrt_price_limit_x_96)?;
+
+        self.liquidity = current_state.liquidity;
+        self.sqrt_price = current_state.sqrt_price_x_96;
+        self.tick = current_state.tick;
+
+        Ok(outcome)
+    }
+
+    /// Returns the token sold into and the token bought from the pool.
+    fn swap_tokens(&self, zero_for_one: bool) -> (&Token, &Token) {
+        if zero_for_one {
+            (&self.token_a, &self.token_b)
+        } else {
+            (&self.token_b, &self.token_a)
+        }
+    }
+
+    fn swap_with_limit(
+        &self,
+        base_token: Address,
+        amount_in: U256,
+        sqrt_price_limit_x_96: U256,
+    ) -> Result<(CurrentState, SwapOutcome), AMMError> {
+        let zero_for_one = base_token == self.token_a.address;
+        check_sqrt_price_limit(self.sqrt_price, sqrt_price_limit_x_96, zero_for_one)?;
+
+        let (token_in, token_out) = self.swap_tokens(zero_for_one);
+        let amount_received = token_in.after_sell_tax(amount_in);
+
+        let current_state = self.swap_to_limit(
+            zero_for_one,
+            I256::from_raw(amount_received),
+            sqrt_price_limit_x_96,
+        )?;
+        let outcome = current_state.outcome(
+            amount_received,
+            price_at_sqrt_price(self.sqrt_price, &self.token_a, &self.token_b, base_token)?,
+            price_at_sqrt_price(
+                current_state.sqrt_price_x_96,
+                &self.token_a,
+                &self.token_b,
+                base_token,
+            )?,
+        );
+
+        Ok((
+            current_state,
+            outcome.with_taxes(amount_in, token_in, token_out),
+        ))
+    }
+
+    /// Storage slot of the pool state in the `PoolManager`.
+    fn state_slot(&self) -> U256 {
+        let mut preimage = [0u8; 64];
+        preimage[..32].copy_from_slice(self.pool_id.as_slice());
+        preimage[32..].copy_from_slice(&U256::from(POOLS_SLOT).to_be_bytes::<32>());
+        U256::from_be_bytes(keccak256(preimage).0)
+    }
+
+    /// Storage slot of `key` in the mapping at `offset` of the pool state.
+    fn mapping_slot(&self, key: i32, offset: u64) -> U256 {
+        let mapping_slot = self.state_slot() + U256::from(offset);
+        let mut preimage = [0u8; 64];
+        preimage[..32].copy_from_slice(&I256::try_from(key).unwrap().to_be_bytes::<32>());
+        preimage[32..].copy_from_slice(&mapping_slot.to_be_bytes::<32>());
+        U256::from_be_bytes(keccak256(preimage).0)
+    }
+
+    fn storage_slot(&self, slot: PoolSlot) -> B256 {
+        let slot = match slot {
+            PoolSlot::Slot0 => self.state_slot(),
+            PoolSlot::Liquidity => self.state_slot() + U256::from(LIQUIDITY_OFFSET),
+            PoolSlot::Tick(tick) => self.mapping_slot(tick, TICKS_OFFSET),
+        };
+        B256::from(slot)
+    }
+
+    fn apply_slot(&mut self, slot: PoolSlot, value: B256) {
+        let value = U256::from_be_bytes(value.0);
+        match slot {
+            PoolSlot::Slot0 => {
+                let tick: u32 = ((value >> 160_usize) & U256::from(0xffffff)).to();
+                self.sqrt_price = value & ((U256_1 << 160_usize) - U256_1);
+                self.tick = ((tick << 8) as i32) >> 8;
+                self.protocol_fee = ((value >> 184_usize) & U256::from(0xffffff)).to::<u32>();
+                self.lp_fee = ((value >> 208_usize) & U256::from(0xffffff)).to::<u32>();
+            }
+            PoolSlot::Liquidity => {
+                self.liquidity = (value & U256::from(u128::MAX)).to::<u128>();
+            }
+            PoolSlot::Tick(tick) => {
+                let liquidity_gross = (value & U256::from(u128::MAX)).to::<u128>();
+                let liquidity_net = (value >> 128_usize).to::<u128>() as i128;
+                self.ticks.insert(
+                    tick,
+                    Info::new(liquidity_gross, liquidity_net, liquidity_gross > 0),
+                );
+            }
+        }
+    }
+}
+
+/// A value of the pool state read through `extsload`
+#[derive(Debug, Clone, Copy)]
+enum PoolSlot {
+    Slot0,
+    Liquidity,
+    Tick(i32),
+}
+
+/// The Uniswap V4 `PoolManager`, which creates and holds every V4 pool on a chain.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct UniswapV4PoolManager {
+    pub address: Address,
+    pub creation_block: u64,
+}
+
+impl UniswapV4PoolManager {
+    pub fn new(address: Address, creation_block: u64) -> Self {
+        UniswapV4PoolManager {
+            address,
+            creation_block,
+        }
+    }
+
+    pub async fn get_all_pools<N, P>(
+        &self,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let disc_filter = Filter::new()
+            .event_signature(FilterSet::from(vec![self.pool_creation_event()]))
+            .address(vec![self.address()]);
+
+        let sync_provider = provider.clone();
+        let mut futures = FuturesUnordered::new();
+
+        let sync_step = 100_000;
+        let mut latest_block = self.creation_block;
+        while latest_block < block_number.as_u64().unwrap_or_default() {
+            let mut block_filter = disc_filter.clone();
+            let from_block = latest_block;
+            let to_block = (from_block + sync_step).min(block_number.as_u64().unwrap_or_default());
+
+            block_filter = block_filter.from_block(from_block);
+            block_filter = block_filter.to_block(to_block);
+
+            let sync_provider = sync_provider.clone();
+
+            futures.push(async move { sync_provider.get_logs(&block_filter).await });
+
+            latest_block = to_block + 1;
+        }
+
+        let mut pools = vec![];
+        while let Some(res) = futures.next().await {
+            let logs = res?;
+
+            for log in logs {
+                pools.push(self.create_pool(log)?);
+            }
+        }
+
+        Ok(pools)
+    }
+
+    pub async fn sync_all_pools<N, P>(
+        mut pools: Vec<AMM>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<Vec<AMM>, AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        UniswapV4PoolManager::sync_token_decimals(&mut pools, provider.clone()).await?;
+        UniswapV4PoolManager::sync_pool_state(&mut pools, block_number, provider.clone()).await?;
+
+        pools.retain(|pool| match pool {
+            AMM::UniswapV4Pool(uv4_pool) => uv4_pool.liquidity > 0,
+            _ => true,
+        });
+
+        UniswapV4PoolManager::sync_tick_bitmaps(&mut pools, block_number, provider.clone()).await?;
+        UniswapV4PoolManager::sync_tick_data(&mut pools, block_number, provider).await?;
+
+        Ok(pools)
+    }
+
+    async fn sync_token_decimals<N, P>(
+        pools: &mut [AMM],
+        provider: P,
+    ) -> Result<(), BatchContractError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut tokens = HashSet::new();
+        for pool in pools.iter() {
+            for token in pool.tokens() {
+                if !token.is_zero() {
+                    tokens.insert(token);
+                }
+            }
+        }
+        let token_decimals = get_token_decimals(tokens.into_iter().collect(), provider).await?;
+
+        for pool in pools.iter_mut() {
+            let AMM::UniswapV4Pool(uniswap_v4_pool) = pool else {
+                unreachable!()
+            };
+
+            for token in [&mut uniswap_v4_pool.token_a, &mut uniswap_v4_pool.token_b] {
+                // The zero address denotes the native currency
+                if token.address.is_zero() {
+                    token.decimals = 18;
+                } else if let Some(decimals) = token_decimals.get(&token.address) {
+                    token.decimals = *decimals;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Syncs slot0 and liquidity of each pool.
+    async fn sync_pool_state<N, P>(
+        pools: &mut [AMM],
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let slots = (0..pools.len())
+            .flat_map(|idx| [(idx, PoolSlot::Slot0), (idx, PoolSlot::Liquidity)])
+            .collect();
+
+        UniswapV4PoolManager::load_slots(pools, slots, block_number, provider).await
+    }
+
+    /// Syncs the non-empty words of the tick bitmap of each pool.
+    async fn sync_tick_bitmaps<N, P>(
+        pools: &mut [AMM],
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut groups = vec![];
+        let mut group = vec![];
+        let mut group_range = 0;
+
+        for (idx, pool) in pools.iter().enumerate() {
+            let AMM::UniswapV4Pool(uniswap_v4_pool) = pool else {
+                unreachable!()
+            };
+
+            // Only the words holding usable ticks of the pool are scanned
+            let mut min_word = tick_to_word(MIN_TICK, uniswap_v4_pool.tick_spacing);
+            let max_word = tick_to_word(MAX_TICK, uniswap_v4_pool.tick_spacing);
+
+            while min_word <= max_word {
+                let range = (max_word - min_word + 1).min(TICK_BITMAP_STEP - group_range);
+
+                group.push((
+                    idx,
+                    TickBitmapInfo {
+                        poolManager: uniswap_v4_pool.pool_manager,
+                        poolId: uniswap_v4_pool.pool_id,
+                        minWord: min_word as i16,
+                        maxWord: (min_word + range - 1) as i16,
+                    },
+                ));
+
+                min_word += range;
+                group_range += range;
+
+                if group_range >= TICK_BITMAP_STEP {
+                    groups.push(std::mem::take(&mut group));
+                    group_range = 0;
+                }
+            }
+        }
+
+        if !group.is_empty() {
+            groups.push(group);
+        }
+
+        let mut futures = FuturesUnordered::new();
+        for group in groups {
+            let provider = provider.clone();
+
+            futures.push(async move {
+                let (indices, calldata): (Vec<usize>, Vec<TickBitmapInfo>) =
+                    group.into_iter().unzip();
+                let return_data =
+                    GetUniswapV4PoolTickBitmapBatchRequest::deploy_builder(provider, calldata)
+                        .call_raw()
+                        .block(block_number)
+                        .await?;
+
+                Ok::<(Vec<usize>, Bytes), AMMError>((indices, return_data))
+            });
+        }
+
+        while let Some(res) = futures.next().await {
+            let (indices, return_data) = res?;
+            let return_data = <Vec<Vec<U256>> as SolValue>::abi_decode(&return_data)?;
+
+            for (idx, tick_bitmaps) in indices.into_iter().zip(return_data) {
+                let AMM::UniswapV4Pool(ref mut uniswap_v4_pool) = pools[idx] else {
+                    unreachable!()
+                };
+
+                for chunk in tick_bitmaps.chunks_exact(2) {
+                    let word_pos = I256::from_raw(chunk[0]).as_i16();
+                    uniswap_v4_pool.tick_bitmap.insert(word_pos, chunk[1]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Syncs the liquidity of every initialized tick of each pool.
+    async fn sync_tick_data<N, P>(
+        pools: &mut [AMM],
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut slots = vec![];
+        for (idx, pool) in pools.iter().enumerate() {
+            let AMM::UniswapV4Pool(uniswap_v4_pool) = pool else {
+                unreachable!()
+            };
+
+            for (word_pos, bitmap) in uniswap_v4_pool.tick_bitmap.iter() {
+                for bit in 0..256 {
+                    if bitmap.bit(bit) {
+                        let tick =
+                            (*word_pos as i32 * 256 + bit as i32) * uniswap_v4_pool.tick_spacing;
+                        slots.push((idx, PoolSlot::Tick(tick)));
+                    }
+                }
+            }
+        }
+
+        UniswapV4PoolManager::load_slots(pools, slots, block_number, provider).await
+    }
+
+    /// Reads the requested pool storage slots from the `PoolManager` and applies them to the pools.
+    async fn load_slots<N, P>(
+        pools: &mut [AMM],
+        slots: Vec<(usize, PoolSlot)>,
+        block_number: BlockId,
+        provider: P,
+    ) -> Result<(), AMMError>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        // Group the slots by pool manager, as every extsload call targets a single manager
+        let mut manager_slots: HashMap<Address, Vec<(usize, PoolSlot, B256)>> = HashMap::new();
+        for (idx, slot) in slots {
+            let AMM::UniswapV4Pool(ref uniswap_v4_pool) = pools[idx] else {
+                unreachable!()
+            };
+
+            manager_slots
+                .entry(uniswap_v4_pool.pool_manager)
+                .or_default()
+                .push((idx, slot, uniswap_v4_pool.storage_slot(slot)));
+        }
+
+        let mut futures = FuturesUnordered::new();
+        for (manager, slots) in manager_slots {
+            for group in slots.chunks(EXTSLOAD_STEP) {
+                let group = group.to_vec();
+                let pool_manager = IPoolManager::new(manager, provider.clone());
+
+                futures.push(async move {
+                    let storage_slots = group.iter().map(|(_, _, slot)| *slot).collect();
+                    let values = pool_manager
+                        .extsload(storage_slots)
+                        .block(block_number)
+                        .call()
+                        .await?;
+
+                    Ok::<(Vec<(usize, PoolSlot, B256)>, Vec<B256>), AMMError>((group, values))
+                });
+            }
+        }
+
+        while let Some(res) = futures.next().await {
+            let (group, values) = res?;
+
+            for ((idx, slot, _), value) in group.into_iter().zip(values) {
+                let AMM::UniswapV4Pool(ref mut uniswap_v4_pool) = pools[idx] else {
+                    unreachable!()
+                };
+
+                uniswap_v4_pool.apply_slot(slot, value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AutomatedMarketMakerFactory for UniswapV4PoolManager {
+    type PoolVariant = UniswapV4Pool;
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn pool_creation_event(&self) -> B256 {
+        IPoolManager::Initialize::SIGNATURE_HASH
+    }
+
+    fn create_pool(&self, log: Log) -> Result<AMM, AMMError> {
+        let initialize_event = IPoolManager::Initialize::decode_log(&log.inner)?;
+
+        let mut pool = UniswapV4Pool::new(
+            initialize_event.address,
+            PoolKey {
+                currency0: initialize_event.currency0,
+                currency1: initialize_event.currency1,
+                fee: initialize_event.fee,
+                tickSpacing: initialize_event.tickSpacing,
+                hooks: initialize_event.hooks,
+            },
+        );
+        pool.sqrt_price = initialize_event.sqrtPriceX96.to();
+        pool.tick = initialize_event.tick.unchecked_into();
+
+        Ok(AMM::UniswapV4Pool(pool))
+    }
+
+    fn creation_block(&self) -> u64 {
+        self.creation_block
+    }
+}
+
+impl DiscoverySync for UniswapV4PoolManager {
+    fn discover<N, P>(
+        &self,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::uniswap_v4::discover",
+            address = ?self.address,
+            "Discovering all pools"
+        );
+
+        self.get_all_pools(to_block, provider.clone())
+    }
+
+    fn sync<N, P>(
+        &self,
+        amms: Vec<AMM>,
+        to_block: BlockId,
+        provider: P,
+    ) -> impl Future<Output = Result<Vec<AMM>, AMMError>>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        info!(
+            target = "amms::uniswap_v4::sync",
+            address = ?self.address,
+            "Syncing all pools"
+        );
+
+        UniswapV4PoolManager::sync_all_pools(amms, to_block, provider)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy::primitives::{address, aliases::U160, Signed};
+
+    fn pool(hooks: Address, fee: u32) -> UniswapV4Pool {
+        UniswapV4Pool::new(
+            address!("000000000004444c5dc75cB358380D2e3dE08A90"),
+            PoolKey {
+                currency0: Address::ZERO,
+                currency1: address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                fee: U24::from(fee),
+                tickSpacing: Signed::<24, 1>::unchecked_from(10),
+                hooks,
+            },
+        )
+    }
+
+    #[test]
+    fn test_pool_id() {
+        // ETH/USDC 0.05% pool
+        let pool = pool(Address::ZERO, 500);
+        assert_eq!(
+            pool.pool_id,
+            alloy::primitives::b256!(
+                "21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27"
+            )
+        );
+        assert_eq!(
+            pool.key(),
+            AMMKey::Singleton {
+                singleton: pool.pool_manager,
+                id: pool.pool_id,
+            }
+        );
+    }
+
+    #[test]
+    fn test_log_key() {
+        let pool = pool(Address::ZERO, 500);
+        let swap_event = IPoolManager::Swap {
+            id: pool.pool_id,
+            sender: Address::ZERO,
+            amount0: 0,
+            amount1: 0,
+            sqrtPriceX96: U160::from(1),
+            liquidity: 1,
+            tick: Signed::ZERO,
+            fee: U24::ZERO,
+        };
+        let log = |address| Log {
+            inner: alloy::primitives::Log {
+                address,
+                data: swap_event.encode_log_data(),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            UniswapV4Pool::log_key(&log(pool.pool_manager)),
+            Some(pool.key())
+        );
+
+        // Logs of another contract are neither routed to the pool nor applied by it
+        let spoofed = log(Address::with_last_byte(1));
+        assert_ne!(UniswapV4Pool::log_key(&spoofed), Some(pool.key()));
+
+        let mut synced = pool.clone();
+        synced.sync(&spoofed).unwrap();
+        assert_eq!(synced.sqrt_price, pool.sqrt_price);
+        assert_eq!(synced.liquidity, pool.liquidity);
+
+        synced.sync(&log(pool.pool_manager)).unwrap();
+        assert_eq!(synced.sqrt_price, U256::from(1));
+        assert_eq!(synced.liquidity, 1);
+    }
+
+    #[test]
+    fn test_hooks_modify_swap() {
+        assert!(!pool(Address::ZERO, 500).hooks_modify_swap());
+
+        // beforeSwap only, static fee
+        let hooks = address!("0000000000000000000000000000000000000080");
+        assert!(!pool(hooks, 500).hooks_modify_swap());
+        assert!(pool(hooks, DYNAMIC_FEE_FLAG).hooks_modify_swap());
+
+        // afterSwap returns delta
+        let hooks = address!("0000000000000000000000000000000000000044");
+        assert!(pool(hooks, 500).hooks_modify_swap());
+
+        let mut hooked = pool(hooks, 500);
+        hooked.liquidity = 1;
+        assert!(matches!(
+            hooked.simulate_swap(Address::ZERO, Address::ZERO, U256::from(1)),
+            Err(AMMError::UniswapV4Error(UniswapV4Error::HookModifiesSwap))
+        ));
+    }
+
+    #[test]
+    fn test_swap_fee() {
+        let mut pool = pool(Address::ZERO, 3000);
+        assert_eq!(pool.swap_fee(true), 3000);
+
+        // 0.1% protocol fee on zero for one swaps only
+        pool.protocol_fee = 1000;
+        assert_eq!(pool.swap_fee(true), 3997);
+        assert_eq!(pool.swap_fee(false), 3000);
+    }
+
+    #[test]
+    fn test_apply_slot0() {
+        let mut pool = pool(Address::ZERO, 500);
+        let sqrt_price = U256::from(79228162514264337593543950336_u128);
+        let tick = U256::from(0xffffffu32); // -1
+        let slot0 = sqrt_price | (tick << 160_usize) | (U256::from(500) << 208_usize);
+
+        pool.apply_slot(PoolSlot::Slot0, B256::from(slot0));
+
+        assert_eq!(pool.sqrt_price, sqrt_price);
+        assert_eq!(pool.tick, -1);
+        assert_eq!(pool.protocol_fee, 0);
+        assert_eq!(pool.lp_fee, 500);
+    }
+}
@@ -0,0 +1,184 @@
+//! Detection of cyclic arbitrage through a base token.
+//!
+//! A [`CycleSearcher`] enumerates the cycles from the base token back to itself once, indexing
+//! them by pool so that after each block only the cycles through the affected AMMs of the
+//! [`StateSpaceUpdate`](crate::state_space::StateSpaceUpdate) yielded by
+//! [`StateSpaceManager::subscribe`](crate::state_space::StateSpaceManager::subscribe) have to be
+//! evaluated again. The cycles are enumerated again with [`CycleSearcher::refresh`] when the update
+//! reports new AMMs.
+
+pub mod sizing;
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use alloy::primitives::{Address, U256};
+
+use crate::{
+    amms::amm::AMMKey,
+    state_space::{graph::Hop, StateSpace},
+};
+
+/// A profitable cycle, with the input maximising its profit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opportunity {
+    pub cycle: Vec<Hop>,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub profit: U256,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CycleSearcher {
+    pub base_token: Address,
+    pub max_hops: usize,
+    cycles: Vec<Vec<Hop>>,
+    cycles_by_pool: HashMap<AMMKey, Vec<usize>>,
+}
+
+impl CycleSearcher {
+    /// Enumerates the cycles of at most `max_hops` hops through `base_token` in the state space.
+    pub fn new(state_space: &StateSpace, base_token: Address, max_hops: usize) -> Self {
+        let mut searcher = Self {
+            base_token,
+            max_hops,
+            ..Default::default()
+        };
+        searcher.refresh(state_space);
+
+        searcher
+    }
+
+    /// Enumerates the cycles again, after AMMs have been inserted into or removed from the state
+    /// space.
+    pub fn refresh(&mut self, state_space: &StateSpace) {
+        self.cycles = state_space.cycles(self.base_token, self.max_hops);
+        self.cycles_by_pool.clear();
+        for (i, cycle) in self.cycles.iter().enumerate() {
+            for (pool, _, _) in cycle {
+                self.cycles_by_pool.entry(*pool).or_default().push(i);
+            }
+        }
+    }
+
+    pub fn cycles(&self) -> &[Vec<Hop>] {
+        &self.cycles
+    }
+
+    /// Evaluates the cycles through any of `pools`, returning the profitable ones, most profitable
+    /// first.
+    pub fn evaluate(&self, state_space: &StateSpace, pools: &[AMMKey]) -> Vec<Opportunity> {
+        let cycles = pools
+            .iter()
+            .filter_map(|pool| self.cycles_by_pool.get(pool))
+            .flatten()
+            .copied()
+            .collect::<HashSet<usize>>();
+
+        self.evaluate_cycles(state_space, cycles.into_iter())
+    }
+
+    /// Evaluates every cycle, returning the profitable ones, most profitable first.
+    pub fn evaluate_all(&self, state_space: &StateSpace) -> Vec<Opportunity> {
+        self.evaluate_cycles(state_space, 0..self.cycles.len())
+    }
+
+    fn evaluate_cycles(
+        &self,
+        state_space: &StateSpace,
+        cycles: impl Iterator<Item = usize>,
+    ) -> Vec<Opportunity> {
+        let mut opportunities = cycles
+            .filter_map(|i| sizing::optimal_input(state_space, &self.cycles[i]))
+            .collect::<Vec<_>>();
+        opportunities.sort_by_key(|opportunity| Reverse(opportunity.profit));
+
+        opportunities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::{uniswap_v2::UniswapV2Pool, Token};
+    use alloy::primitives::address;
+
+    #[test]
+    fn test_evaluate() {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let pool = |address, token_a, token_b, reserve_0: u128, reserve_1: u128| {
+            UniswapV2Pool {
+                address,
+                token_a: Token::new_with_decimals(token_a, 18),
+                token_b: Token::new_with_decimals(token_b, 18),
+                reserve_0,
+                reserve_1,
+                fee: 300,
+                ..Default::default()
+            }
+            .into()
+        };
+        let weth_usdc = address!("0000000000000000000000000000000000000001");
+        let usdc_dai = address!("0000000000000000000000000000000000000002");
+        let dai_weth = address!("0000000000000000000000000000000000000003");
+        let usdc_dai_2 = address!("0000000000000000000000000000000000000004");
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool(
+            weth_usdc,
+            weth,
+            usdc,
+            10_u128.pow(21),
+            2 * 10_u128.pow(24),
+        ));
+        state_space.insert(pool(usdc_dai, usdc, dai, 10_u128.pow(24), 10_u128.pow(24)));
+        // DAI is 5% cheaper against WETH
+        state_space.insert(pool(
+            dai_weth,
+            dai,
+            weth,
+            21 * 10_u128.pow(23),
+            10_u128.pow(21),
+        ));
+
+        let mut searcher = CycleSearcher::new(&state_space, weth, 3);
+        assert_eq!(searcher.cycles().len(), 2);
+
+        let opportunities = searcher.evaluate_all(&state_space);
+        assert_eq!(opportunities.len(), 1);
+        assert_eq!(
+            opportunities[0].cycle,
+            vec![
+                (dai_weth.into(), weth, dai),
+                (usdc_dai.into(), dai, usdc),
+                (weth_usdc.into(), usdc, weth)
+            ]
+        );
+        assert_eq!(
+            searcher.evaluate(&state_space, &[usdc_dai.into()]),
+            opportunities
+        );
+
+        // Only the cycles through the given pools are evaluated
+        state_space.insert(pool(
+            usdc_dai_2,
+            usdc,
+            dai,
+            10_u128.pow(24),
+            10_u128.pow(24),
+        ));
+        assert!(searcher
+            .evaluate(&state_space, &[usdc_dai_2.into()])
+            .is_empty());
+        searcher.refresh(&state_space);
+        assert_eq!(searcher.cycles().len(), 4);
+        assert_eq!(
+            searcher.evaluate(&state_space, &[usdc_dai_2.into()]).len(),
+            1
+        );
+    }
+}
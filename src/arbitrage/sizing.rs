@@ -0,0 +1,211 @@
+//! Sizing of the input that maximises the profit of a cycle.
+//!
+//! A cycle made only of [`UniswapV2Pool`]s without token taxes is itself a constant product curve
+//! `f(x) = n * x / (d + m * x)`, whose profit `f(x) - x` is maximised in closed form at
+//! `x = (sqrt(n * d) - d) / m`. Any other cycle is sized by searching its profit, which is concave
+//! in the input, with `simulate_swap`.
+
+use alloy::primitives::{Address, I256, U256};
+use rug::{integer::Order, Integer};
+
+use crate::{
+    amms::{amm::AMM, uniswap_v2::UniswapV2Pool},
+    state_space::{graph::Hop, StateSpace},
+};
+
+use super::Opportunity;
+
+/// Largest power of two the search for the input of a cycle starts from
+const MAX_INPUT_BITS: usize = 128;
+
+/// Returns the input maximising the profit of `cycle` and the resulting amounts, or `None` if the
+/// cycle is not profitable.
+pub fn optimal_input(state_space: &StateSpace, cycle: &[Hop]) -> Option<Opportunity> {
+    let amount_in = match uniswap_v2_curve(state_space, cycle) {
+        Some(curve) => curve.optimal_input()?,
+        None => search_optimal_input(state_space, cycle)?,
+    };
+
+    let amount_out = *state_space
+        .simulate_path_mut(cycle, amount_in)
+        .ok()?
+        .last()?;
+    let profit = amount_out.checked_sub(amount_in).filter(|p| !p.is_zero())?;
+
+    Some(Opportunity {
+        cycle: cycle.to_vec(),
+        amount_in,
+        amount_out,
+        profit,
+    })
+}
+
+/// The amount out of consecutive constant product swaps, `n * x / (d + m * x)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Curve {
+    n: Integer,
+    d: Integer,
+    m: Integer,
+}
+
+impl Curve {
+    /// Returns the curve of a swap of `x` followed by a swap through `pool`
+    fn then(self, pool: &UniswapV2Pool, token_in: Address) -> Self {
+        let (reserve_in, reserve_out) = if token_in == pool.token_a.address {
+            (pool.reserve_0, pool.reserve_1)
+        } else {
+            (pool.reserve_1, pool.reserve_0)
+        };
+        let fee = Integer::from(100_000 - pool.fee);
+        let reserve_in = Integer::from(reserve_in) * 100_000;
+        let reserve_out = Integer::from(reserve_out);
+
+        // g(y) = fee * y * reserve_out / (reserve_in + fee * y), with y = n * x / (d + m * x)
+        let fee_n = fee * self.n;
+        Self {
+            n: Integer::from(&fee_n * &reserve_out),
+            d: Integer::from(&reserve_in * &self.d),
+            m: reserve_in * self.m + fee_n,
+        }
+    }
+
+    fn optimal_input(&self) -> Option<U256> {
+        // The marginal rate at zero, n / d, must be above one
+        if self.n <= self.d || self.m == 0 {
+            return None;
+        }
+
+        let amount_in = (Integer::from(&self.n * &self.d).sqrt() - &self.d) / &self.m;
+        integer_to_u256(&amount_in)
+    }
+}
+
+/// Returns the curve of `cycle` if it only goes through Uniswap V2 pools without token taxes
+fn uniswap_v2_curve(state_space: &StateSpace, cycle: &[Hop]) -> Option<Curve> {
+    cycle.iter().try_fold(
+        Curve {
+            n: Integer::from(1),
+            d: Integer::from(1),
+            m: Integer::ZERO,
+        },
+        |curve, (pool, token_in, _)| match state_space.get(pool)? {
+            AMM::UniswapV2Pool(pool)
+                if pool.token_a.tax.is_none() && pool.token_b.tax.is_none() =>
+            {
+                Some(curve.then(pool, *token_in))
+            }
+            _ => None,
+        },
+    )
+}
+
+/// Finds the input maximising the profit of `cycle` by simulating it, first over powers of two
+/// to bracket the maximum and then with a ternary search within the bracket.
+fn search_optimal_input(state_space: &StateSpace, cycle: &[Hop]) -> Option<U256> {
+    let profit = |amount_in: U256| -> I256 {
+        state_space
+            .simulate_path_mut(cycle, amount_in)
+            .ok()
+            .and_then(|amounts_out| amounts_out.last().copied())
+            .map(|amount_out| I256::from_raw(amount_out) - I256::from_raw(amount_in))
+            .unwrap_or(I256::MIN)
+    };
+
+    // Rounding makes small inputs unprofitable, so only stop once the profit falls back below zero
+    let mut best: Option<(usize, I256)> = None;
+    for bits in 0..=MAX_INPUT_BITS {
+        let p = profit(U256::from(1) << bits);
+        if p > best.map_or(I256::ZERO, |(_, best_profit)| best_profit) {
+            best = Some((bits, p));
+        } else if best.is_some() && p <= I256::ZERO {
+            break;
+        }
+    }
+    let (bits, _) = best?;
+
+    let mut lo = U256::from(1) << bits.saturating_sub(1);
+    let mut hi = U256::from(1) << (bits + 1);
+    while hi - lo > U256::from(2) {
+        let third = (hi - lo) / U256::from(3);
+        let (m1, m2) = (lo + third, hi - third);
+        if profit(m1) < profit(m2) {
+            lo = m1;
+        } else {
+            hi = m2;
+        }
+    }
+
+    let mut amount_in = lo;
+    let mut amount = lo;
+    while amount <= hi {
+        if profit(amount) > profit(amount_in) {
+            amount_in = amount;
+        }
+        amount += U256::from(1);
+    }
+
+    Some(amount_in)
+}
+
+fn integer_to_u256(value: &Integer) -> Option<U256> {
+    if *value < 0 || value.significant_bits() > 256 {
+        return None;
+    }
+
+    let mut bytes = [0u8; 32];
+    value.write_digits(&mut bytes[..value.significant_digits::<u8>()], Order::Lsf);
+    Some(U256::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::{amm::AMMKey, Token};
+    use alloy::primitives::address;
+
+    #[test]
+    fn test_closed_form_matches_search() -> eyre::Result<()> {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let pool = |address, reserve_weth: u128, reserve_usdc: u128, fee| UniswapV2Pool {
+            address,
+            token_a: Token::new_with_decimals(usdc, 6),
+            token_b: Token::new_with_decimals(weth, 18),
+            reserve_0: reserve_usdc,
+            reserve_1: reserve_weth,
+            fee,
+            ..Default::default()
+        };
+        // WETH is 1% cheaper in the second pool
+        let cheap = address!("0000000000000000000000000000000000000001");
+        let expensive = address!("0000000000000000000000000000000000000002");
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool(cheap, 10_u128.pow(21), 1_980 * 10_u128.pow(9), 300).into());
+        state_space.insert(pool(expensive, 10_u128.pow(21), 2_000 * 10_u128.pow(9), 250).into());
+
+        let (cheap_key, expensive_key) = (AMMKey::from(cheap), AMMKey::from(expensive));
+        let cycle = [(cheap_key, weth, usdc), (expensive_key, usdc, weth)];
+        assert!(optimal_input(&state_space, &cycle).is_none());
+
+        let cycle = [(expensive_key, weth, usdc), (cheap_key, usdc, weth)];
+        let closed_form = uniswap_v2_curve(&state_space, &cycle)
+            .and_then(|curve| curve.optimal_input())
+            .expect("the cycle is profitable");
+        let searched = search_optimal_input(&state_space, &cycle).expect("the cycle is profitable");
+
+        let profit = |amount_in| -> eyre::Result<U256> {
+            Ok(state_space.simulate_path_mut(&cycle, amount_in)?[1] - amount_in)
+        };
+        // Both are within rounding of each other, with a unit of USDC worth about 5e8 wei
+        assert!(profit(closed_form)?.abs_diff(profit(searched)?) < U256::from(10_u64.pow(9)));
+        let delta = closed_form / U256::from(1_000);
+        assert!(profit(closed_form)? >= profit(closed_form - delta)?);
+        assert!(profit(closed_form)? >= profit(closed_form + delta)?);
+
+        let opportunity = optimal_input(&state_space, &cycle).expect("the cycle is profitable");
+        assert_eq!(opportunity.amount_in, closed_form);
+        assert_eq!(opportunity.profit, profit(closed_form)?);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::amms::amm::{AutomatedMarketMaker, AMM};
+use crate::amms::amm::{AMMKey, AutomatedMarketMaker, AMM};
 use arraydeque::ArrayDeque;
 
 #[derive(Debug)]
@@ -43,6 +43,13 @@ impl<const CAP: usize> StateChangeCache<CAP> {
     /// Unwinds the state changes up to the given block number
     /// Returns the state of the affected AMMs at the block number provided
     pub fn unwind_state_changes(&mut self, block_to_unwind: u64) -> Vec<AMM> {
+        self.unwind(block_to_unwind).0
+    }
+
+    /// Unwinds the state changes up to the given block number
+    /// Returns the state of the affected AMMs at the block number provided, and the AMMs added
+    /// since, which should be removed
+    pub fn unwind(&mut self, block_to_unwind: u64) -> (Vec<AMM>, Vec<AMMKey>) {
         let cache = &mut self.cache;
 
         if block_to_unwind < self.oldest_block {
@@ -54,7 +61,7 @@ impl<const CAP: usize> StateChangeCache<CAP> {
             .front()
             .is_none_or(|latest| block_to_unwind > latest.block_number)
         {
-            return vec![];
+            return (vec![], vec![]);
         }
 
         let pivot_idx = cache
@@ -67,7 +74,12 @@ impl<const CAP: usize> StateChangeCache<CAP> {
             cache.drain(..).collect::<Vec<StateChange>>()
         };
 
-        self.flatten_state_changes(state_changes)
+        let new_amms = state_changes
+            .iter()
+            .flat_map(|state_change| state_change.new_amms.iter().copied())
+            .collect();
+
+        (self.flatten_state_changes(state_changes), new_amms)
     }
 
     fn flatten_state_changes(&self, state_changes: Vec<StateChange>) -> Vec<AMM> {
@@ -76,7 +88,7 @@ impl<const CAP: usize> StateChangeCache<CAP> {
             .rev()
             .fold(HashMap::new(), |mut amms, state_change| {
                 for amm in state_change.state_change {
-                    amms.entry(amm.address()).or_insert(amm);
+                    amms.entry(amm.key()).or_insert(amm);
                 }
                 amms
             })
@@ -91,6 +103,8 @@ impl<const CAP: usize> StateChangeCache<CAP> {
 pub struct StateChange {
     pub state_change: Vec<AMM>,
     pub block_number: u64,
+    /// AMMs added to the state space in the block
+    pub new_amms: Vec<AMMKey>,
 }
 
 impl StateChange {
@@ -98,6 +112,16 @@ impl StateChange {
         Self {
             block_number,
             state_change,
+            new_amms: vec![],
+        }
+    }
+
+    /// A state change adding `new_amms` to the state space
+    pub fn with_new_amms(new_amms: Vec<AMMKey>, block_number: u64) -> Self {
+        Self {
+            block_number,
+            state_change: vec![],
+            new_amms,
         }
     }
 }
@@ -0,0 +1,402 @@
+use std::{collections::HashMap, fs, ops::RangeInclusive, path::Path};
+
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, FixedBytes},
+    providers::Provider,
+    rpc::types::{Filter, Log},
+};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::amms::{
+    amm::{AutomatedMarketMaker, AMM},
+    error::AMMError,
+    factory::Factory,
+};
+
+use super::{
+    error::StateSpaceError,
+    filters::{apply_filters, FilterStage, PoolFilter},
+    StateSpace,
+};
+
+/// Number of blocks fetched per request by default when replaying logs since a checkpoint, small
+/// enough for the log limits of most providers
+pub const DEFAULT_REPLAY_STEP: u64 = 100;
+/// Max number of log requests in flight when replaying logs since a checkpoint
+const REPLAY_CONCURRENCY: usize = 8;
+
+/// The full state of a [`StateSpaceManager`](super::StateSpaceManager) at a block, from which it
+/// can be restarted without discovering and syncing every AMM again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Block the AMMs are synced to
+    pub block_number: u64,
+    pub factories: Vec<Factory>,
+    pub filters: Vec<PoolFilter>,
+    pub amms: Vec<AMM>,
+}
+
+impl Checkpoint {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, StateSpaceError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Writes the checkpoint to a temporary file first, so that an interrupted write never
+    /// replaces a previous checkpoint.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), StateSpaceError> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(self)?)?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Brings a state space restored from a checkpoint up to the end of `blocks`, syncing its AMMs from
+/// the logs matching `filter` and adding the pools `factories` created in the meantime. The logs
+/// are fetched `step` blocks at a time.
+pub(crate) async fn replay<N, P>(
+    state_space: &mut StateSpace,
+    factories: &[Factory],
+    filters: &[PoolFilter],
+    filter: &Filter,
+    blocks: RangeInclusive<u64>,
+    step: u64,
+    provider: P,
+) -> Result<(), AMMError>
+where
+    N: Network,
+    P: Provider<N> + Clone,
+{
+    let (from_block, to_block) = (*blocks.start(), *blocks.end());
+    if from_block > to_block {
+        return Ok(());
+    }
+
+    let step = step.max(1);
+    let mut requests = vec![];
+    let mut block = from_block;
+    while block <= to_block {
+        let step_filter = filter
+            .clone()
+            .from_block(block)
+            .to_block((block + step - 1).min(to_block));
+        let provider = provider.clone();
+        requests.push(async move { provider.get_logs(&step_filter).await });
+
+        block += step;
+    }
+
+    let mut responses = stream::iter(requests).buffered(REPLAY_CONCURRENCY);
+    let mut logs = vec![];
+    while let Some(res) = responses.next().await {
+        logs.extend(res?);
+    }
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    info!(
+        target: "state_space::checkpoint",
+        from_block,
+        to_block,
+        logs = logs.len(),
+        "Replaying logs since checkpoint"
+    );
+
+    // Restored AMMs have no state changes to unwind, so they are synced without the reorg cache
+    for log in &logs {
+        for key in state_space.log_targets(log) {
+            if let Some(amm) = state_space.get_mut(key) {
+                amm.sync(log)?;
+            }
+        }
+    }
+
+    for amm in discover_from_logs(factories, filters, &logs, to_block, provider).await {
+        if state_space.get(amm.key()).is_none() {
+            state_space.insert(amm);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates the pools announced by the discovery events of `factories` in `logs`, then filters and
+/// syncs them at `block_number`. Pools that cannot be created are skipped, as are the pools of a
+/// factory that cannot be filtered or synced, which are logged.
+pub(crate) async fn discover_from_logs<N, P>(
+    factories: &[Factory],
+    filters: &[PoolFilter],
+    logs: &[Log],
+    block_number: u64,
+    provider: P,
+) -> Vec<AMM>
+where
+    N: Network,
+    P: Provider<N> + Clone,
+{
+    let factories = factories
+        .iter()
+        .map(|factory| ((factory.address(), factory.discovery_event()), factory))
+        .collect::<HashMap<(Address, FixedBytes<32>), &Factory>>();
+
+    let mut created: HashMap<Address, Vec<AMM>> = HashMap::new();
+    for log in logs {
+        let Some(topic) = log.topic0() else {
+            continue;
+        };
+        let Some(factory) = factories.get(&(log.address(), *topic)) else {
+            continue;
+        };
+
+        match factory
+            .create_pool_with_provider(log.clone(), provider.clone())
+            .await
+        {
+            Ok(amm) => created.entry(factory.address()).or_default().push(amm),
+            Err(err) => warn!(
+                target: "state_space::checkpoint",
+                factory = %factory.address(),
+                ?err,
+                "Skipping pool that could not be created"
+            ),
+        }
+    }
+
+    let mut discovered = vec![];
+    for (address, amms) in created {
+        let factory = factories
+            .values()
+            .find(|factory| factory.address() == address)
+            .expect("pools are only created by known factories");
+
+        let amms = async {
+            let amms = apply_filters(filters, FilterStage::Discovery, amms).await?;
+            let amms = factory
+                .sync(amms, BlockId::from(block_number), provider.clone())
+                .await?;
+            apply_filters(filters, FilterStage::Sync, amms).await
+        };
+        match amms.await {
+            Ok(amms) => {
+                info!(
+                    target: "state_space::checkpoint",
+                    factory = %address,
+                    amms = amms.len(),
+                    "Discovered AMMs"
+                );
+                discovered.extend(amms);
+            }
+            Err(err) => warn!(
+                target: "state_space::checkpoint",
+                factory = %address,
+                ?err,
+                "Skipping pools that could not be synced"
+            ),
+        }
+    }
+
+    discovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        amms::{
+            uniswap_v2::{IUniswapV2Factory, IUniswapV2Pair, UniswapV2Factory, UniswapV2Pool},
+            uniswap_v3::{Info, UniswapV3Factory, UniswapV3Pool},
+            Token,
+        },
+        state_space::{filters::blacklist::BlacklistFilter, StateSpaceBuilder},
+    };
+    use alloy::{
+        primitives::{address, aliases::U112, Bytes, U256, U64},
+        providers::ProviderBuilder,
+        sol_types::{SolEvent, SolValue},
+        transports::mock::Asserter,
+    };
+
+    #[test]
+    fn test_write_and_read() -> eyre::Result<()> {
+        let weth =
+            Token::new_with_decimals(address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), 18);
+        let usdc =
+            Token::new_with_decimals(address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), 6);
+        let mut v3_pool = UniswapV3Pool {
+            address: address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"),
+            token_a: usdc.clone(),
+            token_b: weth.clone(),
+            liquidity: 10_u128.pow(18),
+            sqrt_price: U256::from(2).pow(U256::from(96)),
+            tick: -200_000,
+            tick_spacing: 10,
+            fee: 500,
+            ..Default::default()
+        };
+        v3_pool.tick_bitmap.insert(-79, U256::from(1) << 255);
+        v3_pool.ticks.insert(
+            -200_010,
+            Info {
+                liquidity_gross: 10_u128.pow(18),
+                liquidity_net: 10_i128.pow(18),
+                initialized: true,
+                ..Default::default()
+            },
+        );
+        let v2_pool = UniswapV2Pool {
+            address: address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"),
+            token_a: usdc,
+            token_b: weth,
+            reserve_0: 10_u128.pow(12),
+            reserve_1: 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        };
+
+        let checkpoint = Checkpoint {
+            block_number: 21_000_000,
+            factories: vec![UniswapV3Factory::new(
+                address!("1F98431c8aD98523631AE4a59f267346ea31F984"),
+                12369621,
+            )
+            .into()],
+            filters: vec![BlacklistFilter::new(vec![Address::ZERO]).into()],
+            amms: vec![v3_pool.into(), v2_pool.into()],
+        };
+
+        let path =
+            std::env::temp_dir().join(format!("amms-checkpoint-{}.json", std::process::id()));
+        checkpoint.write(&path)?;
+        let restored = Checkpoint::read(&path)?;
+        fs::remove_file(&path)?;
+
+        assert!(!path.with_extension("tmp").exists());
+        assert_eq!(
+            serde_json::to_string(&restored)?,
+            serde_json::to_string(&checkpoint)?
+        );
+        assert_eq!(restored.block_number, 21_000_000);
+        assert_eq!(restored.factories, checkpoint.factories);
+        assert_eq!(restored.amms.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay() -> eyre::Result<()> {
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let usdt = address!("dAC17F958D2ee523a2206206994597C13D831ec7");
+        let usdc_weth = address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc");
+        let usdt_weth = address!("0d4a11d5EEaaC28EC3F61d100daF4d40471f1852");
+        let factory = UniswapV2Factory::new(
+            address!("5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f"),
+            300,
+            10000835,
+        );
+
+        // A factory with another fee is not the factory of the checkpoint
+        let reconfigured = UniswapV2Factory::new(factory.address, 250, factory.creation_block);
+        assert_eq!(Factory::from(reconfigured.clone()), factory.clone().into());
+        assert!(!Factory::from(reconfigured).same_config(&factory.clone().into()));
+
+        let checkpoint = Checkpoint {
+            block_number: 100,
+            factories: vec![factory.clone().into()],
+            filters: vec![],
+            amms: vec![UniswapV2Pool {
+                address: usdc_weth,
+                token_a: Token::new_with_decimals(usdc, 6),
+                token_b: Token::new_with_decimals(weth, 18),
+                reserve_0: 10_u128.pow(12),
+                reserve_1: 10_u128.pow(21),
+                fee: 300,
+                ..Default::default()
+            }
+            .into()],
+        };
+        let path = std::env::temp_dir().join(format!("amms-replay-{}.json", std::process::id()));
+        checkpoint.write(&path)?;
+
+        let log = |address, data, block_number| Log {
+            inner: alloy::primitives::Log { address, data },
+            block_number: Some(block_number),
+            log_index: Some(0),
+            ..Default::default()
+        };
+        let sync = log(
+            usdc_weth,
+            IUniswapV2Pair::Sync {
+                reserve0: U112::from(2 * 10_u128.pow(12)),
+                reserve1: U112::from(5 * 10_u128.pow(20)),
+            }
+            .encode_log_data(),
+            101,
+        );
+        let pair_created = log(
+            factory.address,
+            IUniswapV2Factory::PairCreated {
+                token0: weth,
+                token1: usdt,
+                pair: usdt_weth,
+                _3: U256::from(2),
+            }
+            .encode_log_data(),
+            102,
+        );
+
+        // The chain tip, then the logs of each block, then the data of the new pair
+        let asserter = Asserter::new();
+        asserter.push_success(&U64::from(102));
+        asserter.push_success(&vec![sync]);
+        asserter.push_success(&vec![pair_created]);
+        let pool_data = vec![(
+            weth,
+            usdt,
+            10_u128.pow(21),
+            2 * 10_u128.pow(12),
+            18_u32,
+            6_u32,
+            U256::from(10_u128.pow(18)),
+            0_u32,
+            U256::ZERO,
+            U256::ZERO,
+        )];
+        asserter.push_success(&Bytes::from(pool_data.abi_encode()));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+
+        let manager = StateSpaceBuilder::from_checkpoint(provider, &path)?
+            .with_replay_step(1)
+            .sync()
+            .await?;
+        fs::remove_file(&path)?;
+        assert!(asserter.read_q().is_empty());
+
+        let state = manager.state.read().await;
+        assert_eq!(state.len(), 2);
+        let Some(AMM::UniswapV2Pool(pool)) = state.get(usdc_weth) else {
+            panic!("the restored pool is in the state space");
+        };
+        assert_eq!(
+            (pool.reserve_0, pool.reserve_1),
+            (2 * 10_u128.pow(12), 5 * 10_u128.pow(20))
+        );
+        let Some(AMM::UniswapV2Pool(pool)) = state.get(usdt_weth) else {
+            panic!("the pool created since the checkpoint is in the state space");
+        };
+        assert_eq!((pool.token_a.address, pool.fee), (weth, 300));
+        assert_eq!(
+            manager
+                .latest_block
+                .load(std::sync::atomic::Ordering::Relaxed),
+            102
+        );
+
+        Ok(())
+    }
+}
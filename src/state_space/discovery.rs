@@ -1,16 +1,29 @@
 use std::collections::{HashMap, HashSet};
 
-use alloy::primitives::{Address, FixedBytes};
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, FixedBytes},
+    providers::Provider,
+    rpc::types::Log,
+};
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use tracing::{info, warn};
 
-use crate::amms::factory::Factory;
+use crate::amms::{
+    amm::{AutomatedMarketMaker, AMM},
+    factory::Factory,
+};
 
-use super::filters::PoolFilter;
+use super::filters::{apply_filters, FilterStage, PoolFilter};
 
 #[derive(Debug, Default, Clone)]
 pub struct DiscoveryManager {
     pub factories: HashMap<Address, Factory>,
     pub pool_filters: Option<Vec<PoolFilter>>,
     pub token_decimals: HashMap<Address, u8>,
+    /// Detects the token taxes of the discovered pools once initialized
+    pub tax_detection: bool,
 }
 
 impl DiscoveryManager {
@@ -35,6 +48,13 @@ impl DiscoveryManager {
         }
     }
 
+    pub fn with_tax_detection(self) -> Self {
+        Self {
+            tax_detection: true,
+            ..self
+        }
+    }
+
     pub fn disc_events(&self) -> HashSet<FixedBytes<32>> {
         self.factories
             .iter()
@@ -43,4 +63,234 @@ impl DiscoveryManager {
                 events_set
             })
     }
+
+    /// Creates the pools announced by the discovery events in `logs`, then filters and initializes
+    /// them at `block_number`. Pools that cannot be created, filtered or initialized are logged and
+    /// skipped, so that a single pool cannot end a subscription.
+    pub async fn discover<N, P>(&self, logs: &[Log], block_number: u64, provider: P) -> Vec<AMM>
+    where
+        N: Network,
+        P: Provider<N> + Clone,
+    {
+        let mut amms = vec![];
+        for log in logs {
+            let Some(factory) = self.factories.get(&log.address()) else {
+                continue;
+            };
+            if log.topic0() != Some(&factory.discovery_event()) {
+                continue;
+            }
+
+            match factory
+                .create_pool_with_provider(log.clone(), provider.clone())
+                .await
+            {
+                Ok(amm) => amms.push(amm),
+                Err(err) => warn!(
+                    target: "state_space::discovery",
+                    block_number,
+                    factory = %factory.address(),
+                    ?err,
+                    "Skipping pool that could not be created"
+                ),
+            }
+        }
+
+        if amms.is_empty() {
+            return amms;
+        }
+
+        let filters = self.pool_filters.as_deref().unwrap_or_default();
+        let amms = match apply_filters(filters, FilterStage::Discovery, amms).await {
+            Ok(amms) => amms,
+            Err(err) => {
+                warn!(target: "state_space::discovery", block_number, ?err, "Skipping pools that could not be filtered");
+                return vec![];
+            }
+        };
+
+        let mut futures = amms
+            .into_iter()
+            .map(|amm| {
+                let key = amm.key();
+                amm.init(BlockId::from(block_number), provider.clone())
+                    .map(move |res| (key, res))
+            })
+            .collect::<FuturesUnordered<_>>();
+        let mut initialized = vec![];
+        while let Some((key, res)) = futures.next().await {
+            match res {
+                Ok(amm) => initialized.push(amm),
+                Err(err) => warn!(
+                    target: "state_space::discovery",
+                    block_number,
+                    %key,
+                    ?err,
+                    "Skipping pool that could not be initialized"
+                ),
+            }
+        }
+
+        if self.tax_detection {
+            initialized =
+                super::detect_taxes(initialized, BlockId::from(block_number), provider).await;
+        }
+
+        let amms = match apply_filters(filters, FilterStage::Sync, initialized).await {
+            Ok(amms) => amms,
+            Err(err) => {
+                warn!(target: "state_space::discovery", block_number, ?err, "Skipping pools that could not be filtered");
+                return vec![];
+            }
+        };
+
+        info!(
+            target: "state_space::discovery",
+            block_number,
+            amms = ?amms.iter().map(|amm| amm.address()).collect::<Vec<_>>(),
+            "Discovered AMMs"
+        );
+
+        amms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        amms::{
+            curve::crypto_swap::{CurveCryptoSwapFactory, ICurveCryptoSwapFactory},
+            uniswap_v2::{IUniswapV2Factory, UniswapV2Factory},
+        },
+        state_space::filters::whitelist::PoolWhitelistFilter,
+    };
+    use alloy::{
+        primitives::{address, b256, Bytes, U256},
+        providers::ProviderBuilder,
+        sol_types::{SolEvent, SolValue},
+        transports::mock::Asserter,
+    };
+
+    #[tokio::test]
+    async fn test_discover() -> eyre::Result<()> {
+        let v2_factory = UniswapV2Factory::new(
+            address!("5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f"),
+            300,
+            10000835,
+        );
+        let crypto_factory = CurveCryptoSwapFactory::new(
+            address!("F18056Bbd320E96A48e3Fbf8bC061322531aac99"),
+            14005321,
+        );
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let usdc_weth = address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc");
+        let usdt_weth = address!("0d4a11d5EEaaC28EC3F61d100daF4d40471f1852");
+
+        let log = |address, data, block_number, log_index| Log {
+            inner: alloy::primitives::Log { address, data },
+            block_number: Some(block_number),
+            transaction_hash: Some(b256!(
+                "d07cbde817318492092cc7a27b3064a69bd893c01cb593d6029683ffd290ab3a"
+            )),
+            log_index: Some(log_index),
+            ..Default::default()
+        };
+        let pair_created = |emitter, token1, pair| {
+            log(
+                emitter,
+                IUniswapV2Factory::PairCreated {
+                    token0: usdc,
+                    token1,
+                    pair,
+                    _3: U256::from(1),
+                }
+                .encode_log_data(),
+                10008355,
+                0,
+            )
+        };
+        let pool_deployed = log(
+            crypto_factory.address,
+            ICurveCryptoSwapFactory::CryptoPoolDeployed {
+                token: address!("ED4064f376cB8d68F770FB1Ff088a3d0F3FF5c4d"),
+                coins: [weth, address!("D533a949740bb3306d119CC777fa900bA034cd52")],
+                A: U256::from(400_000),
+                gamma: U256::from(145_000_000_000_000_u64),
+                mid_fee: U256::from(26_000_000),
+                out_fee: U256::from(45_000_000),
+                allowed_extra_profit: U256::from(2_000_000_000_000_u64),
+                fee_gamma: U256::from(230_000_000_000_000_u64),
+                adjustment_step: U256::from(146_000_000_000_000_u64),
+                admin_fee: U256::from(5_000_000_000_u64),
+                ma_half_time: U256::from(600),
+                initial_price: U256::from(10_u128.pow(18)),
+                deployer: Address::ZERO,
+            }
+            .encode_log_data(),
+            10008355,
+            1,
+        );
+        let logs = [
+            pair_created(v2_factory.address, weth, usdc_weth),
+            pool_deployed,
+            // Filtered out, and never initialized
+            pair_created(
+                v2_factory.address,
+                address!("dAC17F958D2ee523a2206206994597C13D831ec7"),
+                usdt_weth,
+            ),
+            // Not emitted by a factory
+            pair_created(Address::ZERO, weth, usdc_weth),
+        ];
+
+        // The CryptoSwap pool cannot be looked up and is skipped, the pair is initialized
+        let asserter = Asserter::new();
+        asserter.push_failure_msg("header not found");
+        let pool_data = vec![(
+            usdc,
+            weth,
+            30_000_000_000_000_u128,
+            15_000_000_000_000_000_000_000_u128,
+            6_u32,
+            18_u32,
+            U256::from(10_u128.pow(19)),
+            1_700_000_000_u32,
+            U256::ZERO,
+            U256::ZERO,
+        )];
+        asserter.push_success(&Bytes::from(pool_data.abi_encode()));
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter);
+
+        let discovery_manager =
+            DiscoveryManager::new(vec![v2_factory.into(), crypto_factory.into()])
+                .with_pool_filters(vec![PoolWhitelistFilter::new(vec![usdc_weth]).into()]);
+        assert_eq!(
+            discovery_manager.disc_events(),
+            HashSet::from([
+                IUniswapV2Factory::PairCreated::SIGNATURE_HASH,
+                ICurveCryptoSwapFactory::CryptoPoolDeployed::SIGNATURE_HASH
+            ])
+        );
+
+        let amms = discovery_manager
+            .discover(&logs, 10008355, provider.clone())
+            .await;
+        assert_eq!(amms.len(), 1);
+        let AMM::UniswapV2Pool(pool) = &amms[0] else {
+            panic!("expected a Uniswap V2 pool");
+        };
+        assert_eq!(pool.address, usdc_weth);
+        assert_eq!((pool.token_a.address, pool.token_b.address), (usdc, weth));
+        assert_eq!(pool.reserve_0, 30_000_000_000_000);
+        assert_eq!(pool.fee, 300);
+
+        assert!(DiscoveryManager::new(vec![])
+            .discover(&logs, 10008355, provider)
+            .await
+            .is_empty());
+
+        Ok(())
+    }
 }
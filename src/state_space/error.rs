@@ -1,7 +1,7 @@
-use alloy::transports::TransportErrorKind;
+use alloy::{primitives::Address, transports::TransportErrorKind};
 use thiserror::Error;
 
-use crate::amms::error::AMMError;
+use crate::amms::{amm::AMMKey, error::AMMError};
 
 #[derive(Error, Debug)]
 pub enum StateSpaceError {
@@ -11,6 +11,18 @@ pub enum StateSpaceError {
     TransportError(#[from] alloy::transports::RpcError<TransportErrorKind>),
     #[error(transparent)]
     JoinError(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
     #[error("Block Number Does not Exist")]
     MissingBlockNumber,
+    #[error("AMM {0} is not in the state space")]
+    AMMNotFound(AMMKey),
+    #[error("Hop {0} does not sell the token bought by the previous hop")]
+    DisconnectedPath(usize),
+    #[error("No pool trades {0} against {1}")]
+    NoPoolsForPair(Address, Address),
+    #[error("The pools trading {0} against {1} cannot fill the order")]
+    InsufficientLiquidity(Address, Address),
 }
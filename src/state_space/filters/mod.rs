@@ -4,6 +4,7 @@ pub mod whitelist;
 
 use async_trait::async_trait;
 use blacklist::BlacklistFilter;
+use serde::{Deserialize, Serialize};
 use whitelist::{PoolWhitelistFilter, TokenWhitelistFilter};
 
 use crate::amms::{amm::AMM, error::AMMError};
@@ -19,9 +20,22 @@ pub enum FilterStage {
     Sync,
 }
 
+/// Applies the filters of `stage` to `amms`, in order
+pub async fn apply_filters(
+    filters: &[PoolFilter],
+    stage: FilterStage,
+    mut amms: Vec<AMM>,
+) -> Result<Vec<AMM>, AMMError> {
+    for filter in filters.iter().filter(|filter| filter.stage() == stage) {
+        amms = filter.filter(amms).await?;
+    }
+
+    Ok(amms)
+}
+
 macro_rules! filter {
     ($($filter_type:ident),+ $(,)?) => {
-        #[derive(Debug, Clone)]
+        #[derive(Debug, Clone, Serialize, Deserialize)]
         pub enum PoolFilter {
             $($filter_type($filter_type),)+
         }
@@ -87,21 +87,11 @@ where
     async fn filter(&self, amms: Vec<AMM>) -> Result<Vec<AMM>, AMMError> {
         let pool_infos = amms
             .iter()
-            .cloned()
-            .map(|amm| {
-                let pool_address = amm.address();
-                let pool_type = match amm {
-                    AMM::BalancerPool(_) => 0,
-                    AMM::UniswapV2Pool(_) => 1,
-                    AMM::UniswapV3Pool(_) => 2,
-                    // TODO: At the moment, filters are not compatible with vaults
-                    AMM::ERC4626Vault(_) => todo!(),
-                };
-
-                PoolInfo {
-                    poolType: pool_type,
-                    poolAddress: pool_address,
-                }
+            .filter_map(|amm| {
+                Some(PoolInfo {
+                    poolType: pool_type(amm)?,
+                    poolAddress: amm.address(),
+                })
             })
             .collect::<Vec<_>>();
 
@@ -119,6 +109,11 @@ where
         let filtered_amms = amms
             .into_iter()
             .filter(|amm| {
+                // AMMs the batch contract cannot value are kept
+                if pool_type(amm).is_none() {
+                    return true;
+                }
+
                 let pool_address = amm.address();
                 pool_info_returns
                     .get(&pool_address)
@@ -134,3 +129,46 @@ where
         FilterStage::Sync
     }
 }
+
+/// Returns the pool type of an AMM in the batch contract, which only values Balancer, Uniswap V2
+/// and Uniswap V3 pools
+fn pool_type(amm: &AMM) -> Option<u8> {
+    match amm {
+        AMM::BalancerPool(_) => Some(0),
+        AMM::UniswapV2Pool(_) => Some(1),
+        AMM::UniswapV3Pool(_) => Some(2),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::{solidly::SolidlyPool, uniswap_v4::UniswapV4Pool};
+    use alloy::{primitives::address, providers::ProviderBuilder, transports::mock::Asserter};
+
+    #[tokio::test]
+    async fn test_filter_keeps_unsupported_pools() -> eyre::Result<()> {
+        // No responses are queued, so any call to the batch contract fails
+        let provider = ProviderBuilder::new().connect_mocked_client(Asserter::new());
+        let filter = ValueFilter::<100, _, _>::new(
+            Address::ZERO,
+            Address::ZERO,
+            Address::ZERO,
+            U256::from(10_u128.pow(18)),
+            provider,
+        );
+
+        let amms: Vec<AMM> = vec![
+            UniswapV4Pool::default().into(),
+            SolidlyPool {
+                address: address!("0000000000000000000000000000000000000001"),
+                ..Default::default()
+            }
+            .into(),
+        ];
+        assert_eq!(filter.filter(amms).await?.len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use alloy::primitives::Address;
+use itertools::Itertools;
+
+use crate::amms::amm::{AMMKey, AutomatedMarketMaker, AMM};
+
+/// A hop of a route, as `(pool, token_in, token_out)`
+pub type Hop = (AMMKey, Address, Address);
+
+/// Index of the pools in the state space by token and by token pair, kept in sync as AMMs are
+/// inserted and removed.
+#[derive(Debug, Default, Clone)]
+pub struct TokenGraph {
+    tokens_by_pool: HashMap<AMMKey, Vec<Address>>,
+    pools_by_token: HashMap<Address, HashSet<AMMKey>>,
+    pools_by_pair: HashMap<(Address, Address), HashSet<AMMKey>>,
+}
+
+impl TokenGraph {
+    pub fn insert(&mut self, amm: &AMM) {
+        let pool = amm.key();
+        self.remove(pool);
+
+        let tokens = amm.tokens();
+        for (i, token) in tokens.iter().enumerate() {
+            self.pools_by_token.entry(*token).or_default().insert(pool);
+
+            for other in &tokens[i + 1..] {
+                self.pools_by_pair
+                    .entry(pair(*token, *other))
+                    .or_default()
+                    .insert(pool);
+            }
+        }
+        self.tokens_by_pool.insert(pool, tokens);
+    }
+
+    pub fn remove(&mut self, pool: AMMKey) {
+        let Some(tokens) = self.tokens_by_pool.remove(&pool) else {
+            return;
+        };
+
+        for (i, token) in tokens.iter().enumerate() {
+            remove_from(&mut self.pools_by_token, *token, pool);
+
+            for other in &tokens[i + 1..] {
+                remove_from(&mut self.pools_by_pair, pair(*token, *other), pool);
+            }
+        }
+    }
+
+    /// Returns the pools holding `token`
+    pub fn pools_with_token(&self, token: Address) -> impl Iterator<Item = AMMKey> + '_ {
+        self.pools_by_token
+            .get(&token)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// Returns the pools trading `token_a` against `token_b`, in either direction
+    pub fn pools_for_pair(
+        &self,
+        token_a: Address,
+        token_b: Address,
+    ) -> impl Iterator<Item = AMMKey> + '_ {
+        self.pools_by_pair
+            .get(&pair(token_a, token_b))
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// Returns every route of at most `max_hops` hops from `token_in` to `token_out` that does not
+    /// visit a token twice. A pool may appear in several hops of a route if it holds more than two
+    /// tokens.
+    pub fn routes(&self, token_in: Address, token_out: Address, max_hops: usize) -> Vec<Vec<Hop>> {
+        let mut routes = vec![];
+        if token_in == token_out || max_hops == 0 {
+            return routes;
+        }
+
+        let mut visited = HashSet::from([token_in]);
+        let mut route = vec![];
+        self.extend_routes(
+            token_in,
+            token_out,
+            max_hops,
+            &mut visited,
+            &mut route,
+            &mut routes,
+        );
+
+        routes
+    }
+
+    /// Returns every cycle of at most `max_hops` hops from `token` back to itself that does not
+    /// visit another token twice or use a pool twice.
+    pub fn cycles(&self, token: Address, max_hops: usize) -> Vec<Vec<Hop>> {
+        let mut cycles = vec![];
+        let mut visited = HashSet::from([token]);
+        let mut route = vec![];
+        self.extend_routes(
+            token,
+            token,
+            max_hops,
+            &mut visited,
+            &mut route,
+            &mut cycles,
+        );
+
+        cycles.retain(|cycle| cycle.iter().map(|(pool, _, _)| pool).all_unique());
+        cycles
+    }
+
+    fn extend_routes(
+        &self,
+        token: Address,
+        token_out: Address,
+        max_hops: usize,
+        visited: &mut HashSet<Address>,
+        route: &mut Vec<Hop>,
+        routes: &mut Vec<Vec<Hop>>,
+    ) {
+        let hops = self
+            .pools_with_token(token)
+            .flat_map(|pool| {
+                self.tokens_by_pool[&pool]
+                    .iter()
+                    .map(move |&next| (pool, next))
+            })
+            .filter(|(_, next)| *next != token && (*next == token_out || !visited.contains(next)))
+            .collect::<Vec<_>>();
+
+        for (pool, next) in hops {
+            route.push((pool, token, next));
+
+            if next == token_out {
+                routes.push(route.clone());
+            } else if route.len() < max_hops {
+                visited.insert(next);
+                self.extend_routes(next, token_out, max_hops, visited, route, routes);
+                visited.remove(&next);
+            }
+
+            route.pop();
+        }
+    }
+}
+
+/// Returns the key of a token pair, independent of the order of the tokens
+fn pair(token_a: Address, token_b: Address) -> (Address, Address) {
+    if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    }
+}
+
+fn remove_from<K: Eq + std::hash::Hash>(
+    index: &mut HashMap<K, HashSet<AMMKey>>,
+    key: K,
+    pool: AMMKey,
+) {
+    if let Some(pools) = index.get_mut(&key) {
+        pools.remove(&pool);
+        if pools.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
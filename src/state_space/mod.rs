@@ -1,8 +1,12 @@
 pub mod cache;
+pub mod checkpoint;
 pub mod discovery;
 pub mod error;
 pub mod filters;
+pub mod graph;
+pub mod split;
 
+use crate::amms::amm::AMMKey;
 use crate::amms::amm::AutomatedMarketMaker;
 use crate::amms::amm::AMM;
 use crate::amms::error::AMMError;
@@ -13,12 +17,14 @@ use alloy::eips::BlockId;
 use alloy::rpc::types::{Block, Filter, FilterSet, Log};
 use alloy::{
     network::Network,
-    primitives::{Address, FixedBytes},
+    primitives::{Address, FixedBytes, U256},
     providers::Provider,
 };
 use async_stream::stream;
 use cache::StateChange;
 use cache::StateChangeCache;
+use checkpoint::Checkpoint;
+use discovery::DiscoveryManager;
 
 use error::StateSpaceError;
 use filters::AMMFilter;
@@ -26,7 +32,10 @@ use filters::PoolFilter;
 use futures::stream::FuturesUnordered;
 use futures::Stream;
 use futures::StreamExt;
+use graph::{Hop, TokenGraph};
+use std::collections::hash_map::Entry;
 use std::collections::HashSet;
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
@@ -34,25 +43,41 @@ use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 use tokio::sync::RwLock;
 use tracing::debug;
 use tracing::info;
+use tracing::warn;
 
 pub const CACHE_SIZE: usize = 30;
+/// Number of AMMs whose token taxes are detected concurrently
+const TAX_DETECTION_CONCURRENCY: usize = 8;
 
 #[derive(Clone)]
 pub struct StateSpaceManager<N, P> {
     pub state: Arc<RwLock<StateSpace>>,
     pub latest_block: Arc<AtomicU64>,
-    // discovery_manager: Option<DiscoveryManager>,
+    pub discovery_manager: Option<DiscoveryManager>,
     pub block_filter: Filter,
+    pub factories: Vec<Factory>,
+    pub filters: Vec<PoolFilter>,
     pub provider: P,
     phantom: PhantomData<N>,
-    // TODO: add support for caching
+}
+
+/// The changes to the state space from a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateSpaceUpdate {
+    pub block_number: u64,
+    /// AMMs synced from the logs of the block
+    pub affected_amms: Vec<AMMKey>,
+    /// AMMs created in the block and added to the state space, when discovery is enabled
+    pub new_amms: Vec<AMMKey>,
 }
 
 impl<N, P> StateSpaceManager<N, P> {
+    /// Syncs the state space with every new block, adding the pools the factories create in it
+    /// when discovery is enabled.
     pub async fn subscribe(
         &self,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Vec<Address>, StateSpaceError>> + Send>>,
+        Pin<Box<dyn Stream<Item = Result<StateSpaceUpdate, StateSpaceError>> + Send>>,
         StateSpaceError,
     >
     where
@@ -62,6 +87,7 @@ impl<N, P> StateSpaceManager<N, P> {
         let provider = self.provider.clone();
         let latest_block = self.latest_block.clone();
         let state = self.state.clone();
+        let discovery_manager = self.discovery_manager.clone();
         let mut block_filter = self.block_filter.clone();
 
         let block_stream = provider.subscribe_blocks().await?.into_stream();
@@ -74,18 +100,88 @@ impl<N, P> StateSpaceManager<N, P> {
                 block_filter = block_filter.select(block_number);
 
 
-                let logs = provider.get_logs(&block_filter).await?;
+                let mut logs = provider.get_logs(&block_filter).await?;
+                stamp_block_timestamp(&mut logs, block.timestamp());
+
+                // New pools are initialized at the block, so they already reflect its logs
+                let discovered_amms = match &discovery_manager {
+                    Some(discovery_manager) => {
+                        discovery_manager.discover(&logs, block_number, provider.clone()).await
+                    }
+                    None => vec![],
+                };
 
-                let affected_amms = state.write().await.sync(&logs)?;
+                // Update the latest block under the lock so checkpoints see it with the state
+                let mut state = state.write().await;
+                let affected_amms = state.sync(&logs)?;
+                let new_amms = state.insert_discovered(discovered_amms, block_number);
                 latest_block.store(block_number, Ordering::Relaxed);
+                drop(state);
 
-                yield Ok(affected_amms);
+                yield Ok(StateSpaceUpdate {
+                    block_number,
+                    affected_amms,
+                    new_amms,
+                });
             }
         }))
     }
+
+    /// Writes the AMMs, factories and filters along with the block the AMMs are synced to, from
+    /// which [`StateSpaceBuilder::from_checkpoint`] can restart without a full sync.
+    pub async fn checkpoint(&self, path: impl AsRef<Path>) -> Result<(), StateSpaceError> {
+        let state = self.state.read().await;
+        let checkpoint = Checkpoint {
+            block_number: self.latest_block.load(Ordering::Relaxed),
+            factories: self.factories.clone(),
+            filters: self.filters.clone(),
+            amms: state.amms().cloned().collect(),
+        };
+        drop(state);
+
+        checkpoint.write(path)
+    }
+}
+
+/// Sets the block timestamp of logs returned without one, as many providers omit it from
+/// `eth_getLogs`, so that oracles tracked from the logs see when each update happened.
+fn stamp_block_timestamp(logs: &mut [Log], block_timestamp: u64) {
+    for log in logs {
+        log.block_timestamp.get_or_insert(block_timestamp);
+    }
+}
+
+/// Detects the token taxes of `amms` at `block_number`. AMMs whose taxes cannot be detected are
+/// logged and kept without taxes.
+async fn detect_taxes<N, P>(amms: Vec<AMM>, block_number: BlockId, provider: P) -> Vec<AMM>
+where
+    N: Network,
+    P: Provider<N> + Clone,
+{
+    let detections = amms
+        .into_iter()
+        .map(|mut amm| {
+            let provider = provider.clone();
+            async move {
+                if let Err(err) = amm.detect_taxes(block_number, provider).await {
+                    warn!(
+                        target: "state_space::sync",
+                        key = %amm.key(),
+                        ?err,
+                        "Could not detect token taxes"
+                    );
+                }
+                amm
+            }
+        })
+        .collect::<Vec<_>>();
+
+    futures::stream::iter(detections)
+        .buffered(TAX_DETECTION_CONCURRENCY)
+        .collect()
+        .await
 }
 
-// TODO: Drop impl, create a checkpoint
 #[derive(Debug, Default)]
 pub struct StateSpaceBuilder<N, P> {
     pub provider: P,
@@ -93,8 +189,11 @@ pub struct StateSpaceBuilder<N, P> {
     pub factories: Vec<Factory>,
     pub amms: Vec<AMM>,
     pub filters: Vec<PoolFilter>,
+    pub discovery: bool,
+    pub tax_detection: bool,
+    checkpoint: Option<Checkpoint>,
+    replay_step: u64,
     phantom: PhantomData<N>,
-    // TODO: add support for caching
 }
 
 impl<N, P> StateSpaceBuilder<N, P>
@@ -109,11 +208,32 @@ where
             factories: vec![],
             amms: vec![],
             filters: vec![],
-            // discovery: false,
+            discovery: false,
+            tax_detection: false,
+            checkpoint: None,
+            replay_step: checkpoint::DEFAULT_REPLAY_STEP,
             phantom: PhantomData,
         }
     }
 
+    /// Restores the factories, filters and AMMs of a checkpoint written by
+    /// [`StateSpaceManager::checkpoint`]. Syncing then only replays the logs since the checkpoint
+    /// block, and discovers and syncs from scratch only the factories added or reconfigured since.
+    pub fn from_checkpoint(
+        provider: P,
+        path: impl AsRef<Path>,
+    ) -> Result<StateSpaceBuilder<N, P>, StateSpaceError> {
+        let checkpoint = Checkpoint::read(path)?;
+
+        Ok(Self {
+            latest_block: checkpoint.block_number,
+            factories: checkpoint.factories.clone(),
+            filters: checkpoint.filters.clone(),
+            checkpoint: Some(checkpoint),
+            ..Self::new(provider)
+        })
+    }
+
     pub fn block(self, latest_block: u64) -> StateSpaceBuilder<N, P> {
         StateSpaceBuilder {
             latest_block,
@@ -133,11 +253,50 @@ where
         StateSpaceBuilder { filters, ..self }
     }
 
-    pub async fn sync(self) -> Result<StateSpaceManager<N, P>, AMMError> {
-        let chain_tip = BlockId::from(self.provider.get_block_number().await?);
-        let factories = self.factories.clone();
+    /// Sets the number of blocks fetched per request when replaying the logs since a checkpoint,
+    /// [`DEFAULT_REPLAY_STEP`](checkpoint::DEFAULT_REPLAY_STEP) by default.
+    pub fn with_replay_step(self, replay_step: u64) -> StateSpaceBuilder<N, P> {
+        StateSpaceBuilder {
+            replay_step,
+            ..self
+        }
+    }
+
+    /// Adds the pools the factories create after the sync to the state space as they are found by
+    /// [`StateSpaceManager::subscribe`].
+    pub fn with_discovery(self) -> StateSpaceBuilder<N, P> {
+        StateSpaceBuilder {
+            discovery: true,
+            ..self
+        }
+    }
+
+    /// Detects the taxes of the tokens of the AMMs synced or discovered, see
+    /// [`AutomatedMarketMaker::detect_taxes`]. The node must support `eth_call` state overrides.
+    pub fn with_tax_detection(self) -> StateSpaceBuilder<N, P> {
+        StateSpaceBuilder {
+            tax_detection: true,
+            ..self
+        }
+    }
+
+    pub async fn sync(mut self) -> Result<StateSpaceManager<N, P>, AMMError> {
+        let chain_tip_number = self.provider.get_block_number().await?;
+        let chain_tip = BlockId::from(chain_tip_number);
         let mut futures = FuturesUnordered::new();
 
+        // Factories in the checkpoint are brought up to date from their logs instead
+        let checkpoint = self.checkpoint.take();
+        let (warm_factories, factories): (Vec<Factory>, Vec<Factory>) =
+            self.factories.iter().cloned().partition(|factory| {
+                checkpoint.as_ref().is_some_and(|checkpoint| {
+                    checkpoint
+                        .factories
+                        .iter()
+                        .any(|restored| restored.same_config(factory))
+                })
+            });
+
         let mut filter_set = HashSet::new();
         for factory in &self.factories {
             for event in factory.pool_events() {
@@ -145,17 +304,51 @@ where
             }
         }
 
-        for amm in self.amms.iter() {
+        let checkpoint_amms = checkpoint
+            .as_ref()
+            .map(|checkpoint| checkpoint.amms.as_slice())
+            .unwrap_or_default();
+        for amm in self.amms.iter().chain(checkpoint_amms) {
             for event in amm.sync_events() {
                 filter_set.insert(event);
             }
         }
 
+        let discovery_manager = self.discovery.then(|| DiscoveryManager {
+            tax_detection: self.tax_detection,
+            ..DiscoveryManager::new(self.factories.clone()).with_pool_filters(self.filters.clone())
+        });
+        if let Some(discovery_manager) = &discovery_manager {
+            filter_set.extend(discovery_manager.disc_events());
+        }
+
+        // Replaying also needs the pools the restored factories created since the checkpoint
+        let replay_filter = Filter::new().event_signature(FilterSet::from(
+            filter_set
+                .iter()
+                .copied()
+                .chain(
+                    warm_factories
+                        .iter()
+                        .map(|factory| factory.discovery_event()),
+                )
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<FixedBytes<32>>>(),
+        ));
         let block_filter = Filter::new().event_signature(FilterSet::from(
             filter_set.into_iter().collect::<Vec<FixedBytes<32>>>(),
         ));
         let mut amm_variants = HashMap::new();
-        for amm in self.amms.into_iter() {
+        let restored = checkpoint_amms
+            .iter()
+            .map(|amm| amm.key())
+            .collect::<HashSet<_>>();
+        for amm in self
+            .amms
+            .into_iter()
+            .filter(|amm| !restored.contains(&amm.key()))
+        {
             amm_variants
                 .entry(amm.variant())
                 .or_insert_with(Vec::new)
@@ -215,27 +408,68 @@ where
         }
 
         let mut state_space = StateSpace::default();
+        if let Some(checkpoint) = checkpoint {
+            state_space
+                .latest_block
+                .store(checkpoint.block_number, Ordering::Relaxed);
+            for amm in checkpoint.amms {
+                state_space.insert(amm);
+            }
+
+            checkpoint::replay(
+                &mut state_space,
+                &warm_factories,
+                &self.filters,
+                &replay_filter,
+                checkpoint.block_number + 1..=chain_tip_number,
+                self.replay_step,
+                self.provider.clone(),
+            )
+            .await?;
+        }
+
         while let Some(res) = futures.next().await {
             let synced_amms = res??;
 
             for amm in synced_amms {
-                state_space.state.insert(amm.address(), amm);
+                state_space.insert(amm);
             }
         }
 
         // Sync remaining AMM variants
         for (_, remaining_amms) in amm_variants.drain() {
             for mut amm in remaining_amms {
-                let address = amm.address();
                 amm = amm.init(chain_tip, self.provider.clone()).await?;
-                state_space.state.insert(address, amm);
+                state_space.insert(amm);
+            }
+        }
+
+        // Restored AMMs keep the taxes they were checkpointed with
+        if self.tax_detection {
+            let amms = state_space
+                .amms()
+                .map(|amm| amm.key())
+                .filter(|key| !restored.contains(key))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .filter_map(|key| state_space.remove(key))
+                .collect();
+            for amm in detect_taxes(amms, chain_tip, self.provider.clone()).await {
+                state_space.insert(amm);
             }
         }
 
+        state_space
+            .latest_block
+            .store(chain_tip_number, Ordering::Relaxed);
+
         Ok(StateSpaceManager {
-            latest_block: Arc::new(AtomicU64::new(self.latest_block)),
+            latest_block: state_space.latest_block.clone(),
             state: Arc::new(RwLock::new(state_space)),
+            discovery_manager,
             block_filter,
+            factories: self.factories,
+            filters: self.filters,
             provider: self.provider,
             phantom: PhantomData,
         })
@@ -244,21 +478,224 @@ where
 
 #[derive(Debug, Default)]
 pub struct StateSpace {
-    pub state: HashMap<Address, AMM>,
+    /// AMMs by key. AMMs inserted or removed through the map directly are missing from the token
+    /// index until [`StateSpace::reindex`] is called, so prefer [`StateSpace::insert`] and
+    /// [`StateSpace::remove`].
+    pub state: HashMap<AMMKey, AMM>,
+    graph: TokenGraph,
+    /// AMMs syncing from the logs of another AMM, by the key of that AMM
+    dependents: HashMap<AMMKey, HashSet<AMMKey>>,
     pub latest_block: Arc<AtomicU64>,
     cache: StateChangeCache<CACHE_SIZE>,
 }
 
 impl StateSpace {
-    pub fn get(&self, address: &Address) -> Option<&AMM> {
-        self.state.get(address)
+    /// Returns the AMM with `key`, which is the address of AMMs other than those living in a
+    /// singleton contract, see [`AMMKey`]
+    pub fn get(&self, key: impl Into<AMMKey>) -> Option<&AMM> {
+        self.state.get(&key.into())
+    }
+
+    pub fn get_mut(&mut self, key: impl Into<AMMKey>) -> Option<&mut AMM> {
+        self.state.get_mut(&key.into())
+    }
+
+    /// Inserts an AMM, returning the AMM previously at its key
+    pub fn insert(&mut self, amm: AMM) -> Option<AMM> {
+        let key = amm.key();
+        self.unindex_dependencies(&key);
+        self.graph.insert(&amm);
+        index_dependencies(&mut self.dependents, &amm);
+        self.state.insert(key, amm)
+    }
+
+    pub fn remove(&mut self, key: impl Into<AMMKey>) -> Option<AMM> {
+        let key = key.into();
+        self.graph.remove(key);
+        self.unindex_dependencies(&key);
+        self.state.remove(&key)
+    }
+
+    /// Rebuilds the token index from the AMMs in the state space
+    pub fn reindex(&mut self) {
+        self.graph = TokenGraph::default();
+        self.dependents.clear();
+        for amm in self.state.values() {
+            self.graph.insert(amm);
+            index_dependencies(&mut self.dependents, amm);
+        }
+    }
+
+    fn unindex_dependencies(&mut self, key: &AMMKey) {
+        let Some(amm) = self.state.get(key) else {
+            return;
+        };
+
+        for dependency in amm.dependencies() {
+            let dependency = AMMKey::from(dependency);
+            if let Some(dependents) = self.dependents.get_mut(&dependency) {
+                dependents.remove(key);
+                if dependents.is_empty() {
+                    self.dependents.remove(&dependency);
+                }
+            }
+        }
+    }
+
+    /// Returns the AMMs to sync from `log`, the AMM it is routed to followed by the AMMs depending
+    /// on it
+    fn log_targets(&self, log: &Log) -> Vec<AMMKey> {
+        let key = AMM::log_key(log).unwrap_or(AMMKey::Address(log.address()));
+        std::iter::once(key)
+            .chain(self.dependents.get(&key).into_iter().flatten().copied())
+            .collect()
+    }
+
+    pub fn amms(&self) -> impl Iterator<Item = &AMM> {
+        self.state.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /// Returns the AMMs holding `token`
+    pub fn pools_with_token(&self, token: Address) -> impl Iterator<Item = &AMM> {
+        self.graph
+            .pools_with_token(token)
+            .filter_map(|pool| self.state.get(&pool))
+    }
+
+    /// Returns the AMMs trading `token_a` against `token_b`, in either direction
+    pub fn pools_for_pair(&self, token_a: Address, token_b: Address) -> impl Iterator<Item = &AMM> {
+        self.graph
+            .pools_for_pair(token_a, token_b)
+            .filter_map(|pool| self.state.get(&pool))
+    }
+
+    /// Returns every route of at most `max_hops` hops from `token_in` to `token_out` that does not
+    /// visit a token twice. See [`TokenGraph::routes`].
+    pub fn routes(&self, token_in: Address, token_out: Address, max_hops: usize) -> Vec<Vec<Hop>> {
+        self.graph.routes(token_in, token_out, max_hops)
+    }
+
+    /// Returns every cycle of at most `max_hops` hops from `token` back to itself. See
+    /// [`TokenGraph::cycles`].
+    pub fn cycles(&self, token: Address, max_hops: usize) -> Vec<Vec<Hop>> {
+        self.graph.cycles(token, max_hops)
+    }
+
+    /// Returns the routes of at most `max_hops` hops from `token_in` to `token_out` with the amount
+    /// out of each for `amount_in`, best first. Routes that fail to simulate are skipped.
+    pub fn best_routes(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        max_hops: usize,
+    ) -> Vec<(Vec<Hop>, U256)> {
+        let mut routes = self
+            .routes(token_in, token_out, max_hops)
+            .into_iter()
+            .filter_map(|route| {
+                let amounts_out = self.simulate_path_mut(&route, amount_in).ok()?;
+                let amount_out = *amounts_out.last()?;
+                Some((route, amount_out))
+            })
+            .collect::<Vec<_>>();
+        routes.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        routes
+    }
+
+    /// Simulates a multi-hop swap of `amount_in`, where each hop of `path` is a
+    /// `(pool, token_in, token_out)` selling the output of the previous hop.
+    /// Returns the amount out of each hop.
+    ///
+    /// Every hop is simulated against the current state, so a pool used more than once is quoted
+    /// as if the earlier hops had not traded through it. See [`StateSpace::simulate_path_mut`].
+    pub fn simulate_path(
+        &self,
+        path: &[Hop],
+        amount_in: U256,
+    ) -> Result<Vec<U256>, StateSpaceError> {
+        Self::validate_path(path)?;
+
+        let mut amount = amount_in;
+        let mut amounts_out = Vec::with_capacity(path.len());
+        for (pool, token_in, token_out) in path {
+            let amm = self.get(pool).ok_or(StateSpaceError::AMMNotFound(*pool))?;
+            amount = amm.simulate_swap(*token_in, *token_out, amount)?;
+            amounts_out.push(amount);
+        }
+
+        Ok(amounts_out)
+    }
+
+    /// Simulates a multi-hop swap like [`StateSpace::simulate_path`], applying each hop to a
+    /// scratch copy of its pool so that later hops through the same pool see the updated state.
+    /// The state space itself is left untouched.
+    pub fn simulate_path_mut(
+        &self,
+        path: &[Hop],
+        amount_in: U256,
+    ) -> Result<Vec<U256>, StateSpaceError> {
+        Self::validate_path(path)?;
+
+        let mut scratch: HashMap<AMMKey, AMM> = HashMap::new();
+        let mut amount = amount_in;
+        let mut amounts_out = Vec::with_capacity(path.len());
+        for (pool, token_in, token_out) in path {
+            let amm = match scratch.entry(*pool) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    self.get(pool)
+                        .ok_or(StateSpaceError::AMMNotFound(*pool))?
+                        .clone(),
+                ),
+            };
+            amount = amm.simulate_swap_mut(*token_in, *token_out, amount)?;
+            amounts_out.push(amount);
+        }
+
+        Ok(amounts_out)
+    }
+
+    fn validate_path(path: &[Hop]) -> Result<(), StateSpaceError> {
+        for (hop, window) in path.windows(2).enumerate() {
+            if window[0].2 != window[1].1 {
+                return Err(StateSpaceError::DisconnectedPath(hop + 1));
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn get_mut(&mut self, address: &Address) -> Option<&mut AMM> {
-        self.state.get_mut(address)
+    /// Inserts the AMMs discovered in `block_number` that are not in the state space yet, returning
+    /// their keys. They are removed again if a reorg unwinds the block.
+    pub fn insert_discovered(&mut self, amms: Vec<AMM>, block_number: u64) -> Vec<AMMKey> {
+        let mut new_amms = vec![];
+        for amm in amms {
+            let key = amm.key();
+            if self.get(key).is_none() {
+                self.insert(amm);
+                new_amms.push(key);
+            }
+        }
+
+        if !new_amms.is_empty() {
+            self.cache
+                .push(StateChange::with_new_amms(new_amms.clone(), block_number));
+        }
+
+        new_amms
     }
 
-    pub fn sync(&mut self, logs: &[Log]) -> Result<Vec<Address>, StateSpaceError> {
+    pub fn sync(&mut self, logs: &[Log]) -> Result<Vec<AMMKey>, StateSpaceError> {
         let latest = self.latest_block.load(Ordering::Relaxed);
         let Some(mut block_number) = logs
             .first()
@@ -277,10 +714,14 @@ impl StateSpace {
                 "Unwinding state changes"
             );
 
-            let cached_state = self.cache.unwind_state_changes(block_number);
+            let (cached_state, new_amms) = self.cache.unwind(block_number);
             for amm in cached_state {
                 debug!(target: "state_space::sync", ?amm, "Reverting AMM state");
-                self.state.insert(amm.address(), amm);
+                self.insert(amm);
+            }
+            for key in new_amms {
+                debug!(target: "state_space::sync", %key, "Removing AMM added in an unwound block");
+                self.remove(key);
             }
         }
 
@@ -293,7 +734,7 @@ impl StateSpace {
                 .ok_or(StateSpaceError::MissingBlockNumber)?;
             if log_block_number != block_number {
                 let amms = cached_amms.drain().collect::<Vec<AMM>>();
-                affected_amms.extend(amms.iter().map(|amm| amm.address()));
+                affected_amms.extend(amms.iter().map(|amm| amm.key()));
                 let state_change = StateChange::new(amms, block_number);
 
                 debug!(
@@ -306,23 +747,32 @@ impl StateSpace {
                 block_number = log_block_number;
             }
 
-            // If the AMM is in the state space add the current state to cache and sync from log
-            let address = log.address();
-            if let Some(amm) = self.state.get_mut(&address) {
-                cached_amms.insert(amm.clone());
-                amm.sync(log)?;
+            // If the AMM is in the state space add the current state to cache and sync from log.
+            // Subscribers filter by the events of every AMM, so skip the ones this AMM ignores
+            let Some(event_signature) = log.topic0() else {
+                continue;
+            };
+            for key in self.log_targets(log) {
+                if let Some(amm) = self.state.get_mut(&key) {
+                    if !amm.sync_events().contains(event_signature) {
+                        continue;
+                    }
 
-                info!(
-                    target: "state_space::sync",
-                    ?amm,
-                    "Synced AMM"
-                );
+                    cached_amms.insert(amm.clone());
+                    amm.sync(log)?;
+
+                    info!(
+                        target: "state_space::sync",
+                        ?amm,
+                        "Synced AMM"
+                    );
+                }
             }
         }
 
         if !cached_amms.is_empty() {
             let amms = cached_amms.drain().collect::<Vec<AMM>>();
-            affected_amms.extend(amms.iter().map(|amm| amm.address()));
+            affected_amms.extend(amms.iter().map(|amm| amm.key()));
             let state_change = StateChange::new(amms, block_number);
 
             debug!(
@@ -338,6 +788,15 @@ impl StateSpace {
     }
 }
 
+fn index_dependencies(dependents: &mut HashMap<AMMKey, HashSet<AMMKey>>, amm: &AMM) {
+    for dependency in amm.dependencies() {
+        dependents
+            .entry(dependency.into())
+            .or_default()
+            .insert(amm.key());
+    }
+}
+
 #[macro_export]
 macro_rules! sync {
     // Sync factories with provider
@@ -366,3 +825,395 @@ macro_rules! sync {
             .await?
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::{
+        consts::Q128,
+        curve::stable_swap::{CurveStableSwapPool, ICurveStableSwap},
+        uniswap_v2::{IUniswapV2Pair, UniswapV2Pool},
+        uniswap_v4::{IPoolManager, UniswapV4Pool},
+        Token,
+    };
+    use alloy::{
+        primitives::{
+            address,
+            aliases::{U112, U160, U24},
+            Signed, B256,
+        },
+        sol_types::SolEvent,
+    };
+
+    #[test]
+    fn test_stamped_logs_track_oracle() -> eyre::Result<()> {
+        let pool = UniswapV2Pool {
+            address: address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"),
+            token_a: Token::new_with_decimals(Address::with_last_byte(1), 18),
+            token_b: Token::new_with_decimals(Address::with_last_byte(2), 18),
+            reserve_0: 10_u128.pow(18),
+            reserve_1: 2 * 10_u128.pow(18),
+            block_timestamp_last: 1_000,
+            ..Default::default()
+        }
+        .with_oracle(8);
+        let (token_a, token_b) = (pool.token_a.address, pool.token_b.address);
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool.clone().into());
+
+        // Logs as returned by `eth_getLogs` without a block timestamp, stamped with the timestamp
+        // of their block as in `subscribe`
+        let sync_event = IUniswapV2Pair::Sync {
+            reserve0: U112::from(10_u128.pow(18)),
+            reserve1: U112::from(4 * 10_u128.pow(18)),
+        };
+        for (block_number, block_timestamp) in [(1, 1_010), (2, 1_020)] {
+            let mut logs = vec![Log {
+                inner: alloy::primitives::Log {
+                    address: pool.address,
+                    data: sync_event.encode_log_data(),
+                },
+                block_number: Some(block_number),
+                ..Default::default()
+            }];
+            stamp_block_timestamp(&mut logs, block_timestamp);
+            state_space.sync(&logs)?;
+        }
+
+        let amm = state_space.get(pool.address).unwrap();
+        assert!(matches!(amm, AMM::UniswapV2Pool(pool) if pool.oracle.is_some()));
+        assert_eq!(amm.twap(token_a, token_b, 10, 1_020)?, Q128 * U256::from(4));
+
+        // Timestamps the provider returned are kept
+        let mut logs = vec![Log {
+            block_timestamp: Some(1_030),
+            ..Default::default()
+        }];
+        stamp_block_timestamp(&mut logs, 1_040);
+        assert_eq!(logs[0].block_timestamp, Some(1_030));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unwind_discovered_amms() -> eyre::Result<()> {
+        let (token_a, token_b) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let pool = |address| -> AMM {
+            UniswapV2Pool {
+                address,
+                token_a: Token::new_with_decimals(token_a, 18),
+                token_b: Token::new_with_decimals(token_b, 18),
+                reserve_0: 10_u128.pow(18),
+                reserve_1: 10_u128.pow(18),
+                fee: 300,
+                ..Default::default()
+            }
+            .into()
+        };
+        let sync_log = |address, block_number| Log {
+            inner: alloy::primitives::Log {
+                address,
+                data: IUniswapV2Pair::Sync {
+                    reserve0: U112::from(10_u128.pow(18)),
+                    reserve1: U112::from(2 * 10_u128.pow(18)),
+                }
+                .encode_log_data(),
+            },
+            block_number: Some(block_number),
+            ..Default::default()
+        };
+        let (existing, discovered) = (Address::with_last_byte(3), Address::with_last_byte(4));
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool(existing));
+        state_space.latest_block.store(9, Ordering::Relaxed);
+
+        // Pools already in the state space are not added again
+        let new_amms = state_space.insert_discovered(vec![pool(existing), pool(discovered)], 10);
+        assert_eq!(new_amms, vec![discovered.into()]);
+        state_space.latest_block.store(10, Ordering::Relaxed);
+        state_space.sync(&[sync_log(discovered, 11)])?;
+        state_space.latest_block.store(11, Ordering::Relaxed);
+
+        // A reorg replacing block 10 removes the pool discovered in it
+        state_space.sync(&[sync_log(existing, 10)])?;
+        assert!(state_space.get(discovered).is_none());
+        assert_eq!(
+            state_space
+                .pools_for_pair(token_a, token_b)
+                .map(|amm| amm.address())
+                .collect::<Vec<_>>(),
+            vec![existing]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_dependents() -> eyre::Result<()> {
+        let base_pool = CurveStableSwapPool::new(Address::with_last_byte(1));
+        let meta_pool = CurveStableSwapPool {
+            base_pool: Some(Box::new(base_pool.clone())),
+            ..CurveStableSwapPool::new(Address::with_last_byte(2))
+        };
+        let fee_log = |fee, block_number| Log {
+            inner: alloy::primitives::Log {
+                address: base_pool.address,
+                data: ICurveStableSwap::NewFee {
+                    fee: U256::from(fee),
+                    admin_fee: U256::ZERO,
+                }
+                .encode_log_data(),
+            },
+            block_number: Some(block_number),
+            ..Default::default()
+        };
+        let embedded_fee = |state_space: &StateSpace| match state_space.get(meta_pool.address) {
+            Some(AMM::CurveStableSwapPool(pool)) => pool.base_pool.as_ref().map(|pool| pool.fee),
+            _ => None,
+        };
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(base_pool.clone().into());
+        state_space.insert(meta_pool.clone().into());
+
+        // Logs of the base pool sync the base pool and the meta pool holding it
+        let mut affected_amms = state_space.sync(&[fee_log(4_000_000, 1)])?;
+        affected_amms.sort();
+        assert_eq!(
+            affected_amms,
+            vec![base_pool.address.into(), meta_pool.address.into()]
+        );
+        assert_eq!(embedded_fee(&state_space), Some(U256::from(4_000_000)));
+        state_space.latest_block.store(1, Ordering::Relaxed);
+
+        state_space.remove(meta_pool.address);
+        assert_eq!(
+            state_space.sync(&[fee_log(1_000_000, 2)])?,
+            vec![base_pool.address.into()]
+        );
+        state_space.latest_block.store(2, Ordering::Relaxed);
+
+        // Events the pool does not sync from, such as transfers of its LP token, are skipped
+        let transfer_log = Log {
+            inner: alloy::primitives::Log {
+                address: base_pool.address,
+                data: alloy::primitives::LogData::new_unchecked(
+                    vec![alloy::primitives::keccak256(
+                        "Transfer(address,address,uint256)",
+                    )],
+                    Default::default(),
+                ),
+            },
+            block_number: Some(3),
+            ..Default::default()
+        };
+        assert!(state_space.sync(&[transfer_log])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_singleton_keys() -> eyre::Result<()> {
+        let pool_manager = address!("000000000004444c5dc75cB358380D2e3dE08A90");
+        let pair = Address::with_last_byte(1);
+        // Two V4 pools whose ids share their leading 20 bytes with each other and with the pair
+        let v4_pool = |last_byte| UniswapV4Pool {
+            pool_manager,
+            pool_id: B256::right_padding_from(&[pair.as_slice(), &[last_byte]].concat()),
+            ..Default::default()
+        };
+        let (pool_a, pool_b) = (v4_pool(1), v4_pool(2));
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(
+            UniswapV2Pool {
+                address: pair,
+                ..Default::default()
+            }
+            .into(),
+        );
+        state_space.insert(pool_a.clone().into());
+        state_space.insert(pool_b.clone().into());
+        assert_eq!(state_space.len(), 3);
+
+        let swap_log = |address, pool_id| Log {
+            inner: alloy::primitives::Log {
+                address,
+                data: IPoolManager::Swap {
+                    id: pool_id,
+                    sender: Address::ZERO,
+                    amount0: 0,
+                    amount1: 0,
+                    sqrtPriceX96: U160::from(1),
+                    liquidity: 1,
+                    tick: Signed::ZERO,
+                    fee: U24::ZERO,
+                }
+                .encode_log_data(),
+            },
+            block_number: Some(1),
+            ..Default::default()
+        };
+
+        // Swaps are routed by the full pool id, and only from the pool manager
+        assert_eq!(
+            state_space.sync(&[swap_log(pool_manager, pool_a.pool_id)])?,
+            vec![pool_a.key()]
+        );
+        state_space.latest_block.store(1, Ordering::Relaxed);
+        assert!(state_space
+            .sync(&[Log {
+                block_number: Some(2),
+                ..swap_log(pair, pool_b.pool_id)
+            }])?
+            .is_empty());
+
+        let liquidity = |key: AMMKey| match state_space.get(key) {
+            Some(AMM::UniswapV4Pool(pool)) => pool.liquidity,
+            _ => 0,
+        };
+        assert_eq!(liquidity(pool_a.key()), 1);
+        assert_eq!(liquidity(pool_b.key()), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_path() -> eyre::Result<()> {
+        let usdc =
+            Token::new_with_decimals(address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), 6);
+        let weth =
+            Token::new_with_decimals(address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), 18);
+        let pool = UniswapV2Pool {
+            address: address!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"),
+            token_a: usdc.clone(),
+            token_b: weth.clone(),
+            reserve_0: 10_u128.pow(12),
+            reserve_1: 10_u128.pow(21),
+            fee: 300,
+            ..Default::default()
+        };
+        let mut state_space = StateSpace::default();
+        state_space.insert(AMM::from(pool.clone()));
+
+        // Buy WETH and sell it straight back through the same pool
+        let path = [
+            (pool.address.into(), usdc.address, weth.address),
+            (pool.address.into(), weth.address, usdc.address),
+        ];
+        let amount_in = U256::from(10_u128.pow(11));
+        let amounts_out = state_space.simulate_path(&path, amount_in)?;
+        let amounts_out_mut = state_space.simulate_path_mut(&path, amount_in)?;
+
+        assert_eq!(amounts_out[0], amounts_out_mut[0]);
+        assert_eq!(
+            amounts_out[1],
+            pool.simulate_swap(weth.address, usdc.address, amounts_out[0])?
+        );
+        // The second hop sees the reserves after the first, so the round trip only loses the fees
+        // rather than paying the price impact twice
+        let mut scratch = pool.clone();
+        scratch.simulate_swap_mut(usdc.address, weth.address, amount_in)?;
+        assert_eq!(
+            amounts_out_mut[1],
+            scratch.simulate_swap(weth.address, usdc.address, amounts_out_mut[0])?
+        );
+        assert!(amounts_out_mut[1] < amount_in);
+        assert!(amounts_out_mut[1] > amounts_out[1]);
+
+        // The state space is left untouched
+        assert_eq!(
+            state_space.simulate_path_mut(&path, amount_in)?,
+            amounts_out_mut
+        );
+
+        assert!(matches!(
+            state_space.simulate_path(&[path[0], path[0]], amount_in),
+            Err(StateSpaceError::DisconnectedPath(1))
+        ));
+        assert!(matches!(
+            state_space.simulate_path_mut(
+                &[(Address::ZERO.into(), usdc.address, weth.address)],
+                amount_in
+            ),
+            Err(StateSpaceError::AMMNotFound(AMMKey::Address(Address::ZERO)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_routes() -> eyre::Result<()> {
+        let usdc = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let pool = |address, token_a, token_b, reserve_0, reserve_1| {
+            AMM::from(UniswapV2Pool {
+                address,
+                token_a: Token::new_with_decimals(token_a, 18),
+                token_b: Token::new_with_decimals(token_b, 18),
+                reserve_0,
+                reserve_1,
+                fee: 300,
+                ..Default::default()
+            })
+        };
+        let usdc_weth = address!("0000000000000000000000000000000000000001");
+        let weth_dai = address!("0000000000000000000000000000000000000002");
+        let usdc_dai = address!("0000000000000000000000000000000000000003");
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool(
+            usdc_weth,
+            usdc,
+            weth,
+            10_u128.pow(24),
+            10_u128.pow(21),
+        ));
+        state_space.insert(pool(
+            weth_dai,
+            weth,
+            dai,
+            10_u128.pow(21),
+            2 * 10_u128.pow(24),
+        ));
+        state_space.insert(pool(usdc_dai, usdc, dai, 10_u128.pow(24), 10_u128.pow(24)));
+
+        assert_eq!(state_space.pools_with_token(usdc).count(), 2);
+        assert_eq!(
+            state_space
+                .pools_for_pair(dai, usdc)
+                .map(|amm| amm.address())
+                .collect::<Vec<_>>(),
+            vec![usdc_dai]
+        );
+        assert_eq!(state_space.routes(usdc, dai, 1).len(), 1);
+        assert_eq!(state_space.routes(usdc, dai, 2).len(), 2);
+
+        // DAI is cheaper through WETH
+        let amount_in = U256::from(10_u128.pow(18));
+        let routes = state_space.best_routes(usdc, dai, amount_in, 3);
+        assert_eq!(
+            routes[0].0,
+            vec![(usdc_weth.into(), usdc, weth), (weth_dai.into(), weth, dai)]
+        );
+        assert_eq!(routes[1].0, vec![(usdc_dai.into(), usdc, dai)]);
+        assert!(routes[0].1 > routes[1].1);
+
+        state_space.remove(weth_dai);
+        assert_eq!(state_space.pools_with_token(weth).count(), 1);
+        assert_eq!(state_space.routes(usdc, dai, 3).len(), 1);
+
+        // AMMs inserted into the map directly are indexed once reindexed
+        state_space.state.insert(
+            weth_dai.into(),
+            pool(weth_dai, weth, dai, 10_u128.pow(21), 10_u128.pow(24)),
+        );
+        assert_eq!(state_space.routes(usdc, dai, 3).len(), 1);
+        state_space.reindex();
+        assert_eq!(state_space.routes(usdc, dai, 3).len(), 2);
+
+        Ok(())
+    }
+}
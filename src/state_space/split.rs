@@ -0,0 +1,335 @@
+use alloy::primitives::{Address, U256};
+
+use crate::amms::amm::{AMMKey, AutomatedMarketMaker, AMM};
+
+use super::{error::StateSpaceError, StateSpace};
+
+/// The amount sold to and bought from a pool as part of a split order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub pool: AMMKey,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// An order split across the pools of a pair.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitOrder {
+    pub allocations: Vec<Allocation>,
+    pub amount_out: U256,
+}
+
+impl StateSpace {
+    /// Splits a sale of `amount_in` of `token_in` for `token_out` across every pool of the pair to
+    /// maximise the total amount out, which is reached when the pools that receive an allocation
+    /// all end at the same marginal rate.
+    ///
+    /// When every pool of the pair is a Uniswap V2, Uniswap V3 or Balancer pool, the amount a pool
+    /// takes for its marginal rate to fall to a given rate has a closed form, and the common rate at
+    /// which the pools take `amount_in` is found by bisection. Otherwise the order is sold in
+    /// `steps` equal parts, each to the pool with the best output for it after the parts already
+    /// sold to it, which equalises the marginal rates to within one step.
+    pub fn split_order(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        steps: usize,
+    ) -> Result<SplitOrder, StateSpaceError> {
+        let pools = self
+            .pools_for_pair(token_in, token_out)
+            .collect::<Vec<&AMM>>();
+        if pools.is_empty() {
+            return Err(StateSpaceError::NoPoolsForPair(token_in, token_out));
+        }
+
+        let allocated = match equal_marginal_rate_allocation(&pools, token_in, token_out, amount_in)
+        {
+            Some(allocated) => allocated,
+            None => greedy_allocation(&pools, token_in, token_out, amount_in, steps)?,
+        };
+
+        // Quote each allocation in one swap against the current state
+        let mut order = SplitOrder::default();
+        for (amm, allocated) in pools.into_iter().zip(allocated) {
+            if allocated.is_zero() {
+                continue;
+            }
+
+            let amount_out = amm.simulate_swap(token_in, token_out, allocated)?;
+            order.amount_out += amount_out;
+            order.allocations.push(Allocation {
+                pool: amm.key(),
+                amount_in: allocated,
+                amount_out,
+            });
+        }
+
+        Ok(order)
+    }
+}
+
+/// Returns the amount of `token_in` `amm` takes for its marginal rate to fall to `marginal_rate`,
+/// or `None` if the pool has no closed form for it
+fn amount_in_to_marginal_rate(
+    amm: &AMM,
+    token_in: Address,
+    token_out: Address,
+    marginal_rate: f64,
+) -> Option<U256> {
+    match amm {
+        AMM::UniswapV2Pool(pool) => pool.amount_in_to_marginal_rate(token_in, marginal_rate),
+        AMM::UniswapV3Pool(pool) => pool.amount_in_to_marginal_rate(token_in, marginal_rate),
+        AMM::BalancerPool(pool) => {
+            pool.amount_in_to_marginal_rate(token_in, token_out, marginal_rate)
+        }
+        _ => return None,
+    }
+    .ok()
+}
+
+/// Splits `amount_in` so that every pool receiving a part ends at the same marginal rate, or
+/// returns `None` if a pool has no closed form or the pools cannot take the whole amount
+fn equal_marginal_rate_allocation(
+    pools: &[&AMM],
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> Option<Vec<U256>> {
+    let allocation = |marginal_rate: f64| -> Option<Vec<U256>> {
+        pools
+            .iter()
+            .map(|amm| amount_in_to_marginal_rate(amm, token_in, token_out, marginal_rate))
+            .collect()
+    };
+    let total = |allocation: &[U256]| {
+        allocation
+            .iter()
+            .fold(U256::ZERO, |total, amount| total.saturating_add(*amount))
+    };
+
+    // The pools take less as the rate rises, so bracket the common rate between powers of two
+    let mut high = 1.0_f64;
+    while total(&allocation(high)?) > amount_in {
+        high *= 2.0;
+        if !high.is_finite() {
+            return None;
+        }
+    }
+    let mut low = high;
+    let mut above = allocation(low)?;
+    while total(&above) < amount_in {
+        low /= 2.0;
+        if low == 0.0 {
+            return None;
+        }
+        above = allocation(low)?;
+    }
+
+    // Bisect until the bracket cannot be split, keeping an allocation on each side of `amount_in`
+    let mut below = allocation(high)?;
+    loop {
+        let mid = low / 2.0 + high / 2.0;
+        if mid <= low || mid >= high {
+            break;
+        }
+
+        let allocated = allocation(mid)?;
+        if total(&allocated) > amount_in {
+            (low, above) = (mid, allocated);
+        } else {
+            (high, below) = (mid, allocated);
+        }
+    }
+
+    // What rounding leaves over goes to the pool taking the most just above the common rate
+    let remainder = amount_in - total(&below);
+    let (largest, _) = above
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, amount)| **amount)?;
+    below[largest] += remainder;
+
+    Some(below)
+}
+
+/// Sells `amount_in` in `steps` equal parts, each to the pool with the best output for it after the
+/// parts already sold to it, and returns the amount sold to each pool
+fn greedy_allocation(
+    pools: &[&AMM],
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+    steps: usize,
+) -> Result<Vec<U256>, StateSpaceError> {
+    let mut pools = pools
+        .iter()
+        .map(|amm| ((*amm).clone(), U256::ZERO))
+        .collect::<Vec<(AMM, U256)>>();
+
+    let steps = U256::from(steps.max(1)).min(amount_in.max(U256::from(1)));
+    let step = amount_in / steps;
+    // The first part carries the remainder
+    let mut part = step + amount_in % steps;
+
+    // The output of the next part on each pool, `None` once a pool cannot take it
+    let mut next_outputs = pools
+        .iter()
+        .map(|(amm, _)| amm.simulate_swap(token_in, token_out, part).ok())
+        .collect::<Vec<_>>();
+
+    let mut remaining = amount_in;
+    while !remaining.is_zero() {
+        let Some((i, _)) = next_outputs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, output)| output.map(|output| (i, output)))
+            .max_by_key(|(_, output)| *output)
+        else {
+            break;
+        };
+
+        let (amm, allocated) = &mut pools[i];
+        amm.simulate_swap_mut(token_in, token_out, part)?;
+        *allocated += part;
+        remaining -= part;
+
+        if part == step {
+            next_outputs[i] = amm.simulate_swap(token_in, token_out, step).ok();
+        } else {
+            // The outputs were quoted for the first part, so requote every pool for a step
+            part = step;
+            next_outputs = pools
+                .iter()
+                .map(|(amm, _)| amm.simulate_swap(token_in, token_out, part).ok())
+                .collect();
+        }
+    }
+
+    if !remaining.is_zero() {
+        return Err(StateSpaceError::InsufficientLiquidity(token_in, token_out));
+    }
+
+    Ok(pools.into_iter().map(|(_, allocated)| allocated).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::{erc_4626::ERC4626Vault, uniswap_v2::UniswapV2Pool, Token};
+    use alloy::primitives::address;
+
+    #[test]
+    fn test_split_order() -> eyre::Result<()> {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let dai = address!("6B175474E89094C44Da98b954EedeAC495271d0F");
+        let pool = |address, reserve_weth: u128, reserve_dai: u128| -> AMM {
+            UniswapV2Pool {
+                address,
+                token_a: Token::new_with_decimals(dai, 18),
+                token_b: Token::new_with_decimals(weth, 18),
+                reserve_0: reserve_dai,
+                reserve_1: reserve_weth,
+                fee: 300,
+                ..Default::default()
+            }
+            .into()
+        };
+        let shallow = address!("0000000000000000000000000000000000000001");
+        let deep = address!("0000000000000000000000000000000000000002");
+
+        let mut state_space = StateSpace::default();
+        state_space.insert(pool(shallow, 10_u128.pow(21), 2 * 10_u128.pow(24)));
+        state_space.insert(pool(deep, 3 * 10_u128.pow(21), 6 * 10_u128.pow(24)));
+
+        let amount_in = U256::from(10_u128.pow(20));
+        let order = state_space.split_order(weth, dai, amount_in, 100)?;
+        let allocated = |pool| {
+            order
+                .allocations
+                .iter()
+                .find(|allocation| allocation.pool == AMMKey::from(pool))
+                .map(|allocation| allocation.amount_in)
+                .unwrap_or_default()
+        };
+
+        // The pools have the same price, so the order is split by depth, up to the precision of
+        // the common marginal rate
+        assert_eq!(allocated(shallow) + allocated(deep), amount_in);
+        assert!(
+            allocated(shallow).abs_diff(amount_in / U256::from(4))
+                <= amount_in / U256::from(10_u64.pow(12))
+        );
+        assert_eq!(
+            order.amount_out,
+            order
+                .allocations
+                .iter()
+                .map(|allocation| allocation.amount_out)
+                .sum::<U256>()
+        );
+
+        let single = state_space
+            .get(deep)
+            .map(|amm| amm.simulate_swap(weth, dai, amount_in))
+            .transpose()?
+            .unwrap_or_default();
+        assert!(order.amount_out > single);
+
+        assert!(matches!(
+            state_space.split_order(weth, Address::ZERO, amount_in, 100),
+            Err(StateSpaceError::NoPoolsForPair(..))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_order_fallback() -> eyre::Result<()> {
+        let weth = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let share = address!("0000000000000000000000000000000000000003");
+        let pool = address!("0000000000000000000000000000000000000004");
+
+        // The vault has no closed form, so the order is split greedily
+        let uniswap_v2 = UniswapV2Pool {
+            address: pool,
+            token_a: Token::new_with_decimals(weth, 18),
+            token_b: Token::new_with_decimals(share, 18),
+            reserve_0: 10_u128.pow(21),
+            reserve_1: 11 * 10_u128.pow(20),
+            fee: 300,
+            ..Default::default()
+        };
+        let mut state_space = StateSpace::default();
+        state_space.insert(uniswap_v2.clone().into());
+        state_space.insert(
+            ERC4626Vault {
+                vault_token: Token::new_with_decimals(share, 18),
+                asset_token: Token::new_with_decimals(weth, 18),
+                vault_reserve: U256::from(10_u128.pow(24)),
+                asset_reserve: U256::from(10_u128.pow(24)),
+                ..Default::default()
+            }
+            .into(),
+        );
+
+        // An uneven amount makes the first part larger than the others
+        let amount_in = U256::from(10_u128.pow(20) + 37);
+        let order = state_space.split_order(weth, share, amount_in, 100)?;
+        let allocated = |pool| {
+            order
+                .allocations
+                .iter()
+                .find(|allocation| allocation.pool == AMMKey::from(pool))
+                .map(|allocation| allocation.amount_in)
+                .unwrap_or_default()
+        };
+        assert_eq!(allocated(pool) + allocated(share), amount_in);
+
+        // The pool takes parts until its marginal rate falls to the rate of the vault
+        let expected = uniswap_v2.amount_in_to_marginal_rate(weth, 1.0)?;
+        assert!(allocated(pool).abs_diff(expected) <= amount_in / U256::from(100));
+
+        Ok(())
+    }
+}